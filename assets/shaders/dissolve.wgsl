@@ -0,0 +1,23 @@
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#import "shaders/dissolve_common.wgsl"::{dissolve, dissolve_fragment}
+
+// Lit like the standard material, with the dissolved fragments discarded and
+// the ones on the edge glowing.
+@fragment
+fn fragment(in: VertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    let edge = dissolve_fragment(in.world_position.xyz);
+
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+    pbr_input.material.base_color =
+        alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    out.color = vec4(out.color.rgb + dissolve.edge_color.rgb * edge, out.color.a);
+    return out;
+}
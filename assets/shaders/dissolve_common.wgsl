@@ -0,0 +1,50 @@
+// The dissolve pattern, shared by the main pass in `dissolve.wgsl` and the
+// prepass in `dissolve_prepass.wgsl` so both cut the same holes.
+
+// Must match `DissolveParams` in `dissolve.rs`.
+struct DissolveParams {
+    edge_color: vec4<f32>,
+    cutoff: f32,
+    edge_width: f32,
+    noise_frequency: f32,
+}
+
+@group(2) @binding(100) var<uniform> dissolve: DissolveParams;
+
+fn hash(p: vec3<f32>) -> f32 {
+    return fract(sin(dot(p, vec3(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+// Value noise from 0 to 1, smoothly blending random values at the lattice
+// points.
+fn noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(
+            mix(hash(i), hash(i + vec3(1.0, 0.0, 0.0)), u.x),
+            mix(hash(i + vec3(0.0, 1.0, 0.0)), hash(i + vec3(1.0, 1.0, 0.0)), u.x),
+            u.y,
+        ),
+        mix(
+            mix(hash(i + vec3(0.0, 0.0, 1.0)), hash(i + vec3(1.0, 0.0, 1.0)), u.x),
+            mix(hash(i + vec3(0.0, 1.0, 1.0)), hash(i + vec3(1.0, 1.0, 1.0)), u.x),
+            u.y,
+        ),
+        u.z,
+    );
+}
+
+// Discards the fragments below the cutoff and returns how much the rest glow,
+// from 1 on the burning edge to 0. The noise is sampled in world space, which
+// holds still as the characters are down by the time they dissolve.
+fn dissolve_fragment(world_position: vec3<f32>) -> f32 {
+    let n = noise(world_position * dissolve.noise_frequency);
+    // Scaled so a cutoff of 1 discards everything, edge included
+    let threshold = dissolve.cutoff * (1.0 + dissolve.edge_width);
+    if (n < threshold) {
+        discard;
+    }
+    return 1.0 - smoothstep(0.0, dissolve.edge_width, n - threshold);
+}
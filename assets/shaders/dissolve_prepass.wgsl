@@ -0,0 +1,36 @@
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_prepass_functions,
+}
+#import "shaders/dissolve_common.wgsl"::dissolve_fragment
+
+// Discards what the main pass discards, so the holes don't write depth or cast
+// shadows. Normals are the interpolated ones, without normal maps.
+#ifdef PREPASS_FRAGMENT
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    dissolve_fragment(in.world_position.xyz);
+    pbr_prepass_functions::prepass_alpha_discard(in);
+
+    var out: FragmentOutput;
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+    out.frag_depth = in.unclipped_depth;
+#endif
+#ifdef NORMAL_PREPASS
+    out.normal = vec4(normalize(in.world_normal) * 0.5 + vec3(0.5), 1.0);
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    out.motion_vector = pbr_prepass_functions::calculate_motion_vector(
+        in.world_position,
+        in.previous_world_position,
+    );
+#endif
+    return out;
+}
+#else
+@fragment
+fn fragment(in: VertexOutput) {
+    dissolve_fragment(in.world_position.xyz);
+    pbr_prepass_functions::prepass_alpha_discard(in);
+}
+#endif
@@ -0,0 +1,41 @@
+#import bevy_pbr::{
+    forward_io::VertexOutput,
+    mesh_view_bindings::view,
+}
+
+// Must match `HighlightParams` in `highlight.rs`.
+struct HighlightParams {
+    color: vec4<f32>,
+    style: u32,
+    width: f32,
+    pulse_speed: f32,
+    pulse_amount: f32,
+    time: f32,
+}
+
+@group(2) @binding(0) var<uniform> params: HighlightParams;
+
+const TAU: f32 = 6.28318530718;
+
+// Drawn additively over the highlighted mesh, so black is see-through. The
+// edge factor is 0 facing the camera and 1 at the silhouette.
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(mesh.world_normal);
+    let to_camera = normalize(view.world_position - mesh.world_position.xyz);
+    let edge = 1.0 - abs(dot(normal, to_camera));
+
+    var strength = 0.0;
+    if (params.style == 0u) {
+        // A hard band along the silhouette
+        strength = smoothstep(1.0 - params.width, 1.0 - params.width * 0.8, edge);
+    } else {
+        // A soft falloff from the silhouette inwards
+        strength = pow(edge, mix(8.0, 1.0, clamp(params.width, 0.0, 1.0)));
+    }
+
+    let wave = 0.5 + 0.5 * sin(params.time * params.pulse_speed * TAU);
+    let pulse = 1.0 - params.pulse_amount * wave;
+
+    return vec4(params.color.rgb * params.color.a * strength * pulse, 1.0);
+}
@@ -1,26 +1,43 @@
 #import bevy_pbr::{
-    mesh_view_bindings::globals,
     forward_io::VertexOutput,
+    mesh_functions::get_tag,
 }
 
-@group(2) @binding(0) var<uniform> tracer_start: vec4<f32>;
-@group(2) @binding(1) var<uniform> tracer_end: vec4<f32>;
+// One uniform buffer for all the parameters, so the shader stays within the
+// WebGL2 limits. The struct is padded to 64 bytes, a multiple of 16 as WebGL2
+// requires. It must match `TracerParams` in `tracer.rs`, custom tracer shaders
+// can copy it as is.
+struct TracerParams {
+    tracer_start: vec4<f32>,
+    tracer_end: vec4<f32>,
+    now_millis: u32,
+    time_alive: f32,
+    tracer_length: f32,
+    brightness: f32,
+    speed: f32,
+    fade_exponent: f32,
+}
 
-@group(2) @binding(2) var<uniform> time_spawned: f32;
-@group(2) @binding(3) var<uniform> time_alive: f32;
-@group(2) @binding(4) var<uniform> tracer_length: f32;
+@group(2) @binding(0) var<uniform> params: TracerParams;
 
+// Only the mesh uv and the tracer's age are used, so the shader is view
+// independent and renders the same in every view of a multiview (XR) pass.
+// The age is the tracer's clock minus its spawn time, which is passed per
+// instance in the mesh tag so tracers sharing a material can be batched. The
+// clock is fed in rather than read from the global time, so tracers slow down
+// with virtual time.
 @fragment
 fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
     // Compute the elapsed time and lifetime fraction
-    let elapsed_time = globals.time - time_spawned;
-    let lifetime_fraction = clamp(elapsed_time / time_alive, 0.0, 1.0);
+    let age = f32(params.now_millis - get_tag(mesh.instance_index)) / 1000.0;
+    let lifetime_fraction = clamp(age / params.time_alive, 0.0, 1.0);
+    let position = lifetime_fraction * params.speed;
 
     // Calculate the moving visible segment
 	// The visible segment starts at the lifetime fraction
-    let start = lifetime_fraction - tracer_length;
+    let start = position - params.tracer_length;
 	// Moves to the end over time
-    let end = lifetime_fraction + tracer_length;
+    let end = position + params.tracer_length;
 
     // Check if the current fragment is within the visible range
     if (mesh.uv.y < start || mesh.uv.y > end) {
@@ -29,7 +46,13 @@ fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
 
     // Normalize t within the visible range for color interpolation
     let t = (mesh.uv.y - start) / (end - start);
-    let color = mix(tracer_start, tracer_end, clamp(t, 0.0, 1.0));
+    let color = mix(params.tracer_start, params.tracer_end, clamp(t, 0.0, 1.0));
+
+    // Fade out over the lifetime, an exponent of 0 doesn't fade
+    var fade = 1.0;
+    if (params.fade_exponent > 0.0) {
+        fade = pow(1.0 - lifetime_fraction, params.fade_exponent);
+    }
 
-    return color;
+    return vec4(color.rgb * params.brightness, color.a * fade);
 }
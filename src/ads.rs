@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::camera_rig::CameraRig;
+use crate::damping::update_anim_params;
+use crate::spread::WeaponSpread;
+use crate::state::PlayerAnimationState;
+use crate::velocity::drive_animation_from_velocity;
+use crate::weapon_pose::swap_weapon_poses;
+
+/// Aiming down sights. Setting [`AimDownSights::aiming`] blends the upper body
+/// from the hip pose into the sights pose, zooms the camera in, slows the
+/// locomotion and tightens the `WeaponSpread`, all by the same weight so they
+/// stay in sync. A camera with a `CameraRig` zooms by moving in to its
+/// `ads_distance` instead of narrowing its field of view.
+pub struct AdsPlugin;
+
+impl Plugin for AdsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AimDownSights>();
+        app.add_systems(
+            Update,
+            aim_down_sights
+                .in_set(CharAnimSet::ModifyInput)
+                .after(drive_animation_from_velocity)
+                .after(update_anim_params)
+                .after(swap_weapon_poses),
+        );
+    }
+}
+
+/// Add to a character root.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct AimDownSights {
+    /// Whether the character is aiming down sights.
+    pub aiming: bool,
+    /// The upper body pose while aiming. The pose from before aiming is
+    /// played again after.
+    pub sights_pose: Handle<AnimationClip>,
+    /// Seconds to blend in and out of the sights.
+    pub blend_secs: f32,
+    /// The camera to zoom in, if any.
+    pub camera: Option<Entity>,
+    /// The camera's field of view is scaled by this when fully aimed, unless
+    /// it has a `CameraRig`.
+    pub zoom: f32,
+    /// How much slower the character moves when fully aimed, from 0 to 1.
+    /// Aiming characters move like cautious ones, so they don't sprint.
+    pub slowdown: f32,
+    /// How much the spread is tightened when fully aimed, from 0 to 1.
+    pub tightness: f32,
+    weight: f32,
+    /// The upper body pose to go back to, while aiming.
+    #[reflect(ignore)]
+    hip_pose: Option<Option<Handle<AnimationClip>>>,
+    /// The camera's field of view before zooming in, while zoomed.
+    hip_fov: Option<f32>,
+}
+
+impl AimDownSights {
+    pub fn new(sights_pose: Handle<AnimationClip>) -> Self {
+        Self {
+            aiming: false,
+            sights_pose,
+            blend_secs: 0.2,
+            camera: None,
+            zoom: 0.7,
+            slowdown: 0.5,
+            tightness: 0.6,
+            weight: 0.0,
+            hip_pose: None,
+            hip_fov: None,
+        }
+    }
+
+    pub fn with_camera(mut self, camera: Entity) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    pub fn set_aiming(&mut self, aiming: bool) {
+        self.aiming = aiming;
+    }
+
+    /// How far into the sights the character is, from 0 at the hip to 1.
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Replaces the pose played again after aiming, e.g. when the weapon is
+    /// swapped while aiming, so the sights pose stays until the aim is
+    /// released. Returns false without changing anything if the character
+    /// isn't in the sights pose.
+    pub(crate) fn swap_hip_pose(&mut self, pose: Option<Handle<AnimationClip>>) -> bool {
+        match &mut self.hip_pose {
+            Some(hip_pose) => {
+                *hip_pose = pose;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn aim_down_sights(
+    mut characters: Query<(Entity, &mut AimDownSights, Option<&mut WeaponSpread>)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    mut projections: Query<&mut Projection, Without<CameraRig>>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    for (root, mut ads, spread) in characters.iter_mut() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let ads = ads.as_mut();
+
+        let target = if ads.aiming { 1.0 } else { 0.0 };
+        let step = time.delta_secs() * state.time_scale() / ads.blend_secs.max(f32::EPSILON);
+        ads.weight += (target - ads.weight).clamp(-step, step);
+
+        // The state blends the poses over its own `pose_blend_secs`.
+        match (ads.aiming, ads.hip_pose.is_some()) {
+            (true, false) => {
+                ads.hip_pose = Some(state.upper_body_pose().cloned());
+                state.set_upper_body_pose(Some(ads.sights_pose.clone()));
+            }
+            (false, true) => state.set_upper_body_pose(ads.hip_pose.take().unwrap()),
+            _ => {}
+        }
+
+        if let Some(input) = state.input_mut() {
+            input.caution = input.caution.max(ads.weight * ads.slowdown);
+        }
+        if let Some(mut spread) = spread {
+            spread.set_tightness(ads.weight * ads.tightness);
+        }
+
+        let Some(Ok(mut projection)) = ads.camera.map(|camera| projections.get_mut(camera))
+        else {
+            continue;
+        };
+        let Projection::Perspective(perspective) = projection.as_mut() else {
+            continue;
+        };
+        if ads.weight > 0.0 {
+            let hip_fov = *ads.hip_fov.get_or_insert(perspective.fov);
+            perspective.fov = hip_fov * 1f32.lerp(ads.zoom, ads.weight);
+        } else if let Some(hip_fov) = ads.hip_fov.take() {
+            perspective.fov = hip_fov;
+        }
+    }
+}
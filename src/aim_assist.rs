@@ -0,0 +1,177 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::camera_rig::{aim_with_camera_rigs, CameraRig};
+use crate::hitbox::Hitbox;
+use crate::hitscan::Hitscan;
+
+/// Aim assist against the per bone hitboxes, off unless a shooter has an
+/// [`AimAssist`]. Magnetism pulls the shooter's `CameraRig` towards the
+/// nearest hitbox in a cone around the aim, and bullet bending turns its
+/// shots onto one, which [`Hitscan::fire_ray`] does before it traces the shot,
+/// so the tracer and the `HitEvent` follow the bent shot. Only targets in
+/// sight pull or bend.
+pub struct AimAssistPlugin;
+
+impl Plugin for AimAssistPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AimAssist>();
+        app.add_systems(Update, apply_aim_magnetism.before(aim_with_camera_rigs));
+    }
+}
+
+/// Add to the shooter, the entity set as `Shot::shooter`. Both kinds of assist
+/// are off at the default angles of 0.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct AimAssist {
+    /// The camera with the `CameraRig` magnetism turns.
+    pub camera: Option<Entity>,
+    /// How far off a hitbox the aim can be for magnetism to pull it, in
+    /// radians.
+    pub magnetism_angle: f32,
+    /// Seconds for magnetism to close half the gap to the hitbox.
+    pub magnetism_halflife: f32,
+    /// How far shots bend onto a hitbox, in radians.
+    pub bend_angle: f32,
+    /// Hitboxes further than this in meters don't pull the aim. Shots bend as
+    /// far as they reach.
+    pub max_distance: f32,
+}
+
+impl Default for AimAssist {
+    fn default() -> Self {
+        Self {
+            camera: None,
+            magnetism_angle: 0.0,
+            magnetism_halflife: 0.15,
+            bend_angle: 0.0,
+            max_distance: 50.0,
+        }
+    }
+}
+
+impl AimAssist {
+    pub fn with_magnetism(mut self, camera: Entity, angle: f32) -> Self {
+        self.camera = Some(camera);
+        self.magnetism_angle = angle;
+        self
+    }
+
+    pub fn with_bending(mut self, angle: f32) -> Self {
+        self.bend_angle = angle;
+        self
+    }
+}
+
+/// The hitbox closest to an aim.
+#[derive(Clone, Copy, Debug)]
+pub struct AimTarget {
+    /// The character root the hitbox belongs to.
+    pub character: Entity,
+    /// The point on the hitbox's bone closest to the aim.
+    pub point: Vec3,
+    /// The angle between the aim and the point, in radians.
+    pub angle: f32,
+}
+
+/// Finds the hitbox whose bone passes closest to the aim from `origin` along
+/// `direction`, within `max_angle` radians and `max_distance` meters, leaving
+/// out the shooter's own.
+pub fn find_aim_target<'a>(
+    origin: Vec3,
+    direction: Vec3,
+    max_angle: f32,
+    max_distance: f32,
+    shooter: Option<Entity>,
+    hitboxes: impl IntoIterator<Item = (&'a Hitbox, &'a GlobalTransform)>,
+) -> Option<AimTarget> {
+    let direction = direction.try_normalize()?;
+    hitboxes
+        .into_iter()
+        .filter(|(hitbox, ..)| Some(hitbox.character) != shooter)
+        .filter_map(|(hitbox, transform)| {
+            let a = transform.transform_point(hitbox.start);
+            let b = transform.transform_point(hitbox.end);
+            let point = closest_on_segment_to_ray(a, b, origin, direction);
+            let to_point = point - origin;
+            let angle = to_point.angle_between(direction);
+            (angle <= max_angle && to_point.length() <= max_distance).then_some(AimTarget {
+                character: hitbox.character,
+                point,
+                angle,
+            })
+        })
+        .min_by(|a, b| a.angle.total_cmp(&b.angle))
+}
+
+/// The point on the segment from `a` to `b` closest to the line through
+/// `origin` along the unit `direction`.
+fn closest_on_segment_to_ray(a: Vec3, b: Vec3, origin: Vec3, direction: Vec3) -> Vec3 {
+    let segment = b - a;
+    let length_squared = segment.length_squared();
+    if length_squared <= f32::EPSILON {
+        return a;
+    }
+    let offset = a - origin;
+    let along = segment.dot(direction);
+    let denominator = length_squared - along * along;
+    let t = if denominator > f32::EPSILON {
+        (along * direction.dot(offset) - segment.dot(offset)) / denominator
+    } else {
+        0.0
+    };
+    a + segment * t.clamp(0.0, 1.0)
+}
+
+fn apply_aim_magnetism(
+    shooters: Query<(Entity, &AimAssist)>,
+    mut rigs: Query<(&mut CameraRig, &GlobalTransform)>,
+    hitboxes: Query<(&Hitbox, &GlobalTransform)>,
+    hitscan: Hitscan,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for (shooter, assist) in shooters.iter() {
+        if assist.magnetism_angle <= 0.0 {
+            continue;
+        }
+        let Some((mut rig, camera)) = assist.camera.and_then(|camera| rigs.get_mut(camera).ok())
+        else {
+            continue;
+        };
+        let origin = camera.translation();
+        let aim = rig.rotation() * Vec3::Z;
+        let Some(target) = find_aim_target(
+            origin,
+            aim,
+            assist.magnetism_angle,
+            assist.max_distance,
+            Some(shooter),
+            hitboxes.iter(),
+        ) else {
+            continue;
+        };
+        let Ok(to_target) = Dir3::new(target.point - origin) else {
+            continue;
+        };
+        let in_sight = hitscan
+            .cast_ray(origin, to_target, assist.max_distance, Some(shooter))
+            .is_some_and(|hit| hit.hitbox.is_some_and(|h| h.character == target.character));
+        if !in_sight {
+            continue;
+        }
+
+        let yaw = to_target.x.atan2(to_target.z);
+        let pitch = to_target.y.asin();
+        let fraction = 1.0 - 0.5f32.powf(dt / assist.magnetism_halflife.max(f32::EPSILON));
+        // Turn the short way round.
+        let yaw_gap = (yaw - rig.yaw() + PI).rem_euclid(TAU) - PI;
+        let (current_yaw, current_pitch) = (rig.yaw(), rig.pitch());
+        rig.set_look(
+            current_yaw + yaw_gap * fraction,
+            current_pitch + (pitch - current_pitch) * fraction,
+        );
+    }
+}
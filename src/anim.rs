@@ -1,40 +1,202 @@
 use bevy::{
-    animation::{ActiveAnimation, AnimationTarget, RepeatAnimation},
+    animation::{advance_animations, ActiveAnimation, AnimationTarget, RepeatAnimation},
+    app::Animation,
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
     asset::AssetPath,
     prelude::*,
     platform::collections::HashMap,
 };
+use serde::Deserialize;
 
 use crate::{
-    state::{run_player_animations, AnimationNodes},
+    state::{
+        clear_consumed_input, restore_animation_speeds, run_player_animations,
+        scale_animation_speeds, AnimationNodes, PlayerAnimationState,
+    },
     utils::*,
 };
 
-pub struct AnimationPlugin;
+/// Runs the character state machines, in `Update` unless moved with
+/// [`AnimationPlugin::in_schedule`], e.g. to `FixedUpdate` to step the state
+/// machines with the game logic. Move the [`VelocityDriverPlugin`] and the
+/// [`DampingPlugin`] along with it, since they feed it.
+///
+/// The [`CharAnimSet`]s are ordered in `Update` and in the chosen schedule.
+/// Input set in `Update` is kept until a step has read it, so a state machine
+/// in `FixedUpdate` reads it on every step, however many a frame has.
+///
+/// [`VelocityDriverPlugin`]: crate::velocity::VelocityDriverPlugin
+/// [`DampingPlugin`]: crate::damping::DampingPlugin
+pub struct AnimationPlugin {
+    /// The schedule the state machines run in.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for AnimationPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl AnimationPlugin {
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
 
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, run_player_animations);
+        if !app.is_plugin_added::<CharAnimSetsPlugin>() {
+            app.add_plugins(CharAnimSetsPlugin);
+        }
+        app.register_type::<PlayerAnimationState>();
+        app.configure_sets(
+            self.schedule,
+            (
+                CharAnimSet::Input,
+                CharAnimSet::ModifyInput,
+                CharAnimSet::StateMachine,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            self.schedule,
+            run_player_animations.in_set(CharAnimSet::StateMachine),
+        );
+        app.add_systems(Update, clear_consumed_input.before(CharAnimSet::Input));
+        app.add_systems(
+            PostUpdate,
+            (
+                scale_animation_speeds.before(advance_animations),
+                restore_animation_speeds.after(advance_animations),
+            ),
+        );
+    }
+}
+
+/// The stages of character animation, to order systems against.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CharAnimSet {
+    /// Sets the animation input in `Update`, e.g. from the keyboard, the AI or
+    /// the network, with `PlayerAnimationState::set_input`.
+    Input,
+    /// Adjusts the input once it's set, e.g. driving the movement from the
+    /// velocity or slowing injured characters, and feeds the state machines
+    /// the rest, e.g. montages, gestures and locomotion overrides. Runs after
+    /// `Input`, so the adjustments aren't overwritten.
+    ModifyInput,
+    /// Runs the state machines in the `AnimationPlugin`'s schedule, which read
+    /// the animation input. Runs after `ModifyInput`.
+    StateMachine,
+    /// Solves IK in `PostUpdate`, after the clips have been applied. Layer
+    /// procedural bone rotations before this, so the limbs still reach.
+    Ik,
+    /// Runs in `PostUpdate` once the pose is final and propagated, so the
+    /// bones' `GlobalTransform`s are where they're drawn this frame. Read
+    /// them here with `RigTransforms`.
+    PostPose,
+}
+
+/// Orders the [`CharAnimSet`]s, against each other and Bevy's own systems.
+/// Added by the animation and IK plugins, so the sets are ordered whichever
+/// of them the game uses.
+pub struct CharAnimSetsPlugin;
+
+impl Plugin for CharAnimSetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            Update,
+            (
+                CharAnimSet::Input,
+                CharAnimSet::ModifyInput,
+                CharAnimSet::StateMachine,
+            )
+                .chain(),
+        );
+        app.configure_sets(
+            PostUpdate,
+            (
+                CharAnimSet::Ik
+                    .after(Animation)
+                    .before(TransformSystem::TransformPropagate),
+                CharAnimSet::PostPose.after(TransformSystem::TransformPropagate),
+            ),
+        );
     }
 }
 
 /// Masks out bones in the lower body.
 const LOWER_BODY_MASK_GROUP: u32 = 1;
-const LOWER_BODY_MASK: u64 = 1 << LOWER_BODY_MASK_GROUP;
+pub(crate) const LOWER_BODY_MASK: u64 = 1 << LOWER_BODY_MASK_GROUP;
 /// Masks out bones in the upper body.
 const UPPER_BODY_MASK_GROUP: u32 = 2;
 const UPPER_BODY_MASK: u64 = 1 << UPPER_BODY_MASK_GROUP;
 
-struct PlayerAnimationPaths {
-    forward: AssetPath<'static>,
-    back: AssetPath<'static>,
-    idle: AssetPath<'static>,
-    left: AssetPath<'static>,
-    right: AssetPath<'static>,
-    jump: AssetPath<'static>,
-    falling: AssetPath<'static>,
-    land: AssetPath<'static>,
-    sprint: AssetPath<'static>,
+/// The clips that the animation graph of a player is built from.
+#[derive(Clone)]
+pub struct PlayerAnimationPaths {
+    pub forward: AssetPath<'static>,
+    pub back: AssetPath<'static>,
+    pub idle: AssetPath<'static>,
+    pub left: AssetPath<'static>,
+    pub right: AssetPath<'static>,
+    pub jump: AssetPath<'static>,
+    pub falling: AssetPath<'static>,
+    pub land: AssetPath<'static>,
+    pub sprint: AssetPath<'static>,
+    pub bones: GraphBones,
+}
+
+/// The bones that the animation graph masks and aims with, by name.
+#[derive(Reflect, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[serde(default)]
+pub struct GraphBones {
+    /// The upper body is masked from this bone down, e.g. the chest.
+    pub upper_body: String,
+    /// The lower body is masked from these bones down.
+    pub left_leg: String,
+    pub right_leg: String,
+    pub spine: String,
+    pub hips: String,
+    /// The bone rotated to aim, in both halves of the body.
+    pub spine1: String,
+    /// The muzzle that's aimed.
+    pub bullet_point: String,
+}
+
+impl GraphBones {
+    /// Every bone by the name of its field.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("upper_body", &self.upper_body),
+            ("left_leg", &self.left_leg),
+            ("right_leg", &self.right_leg),
+            ("spine", &self.spine),
+            ("hips", &self.hips),
+            ("spine1", &self.spine1),
+            ("bullet_point", &self.bullet_point),
+        ]
+        .into_iter()
+        .map(|(field, name)| (field, name.as_str()))
+    }
+}
+
+impl Default for GraphBones {
+    fn default() -> Self {
+        Self {
+            upper_body: "mixamorig:Spine2".into(),
+            left_leg: "mixamorig:LeftUpLeg".into(),
+            right_leg: "mixamorig:RightUpLeg".into(),
+            spine: "mixamorig:Spine".into(),
+            hips: "mixamorig:Hips".into(),
+            spine1: "mixamorig:Spine1".into(),
+            bullet_point: "BlasterN".into(),
+        }
+    }
 }
 
 impl Default for PlayerAnimationPaths {
@@ -73,11 +235,12 @@ impl Default for PlayerAnimationPaths {
             falling: get_anim("FallingIdle"),
             land: get_anim("JumpDown"),
             sprint: get_anim("RifleRun"),
+            bones: GraphBones::default(),
         }
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(Reflect, PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum AnimationName {
     IdleLowerBody,
     IdleUpperBody,
@@ -122,11 +285,16 @@ impl PlayerAnimations {
     }
 
     pub fn get_name(&self, index: AnimationNodeIndex) -> AnimationName {
+        self.find_name(index).unwrap()
+    }
+
+    /// Gets the name of a node, or none if it isn't one of the state machine's
+    /// clips (e.g. a montage or gesture).
+    pub fn find_name(&self, index: AnimationNodeIndex) -> Option<AnimationName> {
         self.anims
             .iter()
             .find(|(_, ix)| **ix == index)
             .map(|(name, _)| *name)
-            .unwrap()
     }
 
     pub fn apply_defaults<'a>(
@@ -140,26 +308,29 @@ impl PlayerAnimations {
     }
 }
 
+#[derive(Reflect)]
 pub struct PlayerProceduralAnimationTargets {
     pub spine1: Entity,
     pub bullet_point: Entity,
 }
 
+/// Builds the animation graph of the rig under `entity` from `player_anims`,
+/// or warns and returns `None` if the rig lacks one of its bones.
 pub fn load_player_animations(
     entity: Entity,
+    player_anims: &PlayerAnimationPaths,
     asset_server: &AssetServer,
     children: &Query<&Children>,
     names: &Query<&Name>,
     animation_targets: &Query<&AnimationTarget>,
     commands: Commands,
     parents: &Query<&ChildOf>,
-) -> (
+) -> Option<(
     PlayerAnimations,
     PlayerProceduralAnimationTargets,
     AnimationGraph,
     AnimationNodes,
-) {
-    let player_anims = PlayerAnimationPaths::default();
+)> {
     let mut graph = AnimationGraph::new();
     let add_node = graph.add_additive_blend(1.0, graph.root);
     let lower_body_blend = graph.add_blend(1.0, add_node);
@@ -168,6 +339,7 @@ pub fn load_player_animations(
 
     let nodes = AnimationNodes {
         upper_lower_add: add_node,
+        upper_body: upper_body_blend,
         full_body,
     };
 
@@ -245,15 +417,16 @@ pub fn load_player_animations(
 
     let proc_targets = init_mixamo_rig_masks(
         entity,
+        &player_anims.bones,
         &mut graph,
         children,
         names,
         animation_targets,
         commands,
         parents,
-    );
+    )?;
 
-    (anims, proc_targets, graph, nodes)
+    Some((anims, proc_targets, graph, nodes))
 }
 
 #[derive(PartialEq)]
@@ -265,30 +438,49 @@ enum Mask {
 
 fn init_mixamo_rig_masks(
     root: Entity,
+    bones: &GraphBones,
     graph: &mut AnimationGraph,
     children: &Query<&Children>,
     names: &Query<&Name>,
     animation_targets: &Query<&AnimationTarget>,
     mut commands: Commands,
     parents: &Query<&ChildOf>,
-) -> PlayerProceduralAnimationTargets {
+) -> Option<PlayerProceduralAnimationTargets> {
     // (name, should masks decendants, mask type)
     let masks = &[
-        ("mixamorig:Spine2", true, Mask::Upper),
-        ("mixamorig:LeftUpLeg", true, Mask::Lower),
-        ("mixamorig:RightUpLeg", true, Mask::Lower),
-        ("mixamorig:Spine", false, Mask::Lower),
-        ("mixamorig:Hips", false, Mask::Lower),
-        ("mixamorig:Spine1", false, Mask::Both),
+        (bones.upper_body.as_str(), true, Mask::Upper),
+        (bones.left_leg.as_str(), true, Mask::Lower),
+        (bones.right_leg.as_str(), true, Mask::Lower),
+        (bones.spine.as_str(), false, Mask::Lower),
+        (bones.hips.as_str(), false, Mask::Lower),
+        (bones.spine1.as_str(), false, Mask::Both),
     ];
 
+    let find_bone = |name: &str| {
+        let bone = find_child_with_name(root, name, children, names);
+        if bone.is_none() {
+            warn!("no bone named {name} in the rig, it can't be animated");
+        }
+        bone
+    };
+    // Check every bone before changing anything.
+    let mut masked = Vec::with_capacity(masks.len());
     for (name, mask_decendants, mask_type) in masks {
-        let entity = find_child_with_name(root, name, &children, &names)
-            .expect("upper body joint not found");
-        let target = animation_targets
-            .get(entity)
-            .expect(&format!("isolate {} should have animation target", name));
+        let entity = find_bone(name)?;
+        let Ok(target) = animation_targets.get(entity) else {
+            warn!("bone {name} has no animation target, it can't be animated");
+            return None;
+        };
+        masked.push((entity, target, mask_decendants, mask_type));
+    }
+    let spine1 = find_bone(&bones.spine1)?;
+    let bullet_point = find_bone(&bones.bullet_point)?;
+    let Ok(spine1_parent) = parents.get(spine1).map(ChildOf::parent) else {
+        warn!("bone {} has no parent, it can't be aimed", bones.spine1);
+        return None;
+    };
 
+    for (entity, target, mask_decendants, mask_type) in masked {
         let targets = if *mask_decendants {
             let entities_to_mask = get_all_descendants(entity, &children);
             map_query(entities_to_mask, &animation_targets)
@@ -312,16 +504,13 @@ fn init_mixamo_rig_masks(
     let spine1_proc_target = commands
         .spawn((Transform::default(), Visibility::default()))
         .id();
-
-    let spine1 = find_child_with_name(root, "mixamorig:Spine1", children, names).unwrap();
-    let spine1_parent = parents.get(spine1).unwrap().get();
     commands
         .entity(spine1_proc_target)
         .set_parent(spine1_parent);
     commands.entity(spine1).set_parent(spine1_proc_target);
 
-    PlayerProceduralAnimationTargets {
+    Some(PlayerProceduralAnimationTargets {
         spine1: spine1_proc_target,
-        bullet_point: find_child_with_name(root, "BlasterN", children, names).unwrap(),
-    }
+        bullet_point,
+    })
 }
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, fmt};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AssetPath, LoadContext},
+    gltf::Gltf,
+    prelude::*,
+};
+use ron::value::Map;
+use serde::Deserialize;
+
+use crate::anim::{GraphBones, PlayerAnimationPaths};
+use crate::schema::{parse_versioned, SchemaError, VersionedAsset};
+
+/// Animation graphs defined in `.animgraph.ron` files rather than in code, see
+/// [`AnimGraphDef`]. Characters built with one are checked against their glTF
+/// file before they're set up, and a graph with missing references is
+/// reported with a [`GraphValidationError`] instead of leaving the character
+/// in its bind pose.
+pub struct AnimGraphPlugin;
+
+impl Plugin for AnimGraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<AnimGraphDef>();
+        app.init_asset_loader::<AnimGraphLoader>();
+        app.add_event::<GraphValidationError>();
+    }
+}
+
+/// The locomotion states of the graph, which each need a clip.
+pub const GRAPH_STATES: [&str; 9] = [
+    "Idle", "Forward", "Back", "Left", "Right", "Jump", "Falling", "Land", "Sprint",
+];
+
+/// An animation graph, loaded from `.animgraph.ron` files.
+#[derive(Asset, Reflect, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+pub struct AnimGraphDef {
+    /// The clip of each of the [`GRAPH_STATES`], by animation name in the
+    /// character's glTF file.
+    pub states: HashMap<String, String>,
+    #[serde(default)]
+    pub bones: GraphBones,
+    /// Where the graph was loaded from, for diagnostics.
+    #[reflect(ignore)]
+    #[serde(skip)]
+    source: GraphSource,
+}
+
+#[derive(Clone, Debug, Default)]
+struct GraphSource {
+    path: String,
+    text: String,
+}
+
+impl VersionedAsset for AnimGraphDef {
+    const CURRENT_VERSION: u32 = 1;
+    const EXTENSIONS: &'static [&'static str] = &["animgraph.ron"];
+
+    fn migrate(from_version: u32, _fields: &mut Map) -> Result<(), SchemaError> {
+        Err(SchemaError::Migration {
+            from_version,
+            reason: "unknown version".into(),
+        })
+    }
+}
+
+/// A reference in a graph that doesn't resolve.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MissingReference {
+    /// One of the [`GRAPH_STATES`] has no clip.
+    State,
+    /// A state that isn't one of the [`GRAPH_STATES`], e.g. a typo.
+    UnknownState,
+    /// No animation with this name in the glTF file.
+    Clip(String),
+    /// No node with this name in the glTF file.
+    Bone(String),
+}
+
+/// A problem found by [`AnimGraphDef::validate`].
+#[derive(Clone, Debug)]
+pub struct GraphDiagnostic {
+    /// The state, or the bone field, that the reference is from.
+    pub state: String,
+    pub missing: MissingReference,
+    /// The file the graph was loaded from.
+    pub file: String,
+    /// The line of the reference, from 1, if it's in the file.
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for GraphDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        let state = &self.state;
+        match &self.missing {
+            MissingReference::State => write!(f, ": state {state} has no clip"),
+            MissingReference::UnknownState => write!(f, ": unknown state {state}"),
+            MissingReference::Clip(clip) => {
+                write!(f, ": state {state} references missing clip {clip}")
+            }
+            MissingReference::Bone(bone) => {
+                write!(f, ": bone {state} references missing node {bone}")
+            }
+        }
+    }
+}
+
+/// Sent when a character's graph doesn't match its glTF file. The character
+/// isn't set up, and each diagnostic is logged as an error too.
+#[derive(Event, Clone, Debug)]
+pub struct GraphValidationError {
+    pub graph: AssetId<AnimGraphDef>,
+    /// The character root that was being built with the graph.
+    pub character: Option<Entity>,
+    pub diagnostics: Vec<GraphDiagnostic>,
+}
+
+impl AnimGraphDef {
+    /// Checks every reference of the graph against a glTF file, without
+    /// spawning a character, and resolves the clips if they're all there.
+    pub fn validate(&self, gltf: &Gltf) -> Result<PlayerAnimationPaths, Vec<GraphDiagnostic>> {
+        let mut diagnostics = Vec::new();
+        let mut diagnose = |state: &str, missing: MissingReference, needle: &str| {
+            diagnostics.push(GraphDiagnostic {
+                state: state.to_string(),
+                missing,
+                file: self.source.path.clone(),
+                line: self.line_of(needle),
+            });
+        };
+
+        for state in self.states.keys() {
+            if !GRAPH_STATES.contains(&state.as_str()) {
+                diagnose(state, MissingReference::UnknownState, state);
+            }
+        }
+        let mut clips: HashMap<&str, AssetPath<'static>> = HashMap::new();
+        for state in GRAPH_STATES {
+            let Some(clip) = self.states.get(state) else {
+                diagnose(state, MissingReference::State, "states");
+                continue;
+            };
+            match gltf.named_animations.get(clip.as_str()).and_then(|c| c.path()) {
+                Some(path) => {
+                    clips.insert(state, path.clone());
+                }
+                None => diagnose(state, MissingReference::Clip(clip.clone()), clip),
+            }
+        }
+        for (field, bone) in self.bones.iter() {
+            if !gltf.named_nodes.contains_key(bone) {
+                diagnose(field, MissingReference::Bone(bone.to_string()), bone);
+            }
+        }
+
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+        let mut clip = |state| clips.remove(state).unwrap();
+        Ok(PlayerAnimationPaths {
+            idle: clip("Idle"),
+            forward: clip("Forward"),
+            back: clip("Back"),
+            left: clip("Left"),
+            right: clip("Right"),
+            jump: clip("Jump"),
+            falling: clip("Falling"),
+            land: clip("Land"),
+            sprint: clip("Sprint"),
+            bones: self.bones.clone(),
+        })
+    }
+
+    /// The first line that quotes `needle`, or mentions it if it's a field.
+    fn line_of(&self, needle: &str) -> Option<usize> {
+        let quoted = format!("\"{needle}\"");
+        let lines = || self.source.text.lines().enumerate();
+        lines()
+            .find(|(_, line)| line.contains(&quoted))
+            .or_else(|| lines().find(|(_, line)| line.contains(needle)))
+            .map(|(index, _)| index + 1)
+    }
+}
+
+/// Loads [`AnimGraphDef`]s like the other versioned assets, keeping the path
+/// and text for diagnostics.
+#[derive(Default)]
+struct AnimGraphLoader;
+
+impl AssetLoader for AnimGraphLoader {
+    type Asset = AnimGraphDef;
+    type Settings = ();
+    type Error = SchemaError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<AnimGraphDef, SchemaError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(SchemaError::Io)?;
+        let mut graph: AnimGraphDef = parse_versioned(&bytes)?;
+        graph.source = GraphSource {
+            path: load_context.path().display().to_string(),
+            text: String::from_utf8_lossy(&bytes).into_owned(),
+        };
+        Ok(graph)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        AnimGraphDef::EXTENSIONS
+    }
+}
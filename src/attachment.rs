@@ -0,0 +1,102 @@
+use bevy::{
+    app::Animation,
+    ecs::component::{ComponentHooks, HookContext, Mutable, StorageType},
+    prelude::*,
+};
+
+use crate::tracer::{MuzzleFlash, TracerProfile};
+
+/// Weapon attachments that change how shots look. Attachments go on the muzzle
+/// entity (e.g. the muzzle socket) and apply to tracers that name it as their
+/// muzzle.
+pub struct AttachmentPlugin;
+
+impl Plugin for AttachmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Suppressor>();
+        app.add_systems(PreUpdate, remove_muzzle_offsets);
+        app.add_systems(
+            PostUpdate,
+            apply_muzzle_offsets
+                .after(Animation)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// A suppressor screwed onto the muzzle. It pushes the muzzle out by its length
+/// while attached, swaps the muzzle flash and dims the tracer light. The push
+/// is applied after animation, so it holds on an animated muzzle bone, and
+/// taken off again at the start of the next frame like weapon lag.
+#[derive(Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Suppressor {
+    pub muzzle_flash: MuzzleFlash,
+    /// Scales the light intensity of the tracers.
+    pub light_intensity_scale: f32,
+    /// How far the muzzle moves out in its local space, i.e. the length of the
+    /// suppressor along the barrel.
+    pub muzzle_offset: Vec3,
+    /// The offset applied this frame, in the muzzle's parent's space.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    applied: Option<Vec3>,
+}
+
+impl Default for Suppressor {
+    fn default() -> Self {
+        Self {
+            muzzle_flash: MuzzleFlash::Minimal,
+            light_intensity_scale: 0.2,
+            muzzle_offset: Vec3::new(0.0, 0.0, 0.15),
+            applied: None,
+        }
+    }
+}
+
+impl Suppressor {
+    /// Changes a tracer's look for a shot through the suppressor.
+    pub fn apply(&self, profile: &mut TracerProfile) {
+        profile.muzzle_flash = self.muzzle_flash;
+        profile.light_intensity *= self.light_intensity_scale;
+    }
+}
+
+impl Component for Suppressor {
+    type Mutability = Mutable;
+
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        // Move the muzzle back in when the suppressor comes off mid frame.
+        hooks.on_replace(|mut world, HookContext { entity, .. }| {
+            let Some(applied) = world.get::<Self>(entity).unwrap().applied else {
+                return;
+            };
+            if let Some(mut transform) = world.get_mut::<Transform>(entity) {
+                transform.translation -= applied;
+            }
+        });
+    }
+}
+
+/// Pushes suppressed muzzles out along the barrel, over the pose animation
+/// gave them.
+fn apply_muzzle_offsets(mut muzzles: Query<(&mut Suppressor, &mut Transform)>) {
+    for (mut suppressor, mut transform) in muzzles.iter_mut() {
+        let applied = transform.rotation * suppressor.muzzle_offset;
+        transform.translation += applied;
+        suppressor.applied = Some(applied);
+    }
+}
+
+/// Takes off the offset applied last frame, so unanimated muzzles don't creep
+/// out.
+fn remove_muzzle_offsets(mut muzzles: Query<(&mut Suppressor, &mut Transform)>) {
+    for (mut suppressor, mut transform) in muzzles.iter_mut() {
+        if let Some(applied) = suppressor.applied.take() {
+            transform.translation -= applied;
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::{audio::Volume, platform::collections::HashMap, prelude::*};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::effect_rng::{EffectRng, EVENT_SOUND_STREAM, FOOTSTEP_SOUND_STREAM};
+use crate::events::{FireEvent, FootstepEvent, HitEvent, LandedEvent, LandingKind, NotifyEvent};
+use crate::hitscan::{find_surface, Hitscan, Surface};
+use crate::surface::SurfaceEffects;
+
+/// Plays sounds for the crate's events at the positions they happened: shots,
+/// impacts by surface, footsteps, animation notifies (e.g. reloads) and
+/// landings. Fill in the [`SoundBank`] resource with the sounds to play. The
+/// camera needs a `SpatialListener` for the sounds to be positional.
+/// Variations are picked from the `EffectRng`.
+pub struct AudioEventsPlugin;
+
+impl Plugin for AudioEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SoundBank>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(PostUpdate, (play_event_sounds, play_footstep_sounds));
+    }
+}
+
+/// A sound with variations, one of which is picked at random each time.
+#[derive(Clone, Debug)]
+pub struct SoundCue {
+    pub clips: Vec<Handle<AudioSource>>,
+    pub volume: f32,
+    /// How much the playback speed varies either way, e.g. 0.1 plays between
+    /// 0.9 and 1.1 times as fast so repeats don't sound identical.
+    pub speed_variance: f32,
+}
+
+impl SoundCue {
+    pub fn new(clips: Vec<Handle<AudioSource>>) -> Self {
+        Self {
+            clips,
+            volume: 1.0,
+            speed_variance: 0.05,
+        }
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+}
+
+/// The sounds played for events. Events without a sound are silent.
+#[derive(Resource, Default)]
+pub struct SoundBank {
+    pub fire: Option<SoundCue>,
+    /// Impact sounds by the `Surface` name of what was hit.
+    pub impacts: HashMap<&'static str, SoundCue>,
+    /// Played for impacts on surfaces without a sound of their own.
+    pub default_impact: Option<SoundCue>,
+    pub footstep: Option<SoundCue>,
+    /// Sounds by notify name, e.g. "MagOut" and "MagIn" for reloads.
+    pub notifies: HashMap<&'static str, SoundCue>,
+    pub landings: HashMap<LandingKind, SoundCue>,
+}
+
+fn play_event_sounds(
+    mut commands: Commands,
+    bank: Res<SoundBank>,
+    mut fires: EventReader<FireEvent>,
+    mut hits: EventReader<HitEvent>,
+    mut notifies: EventReader<NotifyEvent>,
+    mut landings: EventReader<LandedEvent>,
+    surfaces: Query<&Surface>,
+    parents: Query<&ChildOf>,
+    surface_effects: Option<Res<SurfaceEffects>>,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    let rng = effect_rng.stream(EVENT_SOUND_STREAM);
+    let mut play = |cue: Option<&SoundCue>, position: Vec3| play(&mut commands, rng, cue, position);
+
+    for fire in fires.read() {
+        play(bank.fire.as_ref(), fire.meta.position);
+    }
+    for hit in hits.read() {
+        let surface = find_surface(hit.target, &surfaces, &parents);
+        let library = library_cue(
+            surface_effects
+                .as_ref()
+                .map(|effects| effects.impact_sounds(surface)),
+        );
+        let cue = library.as_ref().or_else(|| {
+            surface
+                .and_then(|surface| bank.impacts.get(surface))
+                .or(bank.default_impact.as_ref())
+        });
+        play(cue, hit.meta.position);
+    }
+    for notify in notifies.read() {
+        play(bank.notifies.get(notify.name), notify.meta.position);
+    }
+    for landing in landings.read() {
+        play(bank.landings.get(&landing.kind), landing.meta.position);
+    }
+}
+
+/// Plays footsteps by the surface under them. This is its own system because
+/// the ground trace's `Hitscan` writes the `HitEvent`s read above.
+fn play_footstep_sounds(
+    mut commands: Commands,
+    bank: Res<SoundBank>,
+    mut footsteps: EventReader<FootstepEvent>,
+    surface_effects: Option<Res<SurfaceEffects>>,
+    hitscan: Hitscan,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    let rng = effect_rng.stream(FOOTSTEP_SOUND_STREAM);
+    for footstep in footsteps.read() {
+        let surface = hitscan
+            .cast_ray(
+                footstep.meta.position + Vec3::Y * 0.5,
+                Dir3::NEG_Y,
+                1.0,
+                Some(footstep.meta.entity),
+            )
+            .and_then(|ground| ground.surface);
+        let library = library_cue(
+            surface_effects
+                .as_ref()
+                .map(|effects| effects.footstep_sounds(surface)),
+        );
+        play(
+            &mut commands,
+            rng,
+            library.as_ref().or(bank.footstep.as_ref()),
+            footstep.meta.position,
+        );
+    }
+}
+
+/// Sounds from the surface library win over the bank's.
+fn library_cue(sounds: Option<&[Handle<AudioSource>]>) -> Option<SoundCue> {
+    sounds
+        .filter(|sounds| !sounds.is_empty())
+        .map(|sounds| SoundCue::new(sounds.to_vec()))
+}
+
+fn play(commands: &mut Commands, rng: &mut impl Rng, cue: Option<&SoundCue>, position: Vec3) {
+    let Some(cue) = cue else {
+        return;
+    };
+    let Some(clip) = cue.clips.choose(rng) else {
+        return;
+    };
+    let speed = 1.0 + rng.gen_range(-1.0..=1.0) * cue.speed_variance;
+    commands.spawn((
+        AudioPlayer::new(clip.clone()),
+        PlaybackSettings::DESPAWN
+            .with_spatial(true)
+            .with_volume(Volume::Linear(cue.volume))
+            .with_speed(speed),
+        Transform::from_translation(position),
+    ));
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    asset::RenderAssetUsages,
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use rand::{seq::SliceRandom, Rng};
+
+/// Camera facing quads, and the sprite muzzle flash built on them. The sprite
+/// flash is a cheaper alternative to the particle flashes that reads better in
+/// stylized art: one of a few star shaped frames, randomly rolled and scaled,
+/// drawn additively.
+pub struct BillboardPlugin;
+
+impl Plugin for BillboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Billboard>();
+        app.init_resource::<MuzzleFlashSprites>();
+        app.add_systems(
+            PostUpdate,
+            face_camera.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Turns a quad in the XY plane to face the camera, rolled by `roll` radians.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Billboard {
+    pub roll: f32,
+}
+
+/// How big sprite muzzle flashes are across, in meters.
+pub(crate) const MUZZLE_FLASH_SPRITE_SIZE: f32 = 0.3;
+
+/// The frames of the sprite muzzle flash, in the assets of the world the plugin
+/// was added to.
+#[derive(Resource)]
+pub(crate) struct MuzzleFlashSprites {
+    quad: Handle<Mesh>,
+    frames: Vec<Handle<StandardMaterial>>,
+}
+
+impl MuzzleFlashSprites {
+    /// A random flash about `size` meters across at `translation`, e.g. the
+    /// muzzle.
+    pub(crate) fn sprite(&self, translation: Vec3, size: f32, rng: &mut impl Rng) -> impl Bundle {
+        let frame = self.frames.choose(rng).cloned().unwrap_or_default();
+        (
+            Mesh3d(self.quad.clone()),
+            MeshMaterial3d(frame),
+            Billboard {
+                roll: rng.gen_range(0.0..TAU),
+            },
+            Transform::from_translation(translation)
+                .with_scale(Vec3::splat(size * rng.gen_range(0.8..1.2))),
+            NotShadowCaster,
+        )
+    }
+}
+
+fn face_camera(
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut billboards: Query<(&Billboard, &mut Transform, Option<&ChildOf>)>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    let Some((_, camera)) = cameras.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+    for (billboard, mut transform, parent) in billboards.iter_mut() {
+        // The quad faces +Z, as does the camera's back, so take its rotation.
+        let rotation = camera.rotation() * Quat::from_rotation_z(billboard.roll);
+        let parent_rotation = parent
+            .and_then(|parent| global_transforms.get(parent.parent()).ok())
+            .map_or(Quat::IDENTITY, GlobalTransform::rotation);
+        transform.rotation = parent_rotation.inverse() * rotation;
+    }
+}
+
+impl FromWorld for MuzzleFlashSprites {
+    fn from_world(world: &mut World) -> Self {
+        let frames: Vec<_> = [(4, 0.0), (5, 0.4), (6, 0.9)]
+            .into_iter()
+            .map(|(spikes, phase)| {
+                let image = world
+                    .resource_mut::<Assets<Image>>()
+                    .add(flash_frame(spikes, phase));
+                world
+                    .resource_mut::<Assets<StandardMaterial>>()
+                    .add(StandardMaterial {
+                        base_color_texture: Some(image),
+                        unlit: true,
+                        alpha_mode: AlphaMode::Add,
+                        cull_mode: None,
+                        ..default()
+                    })
+            })
+            .collect();
+        Self {
+            quad: world
+                .resource_mut::<Assets<Mesh>>()
+                .add(Rectangle::new(1.0, 1.0)),
+            frames,
+        }
+    }
+}
+
+/// Draws a star with `spikes` points, hot white in the middle fading to orange.
+fn flash_frame(spikes: u32, phase: f32) -> Image {
+    const SIZE: u32 = 64;
+    let hot = Vec3::new(1.0, 0.95, 0.8);
+    let cool = Vec3::new(1.0, 0.45, 0.1);
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let p = (Vec2::new(x as f32, y as f32) + 0.5) / SIZE as f32 * 2.0 - 1.0;
+            let angle = p.y.atan2(p.x);
+            let spike = (angle * spikes as f32 / 2.0 + phase).cos().abs().powi(8);
+            let reach = 0.3 + 0.7 * spike;
+            let intensity = (1.0 - p.length() / reach).clamp(0.0, 1.0).powi(2);
+            let color = cool.lerp(hot, intensity) * intensity;
+            data.extend(
+                [color.x, color.y, color.z, intensity].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8),
+            );
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
@@ -0,0 +1,145 @@
+use std::f32::consts::TAU;
+
+use bevy::{app::Animation, prelude::*};
+
+use crate::character::{RigBone, RigMap};
+use crate::ik::solve_two_bone_ik;
+use crate::pose_authority::apply_pose_authority;
+use crate::state::{LowerBodyState, PlayerAnimationState};
+
+/// An always on procedural layer of breathing and weight shifting, added on
+/// top of whatever is playing so idle characters never look like statues.
+/// Breathing gets faster and deeper with [`Breathing::exertion`], which builds
+/// up while sprinting and recovers otherwise.
+pub struct BreathingPlugin;
+
+impl Plugin for BreathingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Breathing>();
+        app.add_systems(
+            PostUpdate,
+            breathe
+                .after(Animation)
+                .before(solve_two_bone_ik)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Add to a character root with a `RigMap`. The chest rises about the local X
+/// axes of the spine bones, and the weight shifts by rolling the hips about
+/// their local Z axis, with the spine counter rotating to keep the head level.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Breathing {
+    /// How out of breath the character is, from 0 at rest to 1.
+    pub exertion: f32,
+    /// How much exertion builds up per second of sprinting.
+    pub sprint_exertion_rate: f32,
+    /// How much exertion recovers per second when not sprinting.
+    pub recovery_rate: f32,
+    /// Breaths per second at rest and when fully exerted.
+    pub breath_rate: (f32, f32),
+    /// How far the chest rises in degrees at rest and when fully exerted.
+    pub breath_amplitude: (f32, f32),
+    /// The time in seconds to shift the weight onto one foot and back.
+    pub weight_shift_period: f32,
+    /// How far the hips roll in degrees when shifting the weight. Only while
+    /// standing, it fades out when moving.
+    pub weight_shift_amplitude: f32,
+    breath_phase: f32,
+    weight_shift_phase: f32,
+    /// How much of the weight shift is applied, eased in when standing.
+    standing: f32,
+}
+
+impl Default for Breathing {
+    fn default() -> Self {
+        Self {
+            exertion: 0.0,
+            sprint_exertion_rate: 0.15,
+            recovery_rate: 0.05,
+            breath_rate: (0.25, 0.75),
+            breath_amplitude: (1.0, 4.0),
+            weight_shift_period: 7.0,
+            weight_shift_amplitude: 1.5,
+            breath_phase: 0.0,
+            weight_shift_phase: 0.0,
+            standing: 0.0,
+        }
+    }
+}
+
+impl Breathing {
+    /// Adds exertion, e.g. after a climb or a fight, clamped to 1.
+    pub fn add_exertion(&mut self, amount: f32) {
+        self.exertion = (self.exertion + amount).clamp(0.0, 1.0);
+    }
+
+    /// How far the chest is risen right now, from 0 breathed out to 1 in.
+    pub fn breath(&self) -> f32 {
+        // Breathing in is quicker than breathing out.
+        let t = self.breath_phase.powf(0.7);
+        0.5 - 0.5 * (t * TAU).cos()
+    }
+
+    fn update(&mut self, sprinting: bool, standing: bool, delta_secs: f32) {
+        if sprinting {
+            self.add_exertion(self.sprint_exertion_rate * delta_secs);
+        } else {
+            self.add_exertion(-self.recovery_rate * delta_secs);
+        }
+        // The phases are integrated so a changing rate doesn't make them jump.
+        let rate = self.breath_rate.0.lerp(self.breath_rate.1, self.exertion);
+        self.breath_phase = (self.breath_phase + rate * delta_secs).fract();
+        let shift_rate = 1.0 / self.weight_shift_period.max(f32::EPSILON);
+        self.weight_shift_phase = (self.weight_shift_phase + shift_rate * delta_secs).fract();
+        let target = if standing { 1.0 } else { 0.0 };
+        self.standing = self.standing.lerp(target, (2.0 * delta_secs).min(1.0));
+    }
+}
+
+fn breathe(
+    mut characters: Query<(Entity, &mut Breathing, &RigMap)>,
+    states: Query<&PlayerAnimationState>,
+    mut transforms: Query<&mut Transform>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    for (root, mut breathing, rig) in characters.iter_mut() {
+        let state = children
+            .iter_descendants(root)
+            .find_map(|e| states.get(e).ok());
+        let (sprinting, standing, time_scale) = match state {
+            Some(state) => (
+                state.is_sprinting(),
+                state.lower_body_state() == LowerBodyState::Idle,
+                state.time_scale(),
+            ),
+            None => (false, true, 1.0),
+        };
+        breathing.update(sprinting, standing, time.delta_secs() * time_scale);
+
+        let amplitude = breathing
+            .breath_amplitude
+            .0
+            .lerp(breathing.breath_amplitude.1, breathing.exertion);
+        // Split over the two chest bones, and taken back out at the neck.
+        let chest = (amplitude * breathing.breath()).to_radians() * 0.5;
+        let roll = breathing.weight_shift_amplitude.to_radians()
+            * (breathing.weight_shift_phase * TAU).sin()
+            * breathing.standing;
+
+        let mut rotate = |bone: RigBone, rotation: Quat| {
+            if let Some(mut transform) = rig.get(bone).and_then(|e| transforms.get_mut(e).ok()) {
+                transform.rotation *= rotation;
+            }
+        };
+        rotate(RigBone::Hips, Quat::from_rotation_z(roll));
+        rotate(RigBone::Spine, Quat::from_rotation_z(-roll));
+        rotate(RigBone::Spine1, Quat::from_rotation_x(chest));
+        rotate(RigBone::Spine2, Quat::from_rotation_x(chest));
+        rotate(RigBone::Neck, Quat::from_rotation_x(-2.0 * chest));
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+
+use crate::events::{ExplosionEvent, FireEvent, LandedEvent, LandingKind};
+use crate::xr::XrMode;
+
+/// Camera shake and recoil kick. Add a [`CameraKick`] to the camera. Shots,
+/// landings and explosions near the camera add trauma, which shakes the camera
+/// by its square and wears off over time, and kick the view in a direction
+/// that springs back.
+///
+/// The shake is applied on top of the camera transform after the game has
+/// moved the camera and taken off again at the start of the next frame, so
+/// camera controllers never see it. There's no shake or kick in XR mode, see
+/// [`XrMode`].
+pub struct CameraKickPlugin;
+
+impl Plugin for CameraKickPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CameraKick>();
+        app.add_systems(PreUpdate, remove_camera_kick);
+        app.add_systems(
+            PostUpdate,
+            (trigger_camera_kicks, apply_camera_kick)
+                .chain()
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// How much one kind of event shakes and kicks the camera, at the camera.
+#[derive(Reflect, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct KickSource {
+    /// Added to the trauma, which is in [0, 1].
+    pub trauma: f32,
+    /// The kick in radians, x is yaw to the right and y is pitch up.
+    pub kick: Vec2,
+}
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraKick {
+    pub fire: KickSource,
+    pub light_landing: KickSource,
+    pub hard_landing: KickSource,
+    /// Only for explosions that ask to shake the camera.
+    pub explosion: KickSource,
+    /// Events this far from the camera or further don't affect it. Closer ones
+    /// fall off linearly.
+    pub falloff_distance: f32,
+    /// The most the camera turns at full trauma, in radians.
+    pub max_shake_angle: f32,
+    /// The most the camera moves at full trauma, in meters.
+    pub max_shake_offset: f32,
+    /// How fast the shake wobbles.
+    pub shake_frequency: f32,
+    /// How much trauma wears off per second.
+    pub trauma_decay: f32,
+    /// How fast the kick springs back, as a fraction per second.
+    pub kick_recovery: f32,
+    trauma: f32,
+    kick: Vec2,
+    /// The offset applied this frame, in the camera's local space.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    applied: Option<Transform>,
+}
+
+impl Default for CameraKick {
+    fn default() -> Self {
+        Self {
+            fire: KickSource {
+                trauma: 0.1,
+                kick: Vec2::new(0.0, 0.01),
+            },
+            light_landing: KickSource {
+                trauma: 0.15,
+                kick: Vec2::new(0.0, -0.02),
+            },
+            hard_landing: KickSource {
+                trauma: 0.5,
+                kick: Vec2::new(0.0, -0.06),
+            },
+            explosion: KickSource {
+                trauma: 0.8,
+                kick: Vec2::new(0.0, 0.04),
+            },
+            falloff_distance: 30.0,
+            max_shake_angle: 3f32.to_radians(),
+            max_shake_offset: 0.05,
+            shake_frequency: 15.0,
+            trauma_decay: 1.2,
+            kick_recovery: 10.0,
+            trauma: 0.0,
+            kick: Vec2::ZERO,
+            applied: None,
+        }
+    }
+}
+
+impl CameraKick {
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    /// Kicks the view, x is yaw to the right and y is pitch up in radians.
+    pub fn kick(&mut self, kick: Vec2) {
+        self.kick += kick;
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Adds a source as felt from `distance` away.
+    fn add_source(&mut self, source: KickSource, distance: f32) {
+        let falloff = (1.0 - distance / self.falloff_distance.max(f32::EPSILON)).max(0.0);
+        self.add_trauma(source.trauma * falloff);
+        self.kick(source.kick * falloff);
+    }
+}
+
+fn trigger_camera_kicks(
+    mut cameras: Query<(&mut CameraKick, &GlobalTransform)>,
+    mut fires: EventReader<FireEvent>,
+    mut landings: EventReader<LandedEvent>,
+    mut explosions: EventReader<ExplosionEvent>,
+) {
+    let fires: Vec<Vec3> = fires.read().map(|fire| fire.meta.position).collect();
+    let landings: Vec<(LandingKind, Vec3)> = landings
+        .read()
+        .map(|landing| (landing.kind, landing.meta.position))
+        .collect();
+    let explosions: Vec<Vec3> = explosions
+        .read()
+        .filter(|explosion| explosion.shake)
+        .map(|explosion| explosion.meta.position)
+        .collect();
+    for (mut camera_kick, global_transform) in cameras.iter_mut() {
+        let camera = global_transform.translation();
+        for position in fires.iter() {
+            let source = camera_kick.fire;
+            camera_kick.add_source(source, camera.distance(*position));
+        }
+        for (kind, position) in landings.iter() {
+            let source = match kind {
+                LandingKind::Hard => camera_kick.hard_landing,
+                LandingKind::Light | LandingKind::Roll => camera_kick.light_landing,
+            };
+            camera_kick.add_source(source, camera.distance(*position));
+        }
+        for position in explosions.iter() {
+            let source = camera_kick.explosion;
+            camera_kick.add_source(source, camera.distance(*position));
+        }
+    }
+}
+
+pub fn apply_camera_kick(
+    mut cameras: Query<(&mut CameraKick, &mut Transform)>,
+    xr: Option<Res<XrMode>>,
+    time: Res<Time>,
+) {
+    let delta_secs = time.delta_secs();
+    let t = time.elapsed_secs();
+    let xr = xr.is_some_and(|xr| xr.enabled);
+    for (mut camera_kick, mut transform) in cameras.iter_mut() {
+        let camera_kick = &mut *camera_kick;
+        // Moving the view under a headset makes players sick.
+        if xr {
+            camera_kick.trauma = 0.0;
+            camera_kick.kick = Vec2::ZERO;
+            continue;
+        }
+        camera_kick.trauma = (camera_kick.trauma - camera_kick.trauma_decay * delta_secs).max(0.0);
+        camera_kick.kick *= (1.0 - camera_kick.kick_recovery * delta_secs).max(0.0);
+
+        // Squaring the trauma makes small hits subtle and big ones violent.
+        let shake = camera_kick.trauma * camera_kick.trauma;
+        let frequency = camera_kick.shake_frequency;
+        let angle = camera_kick.max_shake_angle * shake;
+        let offset = camera_kick.max_shake_offset * shake;
+        let rotation = Quat::from_euler(
+            EulerRot::YXZ,
+            -camera_kick.kick.x + angle * noise(t * frequency, 0.0),
+            camera_kick.kick.y + angle * noise(t * frequency, 1.0),
+            angle * noise(t * frequency, 2.0),
+        );
+        let translation = offset
+            * Vec3::new(
+                noise(t * frequency, 3.0),
+                noise(t * frequency, 4.0),
+                noise(t * frequency, 5.0),
+            );
+
+        let applied = Transform::from_translation(translation).with_rotation(rotation);
+        transform.translation += transform.rotation * applied.translation;
+        transform.rotation *= applied.rotation;
+        camera_kick.applied = Some(applied);
+    }
+}
+
+/// Takes off the shake applied last frame, so the game moves the camera from
+/// where it put it.
+fn remove_camera_kick(mut cameras: Query<(&mut CameraKick, &mut Transform)>) {
+    for (mut camera_kick, mut transform) in cameras.iter_mut() {
+        let Some(applied) = camera_kick.applied.take() else {
+            continue;
+        };
+        transform.rotation *= applied.rotation.inverse();
+        let rotation = transform.rotation;
+        transform.translation -= rotation * applied.translation;
+    }
+}
+
+/// Smooth noise in [-1, 1], with a different curve for each seed.
+fn noise(t: f32, seed: f32) -> f32 {
+    let seed = seed * 12.9898;
+    ((t + seed).sin() + (t * 2.3 + seed * 1.7).sin() * 0.5 + (t * 5.1 + seed * 0.3).sin() * 0.25)
+        / 1.75
+}
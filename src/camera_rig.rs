@@ -0,0 +1,235 @@
+use bevy::{app::Animation, prelude::*};
+
+use crate::ads::AimDownSights;
+use crate::anim::CharAnimSet;
+use crate::camera_kick::apply_camera_kick;
+use crate::damping::damp;
+use crate::hitscan::Hitscan;
+use crate::lean::{lean, Lean};
+use crate::state::PlayerAnimationState;
+use crate::velocity::drive_animation_from_velocity;
+
+/// Third-person camera. A [`CameraRig`] on the camera orbits it around a
+/// character, looking over one shoulder, and holds the aim yaw and pitch that
+/// the character's aim offset follows, so the view and the spine never
+/// disagree. A spring arm pulls the camera in front of anything between it and
+/// the character. It also leans with the character's `Lean`, moves in as
+/// `AimDownSights` zooms, and is shaken by a `CameraKick` on top.
+pub struct CameraRigPlugin;
+
+impl Plugin for CameraRigPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CameraRig>();
+        app.add_systems(
+            Update,
+            aim_with_camera_rigs
+                .after(drive_animation_from_velocity)
+                .in_set(CharAnimSet::ModifyInput),
+        );
+        app.add_systems(
+            PostUpdate,
+            follow_camera_rigs
+                .after(Animation)
+                .after(lean)
+                .before(apply_camera_kick)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Shoulder {
+    Left,
+    #[default]
+    Right,
+}
+
+impl Shoulder {
+    pub fn swapped(self) -> Self {
+        match self {
+            Shoulder::Left => Shoulder::Right,
+            Shoulder::Right => Shoulder::Left,
+        }
+    }
+
+    /// 1 for the right shoulder and -1 for the left.
+    fn side(self) -> f32 {
+        match self {
+            Shoulder::Left => -1.0,
+            Shoulder::Right => 1.0,
+        }
+    }
+}
+
+/// Add to the camera. Set the character's input before
+/// [`aim_with_camera_rigs`], which replaces its look with the rig's.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct CameraRig {
+    /// The character root the camera orbits.
+    pub target: Entity,
+    /// The point orbited, from the target's root and turned with the view,
+    /// e.g. the head.
+    pub pivot: Vec3,
+    /// How far to the side of the pivot the camera looks over, in meters.
+    pub shoulder_offset: f32,
+    pub shoulder: Shoulder,
+    /// Looks over the shoulder the character leans to while it leans.
+    pub swap_with_lean: bool,
+    /// How far behind the shoulder the camera sits, in meters.
+    pub distance: f32,
+    /// How far behind when fully aimed down sights.
+    pub ads_distance: f32,
+    /// The pitch range in radians, positive up.
+    pub pitch_limits: (f32, f32),
+    /// How far the camera keeps from walls, in meters.
+    pub probe_radius: f32,
+    /// Seconds for the arm and shoulder to get halfway to where they're
+    /// heading. The arm snaps in when something comes between.
+    pub halflife: f32,
+    yaw: f32,
+    pitch: f32,
+    /// The current length of the arm and side of the shoulder, from -1 on the
+    /// left to 1 on the right.
+    arm: Option<f32>,
+    side: Option<f32>,
+}
+
+impl CameraRig {
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            pivot: Vec3::new(0.0, 1.6, 0.0),
+            shoulder_offset: 0.4,
+            shoulder: Shoulder::Right,
+            swap_with_lean: true,
+            distance: 2.5,
+            ads_distance: 1.2,
+            pitch_limits: (-1.2, 1.2),
+            probe_radius: 0.2,
+            halflife: 0.1,
+            yaw: 0.0,
+            pitch: 0.0,
+            arm: None,
+            side: None,
+        }
+    }
+
+    pub fn with_shoulder(mut self, shoulder: Shoulder) -> Self {
+        self.shoulder = shoulder;
+        self
+    }
+
+    pub fn swap_shoulder(&mut self) {
+        self.shoulder = self.shoulder.swapped();
+    }
+
+    /// Turns the view, x is yaw to the right and y is pitch up in radians.
+    pub fn add_look(&mut self, look: Vec2) {
+        self.set_look(self.yaw - look.x, self.pitch + look.y);
+    }
+
+    /// Sets the yaw and pitch as in `PlayerAnimationInput`, i.e. the yaw is
+    /// about Y from +Z and the pitch is positive up.
+    pub fn set_look(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(self.pitch_limits.0, self.pitch_limits.1);
+    }
+
+    /// The yaw about Y in radians, 0 facing +Z.
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    /// The pitch in radians, positive up.
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// The rotation of the view, facing +Z like characters.
+    pub fn rotation(&self) -> Quat {
+        // Pitching up is a negative rotation about X.
+        Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(-self.pitch)
+    }
+}
+
+/// Passes the rigs' look to their characters' aim offset.
+pub fn aim_with_camera_rigs(
+    rigs: Query<&CameraRig>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+) {
+    for rig in rigs.iter() {
+        let Some(state_entity) = children
+            .iter_descendants(rig.target)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        if let Some(input) = state.input_mut() {
+            input.look_y = rig.yaw;
+            input.look_x = rig.pitch;
+        }
+    }
+}
+
+fn follow_camera_rigs(
+    mut rigs: Query<(&mut CameraRig, &mut Transform)>,
+    targets: Query<&Transform, Without<CameraRig>>,
+    global_transforms: Query<&GlobalTransform>,
+    parents: Query<&ChildOf>,
+    leans: Query<&Lean>,
+    ads: Query<&AimDownSights>,
+    hitscan: Hitscan,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for (mut rig, mut transform) in rigs.iter_mut() {
+        let rig = rig.as_mut();
+        let Ok(target) = targets.get(rig.target) else {
+            continue;
+        };
+        // Where the root is this frame, under its parent as of last frame.
+        let parent = parents
+            .get(rig.target)
+            .ok()
+            .and_then(|parent| global_transforms.get(parent.parent()).ok())
+            .copied()
+            .unwrap_or_default();
+        let root = parent.transform_point(target.translation);
+
+        let lean = leans.get(rig.target).ok();
+        let shoulder = match lean.map_or(0.0, Lean::angle) {
+            angle if rig.swap_with_lean && angle < 0.0 => Shoulder::Left,
+            angle if rig.swap_with_lean && angle > 0.0 => Shoulder::Right,
+            _ => rig.shoulder,
+        };
+        let side = rig.side.get_or_insert(shoulder.side());
+        damp(side, &shoulder.side(), rig.halflife, dt);
+        let ads_weight = ads.get(rig.target).map_or(0.0, AimDownSights::weight);
+
+        let yaw = Quat::from_rotation_y(rig.yaw);
+        let head = root + yaw * rig.pivot + lean.map_or(Vec3::ZERO, Lean::offset);
+        let shoulder = head + yaw * Vec3::NEG_X * rig.shoulder_offset * *side;
+        let aim = rig.rotation() * Vec3::Z;
+        let desired = shoulder - aim * rig.distance.lerp(rig.ads_distance, ads_weight);
+
+        // Keep the arm short enough to stay in front of whatever is between
+        // the head and the camera, snapping in and easing back out.
+        let Ok((direction, reach)) = Dir3::new_and_length(desired - head) else {
+            *transform = Transform::from_translation(head).looking_to(aim, Vec3::Y);
+            continue;
+        };
+        let room = hitscan
+            .cast_ray(head, direction, reach + rig.probe_radius, Some(rig.target))
+            .map_or(reach, |hit| (hit.distance - rig.probe_radius).clamp(0.0, reach));
+        let arm = rig.arm.get_or_insert(room);
+        if room < *arm {
+            *arm = room;
+        } else {
+            damp(arm, &room, rig.halflife, dt);
+        }
+        *transform = Transform::from_translation(head + direction * *arm).looking_to(aim, Vec3::Y);
+    }
+}
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::character::{find_socket, RigBone, RigMap, Socket};
+use crate::ik::{IkTarget, TwoBoneIk};
+use crate::state::PlayerAnimationState;
+
+/// The socket on a carried prop that the left hand grips.
+pub const LEFT_GRIP_SOCKET: &str = "LeftGrip";
+/// The socket on a carried prop that the right hand grips.
+pub const RIGHT_GRIP_SOCKET: &str = "RightGrip";
+
+/// Picking up and carrying crates, bodies and the like. Put a [`Carryable`] on
+/// the prop and a [`Carrier`] on the character root, then send a [`PickUp`].
+/// While carrying, the upper body holds a carry pose, the hands are pinned to
+/// the prop's grip sockets with IK, and the movement input is capped.
+pub struct CarryPlugin;
+
+impl Plugin for CarryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PickUp>();
+        app.add_event::<DropCarried>();
+        app.register_type::<Carryable>();
+        app.register_type::<Carrier>();
+        app.register_type::<Carrying>();
+        app.add_systems(
+            Update,
+            (pick_up, carry).chain().in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum CarryGrip {
+    /// Held in the right hand, e.g. a bucket or a small crate.
+    OneHand,
+    /// Held in both hands, e.g. a large crate or a body.
+    TwoHand,
+}
+
+/// Something a character can pick up. Add to the prop, along with [`Socket`]
+/// children named [`LEFT_GRIP_SOCKET`] and [`RIGHT_GRIP_SOCKET`] where the
+/// hands go. One hand carries only need the right grip.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Carryable {
+    pub grip: CarryGrip,
+    /// The largest movement input while carrying, as a fraction of a full
+    /// input, e.g. 0.4 to walk slowly under a heavy load.
+    pub max_speed: f32,
+    /// Whether the character can sprint while carrying.
+    pub allow_sprint: bool,
+}
+
+impl Carryable {
+    pub fn new(grip: CarryGrip) -> Self {
+        Self {
+            grip,
+            max_speed: 1.0,
+            allow_sprint: false,
+        }
+    }
+}
+
+/// The carry poses of a character that can pick things up. Add to the
+/// character root.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Carrier {
+    /// Upper body poses, played over the locomotion.
+    pub one_hand_pose: Handle<AnimationClip>,
+    pub two_hand_pose: Handle<AnimationClip>,
+    /// Where the prop is held relative to the character root.
+    pub one_hand_offset: Transform,
+    pub two_hand_offset: Transform,
+}
+
+impl Carrier {
+    pub fn new(
+        one_hand_pose: Handle<AnimationClip>,
+        two_hand_pose: Handle<AnimationClip>,
+    ) -> Self {
+        Self {
+            one_hand_pose,
+            two_hand_pose,
+            one_hand_offset: Transform::from_xyz(-0.25, 0.8, 0.2),
+            two_hand_offset: Transform::from_xyz(0.0, 1.0, 0.45),
+        }
+    }
+
+    fn pose(&self, grip: CarryGrip) -> &Handle<AnimationClip> {
+        match grip {
+            CarryGrip::OneHand => &self.one_hand_pose,
+            CarryGrip::TwoHand => &self.two_hand_pose,
+        }
+    }
+
+    fn offset(&self, grip: CarryGrip) -> Transform {
+        match grip {
+            CarryGrip::OneHand => self.one_hand_offset,
+            CarryGrip::TwoHand => self.two_hand_offset,
+        }
+    }
+}
+
+/// Requests a character to pick up a prop, dropping whatever it's carrying.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PickUp {
+    /// The character root with the [`Carrier`].
+    pub character: Entity,
+    /// The entity with the [`Carryable`].
+    pub prop: Entity,
+}
+
+/// Requests a character to drop what it's carrying. The prop is left where it
+/// was held and its rigid body, if any, takes over again.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DropCarried {
+    pub character: Entity,
+}
+
+/// Added to a character root while it's carrying something.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Carrying {
+    pub prop: Entity,
+}
+
+const HANDS: [RigBone; 2] = [RigBone::LeftHand, RigBone::RightHand];
+
+fn pick_up(
+    mut commands: Commands,
+    mut pick_ups: EventReader<PickUp>,
+    mut drops: EventReader<DropCarried>,
+    carriers: Query<(&Carrier, Option<&Carrying>, Option<&RigMap>)>,
+    carryables: Query<&Carryable>,
+    global_transforms: Query<&GlobalTransform>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    mut iks: Query<&mut TwoBoneIk>,
+) {
+    let pick_ups: Vec<PickUp> = pick_ups
+        .read()
+        .filter(|request| {
+            let carryable = carryables.contains(request.prop);
+            if !carryable {
+                warn!("can't pick up {} as it isn't carryable", request.prop);
+            }
+            carryable && carriers.contains(request.character)
+        })
+        .copied()
+        .collect();
+
+    // Picking something up drops what was carried before.
+    let dropping = drops
+        .read()
+        .map(|request| request.character)
+        .chain(pick_ups.iter().map(|request| request.character));
+    for character in dropping {
+        let Ok((_, Some(carrying), rig)) = carriers.get(character) else {
+            continue;
+        };
+        let mut prop = commands.entity(carrying.prop);
+        prop.remove::<(ChildOf, RigidBodyDisabled)>();
+        if let Ok(global_transform) = global_transforms.get(carrying.prop) {
+            prop.insert(global_transform.compute_transform());
+        }
+        commands.entity(character).remove::<Carrying>();
+        for bone in HANDS.into_iter().filter_map(|bone| rig?.get(bone)) {
+            if let Ok(mut ik) = iks.get_mut(bone) {
+                ik.weight = 0.0;
+            }
+        }
+        if let Some(state_entity) = children
+            .iter_descendants(character)
+            .find(|e| states.contains(*e))
+        {
+            states
+                .get_mut(state_entity)
+                .unwrap()
+                .set_upper_body_pose(None);
+        }
+    }
+
+    for request in pick_ups {
+        let (Ok((carrier, _, _)), Ok(carryable)) =
+            (carriers.get(request.character), carryables.get(request.prop))
+        else {
+            continue;
+        };
+        commands.entity(request.prop).insert((
+            carrier.offset(carryable.grip),
+            ChildOf(request.character),
+            RigidBodyDisabled,
+        ));
+        commands
+            .entity(request.character)
+            .insert(Carrying { prop: request.prop });
+        if let Some(state_entity) = children
+            .iter_descendants(request.character)
+            .find(|e| states.contains(*e))
+        {
+            states
+                .get_mut(state_entity)
+                .unwrap()
+                .set_upper_body_pose(Some(carrier.pose(carryable.grip).clone()));
+        }
+    }
+}
+
+/// Pins the hands to the carried props and caps the movement input.
+fn carry(
+    mut commands: Commands,
+    carriers: Query<(Entity, &Carrying, Option<&RigMap>)>,
+    carryables: Query<&Carryable>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    sockets: Query<&Socket>,
+    mut iks: Query<&mut TwoBoneIk>,
+) {
+    for (root, carrying, rig) in carriers.iter() {
+        let Ok(carryable) = carryables.get(carrying.prop) else {
+            continue;
+        };
+
+        if let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        {
+            let mut state = states.get_mut(state_entity).unwrap();
+            if let Some(input) = state.input_mut() {
+                let direction = input.local_movement_direction;
+                input.local_movement_direction = direction.clamp_length_max(carryable.max_speed);
+                input.is_sprinting &= carryable.allow_sprint;
+            }
+        }
+
+        let Some(rig) = rig else {
+            continue;
+        };
+        let grips = [LEFT_GRIP_SOCKET, RIGHT_GRIP_SOCKET];
+        for (bone, socket) in HANDS.into_iter().zip(grips) {
+            let grip = match (carryable.grip, bone) {
+                // One hand carries are held in the right hand.
+                (CarryGrip::OneHand, RigBone::LeftHand) => None,
+                _ => find_socket(carrying.prop, socket, &children, &sockets),
+            };
+            let Some(bone) = rig.get(bone) else {
+                continue;
+            };
+            let (target, weight) = match grip {
+                Some(grip) => (IkTarget::Entity(grip), 1.0),
+                None => (IkTarget::Point(Vec3::ZERO), 0.0),
+            };
+            if let Ok(mut ik) = iks.get_mut(bone) {
+                ik.target = target;
+                ik.weight = weight;
+                ik.match_target_rotation = true;
+            } else if weight > 0.0 {
+                let mut ik = TwoBoneIk::new(target);
+                ik.match_target_rotation = true;
+                commands.entity(bone).insert(ik);
+            }
+        }
+    }
+}
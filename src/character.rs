@@ -0,0 +1,824 @@
+use bevy::{
+    animation::{AnimationNodeType, AnimationTarget},
+    app::Animation,
+    ecs::system::{EntityCommands, SystemParam},
+    gltf::Gltf,
+    platform::collections::HashMap,
+    prelude::*,
+    render::view::VisibilitySystems,
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::anim::{self, CharAnimSet, GraphBones, PlayerAnimationPaths};
+use crate::anim_graph::{AnimGraphDef, AnimGraphPlugin, GraphValidationError};
+use crate::events::{CharacterFailedEvent, CharacterReadyEvent, EventMeta, EventRouting};
+use crate::ik::{IkTarget, TwoBoneIk};
+use crate::proportions::BodyProportions;
+use crate::state::PlayerAnimationState;
+use crate::utils;
+
+/// Spawns and sets up characters made with [`CharacterBuilder`].
+pub struct CharacterPlugin;
+
+impl Plugin for CharacterPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<AnimGraphPlugin>() {
+            app.add_plugins(AnimGraphPlugin);
+        }
+        app.register_type::<RigMap>();
+        app.add_systems(
+            Update,
+            (
+                spawn_character_scenes,
+                init_characters.before(CharAnimSet::Input),
+                update_foot_ik,
+            ),
+        );
+        app.add_systems(
+            PostUpdate,
+            finish_loading
+                .after(Animation)
+                .before(VisibilitySystems::VisibilityPropagate),
+        );
+    }
+}
+
+/// The root of an animated character. The `PlayerAnimationState` is added to
+/// the `AnimationPlayer` below it once its scene has loaded.
+#[derive(Component)]
+pub struct Player;
+
+/// Builds a character from a glTF file, e.g.
+///
+/// ```ignore
+/// CharacterBuilder::new(asset_server.load("models/gltf/character.glb"))
+///     .with_rig_autodetect()
+///     .with_foot_ik()
+///     .spawn(&mut commands);
+/// ```
+pub struct CharacterBuilder {
+    gltf: Handle<Gltf>,
+    name: Name,
+    transform: Transform,
+    graph: Option<PlayerAnimationPaths>,
+    graph_asset: Option<Handle<AnimGraphDef>>,
+    autodetect_rig: bool,
+    foot_ik: Option<FootIk>,
+    sockets: Vec<SocketDef>,
+    warm_start: bool,
+    placeholder: Option<PlaceholderDef>,
+}
+
+impl CharacterBuilder {
+    pub fn new(gltf: Handle<Gltf>) -> Self {
+        Self {
+            gltf,
+            name: Name::new("Character"),
+            transform: Transform::default(),
+            graph: None,
+            graph_asset: None,
+            autodetect_rig: false,
+            foot_ik: None,
+            sockets: Vec::new(),
+            warm_start: true,
+            placeholder: None,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<Name>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Sets the clips the animation graph is built from. Defaults to the clips
+    /// of the bundled character model.
+    pub fn with_graph(mut self, graph: PlayerAnimationPaths) -> Self {
+        self.graph = Some(graph);
+        self
+    }
+
+    /// Builds the animation graph from a graph file instead, which is checked
+    /// against the glTF file before the character is set up, see
+    /// [`AnimGraphDef::validate`].
+    pub fn with_graph_asset(mut self, graph: Handle<AnimGraphDef>) -> Self {
+        self.graph_asset = Some(graph);
+        self
+    }
+
+    /// Adds a [`RigMap`] to the character once it's loaded, and finds the
+    /// graph's bones through it, so rigs that don't use Mixamo's names work.
+    pub fn with_rig_autodetect(mut self) -> Self {
+        self.autodetect_rig = true;
+        self
+    }
+
+    /// Plants the feet on the ground with IK. This needs the rig map, so it also
+    /// enables rig autodetection.
+    pub fn with_foot_ik(mut self) -> Self {
+        self.autodetect_rig = true;
+        self.foot_ik = Some(FootIk::default());
+        self
+    }
+
+    /// Adds a [`Socket`] child to `bone`, e.g. for attaching a weapon to a hand.
+    pub fn with_socket(mut self, name: &'static str, bone: RigBone, offset: Transform) -> Self {
+        self.autodetect_rig = true;
+        self.sockets.push(SocketDef { name, bone, offset });
+        self
+    }
+
+    /// Shows the character as soon as its scene spawns, instead of keeping it
+    /// hidden until its first animated pose. It shows in its bind pose for a
+    /// frame or so.
+    pub fn without_warm_start(mut self) -> Self {
+        self.warm_start = false;
+        self
+    }
+
+    /// Shows a mesh in place of the character while its assets stream in, e.g.
+    /// a capsule. It's swapped for the character on the frame the character is
+    /// first posed, when a `CharacterReadyEvent` is sent, or removed with a
+    /// `CharacterFailedEvent` if the character can't be set up.
+    pub fn with_placeholder(
+        mut self,
+        mesh: Handle<Mesh>,
+        material: Handle<StandardMaterial>,
+        offset: Transform,
+    ) -> Self {
+        self.placeholder = Some(PlaceholderDef {
+            mesh,
+            material,
+            offset,
+        });
+        self
+    }
+
+    /// Spawns the character root. The scene, animation state, rig map and
+    /// sockets are added once the glTF file has loaded, and the character is
+    /// ready once its clips have loaded too.
+    pub fn spawn<'a>(self, commands: &'a mut Commands) -> EntityCommands<'a> {
+        let mut entity = commands.spawn((
+            Player,
+            CharacterSetup {
+                gltf: self.gltf,
+                graph: self.graph.unwrap_or_default(),
+                graph_asset: self.graph_asset,
+                autodetect_rig: self.autodetect_rig,
+                sockets: self.sockets,
+            },
+            self.name,
+            self.transform,
+            Visibility::default(),
+        ));
+        if let Some(foot_ik) = self.foot_ik {
+            entity.insert(foot_ik);
+        }
+        let root = entity.id();
+        let placeholder = self.placeholder.map(|placeholder| {
+            entity
+                .commands()
+                .spawn((
+                    Mesh3d(placeholder.mesh),
+                    MeshMaterial3d(placeholder.material),
+                    placeholder.offset,
+                    ChildOf(root),
+                ))
+                .id()
+        });
+        entity.insert(CharacterLoading {
+            hide: self.warm_start,
+            placeholder,
+            hidden: Vec::new(),
+            failed: false,
+        });
+        entity
+    }
+}
+
+/// How to finish setting up a character once its glTF file has loaded.
+#[derive(Component)]
+struct CharacterSetup {
+    gltf: Handle<Gltf>,
+    graph: PlayerAnimationPaths,
+    /// The graph file to replace `graph` with once it's loaded and valid.
+    graph_asset: Option<Handle<AnimGraphDef>>,
+    autodetect_rig: bool,
+    sockets: Vec<SocketDef>,
+}
+
+/// A character whose assets are still loading. Unless warm start is off, what
+/// spawns under the root is kept hidden until the animations have posed it, so
+/// it never shows in its bind pose. Removed once the character is ready, or
+/// once it has failed to set up.
+#[derive(Component)]
+struct CharacterLoading {
+    hide: bool,
+    placeholder: Option<Entity>,
+    /// The children that were hidden, and their visibility before.
+    hidden: Vec<(Entity, Visibility)>,
+    /// The character can't be set up, so it's shown as it is instead.
+    failed: bool,
+}
+
+struct PlaceholderDef {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    offset: Transform,
+}
+
+struct SocketDef {
+    name: &'static str,
+    bone: RigBone,
+    offset: Transform,
+}
+
+/// A named attachment point on a character, e.g. a hand grip.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Socket {
+    pub name: &'static str,
+}
+
+/// Finds the socket named `name` on the character `root`.
+pub fn find_socket(
+    root: Entity,
+    name: &str,
+    children: &Query<&Children>,
+    sockets: &Query<&Socket>,
+) -> Option<Entity> {
+    children
+        .iter_descendants(root)
+        .find(|e| sockets.get(*e).is_ok_and(|socket| socket.name == name))
+}
+
+/// The bones that are looked up by role rather than by name.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Hash, PartialEq)]
+pub enum RigBone {
+    Hips,
+    Spine,
+    Spine1,
+    Spine2,
+    Neck,
+    Head,
+    LeftArm,
+    LeftForeArm,
+    LeftHand,
+    RightArm,
+    RightForeArm,
+    RightHand,
+    LeftUpLeg,
+    LeftLeg,
+    LeftFoot,
+    RightUpLeg,
+    RightLeg,
+    RightFoot,
+}
+
+impl RigBone {
+    pub const ALL: [Self; 18] = [
+        Self::Hips,
+        Self::Spine,
+        Self::Spine1,
+        Self::Spine2,
+        Self::Neck,
+        Self::Head,
+        Self::LeftArm,
+        Self::LeftForeArm,
+        Self::LeftHand,
+        Self::RightArm,
+        Self::RightForeArm,
+        Self::RightHand,
+        Self::LeftUpLeg,
+        Self::LeftLeg,
+        Self::LeftFoot,
+        Self::RightUpLeg,
+        Self::RightLeg,
+        Self::RightFoot,
+    ];
+
+    /// Bone names used for this bone by common rigs, lowercase and without any
+    /// namespace prefix such as `mixamorig:`. The first name is the Mixamo one.
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Self::Hips => &["hips", "pelvis"],
+            Self::Spine => &["spine", "spine_01"],
+            Self::Spine1 => &["spine1", "spine_02"],
+            Self::Spine2 => &["spine2", "spine_03"],
+            Self::Neck => &["neck", "neck_01"],
+            Self::Head => &["head"],
+            Self::LeftArm => &["leftarm", "upperarm_l"],
+            Self::LeftForeArm => &["leftforearm", "lowerarm_l"],
+            Self::LeftHand => &["lefthand", "hand_l"],
+            Self::RightArm => &["rightarm", "upperarm_r"],
+            Self::RightForeArm => &["rightforearm", "lowerarm_r"],
+            Self::RightHand => &["righthand", "hand_r"],
+            Self::LeftUpLeg => &["leftupleg", "thigh_l"],
+            Self::LeftLeg => &["leftleg", "calf_l"],
+            Self::LeftFoot => &["leftfoot", "foot_l"],
+            Self::RightUpLeg => &["rightupleg", "thigh_r"],
+            Self::RightLeg => &["rightleg", "calf_r"],
+            Self::RightFoot => &["rightfoot", "foot_r"],
+        }
+    }
+
+    /// Gets the bone that a node name refers to, if any.
+    pub fn from_bone_name(name: &str) -> Option<Self> {
+        let name = name.rsplit(':').next().unwrap_or(name).to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|bone| bone.aliases().contains(&name.as_str()))
+    }
+}
+
+/// The bone entities of a character by role, so systems don't need to know the
+/// naming convention of the rig.
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component, Default)]
+pub struct RigMap {
+    bones: HashMap<RigBone, Entity>,
+}
+
+impl RigMap {
+    /// Detects the bones among the animated descendants of `root`.
+    pub fn detect(
+        root: Entity,
+        children: &Query<&Children>,
+        names: &Query<&Name>,
+        animation_targets: &Query<&AnimationTarget>,
+    ) -> Self {
+        let mut bones = HashMap::default();
+        for entity in children.iter_descendants(root) {
+            if !animation_targets.contains(entity) {
+                continue;
+            }
+            let Some(bone) = names.get(entity).ok().and_then(|n| RigBone::from_bone_name(n))
+            else {
+                continue;
+            };
+            bones.entry(bone).or_insert(entity);
+        }
+        Self { bones }
+    }
+
+    pub fn get(&self, bone: RigBone) -> Option<Entity> {
+        self.bones.get(&bone).copied()
+    }
+
+    /// `bones` with each name that's a bone of a known role swapped for the
+    /// name of this rig's bone in that role, so a graph written for Mixamo's
+    /// names drives rigs with other conventions.
+    pub fn graph_bones(&self, bones: &GraphBones, names: &Query<&Name>) -> GraphBones {
+        let rename = |name: &String| {
+            RigBone::from_bone_name(name)
+                .and_then(|bone| self.get(bone))
+                .and_then(|entity| names.get(entity).ok())
+                .map_or_else(|| name.clone(), |name| name.as_str().into())
+        };
+        GraphBones {
+            upper_body: rename(&bones.upper_body),
+            left_leg: rename(&bones.left_leg),
+            right_leg: rename(&bones.right_leg),
+            spine: rename(&bones.spine),
+            hips: rename(&bones.hips),
+            spine1: rename(&bones.spine1),
+            bullet_point: rename(&bones.bullet_point),
+        }
+    }
+}
+
+/// Reads where a character's bones and sockets are in global world space, e.g.
+/// to spawn a tracer from a muzzle bone or place a marker over the head. Read
+/// in `CharAnimSet::PostPose` to get this frame's pose, in `Update` it's last
+/// frame's.
+#[derive(SystemParam)]
+pub struct RigTransforms<'w, 's> {
+    rigs: Query<'w, 's, &'static RigMap>,
+    sockets: Query<'w, 's, &'static Socket>,
+    names: Query<'w, 's, &'static Name>,
+    children: Query<'w, 's, &'static Children>,
+    global_transforms: Query<'w, 's, &'static GlobalTransform>,
+}
+
+impl RigTransforms<'_, '_> {
+    /// A bone of the character `root` by role, which needs a [`RigMap`].
+    pub fn bone(&self, root: Entity, bone: RigBone) -> Option<GlobalTransform> {
+        let bone = self.rigs.get(root).ok()?.get(bone)?;
+        self.global_transforms.get(bone).ok().copied()
+    }
+
+    /// A node under the character `root` by its name in the glTF file, e.g.
+    /// `mixamorig:RightHand` or a muzzle node on a weapon.
+    pub fn named(&self, root: Entity, name: &str) -> Option<GlobalTransform> {
+        let node = utils::find_child_with_name(root, name, &self.children, &self.names)?;
+        self.global_transforms.get(node).ok().copied()
+    }
+
+    /// A [`Socket`] on the character `root` by name.
+    pub fn socket(&self, root: Entity, name: &str) -> Option<GlobalTransform> {
+        let socket = find_socket(root, name, &self.children, &self.sockets)?;
+        self.global_transforms.get(socket).ok().copied()
+    }
+}
+
+/// Keeps the feet on the ground by casting down from each foot and reaching
+/// for the hit point with two bone IK.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component, Default)]
+pub struct FootIk {
+    /// The height of the ankle above the sole.
+    pub ankle_height: f32,
+    /// How far above the foot the ground ray starts, which is the highest a
+    /// foot can be raised.
+    pub max_step_up: f32,
+    /// How far below the foot to look for ground.
+    pub max_step_down: f32,
+}
+
+impl Default for FootIk {
+    fn default() -> Self {
+        Self {
+            ankle_height: 0.1,
+            max_step_up: 0.5,
+            max_step_down: 0.5,
+        }
+    }
+}
+
+fn spawn_character_scenes(
+    mut commands: Commands,
+    mut characters: Query<(Entity, &mut CharacterSetup), Without<SceneRoot>>,
+    gltfs: Res<Assets<Gltf>>,
+    graphs: Res<Assets<AnimGraphDef>>,
+    asset_server: Res<AssetServer>,
+    mut graph_errors: EventWriter<GraphValidationError>,
+    mut loading: Query<&mut CharacterLoading>,
+) {
+    let mut fail = |commands: &mut Commands, entity: Entity| {
+        commands.entity(entity).remove::<CharacterSetup>();
+        if let Ok(mut loading) = loading.get_mut(entity) {
+            loading.failed = true;
+        }
+    };
+    for (entity, mut setup) in characters.iter_mut() {
+        let Some(gltf) = gltfs.get(&setup.gltf) else {
+            continue;
+        };
+        if let Some(handle) = setup.graph_asset.clone() {
+            let Some(graph) = graphs.get(&handle) else {
+                if asset_server.load_state(handle.id()).is_failed() {
+                    error!("character graph failed to load");
+                    fail(&mut commands, entity);
+                }
+                continue;
+            };
+            match graph.validate(gltf) {
+                Ok(paths) => {
+                    setup.graph = paths;
+                    setup.graph_asset = None;
+                }
+                Err(diagnostics) => {
+                    for diagnostic in diagnostics.iter() {
+                        error!("invalid character graph: {diagnostic}");
+                    }
+                    graph_errors.write(GraphValidationError {
+                        graph: handle.id(),
+                        character: Some(entity),
+                        diagnostics,
+                    });
+                    fail(&mut commands, entity);
+                    continue;
+                }
+            }
+        }
+        let Some(scene) = gltf.default_scene.clone().or(gltf.scenes.first().cloned()) else {
+            error!("character glTF has no scenes");
+            fail(&mut commands, entity);
+            continue;
+        };
+        commands.entity(entity).insert(SceneRoot(scene));
+    }
+}
+
+/// Sets up the animation graph, rig map and sockets of each character once its
+/// scene has spawned.
+fn init_characters(
+    mut commands: Commands,
+    new_anim_players: Query<Entity, Added<AnimationPlayer>>,
+    asset_server: Res<AssetServer>,
+    children: Query<&Children>,
+    parents: Query<&ChildOf>,
+    names: Query<&Name>,
+    players: Query<&Player>,
+    setups: Query<&CharacterSetup>,
+    mut animation_graphs: ResMut<Assets<AnimationGraph>>,
+    animation_targets: Query<&AnimationTarget>,
+    mut loading: Query<&mut CharacterLoading>,
+) {
+    for entity in new_anim_players.iter() {
+        let Some((root, _)) = utils::find_upwards(entity, &parents, &players) else {
+            // This is not a player.
+            continue;
+        };
+        let setup = setups.get(root).ok();
+        let rig = setup
+            .filter(|setup| setup.autodetect_rig)
+            .map(|_| RigMap::detect(root, &children, &names, &animation_targets));
+        let mut paths = setup.map_or_else(PlayerAnimationPaths::default, |s| s.graph.clone());
+        // Find the graph's bones by role, whatever the rig calls them.
+        if let Some(rig) = &rig {
+            paths.bones = rig.graph_bones(&paths.bones, &names);
+        }
+
+        let Some((anims, proc_targets, graph, nodes)) = anim::load_player_animations(
+            entity,
+            &paths,
+            &asset_server,
+            &children,
+            &names,
+            &animation_targets,
+            commands.reborrow(),
+            &parents,
+        ) else {
+            error!("character {root} can't be animated, it's shown without animations");
+            commands.entity(root).remove::<CharacterSetup>();
+            if let Ok(mut loading) = loading.get_mut(root) {
+                loading.failed = true;
+            }
+            continue;
+        };
+        commands
+            .entity(entity)
+            .insert(AnimationGraphHandle(animation_graphs.add(graph)))
+            .insert(PlayerAnimationState::new(anims, proc_targets, nodes));
+
+        let Some(setup) = setup else {
+            continue;
+        };
+        if let Some(rig) = rig {
+            for socket in setup.sockets.iter() {
+                let Some(bone) = rig.get(socket.bone) else {
+                    warn!("no {:?} bone for socket {}", socket.bone, socket.name);
+                    continue;
+                };
+                commands.spawn((
+                    Socket { name: socket.name },
+                    socket.offset,
+                    Visibility::default(),
+                    ChildOf(bone),
+                ));
+            }
+            commands.entity(root).insert(rig);
+        }
+        commands.entity(root).remove::<CharacterSetup>();
+    }
+}
+
+/// Hides what spawns under loading characters, and once their clips have
+/// loaded and the animation has posed them this frame, shows them in place of
+/// their placeholders. Characters that failed to set up are shown as they are.
+/// This runs before visibility is worked out, so the first frame drawn is posed.
+fn finish_loading(
+    mut commands: Commands,
+    mut characters: Query<(Entity, &mut CharacterLoading, &GlobalTransform)>,
+    states: Query<(&AnimationPlayer, &AnimationGraphHandle), With<PlayerAnimationState>>,
+    mut visibilities: Query<&mut Visibility>,
+    children: Query<&Children>,
+    graphs: Res<Assets<AnimationGraph>>,
+    clips: Res<Assets<AnimationClip>>,
+    mut ready: EventWriter<CharacterReadyEvent>,
+    mut failed: EventWriter<CharacterFailedEvent>,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+) {
+    for (root, mut loading, transform) in characters.iter_mut() {
+        let loading = loading.as_mut();
+        if loading.hide && !loading.failed {
+            // The scene spawns its nodes as children of the root.
+            for child in children.relationship_sources::<Children>(root) {
+                if Some(child) == loading.placeholder
+                    || loading.hidden.iter().any(|(hidden, _)| *hidden == child)
+                {
+                    continue;
+                }
+                if let Ok(mut visibility) = visibilities.get_mut(child) {
+                    loading.hidden.push((child, *visibility));
+                    *visibility = Visibility::Hidden;
+                }
+            }
+        }
+
+        if !loading.failed && !is_posed(root, &children, &states, &graphs, &clips) {
+            continue;
+        }
+
+        for (child, before) in loading.hidden.drain(..) {
+            if let Ok(mut visibility) = visibilities.get_mut(child) {
+                *visibility = before;
+            }
+        }
+        if let Some(placeholder) = loading.placeholder {
+            commands.entity(placeholder).despawn();
+        }
+        commands.entity(root).remove::<CharacterLoading>();
+        let meta = EventMeta::new(root, &time, transform.translation());
+        if loading.failed {
+            if routing.emits::<CharacterFailedEvent>() {
+                failed.write(CharacterFailedEvent { meta });
+            }
+        } else if routing.emits::<CharacterReadyEvent>() {
+            ready.write(CharacterReadyEvent { meta });
+        }
+    }
+}
+
+/// Whether the animation player under `root` has loaded all its clips and is
+/// playing them.
+fn is_posed(
+    root: Entity,
+    children: &Query<&Children>,
+    states: &Query<(&AnimationPlayer, &AnimationGraphHandle), With<PlayerAnimationState>>,
+    graphs: &Assets<AnimationGraph>,
+    clips: &Assets<AnimationClip>,
+) -> bool {
+    let Some((player, graph)) = children
+        .iter_descendants(root)
+        .find_map(|e| states.get(e).ok())
+    else {
+        return false;
+    };
+    let Some(graph) = graphs.get(graph) else {
+        return false;
+    };
+    let loaded = graph.nodes().all(|node| match graph.get(node) {
+        Some(node) => match &node.node_type {
+            AnimationNodeType::Clip(clip) => clips.contains(clip),
+            _ => true,
+        },
+        None => true,
+    });
+    loaded && player.playing_animations().next().is_some()
+}
+
+/// Updates the foot IK targets from last frame's foot positions. Feet are only
+/// ever raised onto the ground, so they can still leave it while stepping.
+pub(crate) fn update_foot_ik(
+    mut commands: Commands,
+    rapier: ReadRapierContext,
+    characters: Query<(Entity, &FootIk, &RigMap, Option<&BodyProportions>)>,
+    global_transforms: Query<&GlobalTransform>,
+    mut iks: Query<&mut TwoBoneIk>,
+) {
+    let Ok(context) = rapier.single() else {
+        return;
+    };
+
+    for (root, foot_ik, rig, proportions) in characters.iter() {
+        // Hitboxes are sensors, and the feet shouldn't stand on the legs.
+        let filter = QueryFilter::default()
+            .exclude_collider(root)
+            .exclude_sensors();
+        // The foot is bigger or smaller along with the body.
+        let ankle_height = foot_ik.ankle_height * proportions.map_or(1.0, |p| p.scale);
+        for bone in [RigBone::LeftFoot, RigBone::RightFoot] {
+            let Some(foot) = rig.get(bone) else {
+                continue;
+            };
+            let Ok(foot_global) = global_transforms.get(foot) else {
+                continue;
+            };
+
+            let foot_position = foot_global.translation();
+            let origin = foot_position + Vec3::Y * foot_ik.max_step_up;
+            let max_toi = foot_ik.max_step_up + foot_ik.max_step_down + ankle_height;
+            let target = context
+                .cast_ray(origin, Vec3::NEG_Y, max_toi, true, filter)
+                .map(|(_, toi)| origin + Vec3::NEG_Y * (toi - ankle_height))
+                .filter(|target| target.y >= foot_position.y - 0.01);
+
+            let weight = if target.is_some() { 1.0 } else { 0.0 };
+            let target = IkTarget::Point(target.unwrap_or(foot_position));
+            if let Ok(mut ik) = iks.get_mut(foot) {
+                ik.target = target;
+                ik.weight = weight;
+            } else {
+                let mut ik = TwoBoneIk::new(target);
+                ik.weight = weight;
+                commands.entity(foot).insert(ik);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{AnimationTestApp, TestBone};
+
+    #[test]
+    fn missing_bone_shows_the_character_without_animations() {
+        let mut test = AnimationTestApp::new();
+        test.app
+            .add_systems(Update, init_characters)
+            .add_systems(PostUpdate, finish_loading.after(Animation));
+        test.record_events::<CharacterFailedEvent>();
+        test.record_events::<CharacterReadyEvent>();
+        let world = test.app.world_mut();
+        let root = world
+            .spawn((Player, Transform::default(), Visibility::default()))
+            .id();
+        let placeholder = world.spawn((Visibility::default(), ChildOf(root))).id();
+        world.entity_mut(root).insert(CharacterLoading {
+            hide: true,
+            placeholder: Some(placeholder),
+            hidden: Vec::new(),
+            failed: false,
+        });
+        let scene = world
+            .spawn((Transform::default(), Visibility::Inherited, ChildOf(root)))
+            .id();
+        test.tick(1);
+        let world = test.app.world();
+        assert_eq!(world.get::<Visibility>(scene), Some(&Visibility::Hidden));
+
+        // The scene's rig arrives without a thigh the graph masks.
+        let mut bones = TestBone::humanoid();
+        for bone in bones.iter_mut() {
+            if bone.name == "mixamorig:LeftUpLeg" {
+                bone.name = "mixamorig:LeftThigh";
+            }
+        }
+        let rig = test.spawn_bones(scene, &bones);
+        test.tick(1);
+
+        let world = test.app.world();
+        assert!(world.get::<PlayerAnimationState>(rig).is_none());
+        assert!(world.get::<CharacterLoading>(root).is_none());
+        assert!(world.get_entity(placeholder).is_err());
+        assert_eq!(world.get::<Visibility>(scene), Some(&Visibility::Inherited));
+        let failed = test.recorded_events::<CharacterFailedEvent>();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].meta.entity, root);
+        assert!(test.recorded_events::<CharacterReadyEvent>().is_empty());
+    }
+
+    #[cfg(not(feature = "avian"))]
+    #[test]
+    fn foot_ik_ignores_own_hitboxes() {
+        use bevy::ecs::system::SystemState;
+
+        use crate::hitbox::{HitboxPlugin, Hitboxes};
+
+        let mut test = AnimationTestApp::new();
+        test.app
+            .add_plugins((
+                bevy::scene::ScenePlugin,
+                RapierPhysicsPlugin::<NoUserData>::default(),
+                HitboxPlugin,
+            ))
+            .init_asset::<Mesh>()
+            .add_systems(Update, update_foot_ik);
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        let world = test.app.world_mut();
+        let player = world.get::<ChildOf>(rig).unwrap().parent();
+        world.spawn((
+            Collider::cuboid(5.0, 0.5, 5.0),
+            Transform::from_xyz(0.0, -0.5, 0.0),
+        ));
+        test.tick(1);
+
+        let world = test.app.world_mut();
+        let mut system_state: SystemState<(
+            Query<&Children>,
+            Query<&Name>,
+            Query<&AnimationTarget>,
+        )> = SystemState::new(world);
+        let (children, names, targets) = system_state.get(world);
+        let rig_map = RigMap::detect(player, &children, &names, &targets);
+        let left_foot = rig_map.get(RigBone::LeftFoot).unwrap();
+        world
+            .entity_mut(player)
+            .insert((rig_map, FootIk::default(), Hitboxes::humanoid()));
+        test.tick(5);
+
+        // The ray starts inside the thigh's capsule, which would raise the foot
+        // to the knee.
+        let world = test.app.world();
+        assert!(!world.get::<Hitboxes>(player).unwrap().spawned().is_empty());
+        let ik = world.get::<TwoBoneIk>(left_foot).unwrap();
+        let IkTarget::Point(target) = ik.target else {
+            panic!("expected a point target, got {:?}", ik.target);
+        };
+        let ankle_height = FootIk::default().ankle_height;
+        assert!(
+            (target.y - ankle_height).abs() < 0.01,
+            "foot target {target} should be on the ground"
+        );
+        assert_eq!(ik.weight, 1.0);
+    }
+}
@@ -0,0 +1,191 @@
+use std::f32::consts::TAU;
+
+use bevy::{app::Animation, prelude::*};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::events::{ChargeReleasedEvent, EventMeta, EventRouting};
+use crate::ik::solve_two_bone_ik;
+use crate::montage::Montage;
+use crate::pose_authority::apply_pose_authority;
+use crate::state::PlayerAnimationState;
+
+/// Charged attacks, e.g. drawing a bow. A [`ChargedAttack`] plays a charge
+/// montage that holds its last frame for as long as the button is held, with
+/// a shake that grows the longer it's held at full charge, then a release
+/// montage. Releasing sends a `ChargeReleasedEvent` with how charged it was.
+pub struct ChargePlugin;
+
+impl Plugin for ChargePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ChargedAttack>();
+        app.add_systems(Update, update_charges.in_set(CharAnimSet::ModifyInput));
+        app.add_systems(
+            PostUpdate,
+            shake_charges
+                .after(Animation)
+                .before(solve_two_bone_ik)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+enum ChargeRequest {
+    Begin,
+    Release,
+    Cancel,
+}
+
+/// Add to a character root. Call [`ChargedAttack::begin`] when the button is
+/// pressed and [`ChargedAttack::release`] when it's let go.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct ChargedAttack {
+    /// Played from the start of the charge, holding its last frame.
+    pub charge: Montage,
+    /// Played on release.
+    pub release: Montage,
+    /// Seconds from the start of the charge to full charge.
+    pub full_charge_secs: f32,
+    /// Seconds at full charge over which the shake grows to its full angle.
+    pub shake_ramp_secs: f32,
+    /// The most the upper body shakes, in degrees.
+    pub shake_angle: f32,
+    /// How fast the upper body shakes, in Hz.
+    pub shake_frequency: f32,
+    request: Option<ChargeRequest>,
+    /// Seconds since the charge began, if charging.
+    held_secs: Option<f32>,
+}
+
+impl ChargedAttack {
+    pub fn new(charge: Montage, release: Montage, full_charge_secs: f32) -> Self {
+        Self {
+            charge: charge.with_hold(),
+            release,
+            full_charge_secs,
+            shake_ramp_secs: 3.0,
+            shake_angle: 1.5,
+            shake_frequency: 7.0,
+            request: None,
+            held_secs: None,
+        }
+    }
+
+    /// Starts charging, e.g. when the button is pressed.
+    pub fn begin(&mut self) {
+        self.request = Some(ChargeRequest::Begin);
+    }
+
+    /// Releases the charge, e.g. when the button is let go.
+    pub fn release(&mut self) {
+        self.request = Some(ChargeRequest::Release);
+    }
+
+    /// Lowers the weapon without releasing.
+    pub fn cancel(&mut self) {
+        self.request = Some(ChargeRequest::Cancel);
+    }
+
+    pub fn is_charging(&self) -> bool {
+        self.held_secs.is_some()
+    }
+
+    /// How charged the attack is, from 0 to 1 at full charge.
+    pub fn charge(&self) -> f32 {
+        self.held_secs.map_or(0.0, |held_secs| {
+            (held_secs / self.full_charge_secs.max(f32::EPSILON)).min(1.0)
+        })
+    }
+
+    /// How much of the shake is applied, from 0 to 1.
+    fn shake_weight(&self) -> f32 {
+        let held_secs = self.held_secs.unwrap_or(0.0);
+        let overheld = held_secs - self.full_charge_secs;
+        (overheld / self.shake_ramp_secs.max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+}
+
+fn update_charges(
+    mut characters: Query<(Entity, &mut ChargedAttack, &GlobalTransform)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    mut released: EventWriter<ChargeReleasedEvent>,
+    children: Query<&Children>,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+) {
+    for (root, mut attack, transform) in characters.iter_mut() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+
+        // Something else, e.g. a dodge, may have replaced the charge.
+        let charging = state
+            .montage()
+            .is_some_and(|montage| montage.name == attack.charge.name);
+        if attack.held_secs.is_some() && !charging {
+            attack.held_secs = None;
+        }
+
+        match attack.request.take() {
+            Some(ChargeRequest::Begin) => {
+                state.play_montage(attack.charge.clone());
+                attack.held_secs = Some(0.0);
+                continue;
+            }
+            Some(ChargeRequest::Release) if attack.is_charging() => {
+                if routing.emits::<ChargeReleasedEvent>() {
+                    released.write(ChargeReleasedEvent {
+                        meta: EventMeta::new(root, &time, transform.translation()),
+                        charge: attack.charge(),
+                    });
+                }
+                state.play_montage(attack.release.clone());
+                attack.held_secs = None;
+                continue;
+            }
+            Some(ChargeRequest::Cancel) if attack.is_charging() => {
+                state.stop_montage();
+                attack.held_secs = None;
+                continue;
+            }
+            _ => {}
+        }
+
+        let delta_secs = time.delta_secs() * state.time_scale();
+        if let Some(held_secs) = attack.held_secs.as_mut() {
+            *held_secs += delta_secs;
+        }
+    }
+}
+
+fn shake_charges(
+    characters: Query<(&ChargedAttack, &RigMap)>,
+    mut transforms: Query<&mut Transform>,
+    time: Res<Time>,
+) {
+    for (attack, rig) in characters.iter() {
+        let weight = attack.shake_weight();
+        if weight <= 0.0 {
+            continue;
+        }
+        let Some(mut spine) = rig
+            .get(RigBone::Spine2)
+            .and_then(|spine| transforms.get_mut(spine).ok())
+        else {
+            continue;
+        };
+        // Sines at unrelated frequencies so the shake doesn't look periodic.
+        let t = time.elapsed_secs() * attack.shake_frequency * TAU;
+        let angle = (attack.shake_angle * weight).to_radians();
+        let pitch = angle * (0.6 * t.sin() + 0.4 * (2.3 * t + 1.0).sin());
+        let yaw = angle * (0.6 * (1.3 * t + 2.0).sin() + 0.4 * (2.9 * t).sin());
+        spine.rotation *= Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0);
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+
+/// Taking cover behind low and high walls, peeking out to either side and
+/// blind firing. Add a [`CoverSet`] to a character root to let it take cover,
+/// and an [`InCover`] while it's in cover.
+pub struct CoverPlugin;
+
+impl Plugin for CoverPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CoverSet>();
+        app.register_type::<InCover>();
+        app.add_systems(Update, take_cover.in_set(CharAnimSet::ModifyInput));
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoverHeight {
+    /// Cover the character crouches behind and can shoot over.
+    Low,
+    /// Cover the character stands behind and can only shoot around.
+    High,
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoverPeek {
+    #[default]
+    None,
+    Left,
+    Right,
+}
+
+/// Set by the game on the character root while it's in cover.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct InCover {
+    /// The horizontal direction out of the cover, towards the character. The
+    /// character faces into the cover, along the negative normal.
+    pub normal: Vec3,
+    pub height: CoverHeight,
+    /// Which side to peek out of, if any.
+    pub peek: CoverPeek,
+    /// Whether to fire blindly without peeking. Ignored while peeking.
+    pub blind_fire: bool,
+}
+
+/// The clips for one height of cover. The peek and blind fire clips are poses
+/// at full exposure, they're blended with the idle by how far out the
+/// character is.
+#[derive(Reflect, Clone, Debug)]
+pub struct CoverClips {
+    pub idle: Handle<AnimationClip>,
+    pub peek_left: Handle<AnimationClip>,
+    pub peek_right: Handle<AnimationClip>,
+    pub blind_fire: Handle<AnimationClip>,
+}
+
+/// The clips and tuning of a character that can take cover.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct CoverSet {
+    pub low: CoverClips,
+    pub high: CoverClips,
+    /// How fast the character peeks out and back in, in fractions of the full
+    /// peek per second.
+    pub peek_speed: f32,
+    peek_progress: f32,
+    /// The side being peeked out of, kept while peeking back in.
+    peek_side: CoverPeek,
+    blind_fire: f32,
+}
+
+impl CoverSet {
+    pub fn new(low: CoverClips, high: CoverClips) -> Self {
+        Self {
+            low,
+            high,
+            peek_speed: 4.0,
+            peek_progress: 0.0,
+            peek_side: CoverPeek::None,
+            blind_fire: 0.0,
+        }
+    }
+
+    /// How far the character is peeking out of cover in [0, 1]. The spine aims
+    /// in the look direction by this much, so the muzzle (and the tracers fired
+    /// from it) only follow the aim once the character is out of cover.
+    pub fn peek_progress(&self) -> f32 {
+        self.peek_progress
+    }
+
+    /// The side being peeked out of, or none if fully in cover.
+    pub fn peek_side(&self) -> CoverPeek {
+        if self.peek_progress > 0.0 {
+            self.peek_side
+        } else {
+            CoverPeek::None
+        }
+    }
+}
+
+fn take_cover(
+    mut characters: Query<(Entity, &mut CoverSet, Option<&InCover>)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    for (root, mut cover_set, in_cover) in characters.iter_mut() {
+        let Some(in_cover) = in_cover else {
+            cover_set.peek_progress = 0.0;
+            cover_set.peek_side = CoverPeek::None;
+            cover_set.blind_fire = 0.0;
+            continue;
+        };
+
+        // Peek back in before peeking out of the other side.
+        if cover_set.peek_progress <= 0.0 && in_cover.peek != CoverPeek::None {
+            cover_set.peek_side = in_cover.peek;
+        }
+        let peeking = in_cover.peek != CoverPeek::None && in_cover.peek == cover_set.peek_side;
+        let blind_firing = in_cover.blind_fire && in_cover.peek == CoverPeek::None;
+        let step = cover_set.peek_speed * time.delta_secs();
+        let approach = |value: f32, target: bool| {
+            let target = if target { 1.0 } else { 0.0 };
+            value + (target - value).clamp(-step, step)
+        };
+        cover_set.peek_progress = approach(cover_set.peek_progress, peeking);
+        cover_set.blind_fire = approach(cover_set.blind_fire, blind_firing);
+
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let clips = match in_cover.height {
+            CoverHeight::Low => &cover_set.low,
+            CoverHeight::High => &cover_set.high,
+        };
+        let peek_clip = match cover_set.peek_side {
+            CoverPeek::Right => &clips.peek_right,
+            _ => &clips.peek_left,
+        };
+        let peek = cover_set.peek_progress;
+        let idle = (1.0 - peek) * (1.0 - cover_set.blind_fire);
+        let blind_fire = (1.0 - peek) * cover_set.blind_fire;
+        let normal = in_cover.normal.with_y(0.0).normalize_or_zero();
+        states
+            .get_mut(state_entity)
+            .unwrap()
+            .override_locomotion(LocomotionOverride {
+                clips: vec![
+                    LocomotionClip::new(clips.idle.clone(), idle),
+                    LocomotionClip::new(peek_clip.clone(), peek),
+                    LocomotionClip::new(clips.blind_fire.clone(), blind_fire),
+                ],
+                pitch: 0.0,
+                yaw: Some((-normal.x).atan2(-normal.z)),
+                aim_weight: peek,
+            });
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::{animation::RepeatAnimation, prelude::*};
+use rand::Rng;
+
+use crate::effect_rng::{EffectRng, CROWD_REACTION_STREAM};
+use crate::events::{FireEvent, NoiseEvent};
+
+/// Makes background characters react to loud noises. Reactions spread outwards
+/// from the noise like a wave, with a bit of random delay per character, from
+/// the `EffectRng`, so a crowd doesn't duck in lockstep.
+///
+/// Crowd members are animated directly with single clips on their
+/// `AnimationPlayer` rather than through the full animation state machine, so
+/// large crowds stay cheap.
+pub struct CrowdPlugin;
+
+impl Plugin for CrowdPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CrowdReactionConfig>();
+        app.register_type::<CrowdReactionConfig>();
+        app.register_type::<CrowdMember>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(Update, (queue_crowd_reactions, play_crowd_reactions).chain());
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource, Default)]
+pub struct CrowdReactionConfig {
+    /// How fast the reaction wave travels outwards in m/s.
+    pub propagation_speed: f32,
+    /// The most random delay added to each character's reaction, in seconds.
+    pub max_jitter: f32,
+    /// The radius that gunshots without an explicit `NoiseEvent` are heard in.
+    pub gunshot_radius: f32,
+    /// Within this fraction of the noise radius characters start to flee.
+    pub flee_fraction: f32,
+    /// Within this fraction of the noise radius characters duck, beyond it they
+    /// only flinch.
+    pub duck_fraction: f32,
+}
+
+impl Default for CrowdReactionConfig {
+    fn default() -> Self {
+        Self {
+            propagation_speed: 30.0,
+            max_jitter: 0.3,
+            gunshot_radius: 20.0,
+            flee_fraction: 0.3,
+            duck_fraction: 0.6,
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CrowdReaction {
+    Flinch,
+    Duck,
+    FleeStart,
+}
+
+/// A background character. Add to the entity with the `AnimationPlayer`, with
+/// the graph nodes of its clips.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct CrowdMember {
+    pub idle: AnimationNodeIndex,
+    pub flinch: Option<AnimationNodeIndex>,
+    pub duck: Option<AnimationNodeIndex>,
+    pub flee_start: Option<AnimationNodeIndex>,
+    /// The reaction waiting to play and the elapsed time to play it at.
+    pending: Option<(CrowdReaction, f32)>,
+    /// The reaction clip being played.
+    reacting: Option<AnimationNodeIndex>,
+}
+
+impl CrowdMember {
+    pub fn new(idle: AnimationNodeIndex) -> Self {
+        Self {
+            idle,
+            flinch: None,
+            duck: None,
+            flee_start: None,
+            pending: None,
+            reacting: None,
+        }
+    }
+
+    fn node(&self, reaction: CrowdReaction) -> Option<AnimationNodeIndex> {
+        match reaction {
+            CrowdReaction::Flinch => self.flinch,
+            CrowdReaction::Duck => self.duck,
+            CrowdReaction::FleeStart => self.flee_start,
+        }
+    }
+
+    pub fn is_reacting(&self) -> bool {
+        self.pending.is_some() || self.reacting.is_some()
+    }
+}
+
+fn queue_crowd_reactions(
+    mut noises: EventReader<NoiseEvent>,
+    mut fires: EventReader<FireEvent>,
+    config: Res<CrowdReactionConfig>,
+    mut members: Query<(&mut CrowdMember, &GlobalTransform)>,
+    mut effect_rng: ResMut<EffectRng>,
+    time: Res<Time>,
+) {
+    let gunshots = fires.read().map(|fire| (fire.meta.position, config.gunshot_radius));
+    let noises: Vec<(Vec3, f32)> = noises
+        .read()
+        .map(|noise| (noise.meta.position, noise.radius))
+        .chain(gunshots)
+        .collect();
+    if noises.is_empty() {
+        return;
+    }
+
+    let rng = effect_rng.stream(CROWD_REACTION_STREAM);
+    for (mut member, transform) in members.iter_mut() {
+        if member.is_reacting() {
+            continue;
+        }
+        // React to the closest noise relative to its radius.
+        let closest = noises
+            .iter()
+            .map(|(position, radius)| (transform.translation().distance(*position), *radius))
+            .filter(|(distance, radius)| distance < radius)
+            .min_by(|(a, ra), (b, rb)| (a / ra).total_cmp(&(b / rb)));
+        let Some((distance, radius)) = closest else {
+            continue;
+        };
+
+        let reaction = if distance < radius * config.flee_fraction {
+            CrowdReaction::FleeStart
+        } else if distance < radius * config.duck_fraction {
+            CrowdReaction::Duck
+        } else {
+            CrowdReaction::Flinch
+        };
+        let delay =
+            distance / config.propagation_speed + rng.gen_range(0.0..=config.max_jitter);
+        member.pending = Some((reaction, time.elapsed_secs() + delay));
+    }
+}
+
+fn play_crowd_reactions(
+    mut members: Query<(&mut CrowdMember, &mut AnimationPlayer)>,
+    time: Res<Time>,
+) {
+    for (mut member, mut player) in members.iter_mut() {
+        if let Some(node) = member.reacting {
+            if player.animation(node).is_none_or(|a| a.is_finished()) {
+                player.stop_all();
+                player.play(member.idle).repeat();
+                member.reacting = None;
+            }
+        }
+
+        let Some((reaction, at)) = member.pending else {
+            continue;
+        };
+        if time.elapsed_secs() < at {
+            continue;
+        }
+        member.pending = None;
+        let Some(node) = member.node(reaction) else {
+            continue;
+        };
+        player.stop_all();
+        player.start(node).set_repeat(RepeatAnimation::Never);
+        member.reacting = Some(node);
+    }
+}
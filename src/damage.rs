@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::dissolve::Dissolve;
+use crate::events::{DamageEvent, DeathEvent, EventMeta, EventRouting, HitEvent};
+#[cfg(feature = "gore")]
+use crate::gore::Bleeding;
+use crate::gesture::Gesture;
+use crate::hitbox::{BodyPart, Hitbox};
+use crate::injury::Injury;
+use crate::knockback::{respond_to_knockbacks, ApplyKnockback, Ragdolled};
+use crate::state::PlayerAnimationState;
+
+/// One opinionated path from a shot to a cleaned up corpse, for games that
+/// don't want to put it together themselves. A `HitEvent` on a character with
+/// [`CharacterDamage`], on its hitboxes or anything under it, takes health by
+/// the hitbox's multiplier and sends a `DamageEvent`. Light hits play the hit
+/// reaction, heavy ones stagger it through its `KnockbackResponder`, and the
+/// hit that takes the last of its health ragdolls it and sends a `DeathEvent`.
+/// The corpse bleeds with the `gore` feature, and is dissolved or despawned
+/// after a while.
+///
+/// Impact effects and blood sprays come from the `SurfacePlugin` and the
+/// `GorePlugin` as for any hit. Leave the responder's `hit_impulse` at 0 so
+/// hits don't knock the character back twice.
+pub struct CharacterDamagePlugin;
+
+impl Plugin for CharacterDamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CharacterDamage>();
+        app.register_type::<Dead>();
+        app.register_type::<ShotDamage>();
+        app.add_systems(
+            Update,
+            (
+                damage_characters
+                    .before(respond_to_knockbacks)
+                    .in_set(CharAnimSet::ModifyInput),
+                clean_up_corpses,
+            ),
+        );
+    }
+}
+
+/// Set as a shot's `user_data` for it to deal this much damage instead of the
+/// character's [`CharacterDamage::damage`].
+#[derive(Reflect, Clone, Copy, Debug)]
+pub struct ShotDamage(pub f32);
+
+/// Add to a character root.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct CharacterDamage {
+    pub health: f32,
+    pub max_health: f32,
+    /// The damage of a shot without [`ShotDamage`], before the hitbox's
+    /// multiplier.
+    pub damage: f32,
+    /// Hits dealing at least this much damage stagger the character instead of
+    /// playing the hit reaction.
+    pub stagger_damage: f32,
+    /// The impulse a stagger knocks the character back with, along the shot.
+    pub stagger_impulse: f32,
+    /// The upper body gesture played on lighter hits, if any.
+    pub hit_reaction: Option<Gesture>,
+    /// Seconds a corpse stays before it's cleaned up.
+    pub corpse_secs: f32,
+    /// Seconds to dissolve the corpse away over, or despawn it outright if
+    /// none.
+    pub dissolve_secs: Option<f32>,
+    /// How wide the pool of blood under the corpse gets in meters, with the
+    /// `gore` feature. 0 for none.
+    pub pool_size: f32,
+    /// Seconds for the pool to spread.
+    pub pool_secs: f32,
+}
+
+impl CharacterDamage {
+    pub fn new(max_health: f32) -> Self {
+        Self {
+            health: max_health,
+            max_health,
+            damage: 25.0,
+            stagger_damage: 40.0,
+            stagger_impulse: 4.0,
+            hit_reaction: None,
+            corpse_secs: 10.0,
+            dissolve_secs: Some(2.0),
+            pool_size: 1.2,
+            pool_secs: 8.0,
+        }
+    }
+
+    pub fn with_hit_reaction(mut self, gesture: Gesture) -> Self {
+        self.hit_reaction = Some(gesture);
+        self
+    }
+
+    /// The health from 1 when full to 0.
+    pub fn health_fraction(&self) -> f32 {
+        (self.health / self.max_health.max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for CharacterDamage {
+    fn default() -> Self {
+        Self::new(100.0)
+    }
+}
+
+/// Added to a character root when it dies. It takes no more damage.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct Dead {
+    killer: Option<Entity>,
+    secs: f32,
+    /// Whether the corpse has been dissolved or despawned.
+    cleaned_up: bool,
+}
+
+impl Dead {
+    /// Whoever dealt the final blow, if known.
+    pub fn killer(&self) -> Option<Entity> {
+        self.killer
+    }
+
+    /// Seconds since the character died.
+    pub fn secs(&self) -> f32 {
+        self.secs
+    }
+}
+
+fn damage_characters(
+    mut commands: Commands,
+    mut hits: EventReader<HitEvent>,
+    mut characters: Query<
+        (&mut CharacterDamage, &GlobalTransform, Option<&mut Injury>),
+        Without<Dead>,
+    >,
+    mut states: Query<&mut PlayerAnimationState>,
+    hitboxes: Query<&Hitbox>,
+    parents: Query<&ChildOf>,
+    children: Query<&Children>,
+    mut knockbacks: EventWriter<ApplyKnockback>,
+    mut damages: EventWriter<DamageEvent>,
+    mut deaths: EventWriter<DeathEvent>,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+) {
+    // Only the first hit that kills a character counts.
+    let mut killed = Vec::new();
+    for hit in hits.read() {
+        let hitbox = hitboxes.get(hit.target).ok();
+        let Some(root) = hitbox.map(|hitbox| hitbox.character).or_else(|| {
+            std::iter::once(hit.target)
+                .chain(parents.iter_ancestors(hit.target))
+                .find(|e| characters.contains(*e))
+        }) else {
+            continue;
+        };
+        if killed.contains(&root) {
+            continue;
+        }
+        let Ok((mut damage, transform, injury)) = characters.get_mut(root) else {
+            continue;
+        };
+
+        let amount = hit
+            .user_data
+            .as_ref()
+            .and_then(|data| data.downcast_ref::<ShotDamage>())
+            .map_or(damage.damage, |shot| shot.0)
+            * hitbox.map_or(1.0, |hitbox| hitbox.damage_multiplier);
+        damage.health = (damage.health - amount).max(0.0);
+        let source = (hit.meta.entity != hit.target).then_some(hit.meta.entity);
+        let meta = EventMeta::new(root, &time, transform.translation());
+        if routing.emits::<DamageEvent>() {
+            damages.write(DamageEvent {
+                meta,
+                amount,
+                source,
+            });
+        }
+        if let Some(mut injury) = injury {
+            injury.health = damage.health_fraction();
+            match hitbox.map(|hitbox| hitbox.part) {
+                Some(BodyPart::LeftLeg) => injury.legs.left = 1.0,
+                Some(BodyPart::RightLeg) => injury.legs.right = 1.0,
+                _ => {}
+            }
+        }
+
+        let mut state = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+            .map(|e| states.get_mut(e).unwrap());
+        if damage.health <= 0.0 {
+            killed.push(root);
+            if let Some(state) = state.as_mut() {
+                state.stop_montage();
+                state.stop_gesture();
+            }
+            // The game swaps in its ragdoll, like for a knockback.
+            let mut entity = commands.entity(root);
+            entity.insert((
+                Ragdolled,
+                Dead {
+                    killer: source,
+                    secs: 0.0,
+                    cleaned_up: false,
+                },
+            ));
+            #[cfg(feature = "gore")]
+            if damage.pool_size > 0.0 {
+                entity.insert(Bleeding::new(damage.pool_size, damage.pool_secs));
+            }
+            if routing.emits::<DeathEvent>() {
+                deaths.write(DeathEvent {
+                    meta,
+                    killer: source,
+                });
+            }
+        } else if amount >= damage.stagger_damage {
+            knockbacks.write(ApplyKnockback {
+                character: root,
+                impulse: hit.direction.normalize_or_zero() * damage.stagger_impulse,
+            });
+        } else if let (Some(reaction), Some(state)) = (&damage.hit_reaction, state.as_mut()) {
+            state.play_gesture(reaction.clone());
+        }
+    }
+}
+
+/// Dissolves or despawns corpses once they've been around long enough.
+fn clean_up_corpses(
+    mut commands: Commands,
+    mut corpses: Query<(Entity, &mut Dead, &CharacterDamage, Has<Dissolve>)>,
+    time: Res<Time>,
+) {
+    for (entity, mut dead, damage, dissolving) in corpses.iter_mut() {
+        dead.secs += time.delta_secs();
+        if dissolving || dead.cleaned_up || dead.secs < damage.corpse_secs {
+            continue;
+        }
+        dead.cleaned_up = true;
+        match damage.dissolve_secs {
+            Some(secs) => {
+                commands.entity(entity).insert(Dissolve::new(secs));
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+}
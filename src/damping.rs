@@ -0,0 +1,205 @@
+use std::f32::consts::LN_2;
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    platform::collections::HashMap,
+    prelude::*,
+};
+
+use crate::anim::CharAnimSet;
+use crate::state::PlayerAnimationState;
+use crate::velocity::drive_animation_from_velocity;
+
+/// Smoothed animation parameters, so inputs that jump, e.g. a stick flicked
+/// from left to right, blend over instead of popping. Add [`AnimParams`] to
+/// the entity with the `PlayerAnimationState` and set parameters with
+/// [`AnimParams::set_float_damped`] or [`AnimParams::set_float_spring`]; they
+/// move towards their targets every frame.
+///
+/// The parameters named [`MOVE_X`], [`MOVE_Y`], [`LOOK_X`] and [`LOOK_Y`] are
+/// written into the animation input, so the locomotion blend can be smoothed
+/// without any other code.
+pub struct DampingPlugin {
+    /// The schedule the parameters are updated in, the same as the state machines'.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for DampingPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl DampingPlugin {
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for DampingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AnimParams>();
+        app.add_systems(
+            self.schedule,
+            update_anim_params
+                .in_set(CharAnimSet::ModifyInput)
+                .after(drive_animation_from_velocity),
+        );
+    }
+}
+
+/// The sideways movement input, +X is right.
+pub const MOVE_X: &str = "move_x";
+/// The forward movement input.
+pub const MOVE_Y: &str = "move_y";
+pub const LOOK_X: &str = "look_x";
+pub const LOOK_Y: &str = "look_y";
+
+/// Moves `current` towards `target`, covering half the remaining distance
+/// every `halflife` seconds. Independent of the frame rate.
+pub fn damp<T: StableInterpolate>(current: &mut T, target: &T, halflife: f32, delta_secs: f32) {
+    if halflife <= 0.0 {
+        *current = target.clone();
+        return;
+    }
+    current.smooth_nudge(target, LN_2 / halflife, delta_secs);
+}
+
+/// A critically damped spring, which unlike [`damp`] eases in as well as out,
+/// so a target that keeps changing doesn't make the value kink.
+#[derive(Reflect, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpringDamper {
+    pub value: f32,
+    pub velocity: f32,
+}
+
+impl SpringDamper {
+    pub fn new(value: f32) -> Self {
+        Self {
+            value,
+            velocity: 0.0,
+        }
+    }
+
+    /// Moves towards `target`, roughly halfway every `halflife` seconds once
+    /// it's moving.
+    pub fn update(&mut self, target: f32, halflife: f32, delta_secs: f32) {
+        if halflife <= 0.0 {
+            *self = Self::new(target);
+            return;
+        }
+        // The exact solution of a critically damped spring over the step.
+        let y = 2.0 * LN_2 / halflife;
+        let offset = self.value - target;
+        let j = self.velocity + offset * y;
+        let decay = (-y * delta_secs).exp();
+        self.value = target + (offset + j * delta_secs) * decay;
+        self.velocity = (self.velocity - j * y * delta_secs) * decay;
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug)]
+enum Damping {
+    Exponential,
+    Spring,
+}
+
+#[derive(Reflect, Clone, Debug)]
+struct AnimParam {
+    spring: SpringDamper,
+    target: f32,
+    halflife: f32,
+    damping: Damping,
+}
+
+/// Named float parameters that move smoothly towards their targets.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct AnimParams {
+    params: HashMap<&'static str, AnimParam>,
+}
+
+impl AnimParams {
+    /// Sets the parameter, starting from `target` if it's new.
+    pub fn set_float(&mut self, name: &'static str, value: f32) {
+        self.set(name, value, 0.0, Damping::Exponential);
+    }
+
+    /// Eases the parameter towards `target` exponentially, covering half the
+    /// remaining distance every `halflife` seconds.
+    pub fn set_float_damped(&mut self, name: &'static str, target: f32, halflife: f32) {
+        self.set(name, target, halflife, Damping::Exponential);
+    }
+
+    /// Eases the parameter towards `target` with a critically damped spring.
+    pub fn set_float_spring(&mut self, name: &'static str, target: f32, halflife: f32) {
+        self.set(name, target, halflife, Damping::Spring);
+    }
+
+    fn set(&mut self, name: &'static str, target: f32, halflife: f32, damping: Damping) {
+        let param = self.params.entry(name).or_insert_with(|| AnimParam {
+            spring: SpringDamper::new(target),
+            target,
+            halflife,
+            damping,
+        });
+        param.target = target;
+        param.halflife = halflife;
+        param.damping = damping;
+    }
+
+    /// The current, smoothed value.
+    pub fn float(&self, name: &str) -> Option<f32> {
+        self.params.get(name).map(|param| param.spring.value)
+    }
+
+    pub fn target(&self, name: &str) -> Option<f32> {
+        self.params.get(name).map(|param| param.target)
+    }
+
+    /// Steps every parameter towards its target.
+    pub fn update(&mut self, delta_secs: f32) {
+        for param in self.params.values_mut() {
+            match param.damping {
+                Damping::Exponential => {
+                    damp(
+                        &mut param.spring.value,
+                        &param.target,
+                        param.halflife,
+                        delta_secs,
+                    );
+                    param.spring.velocity = 0.0;
+                }
+                Damping::Spring => param.spring.update(param.target, param.halflife, delta_secs),
+            }
+        }
+    }
+}
+
+pub(crate) fn update_anim_params(
+    mut params: Query<(&mut AnimParams, Option<&mut PlayerAnimationState>)>,
+    time: Res<Time>,
+) {
+    for (mut params, state) in params.iter_mut() {
+        params.update(time.delta_secs());
+        let Some(input) = state.and_then(|state| state.into_inner().input_mut()) else {
+            continue;
+        };
+        if let Some(x) = params.float(MOVE_X) {
+            input.local_movement_direction.x = x;
+        }
+        if let Some(y) = params.float(MOVE_Y) {
+            input.local_movement_direction.y = y;
+        }
+        if let Some(x) = params.float(LOOK_X) {
+            input.look_x = x;
+        }
+        if let Some(y) = params.float(LOOK_Y) {
+            input.look_y = y;
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::{animation::AnimationTarget, color::palettes::css::*, prelude::*};
+
+use crate::probe::{EdgeProximity, LocomotionProbe};
+use crate::state::PlayerAnimationState;
+
+/// Draws the skeleton and procedural animation targets of any character with
+/// [`DebugBones`].
+pub struct CharAnimDebugPlugin;
+
+impl Plugin for CharAnimDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DebugBones>();
+        app.add_systems(
+            PostUpdate,
+            (draw_bones, draw_procedural_targets, draw_probes)
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Add to a character root to draw its debug gizmos.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct DebugBones {
+    /// Draw a line from every bone to its parent bone.
+    pub hierarchy: bool,
+    /// Draw the local axes of every bone.
+    pub axes: bool,
+    pub axes_length: f32,
+    /// Draw the aim direction and procedural spine target.
+    pub aim: bool,
+    /// Draw the wall and ledge probe rays.
+    pub probes: bool,
+}
+
+impl Default for DebugBones {
+    fn default() -> Self {
+        Self {
+            hierarchy: true,
+            axes: false,
+            axes_length: 0.05,
+            aim: true,
+            probes: true,
+        }
+    }
+}
+
+fn draw_bones(
+    mut gizmos: Gizmos,
+    roots: Query<(Entity, &DebugBones)>,
+    children: Query<&Children>,
+    parents: Query<&ChildOf>,
+    bones: Query<&GlobalTransform, With<AnimationTarget>>,
+) {
+    for (root, debug) in roots.iter() {
+        for entity in children.iter_descendants(root) {
+            let Ok(bone) = bones.get(entity) else {
+                continue;
+            };
+            if debug.axes {
+                gizmos.axes(*bone, debug.axes_length);
+            }
+            if !debug.hierarchy {
+                continue;
+            }
+            // Skip over any procedural nodes that were inserted between bones.
+            let parent_bone = parents
+                .iter_ancestors(entity)
+                .find_map(|parent| bones.get(parent).ok());
+            if let Some(parent_bone) = parent_bone {
+                gizmos.line(parent_bone.translation(), bone.translation(), ORANGE);
+            }
+        }
+    }
+}
+
+fn draw_procedural_targets(
+    mut gizmos: Gizmos,
+    roots: Query<&DebugBones>,
+    states: Query<(Entity, &PlayerAnimationState)>,
+    parents: Query<&ChildOf>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    for (entity, state) in states.iter() {
+        let Some(root) = parents
+            .iter_ancestors(entity)
+            .find(|e| roots.get(*e).is_ok_and(|debug| debug.aim))
+        else {
+            continue;
+        };
+
+        let targets = &state.proc_targets;
+        if let Ok(spine1) = global_transforms.get(targets.spine1) {
+            gizmos.axes(*spine1, 0.2);
+        }
+        if let Ok(bullet_point) = global_transforms.get(targets.bullet_point) {
+            let start = bullet_point.translation();
+            gizmos.arrow(start, start + bullet_point.rotation() * Vec3::Z, RED);
+        }
+        if let Ok(root) = global_transforms.get(root) {
+            let start = root.translation();
+            gizmos.arrow(start, start + root.rotation() * Vec3::Z * 0.5, BLUE);
+        }
+    }
+}
+
+fn draw_probes(
+    mut gizmos: Gizmos,
+    probes: Query<(&GlobalTransform, &LocomotionProbe, &EdgeProximity, &DebugBones)>,
+) {
+    for (transform, probe, proximity, debug) in probes.iter() {
+        if !debug.probes {
+            continue;
+        }
+        let (wall_origin, forward) = probe.wall_ray(transform);
+        let wall_color = GREEN.mix(&RED, proximity.wall);
+        gizmos.ray(wall_origin, forward * probe.wall_probe_distance, wall_color);
+
+        let ledge_origin = probe.ledge_ray_origin(transform);
+        let ledge_end = proximity
+            .ledge_probe_hit
+            .unwrap_or(ledge_origin + Vec3::NEG_Y * probe.max_drop() * 2.0);
+        let ledge_color = GREEN.mix(&RED, proximity.ledge);
+        gizmos.line(ledge_origin, ledge_end, ledge_color);
+        if let Some(hit) = proximity.ledge_probe_hit {
+            gizmos.sphere(Isometry3d::from_translation(hit), 0.05, ledge_color);
+        }
+    }
+}
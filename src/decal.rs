@@ -0,0 +1,334 @@
+use std::{collections::VecDeque, f32::consts::TAU, time::Duration};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    pbr::decal::{ForwardDecal, ForwardDecalMaterial, ForwardDecalMaterialExt},
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        view::RenderLayers,
+    },
+};
+use rand::Rng;
+
+use crate::effect_rng::{EffectRng, DECAL_STREAM};
+use crate::events::{CharAnimEventsPlugin, ExplosionEvent, HitEvent};
+use crate::hitbox::Hitbox;
+use crate::hitscan::Hitscan;
+use crate::tracer::{DespawnAfter, EffectClock, EffectLifetimePlugin};
+use crate::vfx::{profile_layers, EffectLayers, VfxQuality, VfxQualityPlugin};
+
+/// Decals left on the world by shots and explosions: bullet holes where a
+/// `HitEvent` hit something other than a hitbox, and large scorch marks on
+/// the ground under an `ExplosionEvent`, and blood with the `gore` feature.
+/// Each kind has its own pool, so a
+/// burst of fire doesn't wipe out the scorches, and the oldest decal of a kind
+/// is removed when its pool is full. The pool sizes follow the `VfxQuality`
+/// decal budget.
+///
+/// Decals are Bevy forward decals, which blend onto the surface behind them,
+/// so cameras that should show them need a `DepthPrepass`.
+pub struct DecalPlugin;
+
+impl Plugin for DecalPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<VfxQualityPlugin>() {
+            app.add_plugins(VfxQualityPlugin);
+        }
+        if !app.is_plugin_added::<CharAnimEventsPlugin>() {
+            app.add_plugins(CharAnimEventsPlugin);
+        }
+        if !app.is_plugin_added::<EffectLifetimePlugin>() {
+            app.add_plugins(EffectLifetimePlugin::default());
+        }
+        app.add_event::<SpawnDecal>();
+        app.init_resource::<DecalPools>();
+        app.add_systems(Startup, setup_decal_materials);
+        app.add_systems(
+            Update,
+            (
+                (
+                    forget_despawned_decals,
+                    place_bullet_holes,
+                    place_scorches,
+                    spawn_decals,
+                )
+                    .chain(),
+                resize_decal_pools.run_if(resource_changed::<VfxQuality>),
+            ),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecalKind {
+    BulletHole,
+    /// A blast mark from an explosion.
+    Scorch,
+    Blood,
+}
+
+/// Requests a decal. The position and normal of the surface are in global
+/// world space.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpawnDecal {
+    pub kind: DecalKind,
+    pub position: Vec3,
+    pub normal: Vec3,
+    /// How wide the decal is in meters.
+    pub size: f32,
+    /// The render layers the decal is drawn on, or the default layer.
+    pub render_layers: Option<Vec<usize>>,
+}
+
+/// The decals of one kind that are alive, oldest first.
+pub struct DecalPool {
+    /// The most decals kept at once.
+    pub max: usize,
+    /// How long each decal lasts, or `None` to keep it until the pool needs
+    /// room.
+    pub lifetime: Option<Duration>,
+    live: VecDeque<Entity>,
+}
+
+impl DecalPool {
+    fn new(max: usize, lifetime_secs: u64) -> Self {
+        Self {
+            max,
+            lifetime: Some(Duration::from_secs(lifetime_secs)),
+            live: VecDeque::new(),
+        }
+    }
+
+    /// Removes all the decals.
+    pub fn clear(&mut self, commands: &mut Commands) {
+        for decal in self.live.drain(..) {
+            commands.entity(decal).try_despawn();
+        }
+    }
+
+    /// Removes the oldest decals until there's room for one more.
+    fn make_room(&mut self, commands: &mut Commands) {
+        while self.live.len() >= self.max.max(1) {
+            if let Some(oldest) = self.live.pop_front() {
+                // It may have already despawned at the end of its lifetime.
+                commands.entity(oldest).try_despawn();
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct DecalPools {
+    pub bullet_holes: DecalPool,
+    /// Scorches are bigger and rarer, so they last longer with a smaller pool.
+    pub scorches: DecalPool,
+    pub blood: DecalPool,
+}
+
+impl Default for DecalPools {
+    fn default() -> Self {
+        let budget = VfxQuality::default().settings().decal_budget;
+        Self {
+            bullet_holes: DecalPool::new(budget, 20),
+            scorches: DecalPool::new(budget / 8, 60),
+            blood: DecalPool::new(budget / 4, 60),
+        }
+    }
+}
+
+impl DecalPools {
+    pub fn get_mut(&mut self, kind: DecalKind) -> &mut DecalPool {
+        match kind {
+            DecalKind::BulletHole => &mut self.bullet_holes,
+            DecalKind::Scorch => &mut self.scorches,
+            DecalKind::Blood => &mut self.blood,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct DecalMaterials {
+    bullet_hole: Handle<ForwardDecalMaterial<StandardMaterial>>,
+    scorch: Handle<ForwardDecalMaterial<StandardMaterial>>,
+    blood: Handle<ForwardDecalMaterial<StandardMaterial>>,
+}
+
+/// Drops the decals that despawned, at the end of their lifetime or otherwise,
+/// from their pools, so they don't count against the pool sizes.
+fn forget_despawned_decals(
+    mut removed: RemovedComponents<ForwardDecal>,
+    pools: ResMut<DecalPools>,
+) {
+    let removed: Vec<Entity> = removed.read().collect();
+    if removed.is_empty() {
+        return;
+    }
+    let pools = pools.into_inner();
+    for pool in [
+        &mut pools.bullet_holes,
+        &mut pools.scorches,
+        &mut pools.blood,
+    ] {
+        pool.live.retain(|decal| !removed.contains(decal));
+    }
+}
+
+fn resize_decal_pools(mut pools: ResMut<DecalPools>, quality: Res<VfxQuality>) {
+    let budget = quality.settings().decal_budget;
+    pools.bullet_holes.max = budget;
+    pools.scorches.max = budget / 8;
+    pools.blood.max = budget / 4;
+}
+
+fn place_bullet_holes(
+    mut hits: EventReader<HitEvent>,
+    hitboxes: Query<(), With<Hitbox>>,
+    layers: Query<&EffectLayers>,
+    parents: Query<&ChildOf>,
+    mut decals: EventWriter<SpawnDecal>,
+) {
+    for hit in hits.read() {
+        if hitboxes.contains(hit.target) {
+            continue;
+        }
+        decals.write(SpawnDecal {
+            kind: DecalKind::BulletHole,
+            position: hit.meta.position,
+            normal: hit.normal,
+            size: 0.1,
+            render_layers: EffectLayers::find(hit.meta.entity, &layers, &parents)
+                .map(|layers| layers.iter().collect()),
+        });
+    }
+}
+
+/// Scorches the ground under explosions that are close enough to it.
+fn place_scorches(
+    mut explosions: EventReader<ExplosionEvent>,
+    hitscan: Hitscan,
+    layers: Query<&RenderLayers>,
+    mut decals: EventWriter<SpawnDecal>,
+) {
+    for explosion in explosions.read() {
+        let Some(ground) =
+            hitscan.cast_ray(explosion.meta.position, Dir3::NEG_Y, explosion.radius, None)
+        else {
+            continue;
+        };
+        // Smaller the higher up the blast was.
+        let size = explosion.radius * 2.0 * (1.0 - ground.distance / explosion.radius);
+        decals.write(SpawnDecal {
+            kind: DecalKind::Scorch,
+            position: ground.point,
+            normal: ground.normal,
+            size,
+            render_layers: layers
+                .get(explosion.meta.entity)
+                .ok()
+                .map(|layers| layers.iter().collect()),
+        });
+    }
+}
+
+fn spawn_decals(
+    mut commands: Commands,
+    mut events: EventReader<SpawnDecal>,
+    mut pools: ResMut<DecalPools>,
+    materials: Res<DecalMaterials>,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    let rng = effect_rng.stream(DECAL_STREAM);
+    for event in events.read() {
+        let Ok(normal) = Dir3::new(event.normal) else {
+            continue;
+        };
+        let material = match event.kind {
+            DecalKind::BulletHole => materials.bullet_hole.clone(),
+            DecalKind::Scorch => materials.scorch.clone(),
+            DecalKind::Blood => materials.blood.clone(),
+        };
+        let pool = pools.get_mut(event.kind);
+        pool.make_room(&mut commands);
+
+        // The decal projects along its Y axis, so line that up with the
+        // surface normal and spin it randomly around it so repeats don't line
+        // up.
+        let rotation = Quat::from_rotation_arc(Vec3::Y, *normal)
+            * Quat::from_rotation_y(rng.gen_range(0.0..TAU));
+        let mut decal = commands.spawn((
+            ForwardDecal,
+            MeshMaterial3d(material),
+            Transform::from_translation(event.position)
+                .with_rotation(rotation)
+                .with_scale(Vec3::splat(event.size)),
+            profile_layers(&event.render_layers, None).unwrap_or_default(),
+        ));
+        if let Some(lifetime) = pool.lifetime {
+            decal.insert(DespawnAfter::new(lifetime, EffectClock::Virtual));
+        }
+        let decal = decal.id();
+        pool.live.push_back(decal);
+    }
+}
+
+fn setup_decal_materials(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ForwardDecalMaterial<StandardMaterial>>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let mut material = |image: Image, depth_fade_factor: f32| {
+        materials.add(ForwardDecalMaterial {
+            base: StandardMaterial {
+                base_color_texture: Some(images.add(image)),
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 1.0,
+                ..default()
+            },
+            extension: ForwardDecalMaterialExt { depth_fade_factor },
+        })
+    };
+    commands.insert_resource(DecalMaterials {
+        bullet_hole: material(radial_decal(32, 0.35, 0.0, SOOT), 0.05),
+        // A scorch fades over a bigger depth so it wraps uneven ground.
+        scorch: material(radial_decal(128, 0.9, 0.35, SOOT), 0.5),
+        blood: material(radial_decal(64, 0.6, 0.25, [70, 4, 4]), 0.1),
+    });
+}
+
+/// The sRGB colour of bullet holes and scorches.
+const SOOT: [u8; 3] = [12, 10, 8];
+
+/// A round mark, solid out to `core` of its radius and fading to the edge, with
+/// `ragged` breaking up the edge into uneven streaks.
+fn radial_decal(size: u32, core: f32, ragged: f32, [r, g, b]: [u8; 3]) -> Image {
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let p = (Vec2::new(x as f32, y as f32) + 0.5) / size as f32 * 2.0 - 1.0;
+            let angle = p.y.atan2(p.x);
+            let streaks = (angle * 7.0).sin() * 0.5 + (angle * 13.0 + 1.3).sin() * 0.3;
+            let edge = 1.0 - ragged * (0.5 + 0.5 * streaks);
+            let distance = p.length() / edge;
+            let alpha = if distance <= core {
+                1.0
+            } else {
+                (1.0 - (distance - core) / (1.0 - core).max(f32::EPSILON)).clamp(0.0, 1.0)
+            };
+            data.extend([r, g, b, (alpha * 230.0) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
@@ -0,0 +1,121 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, RegisterDiagnostic},
+    prelude::*,
+};
+
+use crate::ik::TwoBoneIk;
+use crate::state::PlayerAnimationState;
+use crate::tracer::{PooledTracer, Tracer, TracerPool};
+use crate::vfx::ParticleKind;
+
+/// Characters driven by a `PlayerAnimationState`. There are no LOD tiers yet,
+/// so every character is counted at full detail.
+pub const ANIMATED_CHARACTERS: DiagnosticPath = DiagnosticPath::const_new("char_anim/characters");
+/// Animations evaluated this frame, summed over every `AnimationPlayer`.
+pub const POSE_EVALUATIONS: DiagnosticPath =
+    DiagnosticPath::const_new("char_anim/pose_evaluations");
+/// IK solver iterations this frame. The two bone solver is analytic, so this is
+/// one per chain with a non-zero weight.
+pub const IK_ITERATIONS: DiagnosticPath = DiagnosticPath::const_new("char_anim/ik_iterations");
+pub const ACTIVE_TRACERS: DiagnosticPath = DiagnosticPath::const_new("char_anim/tracers");
+/// Finished tracers waiting in the `TracerPool` to be reused.
+pub const POOLED_TRACERS: DiagnosticPath = DiagnosticPath::const_new("char_anim/pooled_tracers");
+/// Particle effects spawned by the crate, Hanabi's or the CPU stand in's.
+pub const ACTIVE_EFFECTS: DiagnosticPath = DiagnosticPath::const_new("char_anim/particle_effects");
+
+const ALL: [(&str, DiagnosticPath); 6] = [
+    ("Characters", ANIMATED_CHARACTERS),
+    ("Pose evaluations", POSE_EVALUATIONS),
+    ("IK iterations", IK_ITERATIONS),
+    ("Tracers", ACTIVE_TRACERS),
+    ("Pooled tracers", POOLED_TRACERS),
+    ("Particle effects", ACTIVE_EFFECTS),
+];
+
+/// Reports where the animation pipeline spends its time. The values can be
+/// read from `DiagnosticsStore`, logged with `LogDiagnosticsPlugin`, or shown
+/// in an on screen overlay.
+#[derive(Default)]
+pub struct CharAnimDiagnosticsPlugin {
+    /// Whether the overlay starts visible. Toggle it by changing the
+    /// `Visibility` of the [`DiagnosticsOverlay`] entity.
+    pub overlay: bool,
+}
+
+impl Plugin for CharAnimDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        for (_, path) in ALL {
+            app.register_diagnostic(Diagnostic::new(path));
+        }
+        let visibility = if self.overlay {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        app.add_systems(Startup, move |mut commands: Commands| {
+            commands.spawn((
+                DiagnosticsOverlay,
+                Text::default(),
+                TextFont::from_font_size(14.0),
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(8.0),
+                    right: Val::Px(8.0),
+                    ..default()
+                },
+                visibility,
+            ));
+        });
+        app.add_systems(PostUpdate, measure.after(TransformSystem::TransformPropagate));
+        app.add_systems(Update, update_overlay);
+    }
+}
+
+/// The text node showing the diagnostics.
+#[derive(Component)]
+pub struct DiagnosticsOverlay;
+
+fn measure(
+    mut diagnostics: Diagnostics,
+    characters: Query<(), With<PlayerAnimationState>>,
+    players: Query<&AnimationPlayer>,
+    iks: Query<&TwoBoneIk>,
+    tracers: Query<(), (With<Tracer>, Without<PooledTracer>)>,
+    effects: Query<(), With<ParticleKind>>,
+    pool: Option<Res<TracerPool>>,
+) {
+    diagnostics.add_measurement(&ANIMATED_CHARACTERS, || characters.iter().count() as f64);
+    diagnostics.add_measurement(&POSE_EVALUATIONS, || {
+        players
+            .iter()
+            .map(|player| player.playing_animations().count())
+            .sum::<usize>() as f64
+    });
+    diagnostics.add_measurement(&IK_ITERATIONS, || {
+        iks.iter().filter(|ik| ik.weight > 0.0).count() as f64
+    });
+    diagnostics.add_measurement(&ACTIVE_TRACERS, || tracers.iter().count() as f64);
+    diagnostics.add_measurement(&POOLED_TRACERS, || {
+        pool.as_ref().map_or(0, |pool| pool.idle()) as f64
+    });
+    diagnostics.add_measurement(&ACTIVE_EFFECTS, || effects.iter().count() as f64);
+}
+
+fn update_overlay(
+    store: Res<DiagnosticsStore>,
+    mut overlays: Query<(&mut Text, &InheritedVisibility), With<DiagnosticsOverlay>>,
+) {
+    for (mut text, visibility) in overlays.iter_mut() {
+        if !visibility.get() {
+            continue;
+        }
+        text.0.clear();
+        for (label, path) in ALL {
+            let value = store
+                .get(&path)
+                .and_then(Diagnostic::smoothed)
+                .unwrap_or_default();
+            text.0.push_str(&format!("{label}: {value:.0}\n"));
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension},
+    platform::collections::HashMap,
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
+};
+
+/// Dissolves defeated characters away instead of leaving their corpses around.
+/// A [`Dissolve`] on a character root swaps the materials of the meshes under
+/// it for [`DissolveMaterial`]s that look the same, then burns holes through
+/// them over its duration, with a glowing edge, and despawns the character.
+pub struct DissolvePlugin;
+
+impl Plugin for DissolvePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<DissolveMaterial>::default());
+        app.register_type::<Dissolve>();
+        app.add_systems(Update, (start_dissolves, animate_dissolves).chain());
+    }
+}
+
+/// Dissolves the meshes under this entity, then despawns it.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dissolve {
+    pub duration_secs: f32,
+    /// The color of the burning edge, brighter than 1 blooms with HDR.
+    pub edge_color: LinearRgba,
+    /// How wide the burning edge is, from 0 to 1 of the noise range.
+    pub edge_width: f32,
+    /// The size of the holes' pattern, in meters.
+    pub noise_scale: f32,
+    elapsed_secs: f32,
+}
+
+impl Dissolve {
+    pub fn new(duration_secs: f32) -> Self {
+        Self {
+            duration_secs,
+            edge_color: LinearRgba::rgb(4.0, 1.2, 0.2),
+            edge_width: 0.08,
+            noise_scale: 0.15,
+            elapsed_secs: 0.0,
+        }
+    }
+
+    pub fn with_edge(mut self, color: impl Into<LinearRgba>, width: f32) -> Self {
+        self.edge_color = color.into();
+        self.edge_width = width;
+        self
+    }
+
+    /// How far along it is, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        (self.elapsed_secs / self.duration_secs.max(f32::EPSILON)).min(1.0)
+    }
+}
+
+impl Default for Dissolve {
+    fn default() -> Self {
+        Self::new(2.0)
+    }
+}
+
+/// The shader the dissolve extension draws with.
+pub const DISSOLVE_SHADER_PATH: &str = "shaders/dissolve.wgsl";
+/// The shader of the depth prepass and shadows, which cuts the same holes.
+pub const DISSOLVE_PREPASS_SHADER_PATH: &str = "shaders/dissolve_prepass.wgsl";
+
+/// A standard material that dissolves away.
+pub type DissolveMaterial = ExtendedMaterial<StandardMaterial, DissolveExtension>;
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct DissolveExtension {
+    // After the standard material's bindings.
+    #[uniform(100)]
+    pub params: DissolveParams,
+}
+
+/// Matches `DissolveParams` in `dissolve.wgsl`, field for field.
+#[derive(ShaderType, Reflect, Clone, Debug)]
+pub struct DissolveParams {
+    pub edge_color: LinearRgba,
+    /// How much has dissolved, from 0 to 1.
+    pub cutoff: f32,
+    pub edge_width: f32,
+    /// One over the pattern size.
+    pub noise_frequency: f32,
+}
+
+impl MaterialExtension for DissolveExtension {
+    fn fragment_shader() -> ShaderRef {
+        DISSOLVE_SHADER_PATH.into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        DISSOLVE_PREPASS_SHADER_PATH.into()
+    }
+
+    // Masked rather than blended, so the character still sorts and writes
+    // depth like an opaque one, with the prepass discarding the holes.
+    fn alpha_mode() -> Option<AlphaMode> {
+        Some(AlphaMode::Mask(0.5))
+    }
+}
+
+/// The dissolve materials of a dissolving entity.
+#[derive(Component)]
+struct DissolveLayer {
+    materials: Vec<Handle<DissolveMaterial>>,
+}
+
+/// Swaps the standard materials under new dissolves for dissolve materials,
+/// one per standard material so meshes that shared one still do.
+fn start_dissolves(
+    mut commands: Commands,
+    dissolves: Query<(Entity, &Dissolve), Added<Dissolve>>,
+    children: Query<&Children>,
+    meshes: Query<&MeshMaterial3d<StandardMaterial>>,
+    standard_materials: Res<Assets<StandardMaterial>>,
+    mut dissolve_materials: ResMut<Assets<DissolveMaterial>>,
+) {
+    for (root, dissolve) in dissolves.iter() {
+        let mut swapped: HashMap<AssetId<StandardMaterial>, Handle<DissolveMaterial>> =
+            HashMap::default();
+        for entity in std::iter::once(root).chain(children.iter_descendants(root)) {
+            let Ok(material) = meshes.get(entity) else {
+                continue;
+            };
+            let Some(base) = standard_materials.get(&material.0) else {
+                continue;
+            };
+            let handle = swapped
+                .entry(material.0.id())
+                .or_insert_with(|| {
+                    dissolve_materials.add(ExtendedMaterial {
+                        base: base.clone(),
+                        extension: DissolveExtension {
+                            params: DissolveParams {
+                                edge_color: dissolve.edge_color,
+                                cutoff: 0.0,
+                                edge_width: dissolve.edge_width,
+                                noise_frequency: 1.0 / dissolve.noise_scale.max(0.001),
+                            },
+                        },
+                    })
+                })
+                .clone();
+            commands
+                .entity(entity)
+                .remove::<MeshMaterial3d<StandardMaterial>>()
+                .insert(MeshMaterial3d(handle));
+        }
+        commands.entity(root).insert(DissolveLayer {
+            materials: swapped.into_values().collect(),
+        });
+    }
+}
+
+fn animate_dissolves(
+    mut commands: Commands,
+    mut dissolves: Query<(Entity, &mut Dissolve, &DissolveLayer)>,
+    mut materials: ResMut<Assets<DissolveMaterial>>,
+    time: Res<Time>,
+) {
+    for (entity, mut dissolve, layer) in dissolves.iter_mut() {
+        dissolve.elapsed_secs += time.delta_secs();
+        let progress = dissolve.progress();
+        if progress >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        for handle in layer.materials.iter() {
+            if let Some(material) = materials.get_mut(handle) {
+                material.extension.params.cutoff = progress;
+            }
+        }
+    }
+}
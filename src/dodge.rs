@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::montage::Montage;
+use crate::state::{LowerBodyState, PlayerAnimationState};
+
+/// The name of the montage window during which a dodging character can't be
+/// hit. Listen for `NotifyWindowEvent`s with this name, or check
+/// `PlayerAnimationState::montage_window_open`.
+pub const IFRAMES_WINDOW: &str = "IFrames";
+
+/// Directional dodge rolls built on montages. Send a [`DodgeInput`] to dodge.
+pub struct DodgePlugin;
+
+impl Plugin for DodgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DodgeInput>();
+        app.register_type::<Dodge>();
+        app.add_systems(Update, start_dodges.in_set(CharAnimSet::ModifyInput));
+    }
+}
+
+/// Requests a character to dodge.
+#[derive(Event, Reflect, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct DodgeInput {
+    /// The character root with the [`Dodge`] component.
+    pub character: Entity,
+    /// The direction to dodge in character space, +Y is forward. If zero the
+    /// character dodges backwards.
+    pub direction: Vec2,
+    /// How far to travel in meters. The root motion of the roll is warped to
+    /// cover exactly this distance in exactly `direction`, whatever the clip
+    /// was authored with. If none, [`Dodge::distance`] is used.
+    pub distance: Option<f32>,
+}
+
+/// Lets a character dodge. Add to the character root.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Dodge {
+    /// The roll montages and the direction each one rolls in, +Y is forward.
+    /// Usually 4 or 8 directions, the closest one to the input is played.
+    pub directions: Vec<(Vec2, Montage)>,
+    /// How long a dodge requested during another montage or while airborne is
+    /// remembered, so it can start as soon as the character is free.
+    pub buffer_secs: f32,
+    /// How far dodges travel when the input doesn't say. If none, the authored
+    /// root motion of the rolls is used.
+    pub distance: Option<f32>,
+    /// The buffered request and how long ago it was made.
+    buffered: Option<(DodgeInput, f32)>,
+    /// The direction of the dodge being played, if any.
+    active: Option<Vec2>,
+}
+
+impl Dodge {
+    pub fn new(directions: Vec<(Vec2, Montage)>) -> Self {
+        Self {
+            directions,
+            buffer_secs: 0.3,
+            distance: None,
+            buffered: None,
+            active: None,
+        }
+    }
+
+    /// Gets the montage that rolls closest to `direction`.
+    pub fn montage_for(&self, direction: Vec2) -> Option<&Montage> {
+        let direction = direction.try_normalize().unwrap_or(Vec2::NEG_Y);
+        self.directions
+            .iter()
+            .max_by(|(a, _), (b, _)| {
+                let a = a.normalize_or_zero().dot(direction);
+                let b = b.normalize_or_zero().dot(direction);
+                a.total_cmp(&b)
+            })
+            .map(|(_, montage)| montage)
+    }
+
+    /// The direction of the dodge being played in character space, if any.
+    /// Camera rigs can use this to e.g. ease their follow lag during a roll.
+    pub fn active_direction(&self) -> Option<Vec2> {
+        self.active
+    }
+}
+
+fn start_dodges(
+    mut inputs: EventReader<DodgeInput>,
+    mut dodges: Query<(Entity, &mut Dodge)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    for input in inputs.read() {
+        if let Ok((_, mut dodge)) = dodges.get_mut(input.character) {
+            dodge.buffered = Some((*input, 0.0));
+        }
+    }
+
+    for (root, mut dodge) in dodges.iter_mut() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+
+        if dodge.active.is_some() {
+            let still_dodging = state.montage().is_some_and(|playing| {
+                dodge
+                    .directions
+                    .iter()
+                    .any(|(_, montage)| montage.name == playing.name)
+            });
+            if !still_dodging {
+                dodge.active = None;
+            }
+        }
+
+        let Some((input, age)) = dodge.buffered else {
+            continue;
+        };
+        if age > dodge.buffer_secs {
+            dodge.buffered = None;
+            continue;
+        }
+
+        let busy = state.montage().is_some()
+            || state.scrubbing().is_some()
+            || matches!(
+                state.lower_body_state(),
+                LowerBodyState::Jump | LowerBodyState::Falling
+            );
+        if busy {
+            dodge.buffered = Some((input, age + time.delta_secs()));
+            continue;
+        }
+
+        if let Some(mut montage) = dodge.montage_for(input.direction).cloned() {
+            if let Some(distance) = input.distance.or(dodge.distance) {
+                montage.root_motion = warped_root_motion(input.direction, distance);
+            }
+            // The montage takes over the full body, so a sprint stance is faded
+            // out for the roll and the locomotion resumes afterwards.
+            state.play_montage(montage);
+            dodge.active = Some(input.direction);
+        }
+        dodge.buffered = None;
+    }
+}
+
+/// The root motion that travels `distance` in `direction`, in character space.
+fn warped_root_motion(direction: Vec2, distance: f32) -> Vec3 {
+    let direction = direction.try_normalize().unwrap_or(Vec2::NEG_Y);
+    Vec3::new(-direction.x, 0.0, direction.y) * distance
+}
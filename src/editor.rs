@@ -0,0 +1,212 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+use crate::anim::{AnimationName, CharAnimSet};
+use crate::events::{Foot, FootstepEvent};
+use crate::state::{LowerBodyState, PlayerAnimationInput, PlayerAnimationState};
+use crate::utils;
+
+/// The frame rate that the timeline steps through clips at.
+const TIMELINE_FPS: f32 = 30.0;
+
+/// An egui panel for inspecting and tuning a character's animation state while
+/// the game is running. Add it after `DefaultPlugins`, as it adds egui's plugin
+/// if it's missing.
+pub struct AnimatorInspectorPlugin;
+
+impl Plugin for AnimatorInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin {
+                enable_multipass_for_primary_context: false,
+            });
+        }
+        app.init_resource::<AnimatorInspector>();
+        app.add_systems(
+            Update,
+            // Replaces the final input, whatever wrote or adjusted it.
+            (animator_inspector_ui, apply_input_override)
+                .chain()
+                .after(CharAnimSet::ModifyInput)
+                .before(CharAnimSet::StateMachine),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct AnimatorInspector {
+    selected: Option<Entity>,
+    /// The last footstep notify that fired for the selected character, and when.
+    last_notify: Option<(Foot, f32)>,
+    /// When set, this input replaces whatever the game sends to the selected
+    /// character.
+    input_override: Option<PlayerAnimationInput>,
+}
+
+fn animator_inspector_ui(
+    mut contexts: EguiContexts,
+    mut inspector: ResMut<AnimatorInspector>,
+    mut states: Query<(
+        Entity,
+        &mut PlayerAnimationState,
+        &AnimationPlayer,
+        &AnimationGraphHandle,
+    )>,
+    graphs: Res<Assets<AnimationGraph>>,
+    clips: Res<Assets<AnimationClip>>,
+    parents: Query<&ChildOf>,
+    mut footsteps: EventReader<FootstepEvent>,
+    time: Res<Time>,
+) {
+    let ctx = contexts.ctx_mut();
+    let inspector = &mut *inspector;
+
+    for footstep in footsteps.read() {
+        let selected_root = inspector.selected.map(|e| parents.root_ancestor(e));
+        if selected_root == Some(footstep.meta.entity) {
+            inspector.last_notify = Some((footstep.foot, footstep.meta.timestamp));
+        }
+    }
+
+    egui::Window::new("Animator").show(ctx, |ui| {
+        egui::ComboBox::from_label("Character")
+            .selected_text(format!("{:?}", inspector.selected))
+            .show_ui(ui, |ui| {
+                for (entity, ..) in states.iter() {
+                    ui.selectable_value(
+                        &mut inspector.selected,
+                        Some(entity),
+                        format!("{entity}"),
+                    );
+                }
+            });
+
+        let Some((_, mut state, player, graph)) =
+            inspector.selected.and_then(|e| states.get_mut(e).ok())
+        else {
+            return;
+        };
+
+        ui.heading("Lower body");
+        for lower_body in LowerBodyState::ALL {
+            let weight = player
+                .animation(state.lower_body_anim(lower_body))
+                .map(|a| a.weight())
+                .unwrap_or(0.0);
+            let label = format!("{lower_body:?}");
+            if lower_body == state.lower_body_state() {
+                ui.colored_label(egui::Color32::YELLOW, label);
+            } else {
+                ui.label(label);
+            }
+            ui.add(egui::ProgressBar::new(weight));
+        }
+        ui.label(format!("Sprinting: {}", state.is_sprinting()));
+        if let Some(gesture) = state.gesture() {
+            ui.label(format!("Gesture: {}", gesture.name));
+        }
+
+        if let Some(graph) = graphs.get(graph) {
+            ui.heading("Layers");
+            let nodes = state.nodes();
+            for (label, node) in [
+                ("Upper/lower", nodes.upper_lower_add),
+                ("Full body", nodes.full_body),
+            ] {
+                let weight = graph.get(node).map(|n| n.weight).unwrap_or(0.0);
+                ui.add(egui::ProgressBar::new(weight).text(label));
+            }
+            let upper = player
+                .animation(state.anims().get(AnimationName::IdleUpperBody))
+                .map(|a| a.weight())
+                .unwrap_or(0.0);
+            ui.add(egui::ProgressBar::new(upper).text("Upper body idle"));
+        }
+
+        ui.heading("Timeline");
+        let anim = state.lower_body_anim(state.lower_body_state());
+        ui.label(format!("Clip: {:?}", state.anims().get_name(anim)));
+        let duration = graphs
+            .get(graph)
+            .and_then(|graph| utils::clip_duration(graph, anim, &clips));
+        if let (Some(active), Some(duration)) = (player.animation(anim), duration) {
+            let mut normalized_time = state
+                .scrubbing()
+                .unwrap_or((active.seek_time() / duration).rem_euclid(1.0));
+            let frame = 1.0 / (TIMELINE_FPS * duration);
+            ui.horizontal(|ui| {
+                if ui.button("<").clicked() {
+                    state.scrub(normalized_time - frame);
+                }
+                let slider = egui::Slider::new(&mut normalized_time, 0.0..=1.0).text("Time");
+                if ui.add(slider).changed() {
+                    state.scrub(normalized_time);
+                }
+                if ui.button(">").clicked() {
+                    state.scrub(normalized_time + frame);
+                }
+                if state.scrubbing().is_some() && ui.button("Resume").clicked() {
+                    state.stop_scrubbing();
+                }
+            });
+        }
+        // Highlight notifies for a short while after they fire.
+        if let Some((foot, fired_at)) = inspector.last_notify {
+            let age = time.elapsed_secs() - fired_at;
+            let color = if age < 0.2 {
+                egui::Color32::GREEN
+            } else {
+                egui::Color32::GRAY
+            };
+            ui.colored_label(color, format!("Footstep ({foot:?})"));
+        }
+
+        ui.heading("Input");
+        let mut overriding = inspector.input_override.is_some();
+        ui.checkbox(&mut overriding, "Override input");
+        if overriding != inspector.input_override.is_some() {
+            inspector.input_override =
+                overriding.then(|| state.input().cloned().unwrap_or_default());
+        }
+        if let Some(ref mut input) = inspector.input_override {
+            let direction = &mut input.local_movement_direction;
+            ui.add(egui::Slider::new(&mut direction.x, -1.0..=1.0).text("Move x"));
+            ui.add(egui::Slider::new(&mut direction.y, -1.0..=1.0).text("Move y"));
+            ui.add(
+                egui::Slider::new(&mut input.look_x, -FRAC_PI_2..=FRAC_PI_2).text("Look x"),
+            );
+            ui.add(egui::Slider::new(&mut input.look_y, -PI..=PI).text("Look y"));
+            ui.add(egui::Slider::new(&mut input.caution, 0.0..=1.0).text("Caution"));
+            ui.checkbox(&mut input.is_sprinting, "Sprinting");
+            ui.checkbox(&mut input.is_grounded, "Grounded");
+        } else if let Some(input) = state.input() {
+            ui.label(format!("{input:#?}"));
+        }
+
+        ui.heading("Config");
+        let config = state.config_mut();
+        ui.add(egui::Slider::new(&mut config.blend_rate, 0.5..=1.0).text("Blend rate"));
+        ui.add(
+            egui::Slider::new(&mut config.blend_threshold, 0.0..=0.1).text("Blend threshold"),
+        );
+        ui.add(
+            egui::Slider::new(&mut config.stationary_turn_lerp_speed, 0.0..=1.0)
+                .text("Stationary turn lerp speed"),
+        );
+        ui.add(egui::Slider::new(&mut config.cautious_speed, 0.0..=1.0).text("Cautious speed"));
+    });
+}
+
+fn apply_input_override(
+    inspector: Res<AnimatorInspector>,
+    mut states: Query<&mut PlayerAnimationState>,
+) {
+    let (Some(selected), Some(input)) = (inspector.selected, &inspector.input_override) else {
+        return;
+    };
+    if let Ok(mut state) = states.get_mut(selected) {
+        state.set_input(input.clone());
+    }
+}
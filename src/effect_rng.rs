@@ -0,0 +1,107 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+#[cfg(feature = "hanabi")]
+use bevy_hanabi::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// The randomness of every randomized effect in the crate: the particles of
+/// muzzle flashes, impacts, blood and explosions, sprite flashes, weapon
+/// spread, idle fidgets and blinks, decal spins, floating text scatter, sound
+/// variations and crowd reactions. Insert one with [`EffectRng::new`] to seed
+/// it, otherwise it's seeded at random.
+///
+/// Each effect draws from a stream of its own, named after it, so the order
+/// systems happen to run in doesn't change what any of them draws. A stream
+/// carries on from draw to draw though, so two apps only show the same
+/// visuals if they're seeded alike and fed the same input from the start,
+/// e.g. a replay.
+#[derive(Resource, Debug)]
+pub struct EffectRng {
+    seed: u64,
+    streams: HashMap<&'static str, StdRng>,
+}
+
+impl Default for EffectRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+impl EffectRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: HashMap::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts every stream over from `seed`, e.g. when a replay or a round
+    /// starts.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+
+    /// The stream named `name`, started from the seed and the name the first
+    /// time it's drawn from.
+    pub fn stream(&mut self, name: &'static str) -> &mut StdRng {
+        let seed = self.seed;
+        self.streams
+            .entry(name)
+            .or_insert_with(|| StdRng::seed_from_u64(seed ^ name_hash(name)))
+    }
+
+    /// A generator of its own, seeded from the stream named `name`, for
+    /// effects spawned where the resource can't be held on to, e.g. in a
+    /// command.
+    pub fn fork(&mut self, name: &'static str) -> StdRng {
+        StdRng::seed_from_u64(self.stream(name).gen())
+    }
+
+    /// `effect` with its particles seeded from the stream named `name`.
+    #[cfg(feature = "hanabi")]
+    pub fn particle_effect(
+        &mut self,
+        name: &'static str,
+        effect: Handle<EffectAsset>,
+    ) -> ParticleEffect {
+        ParticleEffect {
+            prng_seed: Some(self.stream(name).gen()),
+            ..ParticleEffect::new(effect)
+        }
+    }
+}
+
+// The names of the streams, kept together so no two effects share one. The
+// name seeds the stream, so renaming one changes what its effect draws.
+pub(crate) const BLINK_STREAM: &str = "blink";
+pub(crate) const BLOOD_POOL_STREAM: &str = "blood_pool";
+#[cfg(feature = "hanabi")]
+pub(crate) const BLOOD_SPRAY_STREAM: &str = "blood_spray";
+#[cfg(any(feature = "webgl2", not(feature = "hanabi")))]
+pub(crate) const CPU_PARTICLE_STREAM: &str = "cpu_particle";
+pub(crate) const CROWD_REACTION_STREAM: &str = "crowd_reaction";
+pub(crate) const DECAL_STREAM: &str = "decal";
+pub(crate) const EVENT_SOUND_STREAM: &str = "event_sound";
+#[cfg(feature = "hanabi")]
+pub(crate) const EXPLOSION_STREAM: &str = "explosion";
+pub(crate) const FLOATING_TEXT_STREAM: &str = "floating_text";
+pub(crate) const FOOTSTEP_PARTICLE_STREAM: &str = "footstep_particles";
+pub(crate) const FOOTSTEP_SOUND_STREAM: &str = "footstep_sound";
+pub(crate) const IDLE_FIDGET_STREAM: &str = "idle_fidget";
+pub(crate) const IMPACT_PARTICLE_STREAM: &str = "impact_particles";
+#[cfg(feature = "hanabi")]
+pub(crate) const MUZZLE_SMOKE_STREAM: &str = "muzzle_smoke";
+pub(crate) const PROJECTILE_MUZZLE_FLASH_STREAM: &str = "projectile_muzzle_flash";
+pub(crate) const SPREAD_STREAM: &str = "spread";
+pub(crate) const TRACER_MUZZLE_FLASH_STREAM: &str = "tracer_muzzle_flash";
+
+/// FNV-1a, which unlike the std hasher is the same on every build.
+fn name_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,266 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::events::DamageEvent;
+use crate::montage::Montage;
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+use crate::velocity::drive_animation_from_velocity;
+
+/// Emotes, e.g. waves, dances and sitting down. Register each [`Emote`] under
+/// an [`EmoteId`] in the [`EmoteRegistry`], and [`Emotes::play_emote`] plays it
+/// on a character. Moving or taking damage cancels it, as set per emote.
+///
+/// The id is a plain `u16`, so to show a character's emote on other machines,
+/// replicate [`Emotes::playing`] and play the same id on the remote copy.
+pub struct EmotePlugin;
+
+impl Plugin for EmotePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EmoteRegistry>();
+        app.register_type::<EmoteId>();
+        app.register_type::<Emotes>();
+        app.add_systems(
+            Update,
+            play_emotes
+                .after(drive_animation_from_velocity)
+                .in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+/// Identifies an emote, the same on every machine.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmoteId(pub u16);
+
+/// A scene held during an emote, e.g. a guitar or a drink.
+#[derive(Clone, Debug)]
+pub struct EmoteProp {
+    pub scene: Handle<Scene>,
+    /// The bone the prop is attached to.
+    pub bone: RigBone,
+    /// Where the prop is relative to the bone.
+    pub offset: Transform,
+}
+
+/// What interrupts an emote.
+#[derive(Clone, Copy, Debug)]
+pub struct EmoteCancel {
+    /// Movement input or a jump.
+    pub movement: bool,
+    /// A `DamageEvent` for the character.
+    pub damage: bool,
+}
+
+impl Default for EmoteCancel {
+    fn default() -> Self {
+        Self {
+            movement: true,
+            damage: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Emote {
+    /// Played first, e.g. the whole wave, or sitting down.
+    pub montage: Montage,
+    /// Held for as long as the emote lasts, if any.
+    pub prop: Option<EmoteProp>,
+    /// Looped after the montage until the emote is cancelled, e.g. sitting. If
+    /// none, the emote ends with the montage.
+    pub looping: Option<Handle<AnimationClip>>,
+    pub cancel: EmoteCancel,
+}
+
+impl Emote {
+    pub fn new(montage: Montage) -> Self {
+        Self {
+            montage,
+            prop: None,
+            looping: None,
+            cancel: EmoteCancel::default(),
+        }
+    }
+
+    pub fn with_prop(mut self, prop: EmoteProp) -> Self {
+        self.prop = Some(prop);
+        self
+    }
+
+    pub fn with_loop(mut self, looping: Handle<AnimationClip>) -> Self {
+        self.looping = Some(looping);
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: EmoteCancel) -> Self {
+        self.cancel = cancel;
+        self
+    }
+}
+
+/// The emotes by id. Register the same emotes under the same ids everywhere.
+#[derive(Resource, Default)]
+pub struct EmoteRegistry {
+    emotes: HashMap<EmoteId, Emote>,
+}
+
+impl EmoteRegistry {
+    pub fn register(&mut self, id: EmoteId, emote: Emote) {
+        self.emotes.insert(id, emote);
+    }
+
+    pub fn get(&self, id: EmoteId) -> Option<&Emote> {
+        self.emotes.get(&id)
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+enum EmoteRequest {
+    Play(EmoteId),
+    Stop,
+}
+
+#[derive(Reflect, Clone, Copy, Debug)]
+struct ActiveEmote {
+    id: EmoteId,
+    /// Whether the montage is done and the loop is playing.
+    looping: bool,
+    prop: Option<Entity>,
+}
+
+/// Lets a character emote. Add to the character root, with a `RigMap` if any
+/// emotes have props.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct Emotes {
+    request: Option<EmoteRequest>,
+    active: Option<ActiveEmote>,
+}
+
+impl Emotes {
+    /// Plays an emote, replacing the current one if any.
+    pub fn play_emote(&mut self, id: EmoteId) {
+        self.request = Some(EmoteRequest::Play(id));
+    }
+
+    pub fn stop(&mut self) {
+        self.request = Some(EmoteRequest::Stop);
+    }
+
+    /// The emote playing, to replicate.
+    pub fn playing(&self) -> Option<EmoteId> {
+        self.active.map(|active| active.id)
+    }
+}
+
+/// Ends the active emote, stopping its montage if it's still playing.
+fn end_emote(
+    commands: &mut Commands,
+    emotes: &mut Emotes,
+    state: &mut PlayerAnimationState,
+    registry: &EmoteRegistry,
+) {
+    let Some(active) = emotes.active.take() else {
+        return;
+    };
+    if let Some(prop) = active.prop {
+        commands.entity(prop).despawn();
+    }
+    let montage = registry.get(active.id).map(|emote| emote.montage.name);
+    if state.montage().is_some_and(|playing| Some(playing.name) == montage) {
+        state.stop_montage();
+    }
+}
+
+fn play_emotes(
+    mut commands: Commands,
+    mut characters: Query<(Entity, &mut Emotes, Option<&RigMap>)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    mut damage: EventReader<DamageEvent>,
+    children: Query<&Children>,
+    registry: Res<EmoteRegistry>,
+) {
+    let damaged: Vec<Entity> = damage.read().map(|damage| damage.meta.entity).collect();
+    for (root, mut emotes, rig) in characters.iter_mut() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let emotes = emotes.as_mut();
+
+        match emotes.request.take() {
+            Some(EmoteRequest::Play(id)) => {
+                end_emote(&mut commands, emotes, &mut state, &registry);
+                let Some(emote) = registry.get(id) else {
+                    warn!("no emote registered for {id:?}");
+                    continue;
+                };
+                state.play_montage(emote.montage.clone());
+                let prop = emote.prop.as_ref().and_then(|prop| {
+                    let bone = rig?.get(prop.bone)?;
+                    let entity = commands
+                        .spawn((SceneRoot(prop.scene.clone()), prop.offset, ChildOf(bone)))
+                        .id();
+                    Some(entity)
+                });
+                emotes.active = Some(ActiveEmote {
+                    id,
+                    looping: false,
+                    prop,
+                });
+                continue;
+            }
+            Some(EmoteRequest::Stop) => {
+                end_emote(&mut commands, emotes, &mut state, &registry);
+                continue;
+            }
+            None => {}
+        }
+
+        let Some(active) = emotes.active else {
+            continue;
+        };
+        let Some(emote) = registry.get(active.id) else {
+            end_emote(&mut commands, emotes, &mut state, &registry);
+            continue;
+        };
+        let moved = state.input().is_some_and(|input| {
+            input.local_movement_direction.length() >= 0.1 || input.just_jumped
+        });
+        let cancelled = (emote.cancel.movement && moved)
+            || (emote.cancel.damage && damaged.contains(&root));
+        // Something else, e.g. a dodge, may have replaced the montage.
+        let replaced = state
+            .montage()
+            .is_some_and(|montage| montage.name != emote.montage.name);
+        if cancelled || replaced {
+            end_emote(&mut commands, emotes, &mut state, &registry);
+            continue;
+        }
+
+        if !active.looping && state.montage().is_none() {
+            if emote.looping.is_none() {
+                end_emote(&mut commands, emotes, &mut state, &registry);
+                continue;
+            }
+            emotes.active = Some(ActiveEmote {
+                looping: true,
+                ..active
+            });
+        }
+        if let (Some(looping), true) = (&emote.looping, state.montage().is_none()) {
+            state.override_locomotion(LocomotionOverride {
+                clips: vec![LocomotionClip::new(looping.clone(), 1.0)],
+                pitch: 0.0,
+                yaw: None,
+                aim_weight: 0.0,
+            });
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! All events emitted by the crate. Every event carries an [`EventMeta`] with
+//! the entity it's about, when it happened and where, and belongs to an
+//! [`EventChannel`] that can be switched off in [`EventRouting`] to avoid the
+//! cost of emitting events nobody listens to.
+//!
+//! Fields are only ever added to these events; when an event changes in a way
+//! that breaks integrations, [`EVENT_SCHEMA_VERSION`] is bumped.
+
+use std::sync::Arc;
+
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::character::Player;
+use crate::navlink::NavLink;
+use crate::state::{LowerBodyState, PlayerAnimationState};
+use crate::utils;
+
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+pub struct CharAnimEventsPlugin;
+
+impl Plugin for CharAnimEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EventRouting>();
+        app.register_type::<EventRouting>();
+        app.add_event::<FireEvent>();
+        app.add_event::<HitEvent>();
+        app.add_event::<NoiseEvent>();
+        app.add_event::<NotifyEvent>();
+        app.add_event::<NotifyWindowEvent>();
+        app.add_event::<StateChangeEvent>();
+        app.add_event::<FootstepEvent>();
+        app.add_event::<DamageEvent>();
+        app.add_event::<LandedEvent>();
+        app.add_event::<NavLinkTraversedEvent>();
+        app.add_event::<MeleeHitWindowEvent>();
+        app.add_event::<InteractEvent>();
+        app.add_event::<KnockbackEvent>();
+        app.add_event::<ExplosionEvent>();
+        app.add_event::<ChargeReleasedEvent>();
+        app.add_event::<SlideEvent>();
+        app.add_event::<OnTargetEvent>();
+        app.add_event::<CharacterReadyEvent>();
+        app.add_event::<CharacterFailedEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_event::<TargetHitEvent>();
+        app.add_systems(PostUpdate, emit_footsteps);
+    }
+}
+
+/// The fields shared by every event.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventMeta {
+    /// The entity the event is about, e.g. the character that changed state or
+    /// the tracer that was fired.
+    pub entity: Entity,
+    /// Elapsed seconds of the default `Time` when the event happened.
+    pub timestamp: f32,
+    /// Where the event happened in global world space.
+    pub position: Vec3,
+}
+
+impl EventMeta {
+    pub fn new(entity: Entity, time: &Time, position: Vec3) -> Self {
+        Self {
+            entity,
+            timestamp: time.elapsed_secs(),
+            position,
+        }
+    }
+}
+
+/// Arbitrary gameplay data attached to a shot, e.g. the weapon or ability that
+/// fired it, so systems reacting to the effects can find out where they came
+/// from. Cloning is cheap, and it's neither reflected nor serialized.
+#[derive(Clone, Debug)]
+pub struct UserData(Arc<dyn Reflect>);
+
+impl UserData {
+    pub fn new(data: impl Reflect) -> Self {
+        Self(Arc::new(data))
+    }
+
+    pub fn downcast_ref<T: Reflect>(&self) -> Option<&T> {
+        self.0.as_ref().downcast_ref()
+    }
+}
+
+/// User data is equal if it's the same data, not just equal data.
+impl PartialEq for UserData {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventChannel {
+    /// Firing, hits, explosions, melee attacks, loud noises and targets.
+    Weapon,
+    /// Animation notifies, state changes and interactions.
+    Animation,
+    /// Footsteps, landings and nav link traversal.
+    Locomotion,
+    /// Damage, knockbacks and deaths.
+    Damage,
+}
+
+/// Which channels are emitted. All channels are enabled by default.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventRouting {
+    enabled: HashMap<EventChannel, bool>,
+}
+
+impl EventRouting {
+    pub fn is_enabled(&self, channel: EventChannel) -> bool {
+        self.enabled.get(&channel).copied().unwrap_or(true)
+    }
+
+    pub fn set_enabled(&mut self, channel: EventChannel, enabled: bool) {
+        self.enabled.insert(channel, enabled);
+    }
+
+    /// Whether events of type `E` should be emitted.
+    pub fn emits<E: CharAnimEvent>(&self) -> bool {
+        self.is_enabled(E::CHANNEL)
+    }
+}
+
+pub trait CharAnimEvent: Event {
+    const CHANNEL: EventChannel;
+
+    fn meta(&self) -> &EventMeta;
+}
+
+/// A shot was fired. The meta entity is the spawned tracer, or
+/// `Entity::PLACEHOLDER` if it was culled for being out of sight.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct FireEvent {
+    pub meta: EventMeta,
+    pub end: Vec3,
+    /// The muzzle the shot was fired from, passed through from the
+    /// `SpawnTracer` request.
+    pub muzzle: Option<Entity>,
+    /// Passed through from the `SpawnTracer` request.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub user_data: Option<UserData>,
+}
+
+/// A shot hit something. The meta position is the hit point.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitEvent {
+    pub meta: EventMeta,
+    pub target: Entity,
+    pub normal: Vec3,
+    pub direction: Vec3,
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub user_data: Option<UserData>,
+}
+
+/// A loud noise such as an explosion that nearby characters react to. The meta
+/// position is where the noise came from. Gunshots are heard from `FireEvent`s
+/// and don't need a separate noise.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseEvent {
+    pub meta: EventMeta,
+    /// How far away the noise can be heard.
+    pub radius: f32,
+}
+
+/// A named point or window in an animation was reached.
+#[derive(Event, Reflect, Clone, Debug)]
+pub struct NotifyEvent {
+    pub meta: EventMeta,
+    pub name: &'static str,
+}
+
+/// A named window in an animation opened or closed, e.g. invulnerability frames
+/// during a dodge.
+#[derive(Event, Reflect, Clone, Debug)]
+pub struct NotifyWindowEvent {
+    pub meta: EventMeta,
+    pub name: &'static str,
+    pub open: bool,
+}
+
+/// A character's lower body state changed.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateChangeEvent {
+    pub meta: EventMeta,
+    pub from: LowerBodyState,
+    pub to: LowerBodyState,
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum Foot {
+    Left,
+    Right,
+}
+
+/// A foot was planted. The meta entity is the character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct FootstepEvent {
+    pub meta: EventMeta,
+    pub foot: Foot,
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum LandingKind {
+    Light,
+    /// A hard landing, after which the character needs time to recover.
+    Hard,
+    /// A roll that absorbed the impact.
+    Roll,
+}
+
+/// A character touched down after falling. The meta entity is the character
+/// root. Fall damage systems can use the impact speed, e.g. ignoring rolls.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct LandedEvent {
+    pub meta: EventMeta,
+    pub kind: LandingKind,
+    /// The fastest downward speed while falling, in m/s.
+    pub impact_speed: f32,
+}
+
+/// A character finished traversing a nav link. The meta entity is the
+/// character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavLinkTraversedEvent {
+    pub meta: EventMeta,
+    pub link: NavLink,
+    /// False if the traversal couldn't start or was interrupted before reaching
+    /// the end of the link.
+    pub completed: bool,
+}
+
+/// The hit window of a melee attack opened or closed. While it's open the game
+/// should trace the weapon for hits. The meta entity is the character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeleeHitWindowEvent {
+    pub meta: EventMeta,
+    /// The index of the attack in its combo.
+    pub step: usize,
+    pub open: bool,
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum InteractPhase {
+    /// The character started moving onto the interaction marker.
+    Started,
+    /// The prop should react, e.g. the door should start opening.
+    React,
+    /// The interaction montage finished and the character is free again.
+    Finished,
+    /// The interaction was cut short, e.g. by a dodge or a hit reaction.
+    Interrupted,
+}
+
+/// A character's interaction with a prop progressed. The meta entity is the
+/// prop.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct InteractEvent {
+    pub meta: EventMeta,
+    pub character: Entity,
+    pub phase: InteractPhase,
+}
+
+/// A character took damage.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct DamageEvent {
+    pub meta: EventMeta,
+    pub amount: f32,
+    pub source: Option<Entity>,
+}
+
+/// A character was knocked back. The meta entity is the character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct KnockbackEvent {
+    pub meta: EventMeta,
+    /// The impulse in global space.
+    pub impulse: Vec3,
+    /// Whether the impulse was strong enough to ragdoll the character rather
+    /// than stumble.
+    pub ragdoll: bool,
+}
+
+/// Something exploded. The meta entity is the `Explosion`.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExplosionEvent {
+    pub meta: EventMeta,
+    pub radius: f32,
+    /// Whether the explosion should shake nearby cameras.
+    pub shake: bool,
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub user_data: Option<UserData>,
+}
+
+/// A charged attack was released, e.g. a bow shot. The meta entity is the
+/// character root. Scale the damage, or the velocity of the `SpawnProjectile`,
+/// by the charge.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChargeReleasedEvent {
+    pub meta: EventMeta,
+    /// How long it was charged for, from 0 to 1 at full charge.
+    pub charge: f32,
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum SlidePhase {
+    Started,
+    /// The slide ran out of speed or left the ground. The character stays
+    /// crouched if crouch is still held or there's no room to stand.
+    Ended { crouched: bool },
+}
+
+/// A character started or stopped sliding, e.g. to lower the camera or play
+/// the scrape. The meta entity is the character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlideEvent {
+    pub meta: EventMeta,
+    pub phase: SlidePhase,
+}
+
+/// A turret came onto or went off its target, e.g. to start or stop firing.
+/// The meta entity is the `Turret`.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnTargetEvent {
+    pub meta: EventMeta,
+    /// The target entity, if it's aiming at one rather than a point.
+    pub target: Option<Entity>,
+    pub on_target: bool,
+}
+
+/// A character spawned with `CharacterBuilder` has loaded and been posed, and
+/// shows from this frame. The meta entity is the character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct CharacterReadyEvent {
+    pub meta: EventMeta,
+}
+
+/// A character spawned with `CharacterBuilder` can't be set up, e.g. its graph
+/// file is invalid or its rig lacks a bone the graph needs. It shows from this
+/// frame without animations, and its placeholder is removed. The meta entity is
+/// the character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct CharacterFailedEvent {
+    pub meta: EventMeta,
+}
+
+/// A character died. The meta entity is the character root.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeathEvent {
+    pub meta: EventMeta,
+    /// Whoever dealt the final blow, if known.
+    pub killer: Option<Entity>,
+}
+
+/// A shooting range target was knocked over. The meta entity is the target
+/// root and the meta position is the hit point.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct TargetHitEvent {
+    pub meta: EventMeta,
+    /// Whoever fired the shot, if known.
+    pub shooter: Option<Entity>,
+    /// The name of the zone that was hit, if any.
+    pub zone: Option<String>,
+    pub score: u32,
+}
+
+macro_rules! impl_char_anim_event {
+    ($($event:ty => $channel:expr),* $(,)?) => {
+        $(
+            impl CharAnimEvent for $event {
+                const CHANNEL: EventChannel = $channel;
+
+                fn meta(&self) -> &EventMeta {
+                    &self.meta
+                }
+            }
+        )*
+    };
+}
+
+impl_char_anim_event!(
+    FireEvent => EventChannel::Weapon,
+    HitEvent => EventChannel::Weapon,
+    NoiseEvent => EventChannel::Weapon,
+    NotifyEvent => EventChannel::Animation,
+    NotifyWindowEvent => EventChannel::Animation,
+    StateChangeEvent => EventChannel::Animation,
+    FootstepEvent => EventChannel::Locomotion,
+    DamageEvent => EventChannel::Damage,
+    LandedEvent => EventChannel::Locomotion,
+    NavLinkTraversedEvent => EventChannel::Locomotion,
+    MeleeHitWindowEvent => EventChannel::Weapon,
+    InteractEvent => EventChannel::Animation,
+    KnockbackEvent => EventChannel::Damage,
+    ExplosionEvent => EventChannel::Weapon,
+    ChargeReleasedEvent => EventChannel::Weapon,
+    SlideEvent => EventChannel::Locomotion,
+    OnTargetEvent => EventChannel::Weapon,
+    CharacterReadyEvent => EventChannel::Animation,
+    CharacterFailedEvent => EventChannel::Animation,
+    DeathEvent => EventChannel::Damage,
+    TargetHitEvent => EventChannel::Weapon,
+);
+
+/// Emits a footstep whenever a locomotion clip passes the start (left foot) or
+/// the middle (right foot) of its cycle.
+fn emit_footsteps(
+    mut last_phases: Local<HashMap<Entity, f32>>,
+    mut removed: RemovedComponents<PlayerAnimationState>,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+    states: Query<(Entity, &PlayerAnimationState, &AnimationPlayer, &AnimationGraphHandle)>,
+    graphs: Res<Assets<AnimationGraph>>,
+    clips: Res<Assets<AnimationClip>>,
+    parents: Query<&ChildOf>,
+    players: Query<&Player>,
+    global_transforms: Query<&GlobalTransform>,
+    mut footsteps: EventWriter<FootstepEvent>,
+) {
+    // Forget the phases of despawned characters.
+    for entity in removed.read() {
+        last_phases.remove(&entity);
+    }
+    if !routing.emits::<FootstepEvent>() {
+        return;
+    }
+
+    for (entity, state, player, graph) in states.iter() {
+        let moving = matches!(
+            state.lower_body_state(),
+            LowerBodyState::Forward
+                | LowerBodyState::Back
+                | LowerBodyState::Left
+                | LowerBodyState::Right
+        );
+        let anim = state.lower_body_anim(state.lower_body_state());
+        let duration = graphs
+            .get(graph)
+            .and_then(|graph| utils::clip_duration(graph, anim, &clips));
+        let (true, Some(active), Some(duration)) = (moving, player.animation(anim), duration)
+        else {
+            last_phases.remove(&entity);
+            continue;
+        };
+
+        let phase = (active.seek_time() / duration).rem_euclid(1.0);
+        let Some(last_phase) = last_phases.insert(entity, phase) else {
+            continue;
+        };
+
+        let foot = if last_phase > phase {
+            Foot::Left
+        } else if last_phase < 0.5 && phase >= 0.5 {
+            Foot::Right
+        } else {
+            continue;
+        };
+        let root = utils::find_upwards(entity, &parents, &players).map_or(entity, |(root, _)| root);
+        let position = global_transforms
+            .get(root)
+            .map(GlobalTransform::translation)
+            .unwrap_or_default();
+        footsteps.write(FootstepEvent {
+            meta: EventMeta::new(root, &time, position),
+            foot,
+        });
+    }
+}
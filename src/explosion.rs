@@ -0,0 +1,415 @@
+use std::time::Duration;
+
+use bevy::{
+    ecs::component::{ComponentHooks, HookContext, Mutable, StorageType},
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::view::RenderLayers,
+};
+#[cfg(feature = "hanabi")]
+use bevy_hanabi::prelude::*;
+
+#[cfg(feature = "hanabi")]
+use crate::effect_rng::{EffectRng, EXPLOSION_STREAM};
+use crate::events::{CharAnimEventsPlugin, EventMeta, EventRouting, ExplosionEvent, UserData};
+use crate::tracer::{DespawnAfter, EffectClock, EffectLifetimePlugin, EffectsPaused};
+use crate::vfx::{
+    add_particle_plugin, profile_layers, LayeredEffect, ParticleKind, VfxQuality, VfxQualityPlugin,
+};
+
+/// Explosions for grenades and rockets, the counterpart to tracers. Send a
+/// [`SpawnExplosion`] and an [`Explosion`] is spawned with a light flash, a
+/// fireball, a column of smoke and a shockwave ring along the ground, and an
+/// `ExplosionEvent` is emitted, which shakes nearby `CameraKick` cameras.
+pub struct ExplosionPlugin;
+
+impl Plugin for ExplosionPlugin {
+    fn build(&self, app: &mut App) {
+        add_particle_plugin(app);
+        if !app.is_plugin_added::<VfxQualityPlugin>() {
+            app.add_plugins(VfxQualityPlugin);
+        }
+        if !app.is_plugin_added::<CharAnimEventsPlugin>() {
+            app.add_plugins(CharAnimEventsPlugin);
+        }
+        if !app.is_plugin_added::<EffectLifetimePlugin>() {
+            app.add_plugins(EffectLifetimePlugin::default());
+        }
+        app.add_event::<SpawnExplosion>();
+        app.register_type::<Explosion>();
+        app.add_systems(
+            Update,
+            (
+                spawn_explosions,
+                animate_explosions.run_if(|paused: Res<EffectsPaused>| !paused.paused),
+            ),
+        );
+        #[cfg(feature = "hanabi")]
+        app.init_resource::<ExplosionEffects>().add_systems(
+            Update,
+            rebuild_explosion_effects
+                .run_if(resource_changed::<VfxQuality>.and(not(resource_added::<VfxQuality>))),
+        );
+    }
+}
+
+/// How long the light flash lasts.
+const FLASH_SECS: f32 = 0.15;
+/// How long the shockwave ring takes to spread and fade.
+const SHOCKWAVE_SECS: f32 = 0.4;
+/// How long the explosion lives, until the last of the smoke is gone.
+const EXPLOSION_SECS: f32 = 4.0;
+
+/// The name of the effect property that scales the particles to the blast.
+#[cfg(feature = "hanabi")]
+const RADIUS_PROPERTY: &str = "radius";
+
+/// Requests an explosion to be spawned. The position is in global world space.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpawnExplosion {
+    pub position: Vec3,
+    /// The blast radius in meters, which scales the whole effect.
+    pub radius: f32,
+    /// Whether the explosion shakes nearby cameras.
+    pub shake: bool,
+    /// The render layers the explosion is drawn on, or the default layer.
+    pub render_layers: Option<Vec<usize>>,
+    /// Passed on to the `ExplosionEvent`.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub user_data: Option<UserData>,
+}
+
+impl SpawnExplosion {
+    pub fn new(position: Vec3, radius: f32) -> Self {
+        Self {
+            position,
+            radius,
+            shake: true,
+            render_layers: None,
+            user_data: None,
+        }
+    }
+}
+
+/// Built when the plugin is added, so each world gets its own.
+#[cfg(feature = "hanabi")]
+#[derive(Resource)]
+struct ExplosionEffects {
+    fireball: Handle<EffectAsset>,
+    smoke: Handle<EffectAsset>,
+}
+
+#[cfg(feature = "hanabi")]
+impl FromWorld for ExplosionEffects {
+    fn from_world(world: &mut World) -> Self {
+        let quality = *world.resource::<VfxQuality>();
+        let mut effects = world.resource_mut::<Assets<EffectAsset>>();
+        Self {
+            fireball: effects.add(fireball_effect(&quality)),
+            smoke: effects.add(smoke_column_effect(&quality)),
+        }
+    }
+}
+
+/// An explosion, which builds its effects when added and despawns once they've
+/// played out.
+#[derive(Reflect)]
+#[reflect(Component)]
+pub struct Explosion {
+    pub radius: f32,
+    /// Seconds since the explosion went off.
+    pub age: f32,
+    #[reflect(ignore)]
+    parts: Option<ExplosionParts>,
+}
+
+impl Explosion {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            age: 0.0,
+            parts: None,
+        }
+    }
+}
+
+struct ExplosionParts {
+    light: Entity,
+    ring: Entity,
+    ring_material: Handle<StandardMaterial>,
+}
+
+impl Component for Explosion {
+    type Mutability = Mutable;
+
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        hooks.on_add(|mut world, HookContext { entity, .. }| {
+            let radius = world.get::<Self>(entity).unwrap().radius;
+            let layers = world
+                .get::<RenderLayers>(entity)
+                .cloned()
+                .unwrap_or_default();
+            #[cfg(feature = "hanabi")]
+            let (fireball, smoke) = {
+                let effects = world.resource::<ExplosionEffects>();
+                let (fireball, smoke) = (effects.fireball.clone(), effects.smoke.clone());
+                let mut effect_rng = world.resource_mut::<EffectRng>();
+                let radius_property = || {
+                    EffectProperties::default()
+                        .with_properties([(RADIUS_PROPERTY.to_string(), radius.into())])
+                };
+                (
+                    (
+                        effect_rng.particle_effect(EXPLOSION_STREAM, fireball),
+                        radius_property(),
+                    ),
+                    (
+                        effect_rng.particle_effect(EXPLOSION_STREAM, smoke),
+                        radius_property(),
+                    ),
+                )
+            };
+            // Without Hanabi the CPU particles go by the kind alone.
+            #[cfg(not(feature = "hanabi"))]
+            let (fireball, smoke) = ((), ());
+            let asset_server = world.resource::<AssetServer>().clone();
+            let ring_mesh = asset_server.add(Mesh::from(Annulus::new(0.8, 1.0)));
+            let ring_material = asset_server.add(StandardMaterial {
+                base_color: Color::srgba(1.0, 0.85, 0.6, 0.6),
+                unlit: true,
+                alpha_mode: AlphaMode::Blend,
+                cull_mode: None,
+                ..default()
+            });
+
+            let mut commands = world.commands();
+            let light = commands
+                .spawn((
+                    PointLight {
+                        color: Color::srgb(1.0, 0.6, 0.3),
+                        intensity: 0.0,
+                        range: radius * 4.0,
+                        ..default()
+                    },
+                    Transform::from_xyz(0.0, radius * 0.5, 0.0),
+                    layers.clone(),
+                    ChildOf(entity),
+                ))
+                .id();
+            // The ring lies flat on the ground and spreads out from the blast.
+            let ring = commands
+                .spawn((
+                    Mesh3d(ring_mesh),
+                    MeshMaterial3d(ring_material.clone()),
+                    NotShadowCaster,
+                    Transform::from_xyz(0.0, 0.05, 0.0)
+                        .with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2))
+                        .with_scale(Vec3::ZERO),
+                    layers.clone(),
+                    ChildOf(entity),
+                ))
+                .id();
+            commands.spawn((
+                ParticleKind::Fireball,
+                fireball,
+                Transform::default(),
+                layers.clone(),
+                ChildOf(entity),
+            ));
+            commands.spawn((
+                ParticleKind::SmokeColumn,
+                smoke,
+                Transform::default(),
+                layers,
+                ChildOf(entity),
+            ));
+            commands.entity(entity).insert((
+                Visibility::default(),
+                DespawnAfter::new(Duration::from_secs_f32(EXPLOSION_SECS), EffectClock::Virtual),
+            ));
+
+            world.get_mut::<Self>(entity).unwrap().parts = Some(ExplosionParts {
+                light,
+                ring,
+                ring_material,
+            });
+        });
+    }
+}
+
+fn spawn_explosions(
+    mut commands: Commands,
+    mut events: EventReader<SpawnExplosion>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+) {
+    for event in events.read() {
+        let explosion = commands
+            .spawn((
+                profile_layers(&event.render_layers, None).unwrap_or_default(),
+                LayeredEffect,
+                Explosion::new(event.radius),
+                Transform::from_translation(event.position),
+            ))
+            .id();
+        if routing.emits::<ExplosionEvent>() {
+            explosion_events.write(ExplosionEvent {
+                meta: EventMeta::new(explosion, &time, event.position),
+                radius: event.radius,
+                shake: event.shake,
+                user_data: event.user_data.clone(),
+            });
+        }
+    }
+}
+
+/// Fades the flash and spreads the shockwave.
+fn animate_explosions(
+    mut explosions: Query<&mut Explosion>,
+    mut lights: Query<&mut PointLight>,
+    mut transforms: Query<&mut Transform>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time: Res<Time>,
+) {
+    for mut explosion in explosions.iter_mut() {
+        explosion.age += time.delta_secs();
+        let Some(parts) = &explosion.parts else {
+            continue;
+        };
+
+        let flash = (1.0 - explosion.age / FLASH_SECS).max(0.0);
+        if let Ok(mut light) = lights.get_mut(parts.light) {
+            light.intensity = 2_000_000.0 * explosion.radius * flash * flash;
+        }
+
+        let shockwave = (explosion.age / SHOCKWAVE_SECS).min(1.0);
+        if let Ok(mut transform) = transforms.get_mut(parts.ring) {
+            // Fast at first, slowing as it spreads.
+            let spread = 1.0 - (1.0 - shockwave).powi(3);
+            transform.scale = Vec3::splat(explosion.radius * 1.5 * spread);
+        }
+        if let Some(material) = materials.get_mut(&parts.ring_material) {
+            material.base_color.set_alpha(0.6 * (1.0 - shockwave));
+        }
+    }
+}
+
+/// Rebuilds the effects in place when the quality changes.
+#[cfg(feature = "hanabi")]
+fn rebuild_explosion_effects(
+    mut effects: ResMut<Assets<EffectAsset>>,
+    explosion_effects: Res<ExplosionEffects>,
+    quality: Res<VfxQuality>,
+) {
+    effects.insert(&explosion_effects.fireball, fireball_effect(&quality));
+    effects.insert(&explosion_effects.smoke, smoke_column_effect(&quality));
+}
+
+/// A burst of hot particles flying out to about the blast radius.
+#[cfg(feature = "hanabi")]
+fn fireball_effect(quality: &VfxQuality) -> EffectAsset {
+    let writer = ExprWriter::new();
+    let radius = writer.add_property(RADIUS_PROPERTY, 1.0.into());
+
+    let init_pos = SetPositionSphereModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        radius: (writer.prop(radius) * writer.lit(0.2)).expr(),
+        dimension: ShapeDimension::Volume,
+    };
+    let init_velocity = SetVelocitySphereModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        speed: (writer.rand(ScalarType::Float) * writer.lit(3.0) + writer.lit(1.0))
+            .mul(writer.prop(radius))
+            .expr(),
+    };
+    let init_age = SetAttributeModifier::new(Attribute::AGE, writer.lit(0.0).expr());
+    let init_lifetime = SetAttributeModifier::new(
+        Attribute::LIFETIME,
+        (writer.rand(ScalarType::Float) * writer.lit(0.3) + writer.lit(0.3)).expr(),
+    );
+    let init_size = SetAttributeModifier::new(
+        Attribute::SIZE,
+        (writer.prop(radius) * writer.lit(0.3)).expr(),
+    );
+    // Slow down quickly, like burning gas.
+    let drag = LinearDragModifier::new(writer.lit(4.0).expr());
+
+    let mut color = Gradient::new();
+    color.add_key(0.0, Vec4::new(4.0, 3.0, 1.5, 1.0));
+    color.add_key(0.3, Vec4::new(3.0, 1.0, 0.2, 1.0));
+    color.add_key(1.0, Vec4::new(0.2, 0.1, 0.1, 0.0));
+
+    let module = writer.finish();
+
+    EffectAsset::new(512, SpawnerSettings::once(quality.particles(96.0).into()), module)
+        .with_simulation_space(SimulationSpace::Global)
+        .with_name("explosion fireball")
+        .init(init_pos)
+        .init(init_velocity)
+        .init(init_age)
+        .init(init_lifetime)
+        .init(init_size)
+        .update(drag)
+        .render(ColorOverLifetimeModifier::new(color))
+}
+
+/// A column of dark smoke rising from the blast and spreading as it goes.
+#[cfg(feature = "hanabi")]
+fn smoke_column_effect(quality: &VfxQuality) -> EffectAsset {
+    let writer = ExprWriter::new();
+    let radius = writer.add_property(RADIUS_PROPERTY, 1.0.into());
+
+    let init_pos = SetPositionSphereModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        radius: (writer.prop(radius) * writer.lit(0.4)).expr(),
+        dimension: ShapeDimension::Volume,
+    };
+    let init_velocity = SetAttributeModifier::new(
+        Attribute::VELOCITY,
+        (writer.rand(VectorType::VEC3F) * writer.lit(0.6) - writer.lit(0.3)
+            + writer.lit(Vec3::Y) * (writer.rand(ScalarType::Float) + writer.lit(1.0)))
+        .mul(writer.prop(radius))
+        .expr(),
+    );
+    let init_age = SetAttributeModifier::new(Attribute::AGE, writer.lit(0.0).expr());
+    let init_lifetime = SetAttributeModifier::new(
+        Attribute::LIFETIME,
+        (writer.rand(ScalarType::Float) * writer.lit(1.5) + writer.lit(2.0)).expr(),
+    );
+    let init_size = SetAttributeModifier::new(
+        Attribute::F32_0,
+        (writer.prop(radius) * writer.lit(0.4)).expr(),
+    );
+    let update_size = SetAttributeModifier::new(
+        Attribute::SIZE,
+        writer
+            .attr(Attribute::F32_0)
+            .mul(writer.lit(1.0).add(writer.attr(Attribute::AGE)))
+            .expr(),
+    );
+    let drag = LinearDragModifier::new(writer.lit(0.8).expr());
+
+    let mut color = Gradient::new();
+    color.add_key(0.0, Vec4::new(0.15, 0.12, 0.1, 0.0));
+    color.add_key(0.1, Vec4::new(0.15, 0.12, 0.1, 0.7));
+    color.add_key(1.0, Vec4::new(0.3, 0.3, 0.3, 0.0));
+
+    let module = writer.finish();
+
+    EffectAsset::new(256, SpawnerSettings::once(quality.particles(48.0).into()), module)
+        .with_simulation_space(SimulationSpace::Global)
+        .with_name("explosion smoke")
+        .init(init_pos)
+        .init(init_velocity)
+        .init(init_age)
+        .init(init_lifetime)
+        .init(init_size)
+        .update(update_size)
+        .update(drag)
+        .render(ColorOverLifetimeModifier::new(color))
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+
+use bevy::{
+    app::Animation,
+    prelude::*,
+    render::mesh::{inherit_weights, morph::MorphWeights},
+};
+use rand::Rng;
+use ron::value::Map;
+use serde::Deserialize;
+
+use crate::effect_rng::{EffectRng, BLINK_STREAM};
+use crate::pose_authority::apply_pose_authority;
+use crate::schema::{SchemaError, VersionedAsset, VersionedRonLoader};
+
+/// Facial expressions. A [`Face`] on a character root blends expression
+/// presets, e.g. angry or happy, each made of several morph targets and bone
+/// rotations, and layers lip sync and blinking on top. The presets are defined
+/// in `.expressions.ron` files, see [`ExpressionLibrary`].
+///
+/// The face is applied after the animations, so clips that animate the same
+/// morph targets or bones are overridden while an expression is showing.
+pub struct ExpressionPlugin;
+
+impl Plugin for ExpressionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ExpressionLibrary>();
+        app.init_asset_loader::<VersionedRonLoader<ExpressionLibrary>>();
+        app.register_type::<Face>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(
+            PostUpdate,
+            update_faces
+                .after(Animation)
+                .before(inherit_weights)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// A set of morph target weights and bone rotations, at full weight.
+#[derive(Reflect, Deserialize, Clone, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[serde(default)]
+pub struct ExpressionPreset {
+    /// Weights by morph target name.
+    pub morphs: HashMap<String, f32>,
+    /// Rotations by bone name, as XYZ euler angles in degrees on top of the
+    /// animated pose, e.g. for a jaw or brow bones.
+    pub bones: HashMap<String, [f32; 3]>,
+}
+
+impl ExpressionPreset {
+    fn bone_rotation(degrees: [f32; 3], weight: f32) -> Quat {
+        let [x, y, z] = degrees.map(|angle| angle.to_radians() * weight);
+        Quat::from_euler(EulerRot::XYZ, x, y, z)
+    }
+}
+
+/// The expressions, visemes and blink of a face, loaded from
+/// `.expressions.ron` files.
+#[derive(Asset, Reflect, Deserialize, Clone, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[serde(default)]
+pub struct ExpressionLibrary {
+    /// The presets for [`Face::set_expression`], by name.
+    pub expressions: HashMap<String, ExpressionPreset>,
+    /// The mouth shapes for [`Face::set_viseme`], by name.
+    pub visemes: HashMap<String, ExpressionPreset>,
+    /// The eyes closed.
+    pub blink: ExpressionPreset,
+    /// The morph targets that lip sync takes over from the expressions, so a
+    /// smile doesn't keep the mouth from forming words.
+    pub mouth: Vec<String>,
+}
+
+impl VersionedAsset for ExpressionLibrary {
+    const CURRENT_VERSION: u32 = 1;
+    const EXTENSIONS: &'static [&'static str] = &["expressions.ron"];
+
+    fn migrate(from_version: u32, _fields: &mut Map) -> Result<(), SchemaError> {
+        Err(SchemaError::Migration {
+            from_version,
+            reason: "unknown version".into(),
+        })
+    }
+}
+
+#[derive(Reflect, Clone, Debug)]
+struct ActiveExpression {
+    name: String,
+    weight: f32,
+    target: f32,
+    /// How fast the weight moves towards the target, per second.
+    rate: f32,
+}
+
+/// The face of a character. Add to the character root.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Face {
+    pub library: Handle<ExpressionLibrary>,
+    /// The shortest time in seconds between blinks.
+    pub min_blink_interval: f32,
+    /// The longest time in seconds between blinks.
+    pub max_blink_interval: f32,
+    /// How long a blink takes, closing and opening, in seconds.
+    pub blink_secs: f32,
+    expressions: Vec<ActiveExpression>,
+    visemes: HashMap<String, f32>,
+    /// The elapsed time that the current or next blink starts at.
+    blink_at: Option<f32>,
+    blink_requested: bool,
+    /// The bone entities by name, found again whenever the rig under the
+    /// root changes.
+    #[reflect(ignore)]
+    bones: Option<HashMap<String, Entity>>,
+    /// The rotations applied to bones last frame, to undo on bones that
+    /// nothing animates.
+    #[reflect(ignore)]
+    applied: HashMap<Entity, Quat>,
+}
+
+impl Face {
+    pub fn new(library: Handle<ExpressionLibrary>) -> Self {
+        Self {
+            library,
+            min_blink_interval: 2.0,
+            max_blink_interval: 6.0,
+            blink_secs: 0.15,
+            expressions: Vec::new(),
+            visemes: HashMap::new(),
+            blink_at: None,
+            blink_requested: false,
+            bones: None,
+            applied: HashMap::new(),
+        }
+    }
+
+    pub fn with_blink_interval(mut self, min: f32, max: f32) -> Self {
+        self.min_blink_interval = min;
+        self.max_blink_interval = max.max(min);
+        self
+    }
+
+    /// Blends to the expression at `weight` over `blend_time` seconds, and
+    /// the other expressions out over the same time.
+    pub fn set_expression(&mut self, name: &str, weight: f32, blend_time: f32) {
+        let rate = |from: f32, to: f32| (to - from).abs() / blend_time.max(f32::EPSILON);
+        for active in self.expressions.iter_mut() {
+            let target = if active.name == name { weight } else { 0.0 };
+            active.rate = rate(active.weight, target);
+            active.target = target;
+        }
+        if !self.expressions.iter().any(|active| active.name == name) {
+            self.expressions.push(ActiveExpression {
+                name: name.to_string(),
+                weight: 0.0,
+                target: weight,
+                rate: rate(0.0, weight),
+            });
+        }
+    }
+
+    /// Blends every expression out over `blend_time` seconds.
+    pub fn clear_expression(&mut self, blend_time: f32) {
+        for active in self.expressions.iter_mut() {
+            active.rate = active.weight / blend_time.max(f32::EPSILON);
+            active.target = 0.0;
+        }
+    }
+
+    /// The current weight of an expression.
+    pub fn expression(&self, name: &str) -> f32 {
+        self.expressions
+            .iter()
+            .find(|active| active.name == name)
+            .map_or(0.0, |active| active.weight)
+    }
+
+    /// Sets the weight of a viseme for this frame. Lip sync sets them every
+    /// frame, they're cleared once the face is applied.
+    pub fn set_viseme(&mut self, name: &str, weight: f32) {
+        self.visemes.insert(name.to_string(), weight);
+    }
+
+    /// Blinks now, rather than waiting for the next blink.
+    pub fn blink(&mut self) {
+        self.blink_requested = true;
+    }
+
+    /// How closed the eyes are from blinking, from 0 to 1.
+    fn blink_weight(&mut self, elapsed_secs: f32, rng: &mut impl Rng) -> f32 {
+        if std::mem::take(&mut self.blink_requested) {
+            self.blink_at = Some(elapsed_secs);
+        }
+        let blink_at = *self
+            .blink_at
+            .get_or_insert_with(|| elapsed_secs + rng.gen_range(0.0..=self.max_blink_interval));
+        let t = (elapsed_secs - blink_at) / self.blink_secs.max(f32::EPSILON);
+        if t >= 1.0 {
+            let interval = rng.gen_range(self.min_blink_interval..=self.max_blink_interval);
+            self.blink_at = Some(elapsed_secs + interval);
+            return 0.0;
+        }
+        // Closes over the first half and opens over the second.
+        (1.0 - (2.0 * t - 1.0).abs()).max(0.0)
+    }
+
+    fn update_expressions(&mut self, delta_secs: f32) {
+        for active in self.expressions.iter_mut() {
+            let step = active.rate * delta_secs;
+            active.weight += (active.target - active.weight).clamp(-step, step);
+        }
+        self.expressions
+            .retain(|active| active.weight > 0.0 || active.target > 0.0);
+    }
+
+    /// The morph target weights and bone rotations of every layer: the
+    /// expressions, then lip sync over the mouth, then blinking over the eyes.
+    fn compose(
+        &self,
+        library: &ExpressionLibrary,
+        blink: f32,
+    ) -> (HashMap<String, f32>, HashMap<String, Quat>) {
+        let mut morphs: HashMap<String, f32> = HashMap::new();
+        let mut bones: HashMap<String, Quat> = HashMap::new();
+        let mut add = |preset: &ExpressionPreset, weight: f32, morphs: &mut HashMap<_, _>| {
+            for (name, value) in preset.morphs.iter() {
+                *morphs.entry(name.clone()).or_insert(0.0) += value * weight;
+            }
+            for (name, degrees) in preset.bones.iter() {
+                let rotation = bones.entry(name.clone()).or_insert(Quat::IDENTITY);
+                *rotation *= ExpressionPreset::bone_rotation(*degrees, weight);
+            }
+        };
+
+        for active in self.expressions.iter() {
+            if let Some(preset) = library.expressions.get(&active.name) {
+                add(preset, active.weight, &mut morphs);
+            }
+        }
+
+        let lip_sync = self.visemes.values().sum::<f32>().min(1.0);
+        if lip_sync > 0.0 {
+            for name in library.mouth.iter() {
+                if let Some(value) = morphs.get_mut(name) {
+                    *value *= 1.0 - lip_sync;
+                }
+            }
+            for (name, weight) in self.visemes.iter() {
+                if let Some(preset) = library.visemes.get(name) {
+                    add(preset, *weight, &mut morphs);
+                }
+            }
+        }
+
+        if blink > 0.0 {
+            let mut blinked = HashMap::new();
+            add(&library.blink, blink, &mut blinked);
+            for (name, value) in blinked {
+                let morph = morphs.entry(name).or_insert(0.0);
+                *morph = morph.max(value);
+            }
+        }
+
+        // Every morph target the library knows of, so ones that faded out
+        // are reset.
+        let presets = library
+            .expressions
+            .values()
+            .chain(library.visemes.values())
+            .chain([&library.blink]);
+        for preset in presets {
+            for name in preset.morphs.keys() {
+                morphs.entry(name.clone()).or_insert(0.0);
+            }
+        }
+        for value in morphs.values_mut() {
+            *value = value.clamp(0.0, 1.0);
+        }
+        (morphs, bones)
+    }
+}
+
+pub(crate) fn update_faces(
+    mut faces: Query<(Entity, &mut Face)>,
+    mut morph_weights: Query<&mut MorphWeights>,
+    mut transforms: Query<&mut Transform>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    rig_changes: Query<(), Or<(Changed<Children>, Changed<Name>)>>,
+    libraries: Res<Assets<ExpressionLibrary>>,
+    meshes: Res<Assets<Mesh>>,
+    mut effect_rng: ResMut<EffectRng>,
+    time: Res<Time>,
+) {
+    let elapsed_secs = time.elapsed_secs();
+    let rng = effect_rng.stream(BLINK_STREAM);
+    for (root, mut face) in faces.iter_mut() {
+        let Some(library) = libraries.get(&face.library) else {
+            continue;
+        };
+        let face = face.as_mut();
+        face.update_expressions(time.delta_secs());
+        let blink = face.blink_weight(elapsed_secs, rng);
+        let (morphs, bones) = face.compose(library, blink);
+        face.visemes.clear();
+
+        let mut rig_changed = rig_changes.contains(root);
+        for entity in children.iter_descendants(root) {
+            rig_changed |= rig_changes.contains(entity);
+            let Ok(mut weights) = morph_weights.get_mut(entity) else {
+                continue;
+            };
+            let Some(targets) = weights
+                .first_mesh()
+                .and_then(|mesh| meshes.get(mesh))
+                .and_then(|mesh| mesh.morph_target_names())
+            else {
+                continue;
+            };
+            let targets: Vec<_> = targets
+                .iter()
+                .map(|target| morphs.get(target).copied())
+                .collect();
+            for (weight, value) in weights.weights_mut().iter_mut().zip(targets) {
+                if let Some(value) = value {
+                    *weight = value;
+                }
+            }
+        }
+
+        if rig_changed {
+            face.bones = None;
+        }
+        let bone_entities = face.bones.get_or_insert_with(|| {
+            children
+                .iter_descendants(root)
+                .filter_map(|entity| Some((names.get(entity).ok()?.to_string(), entity)))
+                .collect()
+        });
+        let mut applied = HashMap::new();
+        for (name, rotation) in bones.iter() {
+            let Some(&bone) = bone_entities.get(name) else {
+                continue;
+            };
+            let Ok(mut transform) = transforms.get_mut(bone) else {
+                continue;
+            };
+            // A bone the animations didn't pose this frame still has last
+            // frame's rotation on it.
+            let last = face.applied.get(&bone).copied();
+            if let Some(last) = last.filter(|_| !transform.is_changed()) {
+                transform.rotation *= last.inverse();
+            }
+            transform.rotation *= *rotation;
+            applied.insert(bone, *rotation);
+        }
+        // Undo the rotations of bones that the expressions no longer move.
+        for (bone, last) in face.applied.iter() {
+            if applied.contains_key(bone) {
+                continue;
+            }
+            if let Ok(mut transform) = transforms.get_mut(*bone) {
+                if !transform.is_changed() {
+                    transform.rotation *= last.inverse();
+                }
+            }
+        }
+        face.applied = applied;
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+
+use crate::anim::CharAnimSet;
+use crate::effect_rng::{EffectRng, IDLE_FIDGET_STREAM};
+use crate::montage::Montage;
+use crate::state::{LowerBodyState, PlayerAnimationState};
+
+/// Plays random fidgets while characters are idle, so groups of NPCs standing
+/// around don't animate in lockstep. The picks come from the `EffectRng`, so
+/// seeded apps fidget alike.
+pub struct IdleFidgetPlugin;
+
+impl Plugin for IdleFidgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<IdleFidgets>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(Update, play_idle_fidgets.in_set(CharAnimSet::ModifyInput));
+    }
+}
+
+/// Add to a character root. Fidgets are montages, so they take over the full
+/// body, and are stopped as soon as the character stops being idle.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct IdleFidgets {
+    /// The fidgets (weight shifts, looking around, stretches...) and how likely
+    /// each one is relative to the others.
+    pub fidgets: Vec<(Montage, f32)>,
+    /// The shortest time in seconds to stay idle between fidgets.
+    pub min_interval: f32,
+    /// The longest time in seconds to stay idle between fidgets.
+    pub max_interval: f32,
+    /// The elapsed time to play the next fidget at.
+    next_at: Option<f32>,
+    /// The name of the fidget being played.
+    playing: Option<&'static str>,
+}
+
+impl IdleFidgets {
+    pub fn new(fidgets: Vec<(Montage, f32)>) -> Self {
+        Self {
+            fidgets,
+            min_interval: 4.0,
+            max_interval: 12.0,
+            next_at: None,
+            playing: None,
+        }
+    }
+
+    pub fn with_interval(mut self, min: f32, max: f32) -> Self {
+        self.min_interval = min;
+        self.max_interval = max.max(min);
+        self
+    }
+}
+
+fn play_idle_fidgets(
+    mut characters: Query<(Entity, &mut IdleFidgets)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    mut effect_rng: ResMut<EffectRng>,
+    time: Res<Time>,
+) {
+    let rng = effect_rng.stream(IDLE_FIDGET_STREAM);
+    let now = time.elapsed_secs();
+
+    for (root, mut idle) in characters.iter_mut() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+
+        let playing_fidget = idle
+            .playing
+            .is_some_and(|name| state.montage().is_some_and(|m| m.name == name));
+        if !playing_fidget {
+            idle.playing = None;
+        }
+
+        let moving = state
+            .input()
+            .is_some_and(|input| input.local_movement_direction.length() >= 0.1);
+        if state.lower_body_state() != LowerBodyState::Idle || moving {
+            if playing_fidget {
+                state.stop_montage();
+                idle.playing = None;
+            }
+            idle.next_at = None;
+            continue;
+        }
+        if idle.playing.is_some() || state.montage().is_some() {
+            continue;
+        }
+
+        let Some(next_at) = idle.next_at else {
+            let interval = rng.gen_range(idle.min_interval..=idle.max_interval);
+            idle.next_at = Some(now + interval);
+            continue;
+        };
+        if now < next_at {
+            continue;
+        }
+
+        idle.next_at = None;
+        let Ok(weights) = WeightedIndex::new(idle.fidgets.iter().map(|(_, weight)| *weight))
+        else {
+            continue;
+        };
+        let (fidget, _) = &idle.fidgets[weights.sample(rng)];
+        idle.playing = Some(fidget.name);
+        state.play_montage(fidget.clone());
+    }
+}
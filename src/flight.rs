@@ -0,0 +1,208 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::damping::{damp, update_anim_params, AnimParams};
+
+/// Flying creatures, e.g. birds, drones and dragons. A [`Flyer`] picks
+/// between hovering, flapping and gliding from how it moves, and sets the
+/// [`FLAP_RATE`], [`HOVER`] and [`GLIDE`] parameters in its [`AnimParams`]
+/// for a `PropAnimator` or a blend to read. It also banks into turns and
+/// pitches along its climb.
+pub struct FlightPlugin;
+
+impl Plugin for FlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Flyer>();
+        app.add_systems(Update, update_flyers.before(update_anim_params));
+    }
+}
+
+/// How fast the wings flap, relative to the flap clip's speed. Use it as the
+/// flap state's speed parameter.
+pub const FLAP_RATE: &str = "flap_rate";
+/// 1 while hovering, 0 otherwise.
+pub const HOVER: &str = "hover";
+/// 1 while gliding, 0 otherwise.
+pub const GLIDE: &str = "glide";
+
+/// How much faster than `hover_speed` a hovering flyer has to go before it
+/// leaves the hover, so it doesn't flicker between the two.
+const HOVER_HYSTERESIS: f32 = 1.2;
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlightMode {
+    /// Flapping in place, or moving slowly.
+    Hover,
+    /// Flapping to climb or speed up.
+    Flap,
+    /// Wings held out, level or sinking.
+    Glide,
+}
+
+/// Add to the root of a flying creature, with [`AnimParams`] on the root or
+/// the entity with its animation state.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Flyer {
+    /// The node that banks and pitches, e.g. the model under the root. The
+    /// clips mustn't animate it.
+    pub body: Option<Entity>,
+    /// The flap rate when flying level.
+    pub base_flap_rate: f32,
+    /// How much the flap rate rises per m/s of climb.
+    pub climb_flap_rate: f32,
+    pub max_flap_rate: f32,
+    /// Below this horizontal speed in m/s the flyer hovers.
+    pub hover_speed: f32,
+    /// Faster than hovering, it glides unless it climbs faster than this in
+    /// m/s.
+    pub glide_max_climb: f32,
+    /// How far it banks per radian per second of turning, in radians.
+    pub bank_per_turn_rate: f32,
+    pub max_bank: f32,
+    /// How much of the angle it climbs or dives at the body pitches by.
+    pub pitch_scale: f32,
+    pub max_pitch: f32,
+    /// Seconds for the velocity, parameters and tilt to get halfway to where
+    /// they're heading.
+    pub halflife: f32,
+    mode: FlightMode,
+    last_position: Option<Vec3>,
+    last_yaw: Option<f32>,
+    velocity: Vec3,
+    bank: f32,
+    pitch: f32,
+    /// The rotation of the body at rest.
+    rest: Option<Quat>,
+}
+
+impl Default for Flyer {
+    fn default() -> Self {
+        Self {
+            body: None,
+            base_flap_rate: 1.0,
+            climb_flap_rate: 0.3,
+            max_flap_rate: 2.5,
+            hover_speed: 1.5,
+            glide_max_climb: 0.5,
+            bank_per_turn_rate: 0.4,
+            max_bank: 45f32.to_radians(),
+            pitch_scale: 0.8,
+            max_pitch: 40f32.to_radians(),
+            halflife: 0.15,
+            mode: FlightMode::Hover,
+            last_position: None,
+            last_yaw: None,
+            velocity: Vec3::ZERO,
+            bank: 0.0,
+            pitch: 0.0,
+            rest: None,
+        }
+    }
+}
+
+impl Flyer {
+    pub fn with_body(mut self, body: Entity) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn mode(&self) -> FlightMode {
+        self.mode
+    }
+
+    /// The smoothed velocity in m/s.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// The current bank in radians, positive to the right.
+    pub fn bank(&self) -> f32 {
+        self.bank
+    }
+
+    /// The current pitch in radians, positive nose up.
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    fn select_mode(&self) -> FlightMode {
+        let speed = self.velocity.xz().length();
+        let hover_speed = match self.mode {
+            FlightMode::Hover => self.hover_speed * HOVER_HYSTERESIS,
+            _ => self.hover_speed,
+        };
+        if speed < hover_speed {
+            FlightMode::Hover
+        } else if self.velocity.y > self.glide_max_climb {
+            FlightMode::Flap
+        } else {
+            FlightMode::Glide
+        }
+    }
+}
+
+fn update_flyers(
+    mut flyers: Query<(Entity, &mut Flyer, &GlobalTransform)>,
+    mut params: Query<&mut AnimParams>,
+    mut transforms: Query<&mut Transform>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (root, mut flyer, transform) in flyers.iter_mut() {
+        let flyer = flyer.as_mut();
+        let position = transform.translation();
+        if let Some(last) = flyer.last_position.replace(position) {
+            damp(&mut flyer.velocity, &((position - last) / dt), flyer.halflife, dt);
+        }
+        flyer.mode = flyer.select_mode();
+
+        let forward = transform.rotation() * Vec3::Z;
+        let yaw = forward.x.atan2(forward.z);
+        let turn_rate = flyer
+            .last_yaw
+            .replace(yaw)
+            .map_or(0.0, |last| ((yaw - last + PI).rem_euclid(TAU) - PI) / dt);
+        let bank = (-turn_rate * flyer.bank_per_turn_rate).clamp(-flyer.max_bank, flyer.max_bank);
+        let climb = flyer.velocity.y.atan2(flyer.velocity.xz().length());
+        let pitch = (climb * flyer.pitch_scale).clamp(-flyer.max_pitch, flyer.max_pitch);
+        damp(&mut flyer.bank, &bank, flyer.halflife, dt);
+        damp(&mut flyer.pitch, &pitch, flyer.halflife, dt);
+
+        if let Some(body) = flyer.body {
+            if let Ok(mut body) = transforms.get_mut(body) {
+                let rest = *flyer.rest.get_or_insert(body.rotation);
+                // Pitching the nose up is a negative rotation about X, and
+                // rolling right a positive one about the forward axis.
+                body.rotation = rest
+                    * Quat::from_rotation_x(-flyer.pitch)
+                    * Quat::from_rotation_z(flyer.bank);
+            }
+        }
+
+        let Some(params_entity) = std::iter::once(root)
+            .chain(children.iter_descendants(root))
+            .find(|e| params.contains(*e))
+        else {
+            continue;
+        };
+        let mut params = params.get_mut(params_entity).unwrap();
+        let flap_rate = match flyer.mode {
+            FlightMode::Glide => 0.0,
+            FlightMode::Hover | FlightMode::Flap => {
+                let climb = flyer.velocity.y.max(0.0);
+                (flyer.base_flap_rate + flyer.climb_flap_rate * climb).min(flyer.max_flap_rate)
+            }
+        };
+        let weight = |mode| if flyer.mode == mode { 1.0 } else { 0.0 };
+        params.set_float_damped(FLAP_RATE, flap_rate, flyer.halflife);
+        params.set_float_damped(HOVER, weight(FlightMode::Hover), flyer.halflife);
+        params.set_float_damped(GLIDE, weight(FlightMode::Glide), flyer.halflife);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::effect_rng::{EffectRng, FLOATING_TEXT_STREAM};
+use crate::events::HitEvent;
+use crate::hitbox::{BodyPart, Hitbox};
+use crate::vfx::{EffectVisibility, VfxQualityPlugin};
+
+/// Floating text over the world, e.g. damage numbers and "HEADSHOT", that
+/// rises from where it was spawned and fades out. Send [`SpawnFloatingText`]
+/// to show some. The text is UI placed over the point each frame, so it always
+/// faces the camera and stays the same size on screen. The nodes are pooled
+/// and text that no camera can see isn't spawned, like impact effects.
+pub struct FloatingTextPlugin;
+
+impl Plugin for FloatingTextPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<VfxQualityPlugin>() {
+            app.add_plugins(VfxQualityPlugin);
+        }
+        app.add_event::<SpawnFloatingText>();
+        app.init_resource::<FloatingTextConfig>();
+        app.init_resource::<FloatingTextPool>();
+        app.register_type::<FloatingTextConfig>();
+        app.add_systems(
+            Update,
+            (announce_headshots, spawn_floating_text, age_floating_text).chain(),
+        );
+        app.add_systems(
+            PostUpdate,
+            place_floating_text.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Shows `text` at a point in global world space.
+#[derive(Event, Clone, Debug)]
+pub struct SpawnFloatingText {
+    pub position: Vec3,
+    pub text: String,
+    pub color: Color,
+    /// The font size in logical pixels.
+    pub size: f32,
+}
+
+impl SpawnFloatingText {
+    pub fn new(position: Vec3, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            text: text.into(),
+            color: Color::WHITE,
+            size: 24.0,
+        }
+    }
+
+    /// A damage number, rounded to a whole number.
+    pub fn damage(position: Vec3, damage: f32) -> Self {
+        Self::new(position, format!("{}", damage.round()))
+    }
+
+    pub fn with_color(mut self, color: impl Into<Color>) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct FloatingTextConfig {
+    /// How long text stays up, in seconds.
+    pub lifetime_secs: f32,
+    /// How far text rises over its lifetime, in meters.
+    pub rise: f32,
+    /// How far apart text spawned at the same point is scattered sideways, in
+    /// meters, so a burst of hits doesn't stack the numbers.
+    pub scatter: f32,
+    /// Shows "HEADSHOT" for hits on head hitboxes.
+    pub headshots: bool,
+}
+
+impl Default for FloatingTextConfig {
+    fn default() -> Self {
+        Self {
+            lifetime_secs: 1.0,
+            rise: 0.6,
+            scatter: 0.15,
+            headshots: true,
+        }
+    }
+}
+
+/// The floating text nodes, so bursts reuse them instead of spawning new ones.
+#[derive(Resource)]
+pub struct FloatingTextPool {
+    /// The most text shown at once, the oldest is reused past it.
+    pub max: usize,
+    live: VecDeque<Entity>,
+    idle: Vec<Entity>,
+}
+
+impl Default for FloatingTextPool {
+    fn default() -> Self {
+        Self {
+            max: 32,
+            live: VecDeque::new(),
+            idle: Vec::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct FloatingText {
+    position: Vec3,
+    age: f32,
+    color: Color,
+}
+
+/// How wide the node around each text is, in logical pixels, so it can be
+/// centered over its point.
+const NODE_WIDTH: f32 = 200.0;
+
+fn announce_headshots(
+    mut hits: EventReader<HitEvent>,
+    hitboxes: Query<&Hitbox>,
+    config: Res<FloatingTextConfig>,
+    mut texts: EventWriter<SpawnFloatingText>,
+) {
+    if !config.headshots {
+        hits.clear();
+        return;
+    }
+    for hit in hits.read() {
+        if hitboxes
+            .get(hit.target)
+            .is_ok_and(|hitbox| hitbox.part == BodyPart::Head)
+        {
+            texts.write(
+                SpawnFloatingText::new(hit.meta.position, "HEADSHOT")
+                    .with_color(Color::srgb(1.0, 0.3, 0.2))
+                    .with_size(28.0),
+            );
+        }
+    }
+}
+
+fn spawn_floating_text(
+    mut commands: Commands,
+    mut events: EventReader<SpawnFloatingText>,
+    mut pool: ResMut<FloatingTextPool>,
+    config: Res<FloatingTextConfig>,
+    visibility: EffectVisibility,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    let rng = effect_rng.stream(FLOATING_TEXT_STREAM);
+    for event in events.read() {
+        if !visibility.sees(event.position, config.rise) {
+            continue;
+        }
+        let scatter = Vec3::new(rng.gen_range(-1.0..1.0), 0.0, rng.gen_range(-1.0..1.0));
+        let bundle = (
+            FloatingText {
+                position: event.position + scatter * config.scatter,
+                age: 0.0,
+                color: event.color,
+            },
+            Text::new(event.text.clone()),
+            TextFont::from_font_size(event.size),
+            TextColor(event.color),
+            // Hidden until it's placed over its point.
+            Visibility::Hidden,
+        );
+        let node = if pool.live.len() >= pool.max.max(1) {
+            pool.live.pop_front()
+        } else {
+            pool.idle.pop()
+        };
+        let node = match node {
+            Some(node) => {
+                commands.entity(node).insert(bundle);
+                node
+            }
+            None => commands
+                .spawn((
+                    bundle,
+                    Node {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(NODE_WIDTH),
+                        ..default()
+                    },
+                    TextLayout::new_with_justify(JustifyText::Center),
+                    Pickable::IGNORE,
+                ))
+                .id(),
+        };
+        pool.live.push_back(node);
+    }
+}
+
+/// Fades the text out and returns it to the pool at the end of its lifetime.
+fn age_floating_text(
+    mut texts: Query<(&mut FloatingText, &mut TextColor, &mut Visibility)>,
+    mut pool: ResMut<FloatingTextPool>,
+    config: Res<FloatingTextConfig>,
+    time: Res<Time>,
+) {
+    let pool = &mut *pool;
+    pool.live.retain(|&node| {
+        let Ok((mut text, mut color, mut visibility)) = texts.get_mut(node) else {
+            return false;
+        };
+        text.age += time.delta_secs();
+        let t = text.age / config.lifetime_secs.max(f32::EPSILON);
+        if t >= 1.0 {
+            *visibility = Visibility::Hidden;
+            pool.idle.push(node);
+            return false;
+        }
+        color.0 = text.color.with_alpha(text.color.alpha() * (1.0 - t * t));
+        true
+    });
+}
+
+/// Places each text over its point as seen by the active camera, after
+/// transforms have propagated so it doesn't lag a frame behind the camera.
+fn place_floating_text(
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut texts: Query<(&FloatingText, &mut Node, &mut Visibility)>,
+    pool: Res<FloatingTextPool>,
+    config: Res<FloatingTextConfig>,
+) {
+    let camera = cameras.iter().find(|(camera, _)| camera.is_active);
+    for &node in pool.live.iter() {
+        let Ok((text, mut style, mut visibility)) = texts.get_mut(node) else {
+            continue;
+        };
+        let rise = text.age / config.lifetime_secs.max(f32::EPSILON) * config.rise;
+        let screen = camera.and_then(|(camera, transform)| {
+            camera
+                .world_to_viewport(transform, text.position + Vec3::Y * rise)
+                .ok()
+        });
+        let Some(screen) = screen else {
+            // Behind the camera.
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        style.left = Val::Px(screen.x - NODE_WIDTH / 2.0);
+        style.top = Val::Px(screen.y);
+        *visibility = Visibility::Inherited;
+    }
+}
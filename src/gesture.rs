@@ -0,0 +1,154 @@
+//! Gestures are one shot, upper body animations such as pointing, shrugging or
+//! nodding that play over whatever the legs are doing, e.g. for NPCs talking.
+//! Send a [`PlayGesture`] with the name of a gesture in the character's
+//! [`GestureLibrary`], or call
+//! [`PlayerAnimationState::play_gesture`](crate::state::PlayerAnimationState::play_gesture)
+//! directly.
+//!
+//! Gestures animate the bones from the upper spine up, while the look direction
+//! keeps aiming the lower spine, so a character can nod or point while facing
+//! who it's talking to. Sprinting and montages are full body and hide gestures
+//! until they're done.
+
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::state::PlayerAnimationState;
+
+pub struct GesturePlugin;
+
+impl Plugin for GesturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayGesture>();
+        app.register_type::<GestureLibrary>();
+        app.add_systems(Update, play_gestures.in_set(CharAnimSet::ModifyInput));
+    }
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct Gesture {
+    pub name: &'static str,
+    pub clip: Handle<AnimationClip>,
+    pub speed: f32,
+    /// Seconds to blend from the upper body idle into the gesture.
+    pub blend_in: f32,
+    /// Seconds to blend back out, at the end of the clip or when stopped early.
+    pub blend_out: f32,
+    /// How much the spine keeps aiming in the look direction during the
+    /// gesture, in [0, 1]. Pointing wants 1, while a shrug looks better with the
+    /// spine relaxing towards the legs.
+    pub aim_weight: f32,
+}
+
+impl Gesture {
+    pub fn new(name: &'static str, clip: Handle<AnimationClip>) -> Self {
+        Self {
+            name,
+            clip,
+            speed: 1.0,
+            blend_in: 0.2,
+            blend_out: 0.3,
+            aim_weight: 1.0,
+        }
+    }
+
+    pub fn with_blend(mut self, blend_in: f32, blend_out: f32) -> Self {
+        self.blend_in = blend_in;
+        self.blend_out = blend_out;
+        self
+    }
+
+    pub fn with_aim_weight(mut self, aim_weight: f32) -> Self {
+        self.aim_weight = aim_weight.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// The gestures a character knows. Add to the character root.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct GestureLibrary {
+    pub gestures: Vec<Gesture>,
+}
+
+impl GestureLibrary {
+    pub fn new(gestures: Vec<Gesture>) -> Self {
+        Self { gestures }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Gesture> {
+        self.gestures.iter().find(|gesture| gesture.name == name)
+    }
+}
+
+/// Requests a character to gesture, e.g. from a dialogue line. A gesture that
+/// is already playing is blended out into the new one.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PlayGesture {
+    /// The character root with the [`GestureLibrary`].
+    pub character: Entity,
+    pub gesture: &'static str,
+}
+
+pub(crate) struct ActiveGesture {
+    pub gesture: Gesture,
+    /// The graph node playing the clip, once started.
+    pub node: Option<AnimationNodeIndex>,
+    /// The current blend weight in [0, 1].
+    pub weight: f32,
+    /// Whether the gesture is blending out.
+    pub stopping: bool,
+}
+
+impl ActiveGesture {
+    pub fn new(gesture: Gesture) -> Self {
+        Self {
+            gesture,
+            node: None,
+            weight: 0.0,
+            stopping: false,
+        }
+    }
+
+    /// Blends towards the target weight, starting to blend out in time to be
+    /// done when the clip ends. Returns whether the gesture is done.
+    pub fn blend(&mut self, remaining_secs: f32, delta_secs: f32) -> bool {
+        if remaining_secs <= self.gesture.blend_out {
+            self.stopping = true;
+        }
+        if self.stopping {
+            self.weight -= delta_secs / self.gesture.blend_out.max(f32::EPSILON);
+        } else {
+            self.weight += delta_secs / self.gesture.blend_in.max(f32::EPSILON);
+        }
+        self.weight = self.weight.clamp(0.0, 1.0);
+        self.stopping && self.weight <= 0.0
+    }
+}
+
+fn play_gestures(
+    mut requests: EventReader<PlayGesture>,
+    libraries: Query<&GestureLibrary>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+) {
+    for request in requests.read() {
+        let Ok(library) = libraries.get(request.character) else {
+            continue;
+        };
+        let Some(gesture) = library.get(request.gesture) else {
+            warn!("no gesture named {}", request.gesture);
+            continue;
+        };
+        let Some(state_entity) = children
+            .iter_descendants(request.character)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        states
+            .get_mut(state_entity)
+            .unwrap()
+            .play_gesture(gesture.clone());
+    }
+}
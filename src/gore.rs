@@ -0,0 +1,243 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+#[cfg(feature = "hanabi")]
+use bevy_hanabi::prelude::*;
+use rand::Rng;
+
+use crate::decal::{DecalKind, DecalPlugin, DecalPools, SpawnDecal};
+use crate::effect_rng::{EffectRng, BLOOD_POOL_STREAM, BLOOD_SPRAY_STREAM};
+use crate::events::{CharAnimEventsPlugin, HitEvent};
+use crate::hitbox::Hitbox;
+use crate::hitscan::Hitscan;
+use crate::surface::SurfaceParticles;
+#[cfg(feature = "hanabi")]
+use crate::surface::{normal_property, surface_effect};
+use crate::tracer::{DespawnAfter, EffectClock, EffectLifetimePlugin};
+#[cfg(feature = "hanabi")]
+use crate::vfx::VfxQuality;
+use crate::vfx::{
+    add_particle_plugin, profile_layers, EffectLayers, EffectVisibility, LayeredEffect,
+    ParticleKind,
+};
+
+/// Blood, with the `gore` feature: sprays of blood on hits on hitboxes, and
+/// pools of blood decals that spread on the floor under [`Bleeding`]
+/// characters. [`GoreSettings::enabled`] turns it all off at runtime, e.g. for
+/// a parental setting, and clears the blood already on the floor.
+pub struct GorePlugin;
+
+impl Plugin for GorePlugin {
+    fn build(&self, app: &mut App) {
+        add_particle_plugin(app);
+        if !app.is_plugin_added::<DecalPlugin>() {
+            app.add_plugins(DecalPlugin);
+        }
+        if !app.is_plugin_added::<CharAnimEventsPlugin>() {
+            app.add_plugins(CharAnimEventsPlugin);
+        }
+        if !app.is_plugin_added::<EffectLifetimePlugin>() {
+            app.add_plugins(EffectLifetimePlugin::default());
+        }
+        app.init_resource::<GoreSettings>();
+        app.register_type::<GoreSettings>();
+        app.register_type::<Bleeding>();
+        app.add_systems(
+            Update,
+            (
+                apply_gore_settings.run_if(resource_changed::<GoreSettings>),
+                (spray_blood, pool_blood).run_if(|settings: Res<GoreSettings>| settings.enabled),
+            )
+                .chain(),
+        );
+        #[cfg(feature = "hanabi")]
+        app.add_systems(Startup, setup_blood_spray).add_systems(
+            Update,
+            rebuild_blood_spray
+                .run_if(
+                    resource_changed::<GoreSettings>
+                        .or(resource_changed::<VfxQuality>.and(not(resource_added::<VfxQuality>))),
+                )
+                .before(spray_blood),
+        );
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct GoreSettings {
+    pub enabled: bool,
+    /// Keeps blood pools until the decal budget needs room, rather than
+    /// letting them dry up after a minute.
+    pub persistent: bool,
+    pub spray: SurfaceParticles,
+}
+
+impl Default for GoreSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            persistent: false,
+            spray: SurfaceParticles {
+                count: 20.0,
+                color: [0.35, 0.0, 0.0, 1.0],
+                size: 0.02,
+                speed: 2.5,
+                spread: 0.4,
+                gravity: -9.81,
+                lifetime_secs: 0.5,
+                render_layers: None,
+            },
+        }
+    }
+}
+
+/// Add to a dying character's root to spread a pool of blood on the floor
+/// under it.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bleeding {
+    /// How wide the pool gets, in meters.
+    pub max_size: f32,
+    /// How long the pool takes to spread to its full size, in seconds.
+    pub duration_secs: f32,
+    elapsed_secs: f32,
+    next_drop_secs: f32,
+}
+
+impl Bleeding {
+    pub fn new(max_size: f32, duration_secs: f32) -> Self {
+        Self {
+            max_size,
+            duration_secs,
+            elapsed_secs: 0.0,
+            next_drop_secs: 0.0,
+        }
+    }
+}
+
+impl Default for Bleeding {
+    fn default() -> Self {
+        Self::new(1.2, 8.0)
+    }
+}
+
+/// How often a bleeding character adds to its pool, in seconds.
+const DROP_INTERVAL_SECS: f32 = 0.5;
+
+#[cfg(feature = "hanabi")]
+#[derive(Resource)]
+struct BloodSpray(Handle<EffectAsset>);
+
+#[cfg(feature = "hanabi")]
+fn setup_blood_spray(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    settings: Res<GoreSettings>,
+    quality: Res<VfxQuality>,
+) {
+    let spray = effects.add(surface_effect(&settings.spray, &quality));
+    commands.insert_resource(BloodSpray(spray));
+}
+
+/// Rebuilds the spray in place when the settings or the quality change.
+#[cfg(feature = "hanabi")]
+fn rebuild_blood_spray(
+    mut effects: ResMut<Assets<EffectAsset>>,
+    spray: Res<BloodSpray>,
+    settings: Res<GoreSettings>,
+    quality: Res<VfxQuality>,
+) {
+    effects.insert(&spray.0, surface_effect(&settings.spray, &quality));
+}
+
+/// Sets how long the pools last, and clears the blood when gore is turned off.
+fn apply_gore_settings(
+    mut commands: Commands,
+    settings: Res<GoreSettings>,
+    mut pools: ResMut<DecalPools>,
+) {
+    pools.blood.lifetime = (!settings.persistent).then(|| Duration::from_secs(60));
+    if !settings.enabled {
+        pools.blood.clear(&mut commands);
+    }
+}
+
+fn spray_blood(
+    mut commands: Commands,
+    mut hits: EventReader<HitEvent>,
+    hitboxes: Query<(), With<Hitbox>>,
+    #[cfg(feature = "hanabi")] spray: Res<BloodSpray>,
+    settings: Res<GoreSettings>,
+    layers: Query<&EffectLayers>,
+    parents: Query<&ChildOf>,
+    visibility: EffectVisibility,
+    #[cfg(feature = "hanabi")] mut effect_rng: ResMut<EffectRng>,
+) {
+    for hit in hits.read() {
+        if !hitboxes.contains(hit.target) || !visibility.sees(hit.meta.position, 0.0) {
+            continue;
+        }
+        // Out of the exit wound, along the shot.
+        let shooter_layers = EffectLayers::find(hit.meta.entity, &layers, &parents);
+        let mut blood = commands.spawn((
+            ParticleKind::BloodSpray,
+            Transform::from_translation(hit.meta.position).looking_to(hit.direction, Vec3::Y),
+            DespawnAfter::new(
+                Duration::from_secs_f32(settings.spray.lifetime_secs),
+                EffectClock::Virtual,
+            ),
+        ));
+        #[cfg(feature = "hanabi")]
+        blood.insert((
+            effect_rng.particle_effect(BLOOD_SPRAY_STREAM, spray.0.clone()),
+            normal_property(hit.direction),
+        ));
+        if let Some(layers) = profile_layers(&settings.spray.render_layers, shooter_layers) {
+            blood.insert((layers, LayeredEffect));
+        }
+    }
+}
+
+/// Drops blood decals under bleeding characters, each bigger than the last so
+/// the pool spreads.
+fn pool_blood(
+    mut bleeding: Query<(Entity, &mut Bleeding, &GlobalTransform)>,
+    hitscan: Hitscan,
+    layers: Query<&EffectLayers>,
+    parents: Query<&ChildOf>,
+    mut decals: EventWriter<SpawnDecal>,
+    mut effect_rng: ResMut<EffectRng>,
+    time: Res<Time>,
+) {
+    let rng = effect_rng.stream(BLOOD_POOL_STREAM);
+    for (entity, mut bleeding, transform) in bleeding.iter_mut() {
+        if bleeding.elapsed_secs >= bleeding.duration_secs {
+            continue;
+        }
+        bleeding.elapsed_secs += time.delta_secs();
+        if bleeding.elapsed_secs < bleeding.next_drop_secs {
+            continue;
+        }
+        bleeding.next_drop_secs += DROP_INTERVAL_SECS;
+
+        // Just above the feet, under the legs' hitboxes.
+        let origin = transform.translation() + Vec3::Y * 0.05;
+        let Some(floor) = hitscan.cast_ray(origin, Dir3::NEG_Y, 1.0, Some(entity)) else {
+            continue;
+        };
+        let spread = (bleeding.elapsed_secs / bleeding.duration_secs).min(1.0);
+        let size = bleeding.max_size * spread.sqrt();
+        let offset = Vec3::new(rng.gen_range(-1.0..1.0), 0.0, rng.gen_range(-1.0..1.0));
+        decals.write(SpawnDecal {
+            kind: DecalKind::Blood,
+            position: floor.point + offset * size * 0.15,
+            normal: floor.normal,
+            size,
+            render_layers: EffectLayers::find(entity, &layers, &parents)
+                .map(|layers| layers.iter().collect()),
+        });
+    }
+}
@@ -0,0 +1,290 @@
+use bevy::{app::Animation, pbr::NotShadowCaster, prelude::*};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::ik::{solve_two_bone_ik, IkTarget, TwoBoneIk};
+use crate::pose_authority::apply_pose_authority;
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+use crate::tracer::{beam_transform, TracerParams, TracerShader};
+
+/// Grappling hooks. A [`Grapple`] holds an arm-extended pose while aiming,
+/// and once the hook is attached it reaches for the hook point with hand IK,
+/// plays a swing pose in the air that leans the way the character swings, and
+/// draws the rope with the tracer material. The swing itself is up to the
+/// character controller.
+pub struct GrapplePlugin;
+
+impl Plugin for GrapplePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Grapple>();
+        app.add_systems(
+            Update,
+            (grapple.in_set(CharAnimSet::ModifyInput), draw_ropes),
+        );
+        app.add_systems(
+            PostUpdate,
+            lean_swings
+                .after(Animation)
+                .before(solve_two_bone_ik)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// How the rope looks.
+#[derive(Reflect, Clone, Debug)]
+pub struct RopeLook {
+    /// The radius of the rope in meters.
+    pub radius: f32,
+    pub color: [f32; 4],
+    pub brightness: f32,
+}
+
+impl Default for RopeLook {
+    fn default() -> Self {
+        Self {
+            radius: 0.015,
+            color: [0.35, 0.3, 0.25, 1.0],
+            brightness: 1.0,
+        }
+    }
+}
+
+/// Lets a character use a grappling hook. Add to the character root with a
+/// `RigMap`.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct Grapple {
+    /// The upper body pose with the arm held out, played while aiming and
+    /// attached.
+    pub aim_pose: Handle<AnimationClip>,
+    /// The looping full body pose while swinging in the air.
+    pub swing_pose: Handle<AnimationClip>,
+    /// The hand that holds the rope.
+    pub hand: RigBone,
+    /// How far the body leans per m/s of swing speed, in radians.
+    pub lean_per_speed: f32,
+    /// The most the body leans, in radians.
+    pub max_lean: f32,
+    /// How fast the lean moves in radians per second.
+    pub lean_speed: f32,
+    /// How much the spine aims while swinging, so the character can shoot.
+    pub aim_weight: f32,
+    pub rope: RopeLook,
+    aiming: bool,
+    /// The point the hook is attached to, in global world space.
+    hook: Option<Vec3>,
+    /// The upper body pose from before the aim pose, while it's playing.
+    #[reflect(ignore)]
+    prior_pose: Option<Option<Handle<AnimationClip>>>,
+    last_position: Option<Vec3>,
+    /// The horizontal velocity of the swing.
+    velocity: Vec3,
+    /// The current lean as an axis scaled by the angle, in global world space.
+    lean: Vec3,
+    #[reflect(ignore)]
+    rope_entity: Option<Entity>,
+    #[reflect(ignore)]
+    rope_material: Option<Handle<TracerShader>>,
+}
+
+impl Grapple {
+    pub fn new(aim_pose: Handle<AnimationClip>, swing_pose: Handle<AnimationClip>) -> Self {
+        Self {
+            aim_pose,
+            swing_pose,
+            hand: RigBone::LeftHand,
+            lean_per_speed: 3f32.to_radians(),
+            max_lean: 25f32.to_radians(),
+            lean_speed: 90f32.to_radians(),
+            aim_weight: 0.5,
+            rope: RopeLook::default(),
+            aiming: false,
+            hook: None,
+            prior_pose: None,
+            last_position: None,
+            velocity: Vec3::ZERO,
+            lean: Vec3::ZERO,
+            rope_entity: None,
+            rope_material: None,
+        }
+    }
+
+    /// Holds the arm out, e.g. while the grapple button is held.
+    pub fn set_aiming(&mut self, aiming: bool) {
+        self.aiming = aiming;
+    }
+
+    /// Attaches the hook to a point in global world space.
+    pub fn attach(&mut self, point: Vec3) {
+        self.hook = Some(point);
+    }
+
+    pub fn detach(&mut self) {
+        self.hook = None;
+    }
+
+    /// The point the hook is attached to, in global world space.
+    pub fn hook(&self) -> Option<Vec3> {
+        self.hook
+    }
+}
+
+fn grapple(
+    mut commands: Commands,
+    mut characters: Query<(Entity, &mut Grapple, &GlobalTransform, Option<&RigMap>)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    mut iks: Query<&mut TwoBoneIk>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for (root, mut grapple, transform, rig) in characters.iter_mut() {
+        let position = transform.translation();
+        if let (Some(last), true) = (grapple.last_position, dt > 0.0) {
+            grapple.velocity = (position - last).with_y(0.0) / dt;
+        }
+        grapple.last_position = Some(position);
+
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let posed = grapple.aiming || grapple.hook.is_some();
+        match (posed, grapple.prior_pose.is_some()) {
+            (true, false) => {
+                grapple.prior_pose = Some(state.upper_body_pose().cloned());
+                state.set_upper_body_pose(Some(grapple.aim_pose.clone()));
+            }
+            (false, true) => state.set_upper_body_pose(grapple.prior_pose.take().unwrap()),
+            _ => {}
+        }
+        let grounded = state.input().is_some_and(|input| input.is_grounded);
+        if grapple.hook.is_some() && !grounded {
+            state.override_locomotion(LocomotionOverride {
+                clips: vec![LocomotionClip::new(grapple.swing_pose.clone(), 1.0)],
+                pitch: 0.0,
+                yaw: None,
+                aim_weight: grapple.aim_weight,
+            });
+        }
+
+        let Some(hand) = rig.and_then(|rig| rig.get(grapple.hand)) else {
+            continue;
+        };
+        let (target, weight) = match grapple.hook {
+            Some(hook) => (IkTarget::Point(hook), 1.0),
+            None => (IkTarget::Point(Vec3::ZERO), 0.0),
+        };
+        if let Ok(mut ik) = iks.get_mut(hand) {
+            ik.target = target;
+            ik.weight = weight;
+            ik.match_target_rotation = false;
+        } else if weight > 0.0 {
+            commands.entity(hand).insert(TwoBoneIk::new(target));
+        }
+    }
+}
+
+/// Leans the body the way it swings, about the hips.
+fn lean_swings(
+    mut characters: Query<(&mut Grapple, &RigMap)>,
+    mut transforms: Query<&mut Transform>,
+    global_transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
+) {
+    for (mut grapple, rig) in characters.iter_mut() {
+        // Tipping up towards the velocity is a rotation about up cross it.
+        let target = match grapple.hook {
+            Some(_) => {
+                let angle = (grapple.velocity.length() * grapple.lean_per_speed)
+                    .min(grapple.max_lean);
+                Vec3::Y.cross(grapple.velocity).normalize_or_zero() * angle
+            }
+            None => Vec3::ZERO,
+        };
+        let step = grapple.lean_speed * time.delta_secs();
+        let lean = grapple.lean;
+        grapple.lean = lean + (target - lean).clamp_length_max(step);
+        let Some((axis, angle)) = grapple
+            .lean
+            .try_normalize()
+            .map(|axis| (axis, grapple.lean.length()))
+        else {
+            continue;
+        };
+        let Some(hips) = rig.get(RigBone::Hips) else {
+            continue;
+        };
+        let (Ok(hips_global), Ok(mut transform)) =
+            (global_transforms.get(hips), transforms.get_mut(hips))
+        else {
+            continue;
+        };
+        let axis = (hips_global.rotation().inverse() * axis).normalize();
+        transform.rotation *= Quat::from_axis_angle(axis, angle);
+    }
+}
+
+fn draw_ropes(
+    mut commands: Commands,
+    mut characters: Query<(&mut Grapple, &RigMap)>,
+    mut ropes: Query<(&mut Transform, &mut Visibility)>,
+    global_transforms: Query<&GlobalTransform>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<TracerShader>>,
+    mut rope_mesh: Local<Option<Handle<Mesh>>>,
+) {
+    for (mut grapple, rig) in characters.iter_mut() {
+        let hand = rig
+            .get(grapple.hand)
+            .and_then(|hand| global_transforms.get(hand).ok())
+            .map(|hand| hand.translation());
+        let beam = hand
+            .zip(grapple.hook)
+            .and_then(|(hand, hook)| beam_transform(hand, hook, grapple.rope.radius));
+
+        let grapple = grapple.as_mut();
+        let rope = match grapple.rope_entity {
+            Some(rope) => rope,
+            None if beam.is_some() => {
+                let mesh = rope_mesh
+                    .get_or_insert_with(|| meshes.add(Cylinder::new(1.0, 1.0).mesh().build()))
+                    .clone();
+                let look = &grapple.rope;
+                let material = grapple.rope_material.get_or_insert_with(|| {
+                    let color = LinearRgba::from_f32_array(look.color);
+                    materials.add(TracerShader {
+                        params: TracerParams::beam(color, look.brightness),
+                    })
+                });
+                let rope = commands
+                    .spawn((
+                        Mesh3d(mesh),
+                        MeshMaterial3d(material.clone()),
+                        NotShadowCaster,
+                        Transform::default(),
+                        Visibility::Hidden,
+                    ))
+                    .id();
+                grapple.rope_entity = Some(rope);
+                rope
+            }
+            None => continue,
+        };
+        let Ok((mut transform, mut visibility)) = ropes.get_mut(rope) else {
+            continue;
+        };
+        match beam {
+            Some(beam) => {
+                *transform = beam;
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
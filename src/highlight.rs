@@ -0,0 +1,238 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{
+        mesh::skinning::SkinnedMesh,
+        render_resource::{AsBindGroup, ShaderRef, ShaderType},
+    },
+};
+
+/// Highlights characters, e.g. a spotted enemy or an NPC that can be talked
+/// to. A [`Highlight`] on a character root draws its skinned meshes again on
+/// top with a [`HighlightMaterial`], which adds a glowing outline or rim
+/// around the silhouette. Meshes that spawn later, e.g. once the scene loads,
+/// are picked up too.
+pub struct HighlightPlugin;
+
+impl Plugin for HighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<HighlightMaterial>::default());
+        app.register_type::<Highlight>();
+        app.add_systems(
+            Update,
+            (
+                remove_highlights,
+                update_highlight_materials,
+                add_highlight_overlays,
+                pulse_highlights,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum HighlightStyle {
+    /// A thin solid line along the silhouette.
+    #[default]
+    Outline,
+    /// A soft glow that's strongest at grazing angles.
+    RimGlow,
+}
+
+/// Highlights the skinned meshes under this entity.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Highlight {
+    /// Brighter than 1 blooms with HDR.
+    pub color: LinearRgba,
+    pub style: HighlightStyle,
+    /// How wide the outline or rim is, from 0 to 1 of the way in from the
+    /// silhouette.
+    pub width: f32,
+    /// Pulses per second, 0 doesn't pulse.
+    pub pulse_speed: f32,
+    /// How much of the brightness the pulse takes away at its low, from 0 to 1.
+    pub pulse_amount: f32,
+}
+
+impl Highlight {
+    pub fn outline(color: impl Into<LinearRgba>) -> Self {
+        Self {
+            color: color.into(),
+            style: HighlightStyle::Outline,
+            width: 0.25,
+            pulse_speed: 0.0,
+            pulse_amount: 0.0,
+        }
+    }
+
+    pub fn rim_glow(color: impl Into<LinearRgba>) -> Self {
+        Self {
+            style: HighlightStyle::RimGlow,
+            width: 0.6,
+            ..Self::outline(color)
+        }
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_pulse(mut self, speed: f32, amount: f32) -> Self {
+        self.pulse_speed = speed;
+        self.pulse_amount = amount;
+        self
+    }
+}
+
+/// The shader the highlight material draws with.
+pub const HIGHLIGHT_SHADER_PATH: &str = "shaders/highlight.wgsl";
+
+/// Drawn additively over a mesh with the same skin, so only the highlight
+/// shows. Each highlighted character has its own.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct HighlightMaterial {
+    #[uniform(0)]
+    pub params: HighlightParams,
+}
+
+/// Matches `HighlightParams` in `highlight.wgsl`, field for field.
+#[derive(ShaderType, Clone, Debug)]
+pub struct HighlightParams {
+    pub color: LinearRgba,
+    /// 0 for an outline, 1 for a rim glow.
+    pub style: u32,
+    pub width: f32,
+    pub pulse_speed: f32,
+    pub pulse_amount: f32,
+    /// Seconds of real time, fed in so the pulse keeps going while the game
+    /// is paused, e.g. to highlight the selected character in a menu.
+    pub time: f32,
+}
+
+impl HighlightParams {
+    pub fn new(highlight: &Highlight) -> Self {
+        Self {
+            color: highlight.color,
+            style: match highlight.style {
+                HighlightStyle::Outline => 0,
+                HighlightStyle::RimGlow => 1,
+            },
+            width: highlight.width,
+            pulse_speed: highlight.pulse_speed,
+            pulse_amount: highlight.pulse_amount,
+            time: 0.0,
+        }
+    }
+}
+
+impl Material for HighlightMaterial {
+    fn fragment_shader() -> ShaderRef {
+        HIGHLIGHT_SHADER_PATH.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Add
+    }
+}
+
+/// The material of a highlighted entity and the overlay meshes drawn with it.
+#[derive(Component)]
+struct HighlightLayer {
+    material: Handle<HighlightMaterial>,
+    overlays: Vec<Entity>,
+}
+
+/// An overlay mesh, on the skinned mesh it covers.
+#[derive(Component)]
+struct HighlightOverlay;
+
+fn remove_highlights(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Highlight>,
+    layers: Query<&HighlightLayer, Without<Highlight>>,
+) {
+    for entity in removed.read() {
+        let Ok(layer) = layers.get(entity) else {
+            continue;
+        };
+        for &overlay in layer.overlays.iter() {
+            commands.entity(overlay).try_despawn();
+        }
+        commands.entity(entity).remove::<HighlightLayer>();
+    }
+}
+
+fn update_highlight_materials(
+    mut commands: Commands,
+    highlights: Query<(Entity, &Highlight, Option<&HighlightLayer>), Changed<Highlight>>,
+    mut materials: ResMut<Assets<HighlightMaterial>>,
+) {
+    for (entity, highlight, layer) in highlights.iter() {
+        let params = HighlightParams::new(highlight);
+        match layer.and_then(|layer| materials.get_mut(&layer.material)) {
+            Some(material) => material.params = params,
+            None => {
+                commands.entity(entity).insert(HighlightLayer {
+                    material: materials.add(HighlightMaterial { params }),
+                    overlays: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+/// Covers the skinned meshes under each highlight that aren't covered yet.
+fn add_highlight_overlays(
+    mut commands: Commands,
+    mut layers: Query<(Entity, &mut HighlightLayer)>,
+    children: Query<&Children>,
+    meshes: Query<(&Mesh3d, &SkinnedMesh)>,
+    overlays: Query<(), With<HighlightOverlay>>,
+) {
+    for (root, mut layer) in layers.iter_mut() {
+        for entity in children.iter_descendants(root) {
+            let Ok((mesh, skin)) = meshes.get(entity) else {
+                continue;
+            };
+            let covered = children
+                .get(entity)
+                .is_ok_and(|children| children.iter().any(|child| overlays.contains(child)));
+            if overlays.contains(entity) || covered {
+                continue;
+            }
+            // Skinned by the same joints, so it follows the animation.
+            let overlay = commands
+                .spawn((
+                    HighlightOverlay,
+                    mesh.clone(),
+                    skin.clone(),
+                    MeshMaterial3d(layer.material.clone()),
+                    NotShadowCaster,
+                    NotShadowReceiver,
+                    ChildOf(entity),
+                ))
+                .id();
+            layer.overlays.push(overlay);
+        }
+    }
+}
+
+fn pulse_highlights(
+    layers: Query<(&Highlight, &HighlightLayer)>,
+    mut materials: ResMut<Assets<HighlightMaterial>>,
+    time: Res<Time<Real>>,
+) {
+    for (highlight, layer) in layers.iter() {
+        if highlight.pulse_speed == 0.0 {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(&layer.material) {
+            material.params.time = time.elapsed_secs_wrapped();
+        }
+    }
+}
@@ -0,0 +1,407 @@
+#[cfg(feature = "avian")]
+use avian3d::prelude::{
+    Collider, CollisionLayers, LayerMask, LinearVelocity, RigidBody, Sensor, SweptCcd,
+};
+use bevy::prelude::*;
+#[cfg(not(feature = "avian"))]
+use bevy_rapier3d::prelude::{Ccd, Collider, CollisionGroups, Group, RigidBody, Sensor};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::events::NotifyWindowEvent;
+use crate::melee::HIT_WINDOW;
+
+/// The notify window during which [`PushLimbs::melee`] feet push props.
+pub const KICK_WINDOW: &str = "Kick";
+
+/// The collision group bit of [`Hitbox`] capsules and character capsules,
+/// which [`PushLimbs`] balls don't collide with.
+pub const CHARACTER_COLLISION_GROUP: u32 = 1 << 30;
+/// The collision group bit of [`PushLimbs`] balls.
+pub const PUSH_COLLISION_GROUP: u32 = 1 << 31;
+
+/// Per bone hitboxes. Add [`Hitboxes`] to a character root with a `RigMap` and
+/// a capsule sensor is spawned on each bone it lists. The capsules are children
+/// of the bones, so they follow the animation. Hits on them can look up the
+/// [`Hitbox`] to know which body part was hit. The colliders are rapier's, or
+/// avian3d's with the `avian` feature.
+///
+/// Limbs can also push dynamic bodies around, e.g. props knocked over by a
+/// punch: [`PushLimbs`] spawns solid kinematic balls that follow the limbs
+/// while a notify window is open. The balls pass through hitboxes and
+/// character capsules, i.e. colliders in [`CHARACTER_COLLISION_GROUP`]. A
+/// collider on the character root without collision groups is put in it when
+/// the first ball spawns, a capsule elsewhere has to be put in it by the game.
+pub struct HitboxPlugin;
+
+impl Plugin for HitboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Hitboxes>();
+        app.register_type::<Hitbox>();
+        app.register_type::<PushLimbs>();
+        app.add_systems(
+            Update,
+            (
+                spawn_hitboxes,
+                (toggle_push_colliders, follow_push_colliders)
+                    .chain()
+                    .after(CharAnimSet::StateMachine),
+            ),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum BodyPart {
+    Head,
+    Torso,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+}
+
+/// A capsule from one bone to another.
+#[derive(Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitboxDef {
+    pub part: BodyPart,
+    /// The bone the capsule is attached to and starts at.
+    pub bone: RigBone,
+    /// The bone the capsule ends at, in the bind pose. If none, the capsule is
+    /// a sphere on `bone`.
+    pub end: Option<RigBone>,
+    /// The radius in meters.
+    pub radius: f32,
+    pub damage_multiplier: f32,
+}
+
+impl HitboxDef {
+    pub fn new(part: BodyPart, bone: RigBone, end: Option<RigBone>, radius: f32) -> Self {
+        Self {
+            part,
+            bone,
+            end,
+            radius,
+            damage_multiplier: 1.0,
+        }
+    }
+
+    pub fn with_damage_multiplier(mut self, damage_multiplier: f32) -> Self {
+        self.damage_multiplier = damage_multiplier;
+        self
+    }
+}
+
+/// The hitboxes of a character. Add to the character root.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Hitboxes {
+    pub defs: Vec<HitboxDef>,
+    /// The spawned capsules, empty until the rig is detected.
+    spawned: Vec<Entity>,
+}
+
+impl Hitboxes {
+    pub fn new(defs: Vec<HitboxDef>) -> Self {
+        Self {
+            defs,
+            spawned: Vec::new(),
+        }
+    }
+
+    /// Head, torso, and upper and lower limbs for an adult sized humanoid.
+    pub fn humanoid() -> Self {
+        use BodyPart::*;
+        Self::new(vec![
+            HitboxDef::new(Head, RigBone::Head, None, 0.12).with_damage_multiplier(4.0),
+            HitboxDef::new(Torso, RigBone::Hips, Some(RigBone::Spine1), 0.16),
+            HitboxDef::new(Torso, RigBone::Spine1, Some(RigBone::Neck), 0.17),
+            HitboxDef::new(LeftArm, RigBone::LeftArm, Some(RigBone::LeftForeArm), 0.06)
+                .with_damage_multiplier(0.75),
+            HitboxDef::new(LeftArm, RigBone::LeftForeArm, Some(RigBone::LeftHand), 0.05)
+                .with_damage_multiplier(0.75),
+            HitboxDef::new(RightArm, RigBone::RightArm, Some(RigBone::RightForeArm), 0.06)
+                .with_damage_multiplier(0.75),
+            HitboxDef::new(RightArm, RigBone::RightForeArm, Some(RigBone::RightHand), 0.05)
+                .with_damage_multiplier(0.75),
+            HitboxDef::new(LeftLeg, RigBone::LeftUpLeg, Some(RigBone::LeftLeg), 0.08)
+                .with_damage_multiplier(0.75),
+            HitboxDef::new(LeftLeg, RigBone::LeftLeg, Some(RigBone::LeftFoot), 0.06)
+                .with_damage_multiplier(0.75),
+            HitboxDef::new(RightLeg, RigBone::RightUpLeg, Some(RigBone::RightLeg), 0.08)
+                .with_damage_multiplier(0.75),
+            HitboxDef::new(RightLeg, RigBone::RightLeg, Some(RigBone::RightFoot), 0.06)
+                .with_damage_multiplier(0.75),
+        ])
+    }
+
+    pub fn spawned(&self) -> &[Entity] {
+        &self.spawned
+    }
+}
+
+/// A spawned hitbox capsule.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Hitbox {
+    pub part: BodyPart,
+    pub damage_multiplier: f32,
+    /// The character root.
+    pub character: Entity,
+    /// The ends of the capsule's axis in the bone's space, the same point for
+    /// a sphere.
+    pub start: Vec3,
+    pub end: Vec3,
+}
+
+fn spawn_hitboxes(
+    mut commands: Commands,
+    mut characters: Query<(Entity, &mut Hitboxes, &RigMap)>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    for (root, mut hitboxes, rig) in characters.iter_mut() {
+        if !hitboxes.spawned.is_empty() {
+            continue;
+        }
+        let hitboxes = &mut *hitboxes;
+        for def in hitboxes.defs.iter() {
+            let Some(bone) = rig.get(def.bone) else {
+                continue;
+            };
+            let Ok(bone_global) = global_transforms.get(bone) else {
+                continue;
+            };
+            // The capsule is in the bone's space, which may be scaled, e.g. by
+            // the centimetre units of Mixamo rigs.
+            let to_local = bone_global.affine().inverse();
+            let end = def
+                .end
+                .and_then(|end| rig.get(end))
+                .and_then(|end| global_transforms.get(end).ok())
+                .map_or(Vec3::ZERO, |end| to_local.transform_point3(end.translation()));
+            let scale = bone_global.compute_transform().scale.max_element();
+            let radius = def.radius / scale.max(f32::EPSILON);
+            // Keep the capsule's caps within the bones it spans.
+            let axis = end.normalize_or_zero();
+            let length = end.length();
+            let inset = radius.min(length / 2.0);
+            let (start, end) = (axis * inset, end - axis * inset);
+            #[cfg(not(feature = "avian"))]
+            let collider = Collider::capsule(start, end, radius);
+            #[cfg(feature = "avian")]
+            let collider = Collider::capsule_endpoints(radius, start, end);
+            let hitbox = commands
+                .spawn((
+                    Hitbox {
+                        part: def.part,
+                        damage_multiplier: def.damage_multiplier,
+                        character: root,
+                        start,
+                        end,
+                    },
+                    collider,
+                    Sensor,
+                    character_groups(),
+                    Transform::default(),
+                    ChildOf(bone),
+                ))
+                .id();
+            hitboxes.spawned.push(hitbox);
+        }
+    }
+}
+
+/// A limb that pushes while a notify window is open.
+#[derive(Reflect, Clone, Debug)]
+pub struct PushLimb {
+    /// The `NotifyWindowEvent` name, e.g. [`HIT_WINDOW`] for melee swings.
+    pub window: &'static str,
+    pub bone: RigBone,
+    /// The radius in meters, or the radius of the hitbox ending at the bone if
+    /// none, so the push matches what can be hit.
+    pub radius: Option<f32>,
+}
+
+impl PushLimb {
+    pub fn new(window: &'static str, bone: RigBone) -> Self {
+        Self {
+            window,
+            bone,
+            radius: None,
+        }
+    }
+}
+
+/// The limbs of a character that push dynamic bodies. Add to the character
+/// root, next to its `RigMap`.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct PushLimbs {
+    pub limbs: Vec<PushLimb>,
+    /// The spawned colliders, by limb index.
+    active: Vec<(usize, Entity)>,
+}
+
+impl PushLimbs {
+    pub fn new(limbs: Vec<PushLimb>) -> Self {
+        Self {
+            limbs,
+            active: Vec::new(),
+        }
+    }
+
+    /// Hands during melee hit windows and feet during kicks.
+    pub fn melee() -> Self {
+        Self::new(vec![
+            PushLimb::new(HIT_WINDOW, RigBone::LeftHand),
+            PushLimb::new(HIT_WINDOW, RigBone::RightHand),
+            PushLimb::new(KICK_WINDOW, RigBone::LeftFoot),
+            PushLimb::new(KICK_WINDOW, RigBone::RightFoot),
+        ])
+    }
+}
+
+/// A kinematic ball following a limb's bone.
+#[derive(Component)]
+struct PushCollider {
+    bone: Entity,
+}
+
+/// Used when a limb has no radius and no hitbox ends at its bone.
+const DEFAULT_PUSH_RADIUS: f32 = 0.08;
+
+fn toggle_push_colliders(
+    mut commands: Commands,
+    mut windows: EventReader<NotifyWindowEvent>,
+    mut characters: Query<(&mut PushLimbs, &RigMap, Option<&Hitboxes>)>,
+    ungrouped: Query<(), (With<Collider>, Without<ColliderGroups>)>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    for window in windows.read() {
+        let Ok((mut push, rig, hitboxes)) = characters.get_mut(window.meta.entity) else {
+            continue;
+        };
+        let push = &mut *push;
+        for (index, limb) in push.limbs.iter().enumerate() {
+            if limb.window != window.name {
+                continue;
+            }
+            let active = push.active.iter().position(|(i, _)| *i == index);
+            match (window.open, active) {
+                (true, None) => {
+                    let Some(bone) = rig.get(limb.bone) else {
+                        continue;
+                    };
+                    let Ok(bone_global) = global_transforms.get(bone) else {
+                        continue;
+                    };
+                    let radius = limb.radius.unwrap_or_else(|| {
+                        hitboxes
+                            .and_then(|hitboxes| {
+                                hitboxes.defs.iter().find(|def| def.end == Some(limb.bone))
+                            })
+                            .map_or(DEFAULT_PUSH_RADIUS, |def| def.radius)
+                    });
+                    if ungrouped.contains(window.meta.entity) {
+                        commands
+                            .entity(window.meta.entity)
+                            .insert(character_groups());
+                    }
+                    // Not parented to the bone, so the physics moves it as a
+                    // kinematic body and pushes what it runs into.
+                    let collider = commands
+                        .spawn((
+                            PushCollider { bone },
+                            push_body(radius),
+                            Transform::from_translation(bone_global.translation()),
+                        ))
+                        .id();
+                    push.active.push((index, collider));
+                }
+                (false, Some(active)) => {
+                    let (_, collider) = push.active.swap_remove(active);
+                    commands.entity(collider).try_despawn();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "avian"))]
+type ColliderGroups = CollisionGroups;
+
+#[cfg(feature = "avian")]
+type ColliderGroups = CollisionLayers;
+
+#[cfg(not(feature = "avian"))]
+fn character_groups() -> CollisionGroups {
+    CollisionGroups::new(
+        Group::from_bits_retain(CHARACTER_COLLISION_GROUP),
+        Group::ALL,
+    )
+}
+
+#[cfg(feature = "avian")]
+fn character_groups() -> CollisionLayers {
+    CollisionLayers::new(CHARACTER_COLLISION_GROUP, LayerMask::ALL)
+}
+
+#[cfg(not(feature = "avian"))]
+fn push_body(radius: f32) -> impl Bundle {
+    (
+        RigidBody::KinematicPositionBased,
+        Collider::ball(radius),
+        Ccd::enabled(),
+        CollisionGroups::new(
+            Group::from_bits_retain(PUSH_COLLISION_GROUP),
+            Group::from_bits_retain(!CHARACTER_COLLISION_GROUP),
+        ),
+    )
+}
+
+#[cfg(feature = "avian")]
+fn push_body(radius: f32) -> impl Bundle {
+    (
+        RigidBody::Kinematic,
+        Collider::sphere(radius),
+        SweptCcd::default(),
+        CollisionLayers::new(PUSH_COLLISION_GROUP, !CHARACTER_COLLISION_GROUP),
+    )
+}
+
+#[cfg(not(feature = "avian"))]
+fn follow_push_colliders(
+    mut commands: Commands,
+    mut colliders: Query<(Entity, &PushCollider, &mut Transform)>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    for (entity, collider, mut transform) in colliders.iter_mut() {
+        match global_transforms.get(collider.bone) {
+            Ok(bone) => transform.translation = bone.translation(),
+            // The character is gone.
+            Err(_) => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+/// Avian's kinematic bodies only push what they run into when moved by
+/// velocity, so the balls are given the velocity that reaches the bone.
+#[cfg(feature = "avian")]
+fn follow_push_colliders(
+    mut commands: Commands,
+    mut colliders: Query<(Entity, &PushCollider, &Transform, &mut LinearVelocity)>,
+    global_transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
+) {
+    let delta_secs = time.delta_secs().max(f32::EPSILON);
+    for (entity, collider, transform, mut velocity) in colliders.iter_mut() {
+        match global_transforms.get(collider.bone) {
+            Ok(bone) => velocity.0 = (bone.translation() - transform.translation) / delta_secs,
+            // The character is gone.
+            Err(_) => commands.entity(entity).despawn(),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+#[cfg(feature = "avian")]
+use avian3d::prelude::{SpatialQuery, SpatialQueryFilter, SpatialQueryPlugin};
+#[cfg(not(feature = "avian"))]
+use bevy_rapier3d::prelude::{QueryFilter, ReadRapierContext};
+
+use crate::aim_assist::{find_aim_target, AimAssist};
+use crate::events::{EventMeta, EventRouting, HitEvent, UserData};
+use crate::hitbox::Hitbox;
+use crate::surface::{SpawnImpact, SurfacePlugin};
+use crate::tracer::{AmmoType, SpawnTracer, TracerProfile};
+
+/// Hitscan shots in one call: [`Hitscan::fire_ray`] traces the shot through
+/// the physics world, spawns its tracer and impact effect, and emits the
+/// `HitEvent`. The impact effect comes from the [`SurfacePlugin`], which is
+/// added with this plugin if it's missing. The trace uses rapier, or
+/// avian3d with the `avian` feature. The game adds the physics plugins of
+/// either, e.g. avian3d's `PhysicsPlugins`, which are checked for once the
+/// app is built.
+pub struct HitscanPlugin;
+
+impl Plugin for HitscanPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<SurfacePlugin>() {
+            app.add_plugins(SurfacePlugin);
+        }
+        app.register_type::<Surface>();
+    }
+
+    #[cfg(feature = "avian")]
+    fn finish(&self, app: &mut App) {
+        if !app.is_plugin_added::<SpatialQueryPlugin>() {
+            warn!("hitscan needs avian3d's PhysicsPlugins, shots won't hit anything");
+        }
+    }
+}
+
+/// What a collider is made of, e.g. "metal" or "flesh". Hits on colliders
+/// without one look for it on their ancestors. Inserting a `SurfaceKind` adds
+/// one for the common kinds.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Surface {
+    pub name: &'static str,
+}
+
+/// Finds the [`Surface`] of a collider, looking up its ancestors if it has
+/// none.
+pub fn find_surface(
+    entity: Entity,
+    surfaces: &Query<&Surface>,
+    parents: &Query<&ChildOf>,
+) -> Option<&'static str> {
+    std::iter::once(entity)
+        .chain(parents.iter_ancestors(entity))
+        .find_map(|e| surfaces.get(e).ok())
+        .map(|surface| surface.name)
+}
+
+/// A hitscan shot. Points and directions are in global world space.
+#[derive(Clone, Debug)]
+pub struct Shot {
+    /// Where the ray starts, e.g. the camera in first person.
+    pub origin: Vec3,
+    /// Where the tracer is drawn from if not `origin`, e.g. the muzzle in first
+    /// person. It's drawn to where the ray hit.
+    pub visual_origin: Option<Vec3>,
+    pub direction: Vec3,
+    pub max_distance: f32,
+    /// The collider that fired the shot, which it can't hit, e.g. the
+    /// character root, along with its hitboxes. It's also the meta entity of
+    /// the `HitEvent`, or the hit collider if none.
+    pub shooter: Option<Entity>,
+    /// The look of the tracer, or the default look if none.
+    pub profile: Option<Handle<TracerProfile>>,
+    /// Passed on to the tracer and the `HitEvent`.
+    pub user_data: Option<UserData>,
+    /// The muzzle entity, whose attachments change how the tracer looks.
+    pub muzzle: Option<Entity>,
+    /// Colors the tracer by its ammo, or by its profile if none.
+    pub ammo: Option<AmmoType>,
+}
+
+impl Shot {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            visual_origin: None,
+            direction,
+            max_distance: 200.0,
+            shooter: None,
+            profile: None,
+            user_data: None,
+            muzzle: None,
+            ammo: None,
+        }
+    }
+}
+
+/// What a ray hit.
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    /// The collider that was hit.
+    pub entity: Entity,
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub distance: f32,
+    /// The [`Surface`] of the collider, if any.
+    pub surface: Option<&'static str>,
+    /// The hitbox that was hit, to tell which body part was hit.
+    pub hitbox: Option<Hitbox>,
+}
+
+#[derive(SystemParam)]
+pub struct Hitscan<'w, 's> {
+    #[cfg(not(feature = "avian"))]
+    rapier: ReadRapierContext<'w, 's>,
+    #[cfg(feature = "avian")]
+    spatial_query: SpatialQuery<'w, 's>,
+    surfaces: Query<'w, 's, &'static Surface>,
+    hitboxes: Query<'w, 's, &'static Hitbox>,
+    hitbox_shapes: Query<'w, 's, (&'static Hitbox, &'static GlobalTransform)>,
+    aim_assists: Query<'w, 's, &'static AimAssist>,
+    parents: Query<'w, 's, &'static ChildOf>,
+    time: Res<'w, Time>,
+    routing: Res<'w, EventRouting>,
+    tracers: EventWriter<'w, SpawnTracer>,
+    impacts: EventWriter<'w, SpawnImpact>,
+    hits: EventWriter<'w, HitEvent>,
+}
+
+impl Hitscan<'_, '_> {
+    /// Traces a ray without any effects.
+    pub fn cast_ray(
+        &self,
+        origin: Vec3,
+        direction: Dir3,
+        max_distance: f32,
+        exclude: Option<Entity>,
+    ) -> Option<RayHit> {
+        let not_excluded = |e: Entity| {
+            !self
+                .hitboxes
+                .get(e)
+                .is_ok_and(|hitbox| Some(hitbox.character) == exclude)
+        };
+        #[cfg(not(feature = "avian"))]
+        let (entity, distance, normal) = {
+            let context = self.rapier.single().ok()?;
+            let mut filter = QueryFilter::default().predicate(&not_excluded);
+            if let Some(exclude) = exclude {
+                filter = filter.exclude_collider(exclude);
+            }
+            let (entity, intersection) = context.cast_ray_and_get_normal(
+                origin,
+                *direction,
+                max_distance,
+                true,
+                filter,
+            )?;
+            (entity, intersection.time_of_impact, intersection.normal)
+        };
+        #[cfg(feature = "avian")]
+        let (entity, distance, normal) = {
+            let filter = SpatialQueryFilter::default().with_excluded_entities(exclude);
+            let hit = self.spatial_query.cast_ray_predicate(
+                origin,
+                direction,
+                max_distance,
+                true,
+                &filter,
+                &not_excluded,
+            )?;
+            (hit.entity, hit.distance, hit.normal)
+        };
+
+        let surface = find_surface(entity, &self.surfaces, &self.parents);
+        Some(RayHit {
+            entity,
+            point: origin + direction * distance,
+            normal,
+            distance,
+            surface,
+            hitbox: self.hitboxes.get(entity).ok().copied(),
+        })
+    }
+
+    /// Fires a shot: traces it, spawns its tracer to the hit point (or to the
+    /// max distance on a miss), and on a hit spawns the impact effect and
+    /// emits a `HitEvent`. A shooter
+    /// with an `AimAssist` that bends shots has it bent first.
+    pub fn fire_ray(&mut self, shot: Shot) -> Option<RayHit> {
+        let direction = self.bend(&shot, Dir3::new(shot.direction).ok()?);
+        let hit = self.cast_ray(shot.origin, direction, shot.max_distance, shot.shooter);
+        let end = hit.map_or(shot.origin + direction * shot.max_distance, |hit| hit.point);
+        self.tracers.write(SpawnTracer {
+            start: shot.visual_origin.unwrap_or(shot.origin),
+            end,
+            logical_start: shot.visual_origin.map(|_| shot.origin),
+            profile: shot.profile.clone(),
+            user_data: shot.user_data.clone(),
+            muzzle: shot.muzzle,
+            ammo: shot.ammo,
+        });
+
+        let hit = hit?;
+        self.impacts.write(SpawnImpact {
+            position: hit.point,
+            normal: hit.normal,
+            target: hit.entity,
+            source: shot.shooter.unwrap_or(hit.entity),
+        });
+        if self.routing.emits::<HitEvent>() {
+            self.hits.write(HitEvent {
+                meta: EventMeta::new(shot.shooter.unwrap_or(hit.entity), &self.time, hit.point),
+                target: hit.entity,
+                normal: hit.normal,
+                direction: *direction,
+                user_data: shot.user_data,
+            });
+        }
+        Some(hit)
+    }
+    /// The shot's direction bent onto the hitbox nearest its aim, if its
+    /// shooter's `AimAssist` bends shots and the hitbox is in sight.
+    fn bend(&self, shot: &Shot, direction: Dir3) -> Dir3 {
+        let Some(assist) = shot
+            .shooter
+            .and_then(|shooter| self.aim_assists.get(shooter).ok())
+            .filter(|assist| assist.bend_angle > 0.0)
+        else {
+            return direction;
+        };
+        let Some(target) = find_aim_target(
+            shot.origin,
+            *direction,
+            assist.bend_angle,
+            shot.max_distance,
+            shot.shooter,
+            self.hitbox_shapes.iter(),
+        ) else {
+            return direction;
+        };
+        let Ok(bent) = Dir3::new(target.point - shot.origin) else {
+            return direction;
+        };
+        match self.cast_ray(shot.origin, bent, shot.max_distance, shot.shooter) {
+            Some(hit) if hit.hitbox.is_some_and(|h| h.character == target.character) => bent,
+            _ => direction,
+        }
+    }
+}
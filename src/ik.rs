@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+
+use crate::anim::{CharAnimSet, CharAnimSetsPlugin};
+
+pub struct IkPlugin;
+
+impl Plugin for IkPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<CharAnimSetsPlugin>() {
+            app.add_plugins(CharAnimSetsPlugin);
+        }
+        app.register_type::<TwoBoneIk>();
+        app.add_systems(
+            PostUpdate,
+            solve_two_bone_ik.in_set(CharAnimSet::Ik),
+        );
+    }
+}
+
+/// The point that an IK chain reaches for.
+#[derive(Reflect, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum IkTarget {
+    /// Reach for an entity, e.g. a tracked controller or a grip socket.
+    Entity(Entity),
+    /// Reach for a point in global world space.
+    Point(Vec3),
+}
+
+/// Analytic two bone IK. Add to the tip bone of a chain (e.g. a hand or a foot),
+/// the parent of the tip is the mid bone (elbow/knee) and its parent is the root
+/// bone (shoulder/hip).
+///
+/// This runs after animations are applied and before transform propagation, so
+/// it overrides the animated pose for this frame.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct TwoBoneIk {
+    pub target: IkTarget,
+    /// A point in global world space that the mid joint bends towards. If none,
+    /// the chain keeps bending in the direction of the animated pose.
+    pub pole: Option<Vec3>,
+    /// How much the IK overrides the animation, in [0, 1].
+    pub weight: f32,
+    /// Whether the tip should also match the rotation of the target. Only used
+    /// for [`IkTarget::Entity`] targets.
+    pub match_target_rotation: bool,
+}
+
+impl TwoBoneIk {
+    pub fn new(target: IkTarget) -> Self {
+        Self {
+            target,
+            pole: None,
+            weight: 1.0,
+            match_target_rotation: false,
+        }
+    }
+}
+
+/// Composes the local transforms up the hierarchy. This is used instead of
+/// `GlobalTransform` because that isn't propagated yet when IK runs.
+pub fn compute_global_transform(
+    entity: Entity,
+    parents: &Query<&ChildOf>,
+    transforms: &Query<&mut Transform>,
+) -> Transform {
+    let mut global = transforms.get(entity).copied().unwrap_or_default();
+    for parent in parents.iter_ancestors(entity) {
+        if let Ok(parent_transform) = transforms.get(parent) {
+            global = parent_transform.mul_transform(global);
+        }
+    }
+    global
+}
+
+pub(crate) fn solve_two_bone_ik(
+    chains: Query<(Entity, &TwoBoneIk)>,
+    parents: Query<&ChildOf>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for (tip, ik) in chains.iter() {
+        if ik.weight <= 0.0 {
+            continue;
+        }
+        let Ok(mid) = parents.get(tip).map(ChildOf::parent) else {
+            continue;
+        };
+        let Ok(root) = parents.get(mid).map(ChildOf::parent) else {
+            continue;
+        };
+
+        let (target, target_rotation) = match ik.target {
+            IkTarget::Entity(entity) => {
+                let t = compute_global_transform(entity, &parents, &transforms);
+                (t.translation, Some(t.rotation))
+            }
+            IkTarget::Point(point) => (point, None),
+        };
+
+        let root_global = compute_global_transform(root, &parents, &transforms);
+        let mid_global = compute_global_transform(mid, &parents, &transforms);
+        let tip_global = compute_global_transform(tip, &parents, &transforms);
+
+        let (root_rotation, mid_rotation) = solve(
+            root_global,
+            mid_global,
+            tip_global.translation,
+            target,
+            ik.pole,
+        );
+
+        let mut root_local = transforms.get_mut(root).unwrap();
+        let new_root_rotation = root_local.rotation * root_rotation;
+        root_local.rotation = root_local.rotation.slerp(new_root_rotation, ik.weight);
+
+        let mut mid_local = transforms.get_mut(mid).unwrap();
+        let new_mid_rotation = mid_local.rotation * mid_rotation;
+        mid_local.rotation = mid_local.rotation.slerp(new_mid_rotation, ik.weight);
+
+        if let Some(target_rotation) = target_rotation.filter(|_| ik.match_target_rotation) {
+            let mid_global = compute_global_transform(mid, &parents, &transforms);
+            let mut tip_local = transforms.get_mut(tip).unwrap();
+            let new_tip_rotation = mid_global.rotation.inverse() * target_rotation;
+            tip_local.rotation = tip_local.rotation.slerp(new_tip_rotation, ik.weight);
+        }
+    }
+}
+
+/// Solves two bone IK, returning the local space rotations to apply to the root
+/// and mid bones. Based on Daniel Holden's "Simple Two Joint IK".
+fn solve(
+    root: Transform,
+    mid: Transform,
+    tip: Vec3,
+    target: Vec3,
+    pole: Option<Vec3>,
+) -> (Quat, Quat) {
+    const EPS: f32 = 0.001;
+
+    let a = root.translation;
+    let b = mid.translation;
+    let c = tip;
+
+    let lab = (b - a).length();
+    let lcb = (b - c).length();
+    let lat = (target - a).length().clamp(EPS, lab + lcb - EPS);
+
+    let angle = |u: Vec3, v: Vec3| {
+        u.normalize_or_zero()
+            .dot(v.normalize_or_zero())
+            .clamp(-1.0, 1.0)
+            .acos()
+    };
+
+    // Current interior angles.
+    let ac_ab_0 = angle(c - a, b - a);
+    let ba_bc_0 = angle(a - b, c - b);
+    let ac_at_0 = angle(c - a, target - a);
+
+    // Desired interior angles from the law of cosines.
+    let ac_ab_1 = ((lcb * lcb - lab * lab - lat * lat) / (-2.0 * lab * lat))
+        .clamp(-1.0, 1.0)
+        .acos();
+    let ba_bc_1 = ((lat * lat - lab * lab - lcb * lcb) / (-2.0 * lab * lcb))
+        .clamp(-1.0, 1.0)
+        .acos();
+
+    let bend_towards = pole.map(|p| p - a).unwrap_or(b - a);
+    let axis0 = (c - a).cross(bend_towards).normalize_or(Vec3::X);
+    let axis1 = (c - a).cross(target - a).normalize_or(Vec3::X);
+
+    let r0 = Quat::from_axis_angle(root.rotation.inverse() * axis0, ac_ab_1 - ac_ab_0);
+    let r1 = Quat::from_axis_angle(mid.rotation.inverse() * axis0, ba_bc_1 - ba_bc_0);
+    let r2 = Quat::from_axis_angle(root.rotation.inverse() * axis1, ac_at_0);
+
+    (r0 * r2, r1)
+}
@@ -0,0 +1,188 @@
+use bevy::{app::Animation, prelude::*};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::damping::update_anim_params;
+use crate::ik::solve_two_bone_ik;
+use crate::pose_authority::apply_pose_authority;
+use crate::state::{LowerBodyState, PlayerAnimationState};
+use crate::velocity::drive_animation_from_velocity;
+
+/// Injured locomotion. A character with [`Injury`] hunches over and slows down
+/// as its health drops, and limps on the legs that are hurt: it leans over an
+/// injured leg while it carries the weight, and keeps its weight on the good
+/// leg when standing. All on top of the usual locomotion,
+/// so the game only sets the health and which legs are hurt.
+pub struct InjuryPlugin;
+
+impl Plugin for InjuryPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Injury>();
+        app.add_systems(
+            Update,
+            slow_injured
+                .in_set(CharAnimSet::ModifyInput)
+                .after(drive_animation_from_velocity)
+                .after(update_anim_params),
+        );
+        app.add_systems(
+            PostUpdate,
+            limp.after(Animation)
+                .before(solve_two_bone_ik)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// How badly each leg is hurt, from 0 to 1.
+#[derive(Reflect, Clone, Copy, Debug, Default)]
+pub struct LegMask {
+    pub left: f32,
+    pub right: f32,
+}
+
+/// Add to a character root with a `RigMap`.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Injury {
+    /// From 1 when healthy to 0.
+    pub health: f32,
+    /// Which legs are hurt. With neither, the character only hunches over and
+    /// slows down.
+    pub legs: LegMask,
+    /// The health below which the character is hurt at all.
+    pub threshold: f32,
+    /// How far the spine hunches forward in degrees when fully injured.
+    pub hunch_angle: f32,
+    /// How far the body leans over an injured leg that carries the weight, in
+    /// degrees, when fully injured.
+    pub limp_angle: f32,
+    /// How much slower the character moves when fully injured, from 0 to 1.
+    /// Injured characters move like cautious ones, so they don't sprint.
+    pub slowdown: f32,
+    /// The difference in foot height in meters at which the weight is fully on
+    /// the lower foot.
+    pub stance_height: f32,
+    /// How much of the weight is on the left leg, from 0 to 1.
+    left_stance: f32,
+}
+
+impl Default for Injury {
+    fn default() -> Self {
+        Self {
+            health: 1.0,
+            legs: LegMask::default(),
+            threshold: 0.6,
+            hunch_angle: 12.0,
+            limp_angle: 8.0,
+            slowdown: 0.6,
+            stance_height: 0.08,
+            left_stance: 0.5,
+        }
+    }
+}
+
+impl Injury {
+    pub fn with_legs(mut self, left: f32, right: f32) -> Self {
+        self.legs = LegMask { left, right };
+        self
+    }
+
+    /// How hurt the character is, from 0 above the threshold to 1 at no
+    /// health.
+    pub fn injury(&self) -> f32 {
+        (1.0 - self.health / self.threshold.max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+
+    /// How much each leg limps, from 0 to 1.
+    pub fn limp(&self) -> LegMask {
+        let injury = self.injury();
+        LegMask {
+            left: injury * self.legs.left.clamp(0.0, 1.0),
+            right: injury * self.legs.right.clamp(0.0, 1.0),
+        }
+    }
+}
+
+fn slow_injured(
+    characters: Query<(Entity, &Injury)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+) {
+    for (root, injury) in characters.iter() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let Some(input) = state.input_mut() else {
+            continue;
+        };
+        let caution = injury.injury() * injury.slowdown;
+        input.caution = input.caution.max(caution);
+    }
+}
+
+fn limp(
+    mut characters: Query<(Entity, &mut Injury, &RigMap, &GlobalTransform)>,
+    states: Query<&PlayerAnimationState>,
+    mut transforms: Query<&mut Transform>,
+    global_transforms: Query<&GlobalTransform>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    for (root, mut injury, rig, root_global) in characters.iter_mut() {
+        let hurt = injury.injury();
+        if hurt <= 0.0 {
+            continue;
+        }
+        let limp = injury.limp();
+        let standing = children
+            .iter_descendants(root)
+            .find_map(|e| states.get(e).ok())
+            .is_none_or(|state| state.lower_body_state() == LowerBodyState::Idle);
+
+        let target = if standing {
+            // Keep the weight off the worse leg.
+            0.5 + 0.5 * (limp.right - limp.left)
+        } else {
+            // The lower foot carries the weight. The feet are posed but not
+            // propagated yet, so last frame's heights are used.
+            let height = |bone| {
+                let foot = global_transforms.get(rig.get(bone)?).ok()?;
+                let local = root_global.affine().inverse().transform_point3(foot.translation());
+                Some(local.y)
+            };
+            match (height(RigBone::LeftFoot), height(RigBone::RightFoot)) {
+                (Some(left), Some(right)) => {
+                    let t = (right - left) / injury.stance_height.max(f32::EPSILON);
+                    (0.5 + 0.5 * t).clamp(0.0, 1.0)
+                }
+                _ => 0.5,
+            }
+        };
+        let alpha = (12.0 * time.delta_secs()).min(1.0);
+        injury.left_stance = injury.left_stance.lerp(target, alpha);
+
+        // Leaning over the leg that's carrying the weight, the most when it's
+        // hurt and the other isn't.
+        let left_stance = injury.left_stance;
+        let lean = limp.left * left_stance - limp.right * (1.0 - left_stance);
+        let roll = (injury.limp_angle * lean).to_radians();
+        let hunch = (injury.hunch_angle * hurt).to_radians();
+
+        let mut rotate = |bone: RigBone, rotation: Quat| {
+            if let Some(mut transform) = rig.get(bone).and_then(|e| transforms.get_mut(e).ok()) {
+                transform.rotation *= rotation;
+            }
+        };
+        rotate(RigBone::Hips, Quat::from_rotation_z(-roll * 0.5));
+        rotate(RigBone::Spine, Quat::from_rotation_z(roll));
+        rotate(RigBone::Spine1, Quat::from_rotation_x(hunch));
+        // Keep looking ahead.
+        rotate(RigBone::Neck, Quat::from_rotation_x(-hunch * 0.5));
+    }
+}
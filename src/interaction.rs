@@ -0,0 +1,229 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::events::{EventMeta, EventRouting, InteractEvent, InteractPhase, NotifyEvent};
+use crate::montage::Montage;
+use crate::state::{LocomotionOverride, PlayerAnimationState};
+
+/// The notify in an interaction montage at which the prop should react, e.g.
+/// when the hand reaches the door handle.
+pub const REACT_NOTIFY: &str = "React";
+
+/// The usual "use" flow for doors, levers and pickups: send an [`Interact`] and
+/// the character slides onto the prop's marker, plays its montage, tells the
+/// prop when to react, and gets control back. Follow along with
+/// [`InteractEvent`]s.
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Interact>();
+        app.register_type::<Interaction>();
+        app.add_systems(
+            Update,
+            (
+                (start_interactions, update_interactions)
+                    .chain()
+                    .in_set(CharAnimSet::ModifyInput),
+                emit_reactions.after(CharAnimSet::StateMachine),
+            ),
+        );
+    }
+}
+
+/// Something a character can use. Add to the prop.
+///
+/// Not to be confused with bevy's UI `Interaction`.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Interaction {
+    /// Where the character's root stands and which way it faces while
+    /// interacting, relative to the prop.
+    pub marker: Transform,
+    /// Played once the character is on the marker. It should have a
+    /// [`REACT_NOTIFY`] notify.
+    pub montage: Montage,
+    /// How long the character takes to slide onto the marker.
+    pub align_secs: f32,
+}
+
+impl Interaction {
+    pub fn new(marker: Transform, montage: Montage) -> Self {
+        Self {
+            marker,
+            montage,
+            align_secs: 0.25,
+        }
+    }
+}
+
+/// Requests a character to interact with a prop.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Interact {
+    /// The character root.
+    pub character: Entity,
+    /// The entity with the [`Interaction`].
+    pub prop: Entity,
+}
+
+/// Added to a character root while it's interacting.
+#[derive(Component)]
+pub struct Interacting {
+    pub prop: Entity,
+    montage: Montage,
+    from: Vec3,
+    from_yaw: f32,
+    to: Vec3,
+    to_yaw: f32,
+    align_secs: f32,
+    elapsed: f32,
+    /// Whether the montage has started, i.e. the character is on the marker.
+    playing: bool,
+}
+
+impl Interacting {
+    /// How far onto the marker the character is, eased, in [0, 1].
+    fn alignment(&self) -> f32 {
+        let t = (self.elapsed / self.align_secs.max(f32::EPSILON)).min(1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+fn start_interactions(
+    mut commands: Commands,
+    mut requests: EventReader<Interact>,
+    props: Query<(&Interaction, &GlobalTransform)>,
+    characters: Query<&Transform, Without<Interacting>>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut events: EventWriter<InteractEvent>,
+) {
+    for request in requests.read() {
+        let Ok(root) = characters.get(request.character) else {
+            // Already busy with another interaction.
+            continue;
+        };
+        let Ok((interaction, prop_global)) = props.get(request.prop) else {
+            warn!("can't interact with {} as it has no interaction", request.prop);
+            continue;
+        };
+        let marker = prop_global.mul_transform(interaction.marker);
+        let yaw = |rotation: Quat| rotation.to_euler(EulerRot::YXZ).0;
+        commands.entity(request.character).insert(Interacting {
+            prop: request.prop,
+            montage: interaction.montage.clone(),
+            from: root.translation,
+            from_yaw: yaw(root.rotation),
+            to: marker.translation(),
+            to_yaw: yaw(marker.rotation()),
+            align_secs: interaction.align_secs,
+            elapsed: 0.0,
+            playing: false,
+        });
+        if routing.emits::<InteractEvent>() {
+            events.write(InteractEvent {
+                meta: EventMeta::new(request.prop, &time, prop_global.translation()),
+                character: request.character,
+                phase: InteractPhase::Started,
+            });
+        }
+    }
+}
+
+fn update_interactions(
+    mut commands: Commands,
+    mut characters: Query<(Entity, &mut Interacting, &mut Transform)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    global_transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut events: EventWriter<InteractEvent>,
+) {
+    for (root, mut interacting, mut transform) in characters.iter_mut() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+
+        let phase = if interacting.playing {
+            match state.montage() {
+                Some(montage) if montage.name == interacting.montage.name => None,
+                Some(_) => Some(InteractPhase::Interrupted),
+                None => Some(InteractPhase::Finished),
+            }
+        } else if state.montage().is_some() {
+            // Something else took over while sliding onto the marker.
+            Some(InteractPhase::Interrupted)
+        } else {
+            interacting.elapsed += time.delta_secs();
+            let t = interacting.alignment();
+            transform.translation = interacting.from.lerp(interacting.to, t);
+            if t >= 1.0 {
+                state.play_montage(interacting.montage.clone());
+                interacting.playing = true;
+            }
+            None
+        };
+
+        let Some(phase) = phase else {
+            // Face the marker, turning the shortest way round.
+            let turn = (interacting.to_yaw - interacting.from_yaw + PI).rem_euclid(TAU) - PI;
+            state.override_locomotion(LocomotionOverride {
+                yaw: Some(interacting.from_yaw + turn * interacting.alignment()),
+                aim_weight: 1.0,
+                ..default()
+            });
+            continue;
+        };
+
+        commands.entity(root).remove::<Interacting>();
+        if routing.emits::<InteractEvent>() {
+            let position = global_transforms
+                .get(interacting.prop)
+                .map(GlobalTransform::translation)
+                .unwrap_or(interacting.to);
+            events.write(InteractEvent {
+                meta: EventMeta::new(interacting.prop, &time, position),
+                character: root,
+                phase,
+            });
+        }
+    }
+}
+
+/// Tells props to react when their interaction montage reaches its notify.
+fn emit_reactions(
+    mut notifies: EventReader<NotifyEvent>,
+    characters: Query<&Interacting>,
+    global_transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut events: EventWriter<InteractEvent>,
+) {
+    if !routing.emits::<InteractEvent>() {
+        return;
+    }
+    for notify in notifies.read() {
+        if notify.name != REACT_NOTIFY {
+            continue;
+        }
+        let Ok(interacting) = characters.get(notify.meta.entity) else {
+            continue;
+        };
+        let position = global_transforms
+            .get(interacting.prop)
+            .map(GlobalTransform::translation)
+            .unwrap_or(interacting.to);
+        events.write(InteractEvent {
+            meta: EventMeta::new(interacting.prop, &time, position),
+            character: notify.meta.entity,
+            phase: InteractPhase::React,
+        });
+    }
+}
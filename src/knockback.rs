@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::events::{EventMeta, EventRouting, HitEvent, KnockbackEvent};
+use crate::montage::Montage;
+use crate::state::PlayerAnimationState;
+
+/// Staggering from impulses. Add a [`KnockbackResponder`] to a character root
+/// and send an [`ApplyKnockback`], or let shots knock it back through
+/// `HitEvent`s. Weak impulses play a stumble in the direction of the impulse
+/// that travels further the harder the hit, strong ones ragdoll the character.
+pub struct KnockbackPlugin;
+
+impl Plugin for KnockbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyKnockback>();
+        app.register_type::<KnockbackResponder>();
+        app.register_type::<Ragdolled>();
+        app.add_systems(Update, respond_to_knockbacks.in_set(CharAnimSet::ModifyInput));
+    }
+}
+
+/// Pushes a character, e.g. from an explosion or a shoulder charge.
+#[derive(Event, Reflect, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApplyKnockback {
+    /// The character root with the [`KnockbackResponder`].
+    pub character: Entity,
+    /// The impulse in global space. Only its horizontal direction picks the
+    /// stumble, its whole length is compared to the thresholds.
+    pub impulse: Vec3,
+}
+
+/// How a character reacts to impulses. Add to the character root.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct KnockbackResponder {
+    /// Stumbles in the direction they're named after, i.e. `backward` is
+    /// played when pushed from the front. Their root motion is authored for an
+    /// impulse of [`Self::reference_impulse`].
+    pub forward: Montage,
+    pub backward: Montage,
+    pub left: Montage,
+    pub right: Montage,
+    /// Weaker impulses are shrugged off.
+    pub min_impulse: f32,
+    /// The impulse that the stumbles' root motion is authored for. The root
+    /// motion is scaled by the impulse relative to this.
+    pub reference_impulse: f32,
+    /// Impulses at least this strong ragdoll the character instead.
+    pub ragdoll_impulse: f32,
+    /// The impulse of a shot hitting the character or any of its descendants,
+    /// along the shot direction. Zero ignores shots.
+    pub hit_impulse: f32,
+}
+
+impl KnockbackResponder {
+    pub fn new(forward: Montage, backward: Montage, left: Montage, right: Montage) -> Self {
+        Self {
+            forward,
+            backward,
+            left,
+            right,
+            min_impulse: 0.5,
+            reference_impulse: 3.0,
+            ragdoll_impulse: 10.0,
+            hit_impulse: 0.0,
+        }
+    }
+
+    /// Gets the stumble for an impulse in character space, +Y is forward.
+    fn stumble_for(&self, direction: Vec2) -> &Montage {
+        if direction.y.abs() >= direction.x.abs() {
+            if direction.y >= 0.0 {
+                &self.forward
+            } else {
+                &self.backward
+            }
+        } else if direction.x > 0.0 {
+            &self.right
+        } else {
+            &self.left
+        }
+    }
+}
+
+/// Added to a character root when a knockback ragdolls it. The game swaps in
+/// its ragdoll and removes this once the character is back up. Meanwhile the
+/// state machine is paused, holding the pose it was in, and knockbacks are
+/// ignored.
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component, Default)]
+pub struct Ragdolled;
+
+pub fn respond_to_knockbacks(
+    mut commands: Commands,
+    mut requests: EventReader<ApplyKnockback>,
+    mut hits: EventReader<HitEvent>,
+    responders: Query<(&KnockbackResponder, &GlobalTransform), Without<Ragdolled>>,
+    parents: Query<&ChildOf>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut events: EventWriter<KnockbackEvent>,
+) {
+    let shots = hits.read().filter_map(|hit| {
+        let character = std::iter::once(hit.target)
+            .chain(parents.iter_ancestors(hit.target))
+            .find(|e| responders.contains(*e))?;
+        let (responder, _) = responders.get(character).ok()?;
+        (responder.hit_impulse > 0.0).then(|| ApplyKnockback {
+            character,
+            impulse: hit.direction.normalize_or_zero() * responder.hit_impulse,
+        })
+    });
+    let knockbacks: Vec<ApplyKnockback> = requests.read().copied().chain(shots).collect();
+
+    // Only the first knockback that ragdolls a character counts.
+    let mut ragdolled = Vec::new();
+    for knockback in knockbacks {
+        let root = knockback.character;
+        let Ok((responder, global_transform)) = responders.get(root) else {
+            continue;
+        };
+        let strength = knockback.impulse.length();
+        if strength < responder.min_impulse || ragdolled.contains(&root) {
+            continue;
+        }
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+
+        let ragdoll = strength >= responder.ragdoll_impulse;
+        if ragdoll {
+            state.stop_montage();
+            commands.entity(root).insert(Ragdolled);
+            ragdolled.push(root);
+        } else {
+            let local = global_transform.rotation().inverse() * knockback.impulse;
+            let direction = Vec2::new(-local.x, local.z);
+            let mut stumble = responder.stumble_for(direction).clone();
+            stumble.root_motion *= strength / responder.reference_impulse.max(f32::EPSILON);
+            // Knockbacks cut whatever the character was doing short, including
+            // an earlier stumble.
+            state.play_montage(stumble);
+        }
+
+        if routing.emits::<KnockbackEvent>() {
+            events.write(KnockbackEvent {
+                meta: EventMeta::new(root, &time, global_transform.translation()),
+                impulse: knockback.impulse,
+                ragdoll,
+            });
+        }
+    }
+}
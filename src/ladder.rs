@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+
+/// Ladder climbing. Add a [`LadderClimbing`] to a character root and send a
+/// [`GrabLadder`] to put it on a ladder. Forward input climbs up and back input
+/// climbs down, and the character lets go at either end.
+pub struct LadderPlugin;
+
+impl Plugin for LadderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GrabLadder>();
+        app.add_event::<ReleaseLadder>();
+        app.register_type::<LadderClimbing>();
+        app.add_systems(
+            Update,
+            (grab_ladders, climb_ladders)
+                .chain()
+                .in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+/// A straight ladder in global world space.
+#[derive(Reflect, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ladder {
+    /// The bottom of the ladder, at floor height.
+    pub bottom: Vec3,
+    /// The top of the ladder, at the height of the floor it leads to.
+    pub top: Vec3,
+    /// The horizontal direction out of the ladder, towards the climber.
+    pub normal: Vec3,
+}
+
+/// Requests a character to get on a ladder. The character snaps to the closest
+/// point on the ladder.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct GrabLadder {
+    /// The character root with the [`LadderClimbing`] component.
+    pub character: Entity,
+    pub ladder: Ladder,
+}
+
+/// Requests a character to let go of its ladder.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ReleaseLadder {
+    pub character: Entity,
+}
+
+/// The climbing loop and tuning of a character that can climb ladders.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct LadderClimbing {
+    /// One cycle of climbing, in place. Its time is driven by how far up the
+    /// ladder the character is, so hands and feet stay on the rungs.
+    pub climb_loop: Handle<AnimationClip>,
+    /// How far up the ladder one loop of the clip climbs, usually two rungs.
+    pub loop_height: f32,
+    /// How far the root is from the ladder while climbing.
+    pub wall_offset: f32,
+    /// The climbing speed in m/s at full input.
+    pub climb_speed: f32,
+    /// The ladder being climbed and how far up it the character is in meters.
+    active: Option<(Ladder, f32)>,
+}
+
+impl LadderClimbing {
+    pub fn new(climb_loop: Handle<AnimationClip>) -> Self {
+        Self {
+            climb_loop,
+            loop_height: 0.6,
+            wall_offset: 0.35,
+            climb_speed: 1.0,
+            active: None,
+        }
+    }
+
+    pub fn ladder(&self) -> Option<Ladder> {
+        self.active.map(|(ladder, _)| ladder)
+    }
+
+    /// How far up the ladder the character is, in [0, 1].
+    pub fn progress(&self) -> Option<f32> {
+        let (ladder, height) = self.active?;
+        Some((height / ladder.top.distance(ladder.bottom).max(f32::EPSILON)).clamp(0.0, 1.0))
+    }
+}
+
+fn grab_ladders(
+    mut grabs: EventReader<GrabLadder>,
+    mut releases: EventReader<ReleaseLadder>,
+    mut climbers: Query<(&mut LadderClimbing, &Transform)>,
+) {
+    for grab in grabs.read() {
+        let Ok((mut climbing, transform)) = climbers.get_mut(grab.character) else {
+            continue;
+        };
+        let ladder = grab.ladder;
+        let up = ladder.top - ladder.bottom;
+        let height = (transform.translation - ladder.bottom)
+            .dot(up.normalize_or_zero())
+            .clamp(0.0, up.length());
+        climbing.active = Some((ladder, height));
+    }
+    for release in releases.read() {
+        if let Ok((mut climbing, _)) = climbers.get_mut(release.character) {
+            climbing.active = None;
+        }
+    }
+}
+
+fn climb_ladders(
+    mut climbers: Query<(Entity, &mut LadderClimbing, &mut Transform)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    for (root, mut climbing, mut transform) in climbers.iter_mut() {
+        let Some((ladder, height)) = climbing.active else {
+            continue;
+        };
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let climb_input = state.input().map_or(0.0, |input| input.local_movement_direction.y);
+
+        let up = ladder.top - ladder.bottom;
+        let length = up.length();
+        let height = height + climb_input * climbing.climb_speed * time.delta_secs();
+        let normal = ladder.normal.with_y(0.0).normalize_or_zero();
+        // Snap onto the ladder, facing it.
+        transform.translation = ladder.bottom
+            + up.normalize_or_zero() * height.clamp(0.0, length)
+            + normal * climbing.wall_offset;
+        if height < 0.0 || height > length {
+            // Off either end of the ladder. Getting onto the floor at the top is
+            // up to the game, e.g. with a climb nav link.
+            climbing.active = None;
+            continue;
+        }
+        climbing.active = Some((ladder, height));
+
+        let mut climb_loop = LocomotionClip::new(climbing.climb_loop.clone(), 1.0);
+        climb_loop.normalized_time = Some(height / climbing.loop_height.max(f32::EPSILON));
+        state.override_locomotion(LocomotionOverride {
+            clips: vec![climb_loop],
+            pitch: 0.0,
+            yaw: Some((-normal.x).atan2(-normal.z)),
+            aim_weight: 0.0,
+        });
+    }
+}
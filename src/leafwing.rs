@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::anim::CharAnimSet;
+use crate::camera_rig::CameraRig;
+use crate::damping::AnimParams;
+use crate::effect_rng::EffectRng;
+use crate::hitscan::{Hitscan, Shot};
+use crate::slide::Slide;
+use crate::spread::WeaponSpread;
+use crate::state::PlayerAnimationState;
+
+/// `leafwing-input-manager` actions straight into the animator. A character
+/// root with an `InputMap<CharAction>`, e.g. [`CharAction::default_input_map`],
+/// and [`CharActions`] has the movement, look, jump and ground fields of its
+/// `PlayerAnimationInput` and the [`LOOK_PITCH`] and [`CROUCH`] [`AnimParams`]
+/// set from the actions, crouch passed on to its [`Slide`], and fire shot
+/// through the hitscan along the view, so common setups need no glue of their
+/// own. The other input fields, e.g. sprinting, are left to the game.
+pub struct LeafwingInputPlugin;
+
+impl Plugin for LeafwingInputPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<InputManagerPlugin<CharAction>>() {
+            app.add_plugins(InputManagerPlugin::<CharAction>::default());
+        }
+        app.register_type::<CharActions>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(Update, apply_char_actions.in_set(CharAnimSet::Input));
+    }
+}
+
+/// The look pitch in radians, positive up.
+pub const LOOK_PITCH: &str = "look_pitch";
+/// 1 while crouch is held, 0 otherwise.
+pub const CROUCH: &str = "crouch";
+
+#[derive(
+    Actionlike, Reflect, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug,
+)]
+pub enum CharAction {
+    /// Walking, +Y forward and +X right.
+    #[actionlike(DualAxis)]
+    Move,
+    /// Turning the view, +X right and +Y up.
+    #[actionlike(DualAxis)]
+    Aim,
+    Fire,
+    Jump,
+    Crouch,
+}
+
+impl CharAction {
+    /// WASD and the mouse, or the sticks and buttons of a gamepad.
+    pub fn default_input_map() -> InputMap<Self> {
+        InputMap::default()
+            .with_dual_axis(Self::Move, VirtualDPad::wasd())
+            .with_dual_axis(Self::Move, GamepadStick::LEFT)
+            .with_dual_axis(Self::Aim, MouseMove::default().inverted_y())
+            .with_dual_axis(Self::Aim, GamepadStick::RIGHT)
+            .with(Self::Fire, MouseButton::Left)
+            .with(Self::Fire, GamepadButton::RightTrigger2)
+            .with(Self::Jump, KeyCode::Space)
+            .with(Self::Jump, GamepadButton::South)
+            .with(Self::Crouch, KeyCode::ControlLeft)
+            .with(Self::Crouch, GamepadButton::East)
+    }
+}
+
+/// Add to the character root, with its `InputMap<CharAction>`. The game still
+/// moves the character, and tells it whether it's on the ground.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct CharActions {
+    /// Radians the look turns per unit of aim, e.g. per pixel of mouse
+    /// movement.
+    pub aim_sensitivity: f32,
+    /// The look pitch range in radians, positive up.
+    pub pitch_limits: (f32, f32),
+    /// Whether the character is on the ground, which jumping needs.
+    pub is_grounded: bool,
+    /// Seconds for the [`AnimParams`] to get halfway to the input.
+    pub halflife: f32,
+    /// How far the shots reach, in meters.
+    pub max_distance: f32,
+    /// A camera with a `CameraRig` for the aim to turn instead, whose look
+    /// the character then follows.
+    pub camera: Option<Entity>,
+    look: Vec2,
+    last_y: Option<f32>,
+}
+
+impl Default for CharActions {
+    fn default() -> Self {
+        Self {
+            aim_sensitivity: 0.003,
+            pitch_limits: (-1.2, 1.2),
+            is_grounded: true,
+            halflife: 0.1,
+            max_distance: 200.0,
+            camera: None,
+            look: Vec2::ZERO,
+            last_y: None,
+        }
+    }
+}
+
+impl CharActions {
+    /// The look yaw and pitch in radians, positive left and up.
+    pub fn look(&self) -> Vec2 {
+        self.look
+    }
+}
+
+fn apply_char_actions(
+    mut characters: Query<(
+        Entity,
+        &ActionState<CharAction>,
+        &mut CharActions,
+        &GlobalTransform,
+        Option<&mut Slide>,
+        Option<&mut WeaponSpread>,
+    )>,
+    mut states: Query<&mut PlayerAnimationState>,
+    mut rigs: Query<&mut CameraRig>,
+    mut params: Query<&mut AnimParams>,
+    children: Query<&Children>,
+    global_transforms: Query<&GlobalTransform>,
+    mut hitscan: Hitscan,
+    mut effect_rng: ResMut<EffectRng>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for (root, actions, mut config, transform, slide, spread) in characters.iter_mut() {
+        let config = config.as_mut();
+        let movement = actions.axis_pair(&CharAction::Move).clamp_length_max(1.0);
+        let aim = actions.axis_pair(&CharAction::Aim) * config.aim_sensitivity;
+        let crouch = actions.pressed(&CharAction::Crouch);
+        config.look = match config.camera.and_then(|camera| rigs.get_mut(camera).ok()) {
+            Some(mut rig) => {
+                rig.add_look(aim);
+                Vec2::new(rig.yaw(), rig.pitch())
+            }
+            // Aiming right turns right, which lowers the yaw.
+            None => Vec2::new(
+                config.look.x - aim.x,
+                (config.look.y + aim.y).clamp(config.pitch_limits.0, config.pitch_limits.1),
+            ),
+        };
+
+        let y = transform.translation().y;
+        let vertical_speed = match config.last_y.replace(y) {
+            Some(last_y) if dt > 0.0 => (y - last_y) / dt,
+            _ => 0.0,
+        };
+        if let Some(mut slide) = slide {
+            slide.set_crouch(crouch);
+        }
+
+        let descendants = || std::iter::once(root).chain(children.iter_descendants(root));
+        if let Some(params_entity) = descendants().find(|e| params.contains(*e)) {
+            let mut params = params.get_mut(params_entity).unwrap();
+            params.set_float_damped(LOOK_PITCH, config.look.y, config.halflife);
+            params.set_float_damped(CROUCH, if crouch { 1.0 } else { 0.0 }, config.halflife);
+        }
+
+        let Some(state_entity) = descendants().find(|e| states.contains(*e)) else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        // Only the fields the actions own, keeping what the game set.
+        let mut input = state.input().cloned().unwrap_or_default();
+        input.local_movement_direction = movement;
+        input.look_y = config.look.x;
+        input.look_x = config.look.y;
+        input.just_jumped = config.is_grounded && actions.just_pressed(&CharAction::Jump);
+        input.is_grounded = config.is_grounded;
+        input.vertical_speed = vertical_speed;
+        state.set_input(input);
+
+        if !actions.just_pressed(&CharAction::Fire) {
+            continue;
+        }
+        let muzzle = state.proc_targets.bullet_point;
+        let Ok(muzzle_global) = global_transforms.get(muzzle) else {
+            continue;
+        };
+        // Shots go where the view looks, from the camera if there is one, and
+        // the tracer is drawn from the muzzle.
+        let camera = config
+            .camera
+            .and_then(|camera| global_transforms.get(camera).ok());
+        let (origin, aim) = match camera {
+            Some(camera) => (camera.translation(), *camera.forward()),
+            None => (
+                muzzle_global.translation(),
+                transform.rotation()
+                    * Quat::from_euler(EulerRot::YXZ, config.look.x, -config.look.y, 0.0)
+                    * Vec3::Z,
+            ),
+        };
+        let direction = match spread {
+            Some(mut spread) => spread.fire(aim, &mut effect_rng),
+            None => aim,
+        };
+        hitscan.fire_ray(Shot {
+            visual_origin: Some(muzzle_global.translation()),
+            max_distance: config.max_distance,
+            shooter: Some(root),
+            muzzle: Some(muzzle),
+            ..Shot::new(origin, direction)
+        });
+    }
+}
@@ -0,0 +1,139 @@
+use bevy::{app::Animation, prelude::*};
+
+use crate::character::{RigBone, RigMap};
+use crate::hitscan::{Hitscan, Shot};
+use crate::ik::solve_two_bone_ik;
+use crate::pose_authority::apply_pose_authority;
+
+/// Leaning around corners, e.g. with Q and E. A [`Lean`] rolls the spine
+/// sideways up to a max angle, less when there's a wall on that side so the
+/// head doesn't go through it. Everything attached to the upper body, such as
+/// the weapon's bullet point or a camera socket on the head, moves with it.
+pub struct LeanPlugin;
+
+impl Plugin for LeanPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Lean>();
+        app.add_systems(
+            PostUpdate,
+            lean.after(Animation)
+                .before(solve_two_bone_ik)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// The spine bones that the lean is spread over.
+const LEAN_BONES: [RigBone; 3] = [RigBone::Spine, RigBone::Spine1, RigBone::Spine2];
+
+/// Add to a character root with a `RigMap`.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Lean {
+    /// The max angle to lean in radians.
+    pub max_angle: f32,
+    /// How fast the lean moves in radians per second.
+    pub speed: f32,
+    /// The closest the head gets to a wall when leaning into it, in meters.
+    pub clearance: f32,
+    /// From -1 leaning fully left to 1 fully right.
+    input: f32,
+    /// The current angle, positive to the right.
+    angle: f32,
+    /// How far the head has moved from leaning, in global world space.
+    offset: Vec3,
+}
+
+impl Default for Lean {
+    fn default() -> Self {
+        Self {
+            max_angle: 20f32.to_radians(),
+            speed: 120f32.to_radians(),
+            clearance: 0.2,
+            input: 0.0,
+            angle: 0.0,
+            offset: Vec3::ZERO,
+        }
+    }
+}
+
+impl Lean {
+    /// Sets how far to lean, from -1 fully left to 1 fully right.
+    pub fn set_input(&mut self, input: f32) {
+        self.input = input.clamp(-1.0, 1.0);
+    }
+
+    /// The current angle in radians, positive to the right.
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// How far the head has moved from leaning, in global world space.
+    pub fn offset(&self) -> Vec3 {
+        self.offset
+    }
+
+    /// Moves a shot fired from the eyes, e.g. from a camera that isn't
+    /// attached to the skeleton, along with the head.
+    pub fn shift_shot(&self, shot: &mut Shot) {
+        shot.origin += self.offset;
+    }
+}
+
+pub fn lean(
+    mut characters: Query<(Entity, &mut Lean, &RigMap, &GlobalTransform)>,
+    mut transforms: Query<&mut Transform>,
+    global_transforms: Query<&GlobalTransform>,
+    hitscan: Hitscan,
+    time: Res<Time>,
+) {
+    for (root, mut lean, rig, root_global) in characters.iter_mut() {
+        let global = |bone| {
+            rig.get(bone)
+                .and_then(|bone| global_transforms.get(bone).ok())
+        };
+        let (Some(hips), Some(head)) = (global(RigBone::Hips), global(RigBone::Head)) else {
+            continue;
+        };
+        let forward = root_global.rotation() * Vec3::Z;
+        let right = root_global.rotation() * Vec3::NEG_X;
+        let up = *root_global.up();
+        let height = (head.translation() - hips.translation()).length();
+
+        let mut target = lean.input * lean.max_angle;
+        let side = Dir3::new(right * target.signum());
+        if let (Ok(side), true) = (side, target != 0.0 && height > 0.0) {
+            // Cast from the head along the side it leans to, as far as it
+            // would lean plus the clearance.
+            let upright_head = hips.translation() + up * height;
+            let reach = height * target.abs().sin() + lean.clearance;
+            if let Some(hit) = hitscan.cast_ray(upright_head, side, reach, Some(root)) {
+                let room = ((hit.distance - lean.clearance) / height).clamp(0.0, 1.0);
+                target = target.signum() * target.abs().min(room.asin());
+            }
+        }
+        let step = lean.speed * time.delta_secs();
+        lean.angle += (target - lean.angle).clamp(-step, step);
+
+        let angle = lean.angle;
+        lean.offset = (Quat::from_axis_angle(forward, angle) * up - up) * height;
+        if angle == 0.0 {
+            continue;
+        }
+        for bone in LEAN_BONES {
+            let Some(entity) = rig.get(bone) else {
+                continue;
+            };
+            let (Ok(bone_global), Ok(mut transform)) =
+                (global_transforms.get(entity), transforms.get_mut(entity))
+            else {
+                continue;
+            };
+            // The bone's global rotation is last frame's, close enough for the
+            // axis.
+            let axis = (bone_global.rotation().inverse() * forward).normalize();
+            transform.rotation *= Quat::from_axis_angle(axis, angle / LEAN_BONES.len() as f32);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Character animation, procedural posing, weapons and effects for Bevy. The
+//! demo in `main.rs` shows most of it together.
+
+pub mod ads;
+pub mod aim_assist;
+pub mod algo;
+pub mod anim;
+pub mod anim_graph;
+pub mod attachment;
+pub mod billboard;
+pub mod breathing;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod camera_kick;
+pub mod camera_rig;
+pub mod carry;
+pub mod character;
+pub mod charge;
+pub mod cover;
+pub mod crowd;
+pub mod damage;
+pub mod damping;
+pub mod debug;
+pub mod decal;
+pub mod diagnostics;
+pub mod dissolve;
+pub mod dodge;
+pub mod dungeon;
+#[cfg(feature = "editor")]
+pub mod editor;
+pub mod effect_rng;
+pub mod emote;
+pub mod enemy;
+pub mod events;
+pub mod explosion;
+pub mod expression;
+pub mod fidget;
+pub mod flight;
+pub mod floating_text;
+pub mod gesture;
+#[cfg(feature = "gore")]
+pub mod gore;
+pub mod grapple;
+pub mod highlight;
+pub mod hitbox;
+pub mod hitscan;
+pub mod ik;
+pub mod injury;
+pub mod interaction;
+pub mod knockback;
+pub mod ladder;
+pub mod lean;
+#[cfg(feature = "leafwing")]
+pub mod leafwing;
+pub mod melee;
+pub mod montage;
+pub mod mount;
+pub mod mutant;
+pub mod navlink;
+pub mod navmesh;
+pub mod netsync;
+pub mod pose_authority;
+pub mod probe;
+pub mod projectile;
+pub mod prop_anim;
+pub mod proportions;
+pub mod replay;
+#[cfg(feature = "sandbox")]
+pub mod sandbox;
+pub mod schema;
+pub mod sequencer;
+pub mod slide;
+pub mod smoke;
+#[cfg(feature = "soak")]
+pub mod soak;
+pub mod spread;
+pub mod start_stop;
+pub mod state;
+pub mod surface;
+pub mod swim;
+pub mod targets;
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
+pub mod throw;
+pub mod tracer;
+pub mod turret;
+pub mod utils;
+pub mod velocity;
+pub mod vfx;
+pub mod wall_run;
+pub mod weapon_lag;
+pub mod weapon_pose;
+#[cfg(any(feature = "webgl2", not(feature = "hanabi")))]
+pub mod webgl2;
+pub mod xr;
@@ -1,24 +1,26 @@
 use std::env;
 
 use bevy::{
-    animation::AnimationTarget,
     color::palettes::css::*,
+    core_pipeline::prepass::DepthPrepass,
     prelude::*,
     render::{mesh::skinning::SkinnedMesh, view::NoFrustumCulling},
 };
-use state::{PlayerAnimationInput, PlayerAnimationState};
-use tracer::Tracer;
-use utils::{freecam::FreeCamera, toggle_cursor_grab_with_esc};
-
-mod algo;
-mod anim;
-mod dungeon;
-mod enemy;
-mod mutant;
-mod navmesh;
-mod state;
-mod tracer;
-mod utils;
+use bevy_char_anim::*;
+use bevy_char_anim::{
+    camera_kick::CameraKick,
+    character::{CharacterBuilder, Player},
+    debug::DebugBones,
+    diagnostics::DiagnosticsOverlay,
+    effect_rng::EffectRng,
+    hitbox::Hitboxes,
+    hitscan::{Hitscan, Shot},
+    probe::{EdgeProximity, LocomotionProbe},
+    spread::WeaponSpread,
+    state::{PlayerAnimationInput, PlayerAnimationState},
+    utils::{freecam::FreeCamera, toggle_cursor_grab_with_esc},
+    xr::{XrMode, XrMuzzle},
+};
 
 fn main() {
     if env::args().any(|v| v == "navmesh") {
@@ -28,25 +30,106 @@ fn main() {
         dungeon::run();
         return;
     }
-    App::new()
+    #[cfg(feature = "sandbox")]
+    if env::args().any(|v| v == "sandbox") {
+        sandbox::run();
+        return;
+    }
+    let mut app = App::new();
+    #[cfg(feature = "soak")]
+    app.add_plugins(soak::SoakPlugin::default());
+    #[cfg(feature = "audio")]
+    app.add_plugins(audio::AudioEventsPlugin);
+    app
         .add_plugins(DefaultPlugins)
         .add_plugins(utils::freecam::FreeCameraPlugin)
-        .add_plugins(tracer::TracerPlugin)
-        .add_plugins(anim::AnimationPlugin)
+        .add_plugins(events::CharAnimEventsPlugin)
+        .add_plugins(vfx::VfxQualityPlugin)
+        .add_plugins(tracer::TracerPlugin::default())
+        .add_plugins(attachment::AttachmentPlugin)
+        .add_plugins(projectile::ProjectilePlugin)
+        .add_plugins(spread::SpreadPlugin)
+        .add_plugins(hitscan::HitscanPlugin)
+        .add_plugins(aim_assist::AimAssistPlugin)
+        .add_plugins(hitbox::HitboxPlugin)
+        .add_plugins(highlight::HighlightPlugin)
+        .add_plugins(dissolve::DissolvePlugin)
+        .add_plugins(explosion::ExplosionPlugin)
+        .add_plugins(decal::DecalPlugin)
+        .add_plugins(floating_text::FloatingTextPlugin)
+        .add_plugins(smoke::MuzzleSmokePlugin)
+        .add_plugins(camera_kick::CameraKickPlugin)
+        .add_plugins(camera_rig::CameraRigPlugin)
+        .add_plugins(anim::AnimationPlugin::default())
+        .add_plugins(character::CharacterPlugin)
+        .add_plugins(probe::LocomotionProbePlugin)
+        .add_plugins(replay::ReplayPlugin)
+        .add_plugins(debug::CharAnimDebugPlugin)
+        .add_plugins(ik::IkPlugin)
+        .add_plugins(dodge::DodgePlugin)
+        .add_plugins(navlink::NavLinkPlugin)
+        .add_plugins(netsync::NetSyncPlugin)
+        .add_plugins(pose_authority::PoseAuthorityPlugin)
+        .add_plugins(sequencer::SequencerPlugin)
+        .add_plugins(crowd::CrowdPlugin)
+        .add_plugins(velocity::VelocityDriverPlugin::default())
+        .add_plugins(damping::DampingPlugin::default())
+        .add_plugins(start_stop::StartStopPlugin)
+        .add_plugins(fidget::IdleFidgetPlugin)
+        .add_plugins(gesture::GesturePlugin)
+        .add_plugins(expression::ExpressionPlugin)
+        .add_plugins(breathing::BreathingPlugin)
+        .add_plugins(injury::InjuryPlugin)
+        .add_plugins(weapon_pose::WeaponPosePlugin)
+        .add_plugins(charge::ChargePlugin)
+        .add_plugins(throw::ThrowPlugin)
+        .add_plugins(ads::AdsPlugin)
+        .add_plugins(lean::LeanPlugin)
+        .add_plugins(slide::SlidePlugin)
+        .add_plugins(wall_run::WallRunPlugin)
+        .add_plugins(grapple::GrapplePlugin)
+        .add_plugins(emote::EmotePlugin)
+        .add_plugins(proportions::ProportionsPlugin)
+        .add_plugins(prop_anim::PropAnimPlugin)
+        .add_plugins(turret::TurretPlugin)
+        .add_plugins(targets::TargetsPlugin)
+        .add_plugins(flight::FlightPlugin)
+        .add_plugins(weapon_lag::WeaponLagPlugin)
+        .add_plugins(swim::SwimPlugin)
+        .add_plugins(ladder::LadderPlugin)
+        .add_plugins(cover::CoverPlugin)
+        .add_plugins(melee::MeleePlugin)
+        .add_plugins(mount::MountPlugin)
+        .add_plugins(interaction::InteractionPlugin)
+        .add_plugins(carry::CarryPlugin)
+        .add_plugins(knockback::KnockbackPlugin)
+        .add_plugins(damage::CharacterDamagePlugin)
+        .add_plugins(xr::XrPlugin)
+        .add_plugins(diagnostics::CharAnimDiagnosticsPlugin::default())
         // .add_plugins(mutant::MutantPlugin)
         .add_systems(Startup, setup)
         .add_systems(
             Update,
             (
                 draw_xyz_gizmo,
-                init_player_animations,
-                transition_player_animations,
+                transition_player_animations.in_set(anim::CharAnimSet::Input),
                 toggle_cursor_grab_with_esc,
                 toggle_freecam,
+                toggle_debug_bones,
+                toggle_diagnostics_overlay,
                 disable_culling_for_skinned_meshes,
             ),
-        )
-        .run();
+        );
+    // Needs the render plugins, so after `DefaultPlugins`.
+    #[cfg(feature = "gore")]
+    app.add_plugins(gore::GorePlugin);
+    // Reads the input resources, so after `DefaultPlugins` too.
+    #[cfg(feature = "leafwing")]
+    app.add_plugins(leafwing::LeafwingInputPlugin);
+    // Adds `EguiPlugin`, which needs the window and render plugins.
+    #[cfg(feature = "editor")]
+    app.add_plugins(editor::AnimatorInspectorPlugin);
+    app.run();
 }
 
 fn setup(
@@ -58,7 +141,14 @@ fn setup(
     // Spawn the camera.
     commands.spawn((
         Camera3d::default(),
-        FreeCamera::new(4.0),
+        // Flown once toggled on with F.
+        FreeCamera {
+            movement_enabled: false,
+            ..FreeCamera::new(4.0)
+        },
+        CameraKick::default(),
+        // For decals.
+        DepthPrepass,
         Transform::from_translation(Vec3::splat(6.0)).looking_at(Vec3::new(0., 1., 0.), Vec3::Y),
     ));
 
@@ -73,14 +163,15 @@ fn setup(
     ));
 
     // Spawn the player character.
-    commands.spawn((
-        SceneRoot(
-            asset_server.load(GltfAssetLabel::Scene(0).from_asset("models/gltf/character.glb")),
-        ),
-        Player,
-        Name::new("Player"),
-        Transform::from_scale(Vec3::splat(1.0)),
-    ));
+    CharacterBuilder::new(asset_server.load("models/gltf/character.glb"))
+        .with_name("Player")
+        .with_rig_autodetect()
+        .spawn(&mut commands)
+        .insert((
+            LocomotionProbe::default(),
+            WeaponSpread::default(),
+            Hitboxes::humanoid(),
+        ));
 
     // Spawn the ground.
     commands.spawn((
@@ -90,51 +181,42 @@ fn setup(
     ));
 }
 
-fn toggle_freecam(
-    mut enabled: Local<bool>,
-    mut freecam: Query<&mut FreeCamera>,
-    keys: Res<ButtonInput<KeyCode>>,
-) {
-    if keys.just_pressed(KeyCode::KeyF) {
-        *enabled = !*enabled;
+/// Toggles flying the cameras tagged `FreeCamera`.
+fn toggle_freecam(mut freecam: Query<&mut FreeCamera>, keys: Res<ButtonInput<KeyCode>>) {
+    if !keys.just_pressed(KeyCode::KeyF) {
+        return;
+    }
+    for mut freecam in freecam.iter_mut() {
+        freecam.movement_enabled = !freecam.movement_enabled;
     }
-    freecam.single_mut().unwrap().movement_enabled = *enabled;
 }
 
-#[derive(Component)]
-struct Player;
-
-fn init_player_animations(
+fn toggle_debug_bones(
     mut commands: Commands,
-    mut new_anim_players: Query<Entity, Added<AnimationPlayer>>,
-    asset_server: Res<AssetServer>,
-    children: Query<&Children>,
-    parents: Query<&ChildOf>,
-    names: Query<&Name>,
-    players: Query<&Player>,
-    mut animation_graphs: ResMut<Assets<AnimationGraph>>,
-    animation_targets: Query<&AnimationTarget>,
+    players: Query<(Entity, Has<DebugBones>), With<Player>>,
+    keys: Res<ButtonInput<KeyCode>>,
 ) {
-    for entity in new_anim_players.iter_mut() {
-        if !utils::find_upwards(entity, &parents, &players).is_some() {
-            // This is not a player.
-            continue;
-        };
-
-        let (anims, proc_targets, graph, nodes) = anim::load_player_animations(
-            entity,
-            &asset_server,
-            &children,
-            &names,
-            &animation_targets,
-            commands.reborrow(),
-            &parents,
-        );
+    if !keys.just_pressed(KeyCode::KeyB) {
+        return;
+    }
+    for (entity, has_debug) in players.iter() {
+        if has_debug {
+            commands.entity(entity).remove::<DebugBones>();
+        } else {
+            commands.entity(entity).insert(DebugBones::default());
+        }
+    }
+}
 
-        commands
-            .entity(entity)
-            .insert(AnimationGraphHandle(animation_graphs.add(graph)))
-            .insert(PlayerAnimationState::new(anims, proc_targets, nodes));
+fn toggle_diagnostics_overlay(
+    mut overlays: Query<&mut Visibility, With<DiagnosticsOverlay>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut visibility in overlays.iter_mut() {
+        visibility.toggle_visible_hidden();
     }
 }
 
@@ -142,10 +224,17 @@ fn transition_player_animations(
     mut look_x_rotation: Local<f32>,
     mut look_y_rotation: Local<f32>,
     mut airborne: Local<bool>,
+    mut airborne_secs: Local<f32>,
+    time: Res<Time>,
     keys: Res<ButtonInput<KeyCode>>,
     mut players: Query<&mut PlayerAnimationState>,
+    edge_proximity: Query<&EdgeProximity, With<Player>>,
     global_transforms: Query<&GlobalTransform>,
-    mut commands: Commands,
+    mut hitscan: Hitscan,
+    mut shooters: Query<(Entity, &mut WeaponSpread), With<Player>>,
+    mut effect_rng: ResMut<EffectRng>,
+    xr: Res<XrMode>,
+    xr_muzzles: Query<&GlobalTransform, With<XrMuzzle>>,
 ) {
     let local_movement_direction = utils::unit_vector_from_bools(
         keys.pressed(KeyCode::KeyW),
@@ -167,6 +256,12 @@ fn transition_player_animations(
         *look_y_rotation -= 1f32.to_radians();
     }
 
+    // There's no physics yet, so fake the fall speed from the time in the air.
+    *airborne_secs = if *airborne {
+        *airborne_secs + time.delta_secs()
+    } else {
+        0.0
+    };
     let is_grounded = !*airborne;
     let is_sprinting = is_grounded
         && keys.pressed(KeyCode::ShiftLeft)
@@ -178,6 +273,12 @@ fn transition_player_animations(
         local_movement_direction,
         look_y: *look_y_rotation,
         look_x: *look_x_rotation,
+        caution: edge_proximity
+            .single()
+            .map(EdgeProximity::caution)
+            .unwrap_or(0.0),
+        vertical_speed: -9.81 * *airborne_secs,
+        wants_roll: keys.just_pressed(KeyCode::KeyC),
     };
 
     if keys.just_pressed(KeyCode::KeyJ) {
@@ -187,20 +288,23 @@ fn transition_player_animations(
     if let Ok(mut state) = players.single_mut() {
         state.set_input(input);
 
-        let bullet_point_global = global_transforms
-            .get(state.proc_targets.bullet_point)
-            .unwrap();
+        let bullet_point_global = xr::xr_tracer_origin(&xr, &xr_muzzles).unwrap_or_else(|| {
+            *global_transforms
+                .get(state.proc_targets.bullet_point)
+                .unwrap()
+        });
         if keys.just_pressed(KeyCode::KeyT) {
-            commands.spawn((
-                Tracer {
-                    end: bullet_point_global.translation()
-                        + bullet_point_global.rotation() * Vec3::Z * 10.0,
-                },
-                Transform::from_translation(
-                    bullet_point_global.translation()
-                        + bullet_point_global.rotation() * Vec3::Z * 0.3,
-                ),
-            ));
+            let aim = bullet_point_global.rotation() * Vec3::Z;
+            let (shooter, direction) = match shooters.single_mut() {
+                Ok((shooter, mut spread)) => (Some(shooter), spread.fire(aim, &mut effect_rng)),
+                Err(_) => (None, aim),
+            };
+            hitscan.fire_ray(Shot {
+                max_distance: 10.0,
+                shooter,
+                muzzle: Some(state.proc_targets.bullet_point),
+                ..Shot::new(bullet_point_global.translation() + direction * 0.3, direction)
+            });
         }
     }
 }
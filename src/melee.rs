@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::events::{EventRouting, MeleeHitWindowEvent, NotifyWindowEvent};
+use crate::montage::Montage;
+use crate::state::{LowerBodyState, PlayerAnimationState};
+
+/// The montage window during which pressing attack queues the next attack of
+/// the combo. Presses outside it are ignored, so mashing doesn't chain.
+pub const COMBO_INPUT_WINDOW: &str = "ComboInput";
+/// The montage window from which a queued attack cancels into the next one. If
+/// an attack has none, the next one starts when it ends.
+pub const CANCEL_WINDOW: &str = "Cancel";
+/// The montage window during which the attack can hit. A
+/// [`MeleeHitWindowEvent`] is emitted when it opens and closes.
+pub const HIT_WINDOW: &str = "Hit";
+
+/// Melee combos built on montages. Send a [`MeleeInput`] when attack is
+/// pressed.
+///
+/// Hit windows are read from `NotifyWindowEvent`s, so the animation event
+/// channel must be enabled for [`MeleeHitWindowEvent`]s to be emitted.
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MeleeInput>();
+        app.register_type::<MeleeCombo>();
+        app.add_systems(
+            Update,
+            (
+                advance_combos.in_set(CharAnimSet::ModifyInput),
+                emit_hit_windows.after(CharAnimSet::StateMachine),
+            ),
+        );
+    }
+}
+
+/// Attack was pressed.
+#[derive(Event, Reflect, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeleeInput {
+    /// The character root with the [`MeleeCombo`] component.
+    pub character: Entity,
+}
+
+/// A chain of attacks. Add to the character root. Each attack is a montage
+/// with [`COMBO_INPUT_WINDOW`], [`CANCEL_WINDOW`] and [`HIT_WINDOW`] windows.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct MeleeCombo {
+    pub steps: Vec<Montage>,
+    /// The index of the attack being played.
+    step: Option<usize>,
+    /// Whether attack was pressed this frame.
+    pressed: bool,
+    /// Whether the next attack is queued.
+    buffered: bool,
+    /// The attack whose hit window is open.
+    hit_step: Option<usize>,
+}
+
+impl MeleeCombo {
+    pub fn new(steps: Vec<Montage>) -> Self {
+        Self {
+            steps,
+            step: None,
+            pressed: false,
+            buffered: false,
+            hit_step: None,
+        }
+    }
+
+    /// The index of the attack being played, if any.
+    pub fn step(&self) -> Option<usize> {
+        self.step
+    }
+
+    /// Whether the hit window of the current attack is open, for games that
+    /// trace the weapon every frame rather than listening for events.
+    pub fn hit_active(&self) -> bool {
+        self.hit_step.is_some()
+    }
+
+    fn play(&mut self, step: usize, state: &mut PlayerAnimationState) {
+        state.play_montage(self.steps[step].clone());
+        self.step = Some(step);
+        self.buffered = false;
+    }
+}
+
+fn advance_combos(
+    mut inputs: EventReader<MeleeInput>,
+    mut combos: Query<(Entity, &mut MeleeCombo)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+) {
+    for input in inputs.read() {
+        if let Ok((_, mut combo)) = combos.get_mut(input.character) {
+            combo.pressed = true;
+        }
+    }
+
+    for (root, mut combo) in combos.iter_mut() {
+        let pressed = std::mem::take(&mut combo.pressed);
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+
+        let playing = combo.step.is_some_and(|step| {
+            state
+                .montage()
+                .is_some_and(|montage| montage.name == combo.steps[step].name)
+        });
+        let next = combo
+            .step
+            .map(|step| step + 1)
+            .filter(|next| *next < combo.steps.len());
+
+        if playing {
+            if pressed && state.montage_window_open(COMBO_INPUT_WINDOW) {
+                combo.buffered = true;
+            }
+            if let (Some(next), true) = (next, combo.buffered) {
+                if state.montage_window_open(CANCEL_WINDOW) {
+                    combo.play(next, &mut state);
+                }
+            }
+            continue;
+        }
+
+        // The last attack ended, or was interrupted by another montage.
+        let ended = state.montage().is_none();
+        match next {
+            Some(next) if combo.buffered && ended => combo.play(next, &mut state),
+            _ => {
+                combo.step = None;
+                combo.buffered = false;
+                let free = ended
+                    && state.scrubbing().is_none()
+                    && !matches!(
+                        state.lower_body_state(),
+                        LowerBodyState::Jump | LowerBodyState::Falling
+                    );
+                if pressed && free && !combo.steps.is_empty() {
+                    combo.play(0, &mut state);
+                }
+            }
+        }
+    }
+}
+
+fn emit_hit_windows(
+    mut windows: EventReader<NotifyWindowEvent>,
+    mut combos: Query<&mut MeleeCombo>,
+    routing: Res<EventRouting>,
+    mut hit_windows: EventWriter<MeleeHitWindowEvent>,
+) {
+    for window in windows.read() {
+        if window.name != HIT_WINDOW {
+            continue;
+        }
+        let Ok(mut combo) = combos.get_mut(window.meta.entity) else {
+            continue;
+        };
+        // The window of an attack that was cancelled closes after the next
+        // attack has started, so remember which attack it opened for.
+        let step = if window.open {
+            combo.step
+        } else {
+            combo.hit_step
+        };
+        let Some(step) = step else {
+            continue;
+        };
+        combo.hit_step = window.open.then_some(step);
+        if routing.emits::<MeleeHitWindowEvent>() {
+            hit_windows.write(MeleeHitWindowEvent {
+                meta: window.meta,
+                step,
+                open: window.open,
+            });
+        }
+    }
+}
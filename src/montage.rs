@@ -0,0 +1,211 @@
+//! Montages are one shot, full body animations such as rolls that temporarily
+//! override the locomotion state machine. Start one with
+//! [`PlayerAnimationState::play_montage`](crate::state::PlayerAnimationState::play_montage).
+
+use bevy::prelude::*;
+
+use crate::character::RigBone;
+
+#[derive(Reflect, Clone, Debug)]
+pub struct Montage {
+    pub name: &'static str,
+    pub clip: Handle<AnimationClip>,
+    pub speed: f32,
+    /// How far the character moves over the whole montage, in character space
+    /// (+Z is forward). This is applied to the character root in step with the
+    /// clip, so the clip itself should be authored in place.
+    pub root_motion: Vec3,
+    pub notifies: Vec<MontageNotify>,
+    pub windows: Vec<MontageWindow>,
+    pub target_matches: Vec<TargetMatch>,
+    /// Whether to hold the last frame once the clip ends, until the montage is
+    /// replaced or stopped, rather than handing back to the state machine.
+    pub hold: bool,
+}
+
+impl Montage {
+    pub fn new(name: &'static str, clip: Handle<AnimationClip>) -> Self {
+        Self {
+            name,
+            clip,
+            speed: 1.0,
+            root_motion: Vec3::ZERO,
+            notifies: Vec::new(),
+            windows: Vec::new(),
+            target_matches: Vec::new(),
+            hold: false,
+        }
+    }
+
+    pub fn with_root_motion(mut self, root_motion: Vec3) -> Self {
+        self.root_motion = root_motion;
+        self
+    }
+
+    pub fn with_notify(mut self, name: &'static str, time: f32) -> Self {
+        self.notifies.push(MontageNotify { name, time });
+        self
+    }
+
+    pub fn with_window(mut self, name: &'static str, start: f32, end: f32) -> Self {
+        self.windows.push(MontageWindow { name, start, end });
+        self
+    }
+
+    pub fn with_hold(mut self) -> Self {
+        self.hold = true;
+        self
+    }
+
+    /// Warps `bone` onto a target point during the window named `window`. The
+    /// target is given when the montage is played with
+    /// `PlayerAnimationState::set_match_target`.
+    pub fn with_target_match(mut self, bone: RigBone, window: &'static str) -> Self {
+        self.target_matches.push(TargetMatch { bone, window });
+        self
+    }
+}
+
+/// A named point in a montage that emits a `NotifyEvent` when it's passed.
+#[derive(Reflect, Clone, Debug)]
+pub struct MontageNotify {
+    pub name: &'static str,
+    /// Normalized time in (0, 1].
+    pub time: f32,
+}
+
+/// A named span of a montage, e.g. invulnerability frames. A
+/// `NotifyWindowEvent` is emitted when it opens and when it closes, including
+/// when the montage is interrupted while the window is open.
+#[derive(Reflect, Clone, Debug)]
+pub struct MontageWindow {
+    pub name: &'static str,
+    /// Normalized time in [0, 1].
+    pub start: f32,
+    /// Normalized time in [0, 1].
+    pub end: f32,
+}
+
+/// Moves the character during a window so that a bone ends up exactly at a
+/// target point when the window closes, e.g. a hand reaching a ledge or button.
+/// The remaining offset is spread over the rest of the window.
+#[derive(Reflect, Clone, Debug)]
+pub struct TargetMatch {
+    pub bone: RigBone,
+    pub window: &'static str,
+}
+
+impl MontageWindow {
+    fn contains(&self, progress: f32) -> bool {
+        self.start <= progress && progress < self.end
+    }
+}
+
+/// What happened while updating the montages this frame.
+#[derive(Default)]
+pub(crate) struct MontageUpdate {
+    /// Whether a montage is overriding the state machine.
+    pub playing: bool,
+    pub notifies: Vec<&'static str>,
+    /// Windows that opened (true) or closed (false).
+    pub windows: Vec<(&'static str, bool)>,
+    /// How far to move the character root, in global world space.
+    pub root_motion: Vec3,
+}
+
+pub(crate) struct ActiveMontage {
+    pub montage: Montage,
+    /// The graph node playing the clip, once started.
+    pub node: Option<AnimationNodeIndex>,
+    /// Normalized time at the end of the last update, none before the first.
+    pub progress: Option<f32>,
+    /// Target points in global world space by window name.
+    pub match_targets: Vec<(&'static str, Vec3)>,
+    /// Seconds into the clip to jump to on the next update.
+    pub seek: Option<f32>,
+}
+
+impl ActiveMontage {
+    pub fn new(montage: Montage) -> Self {
+        Self {
+            montage,
+            node: None,
+            progress: None,
+            match_targets: Vec::new(),
+            seek: None,
+        }
+    }
+
+    /// Moves the montage to `progress`, recording passed notifies and window
+    /// changes in `update`. Returns the root motion in character space.
+    pub fn advance(&mut self, progress: f32, update: &mut MontageUpdate) -> Vec3 {
+        let last = self.progress.replace(progress);
+        let last_progress = last.unwrap_or(0.0);
+        update.notifies.extend(
+            self.montage
+                .notifies
+                .iter()
+                .filter(|notify| last_progress < notify.time && notify.time <= progress)
+                .map(|notify| notify.name),
+        );
+        for window in self.montage.windows.iter() {
+            let was_open = last.is_some_and(|last| window.contains(last));
+            let is_open = window.contains(progress);
+            if was_open != is_open {
+                update.windows.push((window.name, is_open));
+            }
+        }
+        self.montage.root_motion * (progress - last_progress)
+    }
+
+    /// For every target match whose window was open between `last` and
+    /// `progress`, gets the bone, its target and how much of the remaining offset
+    /// to the target should be covered this frame.
+    pub fn target_match_steps(
+        &self,
+        last: f32,
+        progress: f32,
+    ) -> impl Iterator<Item = (RigBone, Vec3, f32)> + '_ {
+        self.montage.target_matches.iter().filter_map(move |matching| {
+            let window = self
+                .montage
+                .windows
+                .iter()
+                .find(|window| window.name == matching.window)?;
+            let (_, target) = self
+                .match_targets
+                .iter()
+                .find(|(name, _)| *name == matching.window)?;
+            let from = last.max(window.start);
+            if progress <= from || last >= window.end {
+                return None;
+            }
+            let fraction = if progress >= window.end {
+                1.0
+            } else {
+                (progress - from) / (window.end - from)
+            };
+            Some((matching.bone, *target, fraction))
+        })
+    }
+
+    /// Whether the window named `name` is open.
+    pub fn window_open(&self, name: &str) -> bool {
+        let Some(progress) = self.progress else {
+            return false;
+        };
+        self.montage
+            .windows
+            .iter()
+            .any(|window| window.name == name && window.contains(progress))
+    }
+
+    /// Closes every open window, for when the montage stops early.
+    pub fn interrupt(&self, update: &mut MontageUpdate) {
+        for window in self.montage.windows.iter() {
+            if self.window_open(window.name) {
+                update.windows.push((window.name, false));
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::character::{FootIk, RigBone, RigMap};
+use crate::ik::{IkTarget, TwoBoneIk};
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+
+/// Riding vehicles and mounts. Put a [`Seat`] on the vehicle and send a
+/// [`MountSeat`] to seat a character in it. While seated the character is
+/// parented to the seat, holds the riding pose instead of walking, and has its
+/// hands and feet pinned to the vehicle with IK.
+pub struct MountPlugin;
+
+impl Plugin for MountPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MountSeat>();
+        app.add_event::<Dismount>();
+        app.register_type::<Seat>();
+        app.register_type::<Mounted>();
+        app.add_systems(
+            Update,
+            (mount_seats, ride).chain().in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+/// A place to sit on a vehicle or mount, e.g. a child entity of a bike. It
+/// should be placed where the character's root (between the feet) goes, facing
+/// the way the rider faces.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Seat {
+    pub riding_pose: Handle<AnimationClip>,
+    /// The entities the limbs are pinned to, e.g. handlebar grips, reins,
+    /// pedals or stirrups. The tips match both position and rotation.
+    pub left_hand: Option<Entity>,
+    pub right_hand: Option<Entity>,
+    pub left_foot: Option<Entity>,
+    pub right_foot: Option<Entity>,
+    /// How much the spine aims in the look direction while riding, e.g. 1 to
+    /// shoot from the saddle or 0 to keep both hands on the bars.
+    pub aim_weight: f32,
+}
+
+impl Seat {
+    pub fn new(riding_pose: Handle<AnimationClip>) -> Self {
+        Self {
+            riding_pose,
+            left_hand: None,
+            right_hand: None,
+            left_foot: None,
+            right_foot: None,
+            aim_weight: 0.0,
+        }
+    }
+
+    fn ik_targets(&self) -> [(RigBone, Option<Entity>); 4] {
+        [
+            (RigBone::LeftHand, self.left_hand),
+            (RigBone::RightHand, self.right_hand),
+            (RigBone::LeftFoot, self.left_foot),
+            (RigBone::RightFoot, self.right_foot),
+        ]
+    }
+}
+
+/// Requests a character to get on a seat. The character needs a `RigMap` for
+/// its limbs to be pinned.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MountSeat {
+    /// The character root.
+    pub character: Entity,
+    /// The entity with the [`Seat`].
+    pub seat: Entity,
+}
+
+/// Requests a character to get off its seat. It's left where it was sitting.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Dismount {
+    pub character: Entity,
+}
+
+/// Added to a character root while it's seated.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Mounted {
+    pub seat: Entity,
+    /// The foot IK of the character, which is suspended while riding.
+    foot_ik: Option<FootIk>,
+}
+
+fn mount_seats(
+    mut commands: Commands,
+    mut mounts: EventReader<MountSeat>,
+    mut dismounts: EventReader<Dismount>,
+    seats: Query<&Seat>,
+    characters: Query<(Option<&Mounted>, Option<&FootIk>, Option<&RigMap>, &GlobalTransform)>,
+    mut iks: Query<&mut TwoBoneIk>,
+) {
+    for dismount in dismounts.read() {
+        let Ok((Some(mounted), _, rig, global_transform)) = characters.get(dismount.character)
+        else {
+            continue;
+        };
+        // Let go of the vehicle.
+        let limbs = [
+            RigBone::LeftHand,
+            RigBone::RightHand,
+            RigBone::LeftFoot,
+            RigBone::RightFoot,
+        ];
+        for bone in limbs.into_iter().filter_map(|bone| rig?.get(bone)) {
+            if let Ok(mut ik) = iks.get_mut(bone) {
+                ik.weight = 0.0;
+            }
+        }
+        let mut character = commands.entity(dismount.character);
+        character
+            .remove::<(ChildOf, Mounted)>()
+            .insert(global_transform.compute_transform());
+        if let Some(foot_ik) = mounted.foot_ik.clone() {
+            character.insert(foot_ik);
+        }
+    }
+
+    for mount in mounts.read() {
+        let Ok((mounted, foot_ik, _, _)) = characters.get(mount.character) else {
+            continue;
+        };
+        if !seats.contains(mount.seat) {
+            warn!("can't mount {} as it isn't a seat", mount.seat);
+            continue;
+        }
+        // Keep the suspended foot IK when switching seats.
+        let foot_ik = mounted.map_or(foot_ik.cloned(), |mounted| mounted.foot_ik.clone());
+        commands
+            .entity(mount.character)
+            .remove::<FootIk>()
+            .insert((
+                Mounted {
+                    seat: mount.seat,
+                    foot_ik,
+                },
+                Transform::IDENTITY,
+                ChildOf(mount.seat),
+            ));
+    }
+}
+
+/// Holds the riding pose and pins the limbs of seated characters.
+fn ride(
+    mut commands: Commands,
+    riders: Query<(Entity, &Mounted, Option<&RigMap>)>,
+    seats: Query<&Seat>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    mut iks: Query<&mut TwoBoneIk>,
+) {
+    for (root, mounted, rig) in riders.iter() {
+        let Ok(seat) = seats.get(mounted.seat) else {
+            continue;
+        };
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        states
+            .get_mut(state_entity)
+            .unwrap()
+            .override_locomotion(LocomotionOverride {
+                clips: vec![LocomotionClip::new(seat.riding_pose.clone(), 1.0)],
+                pitch: 0.0,
+                // Face the way the seat faces.
+                yaw: Some(0.0),
+                aim_weight: seat.aim_weight,
+            });
+
+        let Some(rig) = rig else {
+            continue;
+        };
+        for (bone, target) in seat.ik_targets() {
+            let Some(bone) = rig.get(bone) else {
+                continue;
+            };
+            let (target, weight) = match target {
+                Some(target) => (IkTarget::Entity(target), 1.0),
+                None => (IkTarget::Point(Vec3::ZERO), 0.0),
+            };
+            if let Ok(mut ik) = iks.get_mut(bone) {
+                ik.target = target;
+                ik.weight = weight;
+                ik.match_target_rotation = true;
+            } else if weight > 0.0 {
+                let mut ik = TwoBoneIk::new(target);
+                ik.match_target_rotation = true;
+                commands.entity(bone).insert(ik);
+            }
+        }
+    }
+}
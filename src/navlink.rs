@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::events::{EventMeta, EventRouting, NavLinkTraversedEvent};
+use crate::montage::Montage;
+use crate::state::PlayerAnimationState;
+
+/// Lets AI navigation hand off links between navmesh regions (gaps, drops,
+/// ledges) to the animation system. Send a [`TraverseNavLink`] and wait for a
+/// [`NavLinkTraversedEvent`].
+pub struct NavLinkPlugin;
+
+impl Plugin for NavLinkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TraverseNavLink>();
+        app.register_type::<NavLinkTraversal>();
+        app.add_systems(
+            Update,
+            (finish_nav_links, start_nav_links)
+                .chain()
+                .in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum NavLinkStyle {
+    JumpGap,
+    DropDown,
+    Climb,
+}
+
+/// A link between two points that can't be walked, in global world space.
+#[derive(Reflect, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavLink {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub style: NavLinkStyle,
+}
+
+/// Requests a character to traverse a link. The character is moved to the
+/// start of the link and should already be facing the end.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TraverseNavLink {
+    /// The character root with the [`NavLinkTraversal`] component.
+    pub character: Entity,
+    pub link: NavLink,
+}
+
+/// The montages a character traverses links with. Add to the character root.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct NavLinkTraversal {
+    pub montages: Vec<(NavLinkStyle, Montage)>,
+    /// How close to the end of the link the character must be for the
+    /// traversal to count as completed rather than interrupted.
+    pub end_tolerance: f32,
+    /// The link being traversed and the name of its montage.
+    active: Option<(NavLink, &'static str)>,
+}
+
+impl NavLinkTraversal {
+    pub fn new(montages: Vec<(NavLinkStyle, Montage)>) -> Self {
+        Self {
+            montages,
+            end_tolerance: 0.25,
+            active: None,
+        }
+    }
+
+    pub fn montage_for(&self, style: NavLinkStyle) -> Option<&Montage> {
+        self.montages
+            .iter()
+            .find(|(s, _)| *s == style)
+            .map(|(_, montage)| montage)
+    }
+
+    pub fn active_link(&self) -> Option<NavLink> {
+        self.active.map(|(link, _)| link)
+    }
+}
+
+fn start_nav_links(
+    mut requests: EventReader<TraverseNavLink>,
+    mut traversals: Query<(&mut NavLinkTraversal, &mut Transform)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut traversed: EventWriter<NavLinkTraversedEvent>,
+) {
+    for request in requests.read() {
+        let Ok((mut traversal, mut root)) = traversals.get_mut(request.character) else {
+            continue;
+        };
+        let montage = traversal.montage_for(request.link.style).cloned();
+        let state_entity = children
+            .iter_descendants(request.character)
+            .find(|e| states.contains(*e));
+        let (Some(mut montage), Some(state_entity)) = (montage, state_entity) else {
+            warn!("can't traverse {:?} link", request.link.style);
+            if routing.emits::<NavLinkTraversedEvent>() {
+                traversed.write(NavLinkTraversedEvent {
+                    meta: EventMeta::new(request.character, &time, root.translation),
+                    link: request.link,
+                    completed: false,
+                });
+            }
+            continue;
+        };
+
+        // Warp the root motion of the montage to span the link exactly.
+        root.translation = request.link.start;
+        montage.root_motion = root.rotation.inverse() * (request.link.end - request.link.start);
+        traversal.active = Some((request.link, montage.name));
+        states.get_mut(state_entity).unwrap().play_montage(montage);
+    }
+}
+
+/// Reports links whose montage has stopped playing.
+fn finish_nav_links(
+    mut traversals: Query<(Entity, &mut NavLinkTraversal, &Transform)>,
+    states: Query<&PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut traversed: EventWriter<NavLinkTraversedEvent>,
+) {
+    for (root, mut traversal, transform) in traversals.iter_mut() {
+        let Some((link, montage_name)) = traversal.active else {
+            continue;
+        };
+        let playing = children
+            .iter_descendants(root)
+            .find_map(|e| states.get(e).ok())
+            .and_then(PlayerAnimationState::montage)
+            .is_some_and(|montage| montage.name == montage_name);
+        if playing {
+            continue;
+        }
+
+        traversal.active = None;
+        if routing.emits::<NavLinkTraversedEvent>() {
+            traversed.write(NavLinkTraversedEvent {
+                meta: EventMeta::new(root, &time, transform.translation),
+                link,
+                completed: transform.translation.distance(link.end) <= traversal.end_tolerance,
+            });
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::character::{update_foot_ik, RigBone, RigMap};
+use crate::ik::{IkTarget, TwoBoneIk};
+
+/// Helpers for server authoritative games, leaving the transport to the game.
+///
+/// - On the server, [`FixedRootMotion`] moves characters by their root motion
+///   in `FixedUpdate`, in step with the simulation, and
+///   [`RootSnapshot::capture`] records the result to replicate.
+/// - On clients, characters controlled by others get a [`SnapshotBuffer`]
+///   that the received snapshots go in. They're shown a little in the past,
+///   interpolating between snapshots, so late packets don't make them stutter.
+/// - The locally predicted character gets a [`RootCorrection`], which eases
+///   it onto the server's position when the prediction was off, moving the
+///   foot IK targets along so the feet don't slide.
+pub struct NetSyncPlugin;
+
+impl Plugin for NetSyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetSyncConfig>();
+        app.register_type::<NetSyncConfig>();
+        app.register_type::<FixedRootMotion>();
+        app.register_type::<RootCorrection>();
+        app.add_systems(FixedUpdate, apply_fixed_root_motion);
+        app.add_systems(
+            Update,
+            (
+                interpolate_snapshots.after(CharAnimSet::StateMachine),
+                apply_root_corrections
+                    .after(CharAnimSet::StateMachine)
+                    .after(update_foot_ik),
+            ),
+        );
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct NetSyncConfig {
+    /// How far in the past remote characters are shown, in seconds. Should
+    /// cover a couple of snapshot intervals plus the network jitter.
+    pub interpolation_delay_secs: f32,
+    /// How much history each [`SnapshotBuffer`] keeps, in seconds.
+    pub buffer_secs: f32,
+    /// How fast a correction is eased in, the fraction left after a second is
+    /// `exp(-correction_rate)`.
+    pub correction_rate: f32,
+    /// Corrections at least this far, in meters, snap instead of easing in,
+    /// e.g. after a teleport.
+    pub snap_distance: f32,
+}
+
+impl Default for NetSyncConfig {
+    fn default() -> Self {
+        Self {
+            interpolation_delay_secs: 0.1,
+            buffer_secs: 1.0,
+            correction_rate: 10.0,
+            snap_distance: 2.0,
+        }
+    }
+}
+
+/// Add to a character root on the server to move it by its root motion in
+/// `FixedUpdate` instead of every frame. The animation still samples the root
+/// motion every frame, it's held here until the next fixed step.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct FixedRootMotion {
+    /// The root motion not applied yet, in global world space.
+    pub pending: Vec3,
+}
+
+/// Where a character root was at a moment of the server's time.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct RootSnapshot {
+    /// Seconds on the server's clock, e.g. its elapsed `Time<Fixed>`.
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+impl RootSnapshot {
+    pub fn capture(time: f64, transform: &Transform) -> Self {
+        Self {
+            time,
+            translation: transform.translation,
+            rotation: transform.rotation,
+        }
+    }
+}
+
+/// The snapshots received for a character that's controlled elsewhere. Its
+/// root follows them, overriding its own root motion and turning.
+#[derive(Component, Default, Debug)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<RootSnapshot>,
+    /// The server time being shown, none until the first snapshot.
+    playback_time: Option<f64>,
+}
+
+impl SnapshotBuffer {
+    /// Adds a snapshot received from the server, which may arrive out of
+    /// order.
+    pub fn push(&mut self, snapshot: RootSnapshot) {
+        let index = self
+            .snapshots
+            .partition_point(|other| other.time <= snapshot.time);
+        self.snapshots.insert(index, snapshot);
+    }
+
+    pub fn latest(&self) -> Option<&RootSnapshot> {
+        self.snapshots.back()
+    }
+
+    /// The root at server time `time`, between the snapshots around it, or
+    /// held at the nearest one outside them.
+    pub fn sample(&self, time: f64) -> Option<(Vec3, Quat)> {
+        let after = self.snapshots.partition_point(|snapshot| snapshot.time <= time);
+        let to = self.snapshots.get(after);
+        let from = after.checked_sub(1).and_then(|before| self.snapshots.get(before));
+        match (from, to) {
+            (Some(from), Some(to)) => {
+                let t = ((time - from.time) / (to.time - from.time).max(f64::EPSILON)) as f32;
+                Some((
+                    from.translation.lerp(to.translation, t),
+                    from.rotation.slerp(to.rotation, t),
+                ))
+            }
+            (Some(snapshot), None) | (None, Some(snapshot)) => {
+                Some((snapshot.translation, snapshot.rotation))
+            }
+            (None, None) => None,
+        }
+    }
+
+    fn trim(&mut self, before: f64) {
+        // Keep one older snapshot to interpolate from.
+        while self.snapshots.len() > 1 && self.snapshots[1].time < before {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+/// Eases a predicted character onto the server's position. Add it to the
+/// character root and call [`RootCorrection::add`] with how far off the
+/// prediction was.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct RootCorrection {
+    /// What's left to correct, in global world space.
+    offset: Vec3,
+}
+
+impl RootCorrection {
+    /// Adds a misprediction: the server's position minus where the client
+    /// predicted the character would be, at the same tick.
+    pub fn add(&mut self, error: Vec3) {
+        self.offset += error;
+    }
+
+    pub fn remaining(&self) -> Vec3 {
+        self.offset
+    }
+}
+
+fn apply_fixed_root_motion(mut roots: Query<(&mut FixedRootMotion, &mut Transform)>) {
+    for (mut fixed, mut transform) in roots.iter_mut() {
+        transform.translation += std::mem::take(&mut fixed.pending);
+    }
+}
+
+/// Moves remote characters to where they were `interpolation_delay_secs`
+/// before the latest snapshot. The playback clock runs with the frame time and
+/// is nudged towards that target, so jitter doesn't make it jump.
+fn interpolate_snapshots(
+    mut roots: Query<(&mut SnapshotBuffer, &mut Transform)>,
+    config: Res<NetSyncConfig>,
+    time: Res<Time>,
+) {
+    let delay = config.interpolation_delay_secs as f64;
+    for (mut buffer, mut transform) in roots.iter_mut() {
+        let Some(latest) = buffer.latest().map(|snapshot| snapshot.time) else {
+            continue;
+        };
+        let target = latest - delay;
+        let playback = match buffer.playback_time {
+            Some(playback) => {
+                let playback = playback + time.delta_secs_f64();
+                if (playback - target).abs() > delay.max(0.05) * 2.0 {
+                    target
+                } else {
+                    playback + (target - playback) * 0.1
+                }
+            }
+            None => target,
+        };
+        buffer.playback_time = Some(playback);
+        buffer.trim(playback - config.buffer_secs as f64);
+        if let Some((translation, rotation)) = buffer.sample(playback) {
+            transform.translation = translation;
+            transform.rotation = rotation;
+        }
+    }
+}
+
+/// Eases the corrections in after the foot IK targets are picked, moving the
+/// targets along with the root. A snap turns the foot IK off for the frame,
+/// as last frame's feet are nowhere near.
+fn apply_root_corrections(
+    mut roots: Query<(&mut RootCorrection, &mut Transform, Option<&RigMap>)>,
+    mut iks: Query<&mut TwoBoneIk>,
+    config: Res<NetSyncConfig>,
+    time: Res<Time>,
+) {
+    for (mut correction, mut transform, rig) in roots.iter_mut() {
+        if correction.offset == Vec3::ZERO {
+            continue;
+        }
+        let snap = correction.offset.length() >= config.snap_distance;
+        let step = if snap {
+            correction.offset
+        } else {
+            correction.offset * (1.0 - (-config.correction_rate * time.delta_secs()).exp())
+        };
+        transform.translation += step;
+        correction.offset -= step;
+        if correction.offset.length_squared() < 1e-8 {
+            correction.offset = Vec3::ZERO;
+        }
+
+        let feet = [RigBone::LeftFoot, RigBone::RightFoot];
+        for foot in feet.into_iter().filter_map(|bone| rig?.get(bone)) {
+            let Ok(mut ik) = iks.get_mut(foot) else {
+                continue;
+            };
+            if snap {
+                ik.weight = 0.0;
+            } else if let IkTarget::Point(target) = &mut ik.target {
+                *target += step;
+            }
+        }
+    }
+}
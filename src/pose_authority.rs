@@ -0,0 +1,136 @@
+use bevy::{app::Animation, platform::collections::HashMap, prelude::*};
+
+use crate::ik::solve_two_bone_ik;
+
+/// Lets a cutscene or sequencer take over a character's skeleton. While a
+/// [`PoseAuthority`] is external the state machine is paused, and the poses the
+/// tool supplies are applied after the animations and IK, so nothing else moves
+/// the bones. Handing back blends from the last supplied pose into whatever the
+/// state machine is playing by then.
+pub struct PoseAuthorityPlugin;
+
+impl Plugin for PoseAuthorityPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PoseAuthority>();
+        app.add_systems(
+            PostUpdate,
+            apply_pose_authority
+                .after(Animation)
+                .after(solve_two_bone_ik)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Local bone transforms supplied by an external tool.
+#[derive(Reflect, Clone, Debug, Default)]
+pub struct ExternalPose {
+    bones: HashMap<Entity, Transform>,
+}
+
+impl ExternalPose {
+    /// Sets the local transform of a bone. Bones that are never set hold the
+    /// animated pose from when the tool took over.
+    pub fn set(&mut self, bone: Entity, transform: Transform) {
+        self.bones.insert(bone, transform);
+    }
+
+    pub fn get(&self, bone: Entity) -> Option<&Transform> {
+        self.bones.get(&bone)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &Transform)> {
+        self.bones.iter().map(|(bone, transform)| (*bone, transform))
+    }
+}
+
+/// Who poses the skeleton. Add to the entity with the `PlayerAnimationState`.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub enum PoseAuthority {
+    /// The state machine animates the skeleton.
+    #[default]
+    StateMachine,
+    /// An external tool poses the skeleton through the [`ExternalPose`], and
+    /// the state machine is paused.
+    External(ExternalPose),
+    /// Blending from the last external pose back to the state machine, which
+    /// is running again.
+    Returning {
+        from: ExternalPose,
+        blend_secs: f32,
+        elapsed_secs: f32,
+    },
+}
+
+impl PoseAuthority {
+    /// Takes over the skeleton with an empty pose for the tool to fill in.
+    pub fn external() -> Self {
+        Self::External(ExternalPose::default())
+    }
+
+    /// The pose to supply, if an external tool has the skeleton.
+    pub fn pose_mut(&mut self) -> Option<&mut ExternalPose> {
+        match self {
+            Self::External(pose) => Some(pose),
+            _ => None,
+        }
+    }
+
+    /// Hands the skeleton back to the state machine, blending over
+    /// `blend_secs`.
+    pub fn hand_back(&mut self, blend_secs: f32) {
+        if let Self::External(pose) = std::mem::take(self) {
+            *self = Self::Returning {
+                from: pose,
+                blend_secs,
+                elapsed_secs: 0.0,
+            };
+        }
+    }
+
+    /// Whether the state machine is paused.
+    pub fn is_external(&self) -> bool {
+        matches!(self, Self::External(_))
+    }
+}
+
+pub(crate) fn apply_pose_authority(
+    mut authorities: Query<&mut PoseAuthority>,
+    mut transforms: Query<&mut Transform>,
+    time: Res<Time>,
+) {
+    for mut authority in authorities.iter_mut() {
+        match authority.as_mut() {
+            PoseAuthority::StateMachine => {}
+            PoseAuthority::External(pose) => {
+                for (bone, pose_transform) in pose.iter() {
+                    if let Ok(mut transform) = transforms.get_mut(bone) {
+                        *transform = *pose_transform;
+                    }
+                }
+            }
+            PoseAuthority::Returning {
+                from,
+                blend_secs,
+                elapsed_secs,
+            } => {
+                *elapsed_secs += time.delta_secs();
+                let weight = 1.0 - (*elapsed_secs / blend_secs.max(f32::EPSILON)).min(1.0);
+                if weight <= 0.0 {
+                    *authority = PoseAuthority::StateMachine;
+                    continue;
+                }
+                for (bone, pose_transform) in from.iter() {
+                    if let Ok(mut transform) = transforms.get_mut(bone) {
+                        transform.translation =
+                            transform.translation.lerp(pose_transform.translation, weight);
+                        transform.rotation =
+                            transform.rotation.slerp(pose_transform.rotation, weight);
+                        transform.scale = transform.scale.lerp(pose_transform.scale, weight);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+pub struct LocomotionProbePlugin;
+
+impl Plugin for LocomotionProbePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LocomotionProbe>();
+        app.register_type::<EdgeProximity>();
+        app.add_systems(Update, update_locomotion_probes);
+    }
+}
+
+/// Casts short rays ahead of and below a character every frame to detect walls
+/// and drops. The results are written to [`EdgeProximity`].
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[require(EdgeProximity)]
+pub struct LocomotionProbe {
+    /// The height above the character origin that the forward (wall) probe is cast from.
+    pub wall_probe_height: f32,
+    /// How far ahead to look for walls.
+    pub wall_probe_distance: f32,
+    /// How far ahead of the character the downward (ledge) probe starts.
+    pub ledge_probe_ahead: f32,
+    /// The height the downward probe starts at.
+    pub ledge_probe_height: f32,
+    /// A drop deeper than this (measured from the character origin) counts as a ledge.
+    pub max_step_down: f32,
+}
+
+impl Default for LocomotionProbe {
+    fn default() -> Self {
+        Self {
+            wall_probe_height: 1.0,
+            wall_probe_distance: 1.0,
+            ledge_probe_ahead: 0.6,
+            ledge_probe_height: 0.5,
+            max_step_down: 0.5,
+        }
+    }
+}
+
+impl LocomotionProbe {
+    /// The origin and direction of the wall probe ray.
+    pub fn wall_ray(&self, transform: &GlobalTransform) -> (Vec3, Vec3) {
+        let origin = transform.translation() + Vec3::Y * self.wall_probe_height;
+        (origin, flat_forward(transform))
+    }
+
+    /// The origin of the ledge probe ray, which is cast straight down.
+    pub fn ledge_ray_origin(&self, transform: &GlobalTransform) -> Vec3 {
+        transform.translation()
+            + flat_forward(transform) * self.ledge_probe_ahead
+            + Vec3::Y * self.ledge_probe_height
+    }
+
+    /// The depth below the ledge probe origin that counts as a drop.
+    pub fn max_drop(&self) -> f32 {
+        self.ledge_probe_height + self.max_step_down
+    }
+}
+
+fn flat_forward(transform: &GlobalTransform) -> Vec3 {
+    (transform.rotation() * Vec3::Z)
+        .with_y(0.0)
+        .normalize_or_zero()
+}
+
+/// The output of a [`LocomotionProbe`]. Values are in [0, 1], where 0 means
+/// nothing was detected and 1 means the character is right up against it. This
+/// is also meant to be read by AI, e.g. to avoid walking enemies off ledges.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdgeProximity {
+    pub wall: f32,
+    pub ledge: f32,
+    /// The world space point that the ledge probe hit, if it hit anything.
+    pub ledge_probe_hit: Option<Vec3>,
+}
+
+impl EdgeProximity {
+    /// How cautiously the character should move, used to slow down locomotion.
+    pub fn caution(&self) -> f32 {
+        self.wall.max(self.ledge)
+    }
+}
+
+fn update_locomotion_probes(
+    rapier: ReadRapierContext,
+    mut probes: Query<(Entity, &GlobalTransform, &LocomotionProbe, &mut EdgeProximity)>,
+) {
+    let Ok(context) = rapier.single() else {
+        return;
+    };
+
+    for (entity, transform, probe, mut proximity) in probes.iter_mut() {
+        // The rays start inside the character's own hitboxes, which are sensors.
+        let filter = QueryFilter::default()
+            .exclude_collider(entity)
+            .exclude_sensors();
+
+        let (wall_origin, forward) = probe.wall_ray(transform);
+        proximity.wall = match context.cast_ray(
+            wall_origin,
+            forward,
+            probe.wall_probe_distance,
+            true,
+            filter,
+        ) {
+            Some((_, toi)) => 1.0 - toi / probe.wall_probe_distance,
+            None => 0.0,
+        };
+
+        // The ledge probe starts in front of the character and looks down. Anything
+        // deeper than a step is a drop, and the further the ground is the more
+        // cautious the character should be.
+        let ledge_origin = probe.ledge_ray_origin(transform);
+        let max_drop = probe.max_drop();
+        let hit = context.cast_ray(ledge_origin, Vec3::NEG_Y, max_drop * 2.0, true, filter);
+        proximity.ledge_probe_hit = hit.map(|(_, toi)| ledge_origin + Vec3::NEG_Y * toi);
+        proximity.ledge = match hit {
+            Some((_, toi)) if toi <= max_drop => 0.0,
+            Some((_, toi)) => ((toi - max_drop) / max_drop).clamp(0.0, 1.0),
+            None => 1.0,
+        };
+    }
+}
@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+use crate::events::{EventMeta, EventRouting, HitEvent, UserData};
+use crate::attachment::Suppressor;
+use crate::billboard::{MuzzleFlashSprites, MUZZLE_FLASH_SPRITE_SIZE};
+use crate::effect_rng::{EffectRng, PROJECTILE_MUZZLE_FLASH_STREAM};
+use crate::hitbox::Hitbox;
+use crate::surface::{SpawnImpact, SurfacePlugin};
+use crate::tracer::{
+    AmmoType, DespawnAfter, MuzzleFlash, MuzzleFlashEffects, TracerGradient, TracerGradients,
+    TracerPlugin, TracerProfile,
+};
+use crate::vfx::{profile_layers, EffectLayers, EffectLight, LayeredEffect, VfxQuality};
+
+/// Moving projectiles for weapons that aren't hitscan, e.g. rockets, arrows and
+/// slow bullets. Send a [`SpawnProjectile`]. Projectiles look like tracers and
+/// report what they hit with the same `HitEvent`s.
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        // For the muzzle flashes, gradients and lifetimes projectiles share
+        // with tracers, and the impact effects.
+        if !app.is_plugin_added::<TracerPlugin>() {
+            app.add_plugins(TracerPlugin::default());
+        }
+        if !app.is_plugin_added::<SurfacePlugin>() {
+            app.add_plugins(SurfacePlugin);
+        }
+        app.add_event::<SpawnProjectile>();
+        app.register_type::<Projectile>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(Update, (spawn_projectiles, move_projectiles).chain());
+    }
+}
+
+/// Requests a projectile to be spawned. Positions and vectors are in global
+/// world space.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpawnProjectile {
+    pub start: Vec3,
+    /// The initial velocity in m/s.
+    pub velocity: Vec3,
+    /// The acceleration in m/s², e.g. zero for a rocket.
+    pub gravity: Vec3,
+    /// How much velocity is lost per second as a fraction, e.g. 0.1 for an
+    /// arrow.
+    pub drag: f32,
+    /// Seconds before the projectile despawns if it hasn't hit anything.
+    pub lifetime_secs: f32,
+    /// The collider that fired the projectile, which it can't hit, e.g. the
+    /// character root. The character's hitboxes are skipped too.
+    pub shooter: Option<Entity>,
+    /// The look of the projectile's streak and light, or the default tracer
+    /// look if none.
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub profile: Option<Handle<TracerProfile>>,
+    /// Passed on to the spawned [`Projectile`] and the `HitEvent`.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub user_data: Option<UserData>,
+    /// The muzzle entity, whose attachments change how the projectile looks.
+    pub muzzle: Option<Entity>,
+    /// Colors the streak and light by the ammo, or by the profile if none.
+    pub ammo: Option<AmmoType>,
+}
+
+impl SpawnProjectile {
+    pub fn new(start: Vec3, velocity: Vec3) -> Self {
+        Self {
+            start,
+            velocity,
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            drag: 0.0,
+            lifetime_secs: 5.0,
+            shooter: None,
+            profile: None,
+            user_data: None,
+            muzzle: None,
+            ammo: None,
+        }
+    }
+}
+
+/// A projectile in flight. Its transform faces along its velocity.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Projectile {
+    pub velocity: Vec3,
+    pub gravity: Vec3,
+    pub drag: f32,
+    /// Seconds left before the projectile despawns.
+    pub lifetime_secs: f32,
+    pub shooter: Option<Entity>,
+    #[reflect(ignore)]
+    pub user_data: Option<UserData>,
+}
+
+/// Triggered on a projectile entity when it hits something, just before it
+/// despawns. Observe it to e.g. explode or stick into the target:
+/// `commands.spawn(...).observe(|trigger: Trigger<ProjectileHit>| ...)`.
+#[derive(Event, Clone, Debug)]
+pub struct ProjectileHit {
+    /// The collider that was hit.
+    pub target: Entity,
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub velocity: Vec3,
+    pub user_data: Option<UserData>,
+}
+
+fn spawn_projectiles(
+    mut commands: Commands,
+    mut events: EventReader<SpawnProjectile>,
+    profiles: Res<Assets<TracerProfile>>,
+    gradients: Res<Assets<TracerGradient>>,
+    tracer_gradients: Res<TracerGradients>,
+    muzzle_flashes: Res<MuzzleFlashEffects>,
+    muzzle_flash_sprites: Res<MuzzleFlashSprites>,
+    mut effect_rng: ResMut<EffectRng>,
+    suppressors: Query<&Suppressor>,
+    effect_layers: Query<&EffectLayers>,
+    parents: Query<&ChildOf>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    quality: Res<VfxQuality>,
+) {
+    let vfx = quality.settings();
+    for event in events.read() {
+        let mut profile = event
+            .profile
+            .as_ref()
+            .and_then(|handle| profiles.get(handle))
+            .cloned()
+            .unwrap_or_default();
+        tracer_gradients.apply(event.ammo, &gradients, &mut profile);
+        if let Some(suppressor) = event.muzzle.and_then(|e| suppressors.get(e).ok()) {
+            suppressor.apply(&mut profile);
+        }
+        let color = LinearRgba::from_f32_array(profile.end_color);
+        let direction = event.velocity.try_normalize().unwrap_or(Vec3::NEG_Z);
+        let shooter_layers = event
+            .muzzle
+            .or(event.shooter)
+            .and_then(|shooter| EffectLayers::find(shooter, &effect_layers, &parents));
+        let layers = profile_layers(&profile.render_layers, shooter_layers).unwrap_or_default();
+
+        // The projectile faces -Z, so the streak trails behind it along +Z.
+        let streak = Cylinder::new(profile.radius, profile.tracer_length).mesh().build();
+        let streak_transform = Transform::from_xyz(0.0, 0.0, profile.tracer_length / 2.0)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Y, Vec3::Z));
+        commands
+            .spawn((
+                Projectile {
+                    velocity: event.velocity,
+                    gravity: event.gravity,
+                    drag: event.drag,
+                    lifetime_secs: event.lifetime_secs,
+                    shooter: event.shooter,
+                    user_data: event.user_data.clone(),
+                },
+                Transform::from_translation(event.start).looking_to(direction, Vec3::Y),
+                Visibility::default(),
+                layers.clone(),
+                LayeredEffect,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Mesh3d(meshes.add(streak)),
+                    MeshMaterial3d(materials.add(StandardMaterial {
+                        base_color: color.into(),
+                        emissive: color,
+                        unlit: true,
+                        ..default()
+                    })),
+                    NotShadowCaster,
+                    streak_transform,
+                    layers.clone(),
+                ));
+                if vfx.tracer_lights {
+                    parent.spawn((
+                        PointLight {
+                            color: color.into(),
+                            shadows_enabled: profile.light_shadows && vfx.tracer_shadows,
+                            intensity: profile.light_intensity,
+                            ..default()
+                        },
+                        EffectLight::default(),
+                        Transform::default(),
+                        layers.clone(),
+                    ));
+                }
+            });
+
+        let rng = effect_rng.stream(PROJECTILE_MUZZLE_FLASH_STREAM);
+        if let Some(muzzle_flash) = muzzle_flashes.particles(profile.muzzle_flash, rng) {
+            commands.spawn((
+                muzzle_flash,
+                Transform::from_translation(event.start)
+                    .with_rotation(Quat::from_rotation_arc(Vec3::NEG_Z, direction)),
+                DespawnAfter::new(Duration::from_secs_f32(profile.lifetime_secs), profile.clock),
+                layers,
+            ));
+        } else if profile.muzzle_flash == MuzzleFlash::Sprite {
+            commands.spawn((
+                muzzle_flash_sprites.sprite(event.start, MUZZLE_FLASH_SPRITE_SIZE, rng),
+                DespawnAfter::new(Duration::from_secs_f32(profile.lifetime_secs), profile.clock),
+                layers,
+            ));
+        }
+    }
+}
+
+/// Moves the projectiles and traces the path they moved along this frame, so
+/// fast ones don't tunnel through thin colliders.
+fn move_projectiles(
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Transform)>,
+    hitboxes: Query<&Hitbox>,
+    rapier: ReadRapierContext,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut impacts: EventWriter<SpawnImpact>,
+    mut hit_events: EventWriter<HitEvent>,
+) {
+    // Projectiles still age without a physics context, so they don't pile up.
+    let context = rapier.single().ok();
+    let delta_secs = time.delta_secs();
+
+    for (entity, mut projectile, mut transform) in projectiles.iter_mut() {
+        projectile.lifetime_secs -= delta_secs;
+        if projectile.lifetime_secs <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let Some(context) = context.as_ref() else {
+            continue;
+        };
+
+        let gravity = projectile.gravity;
+        let drag = projectile.drag;
+        projectile.velocity += gravity * delta_secs;
+        projectile.velocity *= (1.0 - drag * delta_secs).max(0.0);
+        let step = projectile.velocity * delta_secs;
+        let distance = step.length();
+        let Some(direction) = step.try_normalize() else {
+            continue;
+        };
+
+        let shooter = projectile.shooter;
+        let not_shooter = |e: Entity| {
+            !hitboxes
+                .get(e)
+                .is_ok_and(|hitbox| Some(hitbox.character) == shooter)
+        };
+        let mut filter = QueryFilter::default().predicate(&not_shooter);
+        if let Some(shooter) = shooter {
+            filter = filter.exclude_collider(shooter);
+        }
+        let hit = context.cast_ray_and_get_normal(
+            transform.translation,
+            direction,
+            distance,
+            true,
+            filter,
+        );
+        let Some((target, intersection)) = hit else {
+            transform.translation += step;
+            transform.look_to(direction, Vec3::Y);
+            continue;
+        };
+
+        impacts.write(SpawnImpact {
+            position: intersection.point,
+            normal: intersection.normal,
+            target,
+            source: entity,
+        });
+        if routing.emits::<HitEvent>() {
+            hit_events.write(HitEvent {
+                meta: EventMeta::new(entity, &time, intersection.point),
+                target,
+                normal: intersection.normal,
+                direction,
+                user_data: projectile.user_data.clone(),
+            });
+        }
+        commands.trigger_targets(
+            ProjectileHit {
+                target,
+                point: intersection.point,
+                normal: intersection.normal,
+                velocity: projectile.velocity,
+                user_data: projectile.user_data.clone(),
+            },
+            entity,
+        );
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use bevy::animation::RepeatAnimation;
+use bevy::prelude::*;
+
+use crate::damping::{update_anim_params, AnimParams};
+use crate::events::{EventMeta, EventRouting, NotifyEvent};
+use crate::montage::MontageNotify;
+
+/// State machines for props without a humanoid skeleton, e.g. doors, chests
+/// and turrets animated by their nodes. A [`PropAnimator`] on the prop's root
+/// plays a clip per state and moves between states when the [`AnimParams`]
+/// on the root meet a transition's condition, the same parameters characters
+/// use. Notifies in the clips send `NotifyEvent`s for the root, like
+/// montages do.
+pub struct PropAnimPlugin;
+
+impl Plugin for PropAnimPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PropAnimator>();
+        app.add_systems(
+            Update,
+            (
+                setup_prop_animators,
+                update_prop_animators.after(update_anim_params),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// A state of a prop, playing one clip.
+#[derive(Reflect, Clone, Debug)]
+pub struct PropState {
+    pub name: &'static str,
+    pub clip: Handle<AnimationClip>,
+    pub speed: f32,
+    /// A parameter that scales the speed, e.g. a wing flap rate.
+    pub speed_param: Option<&'static str>,
+    /// Whether the clip loops, e.g. a spinning fan, or holds its last frame,
+    /// e.g. an opened door.
+    pub looping: bool,
+    pub notifies: Vec<MontageNotify>,
+}
+
+impl PropState {
+    pub fn new(name: &'static str, clip: Handle<AnimationClip>) -> Self {
+        Self {
+            name,
+            clip,
+            speed: 1.0,
+            speed_param: None,
+            looping: false,
+            notifies: Vec::new(),
+        }
+    }
+
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    pub fn with_notify(mut self, name: &'static str, time: f32) -> Self {
+        self.notifies.push(MontageNotify { name, time });
+        self
+    }
+
+    pub fn with_speed_param(mut self, param: &'static str) -> Self {
+        self.speed_param = Some(param);
+        self
+    }
+
+    fn speed(&self, params: Option<&AnimParams>) -> f32 {
+        let scale = self
+            .speed_param
+            .and_then(|name| params.and_then(|params| params.float(name)));
+        self.speed * scale.unwrap_or(1.0)
+    }
+}
+
+/// When a transition is taken.
+#[derive(Reflect, Clone, Copy, Debug)]
+pub enum PropCondition {
+    /// The parameter is above the value.
+    Above { param: &'static str, value: f32 },
+    /// The parameter is below the value.
+    Below { param: &'static str, value: f32 },
+    /// The state's clip has finished, which looping clips never do.
+    Finished,
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct PropTransition {
+    /// The state the transition leaves, or any other state if none.
+    pub from: Option<&'static str>,
+    pub to: &'static str,
+    pub condition: PropCondition,
+    pub blend_secs: f32,
+}
+
+/// Add to the root of a prop whose scene has an `AnimationPlayer`, along with
+/// [`AnimParams`] for the transitions to read.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct PropAnimator {
+    pub states: Vec<PropState>,
+    /// Checked in order, the first one that applies is taken.
+    pub transitions: Vec<PropTransition>,
+    current: usize,
+    requested: Option<usize>,
+    /// The entity with the `AnimationPlayer` and the graph node of each
+    /// state, once the scene has spawned.
+    player: Option<Entity>,
+    nodes: Vec<AnimationNodeIndex>,
+    started: bool,
+    /// The normalized time of the current clip at the last update.
+    progress: f32,
+}
+
+impl PropAnimator {
+    /// Starts in the first state, so needs at least one.
+    pub fn new(states: Vec<PropState>) -> Option<Self> {
+        if states.is_empty() {
+            return None;
+        }
+        Some(Self {
+            states,
+            transitions: Vec::new(),
+            current: 0,
+            requested: None,
+            player: None,
+            nodes: Vec::new(),
+            started: false,
+            progress: 0.0,
+        })
+    }
+
+    pub fn with_transition(
+        mut self,
+        from: Option<&'static str>,
+        to: &'static str,
+        condition: PropCondition,
+        blend_secs: f32,
+    ) -> Self {
+        self.transitions.push(PropTransition {
+            from,
+            to,
+            condition,
+            blend_secs,
+        });
+        self
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|state| state.name == name)
+    }
+
+    /// The name of the current state.
+    pub fn state(&self) -> &'static str {
+        self.states[self.current].name
+    }
+
+    /// Goes to a state straight away, whatever the transitions say.
+    pub fn set_state(&mut self, name: &str) {
+        match self.index(name) {
+            Some(index) => self.requested = Some(index),
+            None => warn!("prop has no state named {name}"),
+        }
+    }
+
+    /// The transition to take this frame, if any.
+    fn next(&self, params: Option<&AnimParams>, finished: bool) -> Option<(usize, f32)> {
+        let current = self.state();
+        let param = |name| params.and_then(|params| params.float(name));
+        self.transitions.iter().find_map(|transition| {
+            if transition.from.is_some_and(|from| from != current) || transition.to == current {
+                return None;
+            }
+            let met = match transition.condition {
+                PropCondition::Above { param: name, value } => {
+                    param(name).is_some_and(|param| param > value)
+                }
+                PropCondition::Below { param: name, value } => {
+                    param(name).is_some_and(|param| param < value)
+                }
+                PropCondition::Finished => finished,
+            };
+            if !met {
+                return None;
+            }
+            Some((self.index(transition.to)?, transition.blend_secs))
+        })
+    }
+}
+
+/// Sets up an animator when the scene under it spawns, or when it's added to
+/// a prop whose scene already has.
+fn setup_prop_animators(
+    mut commands: Commands,
+    new_players: Query<Entity, Added<AnimationPlayer>>,
+    new_animators: Query<Entity, Added<PropAnimator>>,
+    players: Query<(), With<AnimationPlayer>>,
+    mut animators: Query<&mut PropAnimator>,
+    parents: Query<&ChildOf>,
+    children: Query<&Children>,
+    mut animation_graphs: ResMut<Assets<AnimationGraph>>,
+) {
+    let mut setups: Vec<(Entity, Entity)> = new_animators
+        .iter()
+        .filter_map(|root| {
+            let player = std::iter::once(root)
+                .chain(children.iter_descendants(root))
+                .find(|e| players.contains(*e))?;
+            Some((root, player))
+        })
+        .collect();
+    for entity in new_players.iter() {
+        let Some(root) = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find(|e| animators.contains(*e))
+        else {
+            continue;
+        };
+        if !setups.contains(&(root, entity)) {
+            setups.push((root, entity));
+        }
+    }
+
+    for (root, entity) in setups {
+        let mut animator = animators.get_mut(root).unwrap();
+        if animator.states.is_empty() {
+            warn!("prop animator has no states, it won't animate");
+            continue;
+        }
+        let (graph, nodes) =
+            AnimationGraph::from_clips(animator.states.iter().map(|state| state.clip.clone()));
+        animator.player = Some(entity);
+        animator.nodes = nodes;
+        animator.started = false;
+        commands
+            .entity(entity)
+            .insert(AnimationTransitions::new())
+            .insert(AnimationGraphHandle(animation_graphs.add(graph)));
+    }
+}
+
+fn update_prop_animators(
+    mut animators: Query<(Entity, &mut PropAnimator, Option<&AnimParams>, &GlobalTransform)>,
+    mut players: Query<(&mut AnimationPlayer, &mut AnimationTransitions)>,
+    mut notifies: EventWriter<NotifyEvent>,
+    clips: Res<Assets<AnimationClip>>,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+) {
+    for (root, mut animator, params, transform) in animators.iter_mut() {
+        let Some((mut player, mut transitions)) =
+            animator.player.and_then(|player| players.get_mut(player).ok())
+        else {
+            continue;
+        };
+
+        let node = animator.nodes[animator.current];
+        let finished = player.animation(node).is_none_or(|anim| anim.is_finished());
+        let next = match animator.requested.take() {
+            Some(index) => Some((index, 0.0)),
+            None if animator.started => animator.next(params, finished),
+            None => Some((animator.current, 0.0)),
+        };
+        if let Some((index, blend_secs)) = next {
+            let state = &animator.states[index];
+            let repeat = if state.looping {
+                RepeatAnimation::Forever
+            } else {
+                RepeatAnimation::Never
+            };
+            transitions
+                .play(
+                    &mut player,
+                    animator.nodes[index],
+                    Duration::from_secs_f32(blend_secs.max(0.0)),
+                )
+                .set_repeat(repeat)
+                .set_speed(state.speed(params));
+            animator.current = index;
+            animator.started = true;
+            animator.progress = 0.0;
+            continue;
+        }
+
+        let state = &animator.states[animator.current];
+        if let Some(anim) = player.animation_mut(node) {
+            anim.set_speed(state.speed(params));
+        }
+
+        // Send the notifies that were passed since the last update.
+        let (Some(anim), Some(clip)) = (player.animation(node), clips.get(&state.clip)) else {
+            continue;
+        };
+        let duration = clip.duration().max(f32::EPSILON);
+        let progress = if anim.is_finished() {
+            1.0
+        } else {
+            (anim.seek_time() / duration).clamp(0.0, 1.0)
+        };
+        let last = animator.progress;
+        // A looping clip that wrapped passes the end and then the start.
+        let passed = |time: f32| match progress < last {
+            true => time > last || time <= progress,
+            false => time > last && time <= progress,
+        };
+        if routing.emits::<NotifyEvent>() {
+            notifies.write_batch(
+                state
+                    .notifies
+                    .iter()
+                    .filter(|notify| passed(notify.time))
+                    .map(|notify| NotifyEvent {
+                        meta: EventMeta::new(root, &time, transform.translation()),
+                        name: notify.name,
+                    }),
+            );
+        }
+        animator.progress = progress;
+    }
+}
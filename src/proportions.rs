@@ -0,0 +1,225 @@
+use bevy::{app::Animation, platform::collections::HashMap, prelude::*};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap, Socket};
+use crate::ik::solve_two_bone_ik;
+use crate::pose_authority::apply_pose_authority;
+use crate::state::PlayerAnimationState;
+
+/// Body types, so one animation set works on tall, short, long legged or long
+/// armed characters. A [`BodyProportions`] scales the whole skeleton evenly and
+/// stretches the bones of the legs, arms and spine on top of that, keeping the
+/// mesh's thickness. IK reaches with the stretched limbs, sockets on the bones
+/// move with them, and root motion and the locomotion speed follow the stride.
+pub struct ProportionsPlugin;
+
+impl Plugin for ProportionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<BodyProportions>();
+        app.add_systems(Update, match_strides.in_set(CharAnimSet::ModifyInput));
+        app.add_systems(
+            PostUpdate,
+            stretch_bones
+                .after(Animation)
+                .before(solve_two_bone_ik)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Add to a character root with a `RigMap`. All 1 is the body the animations
+/// were made for.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct BodyProportions {
+    /// Scales the whole skeleton and mesh.
+    pub scale: f32,
+    /// Stretches the thighs and shins, and raises the hips to match.
+    pub legs: f32,
+    /// Stretches the upper arms and forearms.
+    pub arms: f32,
+    /// Stretches the spine and neck.
+    pub spine: f32,
+    /// The authored translation of each stretched bone and socket, and the
+    /// stretched one that was written, to tell whether the animation has
+    /// overwritten it since.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    stretched: HashMap<Entity, (Vec3, Vec3)>,
+    /// The armature and its authored scale, which `scale` multiplies, e.g.
+    /// 0.01 for the centimetre units of Mixamo rigs.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    armature: Option<(Entity, Vec3)>,
+}
+
+impl Default for BodyProportions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            legs: 1.0,
+            arms: 1.0,
+            spine: 1.0,
+            stretched: HashMap::default(),
+            armature: None,
+        }
+    }
+}
+
+impl BodyProportions {
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale,
+            ..default()
+        }
+    }
+
+    pub fn with_legs(mut self, legs: f32) -> Self {
+        self.legs = legs;
+        self
+    }
+
+    pub fn with_arms(mut self, arms: f32) -> Self {
+        self.arms = arms;
+        self
+    }
+
+    pub fn with_spine(mut self, spine: f32) -> Self {
+        self.spine = spine;
+        self
+    }
+
+    /// How much longer the strides are than the authored body's.
+    pub fn stride_scale(&self) -> f32 {
+        self.scale * self.legs
+    }
+
+    /// How much the offset of `bone` from its parent is stretched. A bone's
+    /// offset is the length of the bone it hangs off, e.g. the knee's is the
+    /// thigh.
+    fn stretch(&self, bone: RigBone) -> f32 {
+        match bone {
+            RigBone::Hips
+            | RigBone::LeftLeg
+            | RigBone::LeftFoot
+            | RigBone::RightLeg
+            | RigBone::RightFoot => self.legs,
+            RigBone::LeftForeArm
+            | RigBone::LeftHand
+            | RigBone::RightForeArm
+            | RigBone::RightHand => self.arms,
+            RigBone::Spine
+            | RigBone::Spine1
+            | RigBone::Spine2
+            | RigBone::Neck
+            | RigBone::Head => self.spine,
+            RigBone::LeftArm | RigBone::RightArm | RigBone::LeftUpLeg | RigBone::RightUpLeg => {
+                1.0
+            }
+        }
+    }
+
+    /// How much the bone itself is stretched, i.e. the offset of the next bone
+    /// down the chain, for things attached along it.
+    fn length_stretch(&self, bone: RigBone) -> f32 {
+        match bone {
+            RigBone::LeftUpLeg | RigBone::LeftLeg | RigBone::RightUpLeg | RigBone::RightLeg => {
+                self.legs
+            }
+            RigBone::LeftArm
+            | RigBone::LeftForeArm
+            | RigBone::RightArm
+            | RigBone::RightForeArm => self.arms,
+            RigBone::Spine | RigBone::Spine1 | RigBone::Spine2 | RigBone::Neck => self.spine,
+            _ => 1.0,
+        }
+    }
+
+    /// Stretches the translation of `entity` from its authored one, unless
+    /// the animation has written a new one since the last stretch.
+    fn stretch_translation(&mut self, entity: Entity, transform: &mut Transform, stretch: f32) {
+        let authored = match self.stretched.get(&entity) {
+            Some((authored, written)) if *written == transform.translation => *authored,
+            _ => transform.translation,
+        };
+        transform.translation = authored * stretch;
+        self.stretched.insert(entity, (authored, transform.translation));
+    }
+}
+
+/// Slows the locomotion and scales root motion with the stride.
+fn match_strides(
+    characters: Query<(Entity, &BodyProportions)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+) {
+    for (root, proportions) in characters.iter() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        state.config_mut().stride_scale = proportions.stride_scale();
+    }
+}
+
+fn stretch_bones(
+    mut characters: Query<(&mut BodyProportions, &RigMap)>,
+    mut transforms: Query<&mut Transform>,
+    parents: Query<&ChildOf>,
+    children: Query<&Children>,
+    sockets: Query<(), With<Socket>>,
+) {
+    for (mut proportions, rig) in characters.iter_mut() {
+        let proportions = proportions.as_mut();
+        // Scale evenly from the armature above the hips, so the mesh and
+        // everything on the skeleton scale along.
+        let armature = rig
+            .get(RigBone::Hips)
+            .and_then(|hips| parents.get(hips).ok())
+            .map(ChildOf::parent);
+        if let Some((entity, mut armature)) =
+            armature.and_then(|entity| Some((entity, transforms.get_mut(entity).ok()?)))
+        {
+            let authored = match proportions.armature {
+                Some((cached, authored)) if cached == entity => authored,
+                _ => armature.scale,
+            };
+            proportions.armature = Some((entity, authored));
+            let scale = authored * proportions.scale;
+            if armature.scale != scale {
+                armature.scale = scale;
+            }
+        }
+
+        for bone in RigBone::ALL {
+            let Some(entity) = rig.get(bone) else {
+                continue;
+            };
+            let stretch = proportions.stretch(bone);
+            if stretch != 1.0 {
+                if let Ok(mut transform) = transforms.get_mut(entity) {
+                    proportions.stretch_translation(entity, &mut transform, stretch);
+                }
+            }
+            // Sockets along the bone, e.g. a holster on the spine, move with
+            // its length.
+            let length_stretch = proportions.length_stretch(bone);
+            if length_stretch == 1.0 {
+                continue;
+            }
+            for socket in children.relationship_sources::<Children>(entity) {
+                if !sockets.contains(socket) {
+                    continue;
+                }
+                if let Ok(mut transform) = transforms.get_mut(socket) {
+                    proportions.stretch_translation(socket, &mut transform, length_stretch);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::state::{PlayerAnimationInput, PlayerAnimationState};
+use crate::tracer::SpawnTracer;
+
+/// Records animation inputs and tracers so they can be played back later onto
+/// puppet characters, e.g. for killcams or demos.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ReplayRecorder>();
+        app.register_type::<ReplayPuppet>();
+        app.init_resource::<ReplayedTracers>();
+        // Recorded once the input is final, and played back over it, so the
+        // puppets animate with the input the recorded characters did.
+        app.add_systems(
+            Update,
+            (record_replays, play_replays)
+                .after(CharAnimSet::ModifyInput)
+                .before(CharAnimSet::StateMachine),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReplayEvent {
+    /// The animation input changed. The animation state machine is driven entirely
+    /// by its input, so replaying inputs reproduces the state changes as well.
+    Input(PlayerAnimationInput),
+    Tracer(SpawnTracer),
+}
+
+#[derive(Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayEntry {
+    /// Seconds since the recording started.
+    pub time: f32,
+    pub event: ReplayEvent,
+}
+
+/// A recording sorted by time. Inputs are only stored when they change, so a
+/// character standing still costs nothing.
+#[derive(Reflect, Clone, Default, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayBuffer {
+    entries: Vec<ReplayEntry>,
+}
+
+impl ReplayBuffer {
+    pub fn entries(&self) -> &[ReplayEntry] {
+        &self.entries
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.entries.last().map(|e| e.time).unwrap_or(0.0)
+    }
+
+    fn push(&mut self, time: f32, event: ReplayEvent) {
+        self.entries.push(ReplayEntry { time, event });
+    }
+}
+
+/// Add to a player root to record its animation input and the tracers fired
+/// from muzzles below it while recording, except replayed ones. Remove it with
+/// [`ReplayRecorder::take_buffer`] to keep the recording.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayRecorder {
+    started_at: Option<f32>,
+    last_input: Option<PlayerAnimationInput>,
+    buffer: ReplayBuffer,
+}
+
+impl ReplayRecorder {
+    pub fn buffer(&self) -> &ReplayBuffer {
+        &self.buffer
+    }
+
+    pub fn take_buffer(&mut self) -> ReplayBuffer {
+        self.started_at = None;
+        self.last_input = None;
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+/// The tracers puppets sent since they were last recorded, so they aren't
+/// recorded again.
+#[derive(Resource, Default)]
+struct ReplayedTracers(Vec<EventId<SpawnTracer>>);
+
+/// Add to a player root to drive its animations from a recording instead of
+/// live input.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayPuppet {
+    buffer: ReplayBuffer,
+    started_at: Option<f32>,
+    cursor: usize,
+    input: Option<PlayerAnimationInput>,
+    /// Whether to restart the replay once it's finished.
+    pub looping: bool,
+}
+
+impl ReplayPuppet {
+    pub fn new(buffer: ReplayBuffer) -> Self {
+        Self {
+            buffer,
+            started_at: None,
+            cursor: 0,
+            input: None,
+            looping: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.buffer.entries.len()
+    }
+
+    fn restart(&mut self) {
+        self.started_at = None;
+        self.cursor = 0;
+        self.input = None;
+    }
+}
+
+fn record_replays(
+    time: Res<Time>,
+    mut tracers: EventReader<SpawnTracer>,
+    mut replayed: ResMut<ReplayedTracers>,
+    mut recorders: Query<&mut ReplayRecorder>,
+    states: Query<(Entity, &PlayerAnimationState)>,
+    parents: Query<&ChildOf>,
+) {
+    let now = time.elapsed_secs();
+
+    for mut recorder in recorders.iter_mut() {
+        recorder.started_at.get_or_insert(now);
+    }
+
+    for (tracer, id) in tracers.read_with_id() {
+        if replayed.0.contains(&id) {
+            continue;
+        }
+        let Some(muzzle) = tracer.muzzle else {
+            continue;
+        };
+        let Some(root) = std::iter::once(muzzle)
+            .chain(parents.iter_ancestors(muzzle))
+            .find(|e| recorders.contains(*e))
+        else {
+            continue;
+        };
+        let mut recorder = recorders.get_mut(root).unwrap();
+        let started_at = *recorder.started_at.get_or_insert(now);
+        recorder
+            .buffer
+            .push(now - started_at, ReplayEvent::Tracer(tracer.clone()));
+    }
+    // Every replayed tracer sent so far has just been read.
+    replayed.0.clear();
+
+    for (entity, state) in states.iter() {
+        let Some(input) = state.input() else {
+            continue;
+        };
+        let Some(root) = parents
+            .iter_ancestors(entity)
+            .find(|e| recorders.contains(*e))
+        else {
+            continue;
+        };
+        let mut recorder = recorders.get_mut(root).unwrap();
+        if recorder.last_input.as_ref() == Some(input) {
+            continue;
+        }
+        let started_at = *recorder.started_at.get_or_insert(now);
+        recorder.last_input = Some(input.clone());
+        recorder
+            .buffer
+            .push(now - started_at, ReplayEvent::Input(input.clone()));
+    }
+}
+
+fn play_replays(
+    time: Res<Time>,
+    mut puppets: Query<&mut ReplayPuppet>,
+    mut states: Query<(Entity, &mut PlayerAnimationState)>,
+    parents: Query<&ChildOf>,
+    mut spawn_tracers: EventWriter<SpawnTracer>,
+    mut replayed: ResMut<ReplayedTracers>,
+) {
+    let now = time.elapsed_secs();
+
+    for mut puppet in puppets.iter_mut() {
+        if puppet.is_finished() && puppet.looping {
+            puppet.restart();
+        }
+        let started_at = *puppet.started_at.get_or_insert(now);
+        let replay_time = now - started_at;
+
+        while let Some(entry) = puppet.buffer.entries.get(puppet.cursor) {
+            if entry.time > replay_time {
+                break;
+            }
+            match &entry.event {
+                ReplayEvent::Input(input) => puppet.input = Some(input.clone()),
+                ReplayEvent::Tracer(tracer) => {
+                    replayed.0.push(spawn_tracers.write(tracer.clone()));
+                }
+            }
+            puppet.cursor += 1;
+        }
+    }
+
+    for (entity, mut state) in states.iter_mut() {
+        let Some(root) = parents
+            .iter_ancestors(entity)
+            .find(|e| puppets.contains(*e))
+        else {
+            continue;
+        };
+        // The last recorded input is held until it changes, just like when it was
+        // recorded.
+        if let Some(ref input) = puppets.get(root).unwrap().input {
+            state.set_input(input.clone());
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use bevy::{
+    input::mouse::AccumulatedMouseMotion,
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+#[cfg(not(feature = "avian"))]
+use bevy_rapier3d::prelude::*;
+
+use crate::anim::{self, CharAnimSet};
+use crate::camera_kick::{CameraKick, CameraKickPlugin};
+use crate::camera_rig::{aim_with_camera_rigs, CameraRig, CameraRigPlugin};
+use crate::character::{CharacterBuilder, CharacterPlugin};
+use crate::effect_rng::EffectRng;
+use crate::events::CharAnimEventsPlugin;
+use crate::hitbox::{HitboxPlugin, Hitboxes};
+use crate::hitscan::{Hitscan, HitscanPlugin, Shot};
+use crate::ik::IkPlugin;
+use crate::spread::{SpreadPlugin, WeaponSpread};
+use crate::state::{PlayerAnimationInput, PlayerAnimationState};
+use crate::surface::{SurfaceKind, SurfacePlugin};
+use crate::tracer::TracerPlugin;
+use crate::utils;
+
+/// A ready to use demo character, with every subsystem it needs wired
+/// together, to see how they fit and to strip down into a game's own. It
+/// spawns the bundled rig, with a capsule standing in while it loads, a
+/// `CameraRig` that follows it, and some ground to stand and shoot
+/// at. The keys in [`SandboxInput`] move it, the mouse looks, and clicking
+/// fires hitscan shots that spawn tracers and impacts.
+///
+/// The plugins it builds on are added if they're missing, but not physics,
+/// which the game picks: rapier, or avian3d with the `avian` feature, which
+/// the shots, hitboxes and ground all use. A game adds it from the library,
+/// and [`run`] runs it on its own, with physics, and escape to grab the
+/// cursor.
+pub struct SandboxCharacterPlugin;
+
+impl Plugin for SandboxCharacterPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<CharAnimEventsPlugin>() {
+            app.add_plugins(CharAnimEventsPlugin);
+        }
+        if !app.is_plugin_added::<TracerPlugin>() {
+            app.add_plugins(TracerPlugin::default());
+        }
+        if !app.is_plugin_added::<SurfacePlugin>() {
+            app.add_plugins(SurfacePlugin);
+        }
+        if !app.is_plugin_added::<HitscanPlugin>() {
+            app.add_plugins(HitscanPlugin);
+        }
+        if !app.is_plugin_added::<HitboxPlugin>() {
+            app.add_plugins(HitboxPlugin);
+        }
+        if !app.is_plugin_added::<SpreadPlugin>() {
+            app.add_plugins(SpreadPlugin);
+        }
+        if !app.is_plugin_added::<CameraKickPlugin>() {
+            app.add_plugins(CameraKickPlugin);
+        }
+        if !app.is_plugin_added::<CameraRigPlugin>() {
+            app.add_plugins(CameraRigPlugin);
+        }
+        if !app.is_plugin_added::<anim::AnimationPlugin>() {
+            app.add_plugins(anim::AnimationPlugin::default());
+        }
+        if !app.is_plugin_added::<CharacterPlugin>() {
+            app.add_plugins(CharacterPlugin);
+        }
+        if !app.is_plugin_added::<IkPlugin>() {
+            app.add_plugins(IkPlugin);
+        }
+        app.init_resource::<SandboxInput>();
+        app.register_type::<SandboxInput>();
+        app.register_type::<SandboxCharacter>();
+        app.add_systems(Startup, spawn_sandbox);
+        app.add_systems(
+            Update,
+            (
+                look_sandbox_rigs,
+                move_sandbox_characters,
+                fire_sandbox_weapons,
+            )
+                .chain()
+                .in_set(CharAnimSet::Input)
+                .before(aim_with_camera_rigs),
+        );
+    }
+}
+
+/// Runs the sandbox on its own, with `cargo run --features sandbox -- sandbox`.
+pub fn run() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    #[cfg(not(feature = "avian"))]
+    app.add_plugins(RapierPhysicsPlugin::<NoUserData>::default());
+    #[cfg(feature = "avian")]
+    app.add_plugins(avian3d::PhysicsPlugins::default());
+    app.add_plugins(SandboxCharacterPlugin)
+        .add_systems(Update, utils::toggle_cursor_grab_with_esc)
+        .run();
+}
+
+/// The controls of the sandbox character.
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct SandboxInput {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub sprint: KeyCode,
+    pub jump: KeyCode,
+    pub roll: KeyCode,
+    pub fire: MouseButton,
+    /// Radians turned per pixel the mouse moves.
+    pub mouse_sensitivity: f32,
+}
+
+impl Default for SandboxInput {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            back: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            sprint: KeyCode::ShiftLeft,
+            jump: KeyCode::Space,
+            roll: KeyCode::KeyC,
+            fire: MouseButton::Left,
+            mouse_sensitivity: 0.003,
+        }
+    }
+}
+
+/// The root of the sandbox character. It turns to face where the camera
+/// looks and moves along the ground, with a simple jump in place of a
+/// character controller.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct SandboxCharacter {
+    /// The camera whose view it turns to and fires along.
+    pub camera: Entity,
+    /// In m/s.
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    /// The upwards speed a jump starts with, in m/s.
+    pub jump_speed: f32,
+    pub gravity: f32,
+    /// The height of the ground it lands on.
+    pub ground_height: f32,
+    vertical_speed: f32,
+}
+
+impl SandboxCharacter {
+    pub fn new(camera: Entity) -> Self {
+        Self {
+            camera,
+            walk_speed: 1.5,
+            sprint_speed: 4.5,
+            jump_speed: 4.0,
+            gravity: 9.81,
+            ground_height: 0.0,
+            vertical_speed: 0.0,
+        }
+    }
+
+    /// The vertical speed in m/s, negative when falling.
+    pub fn vertical_speed(&self) -> f32 {
+        self.vertical_speed
+    }
+}
+
+fn spawn_sandbox(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let camera = commands.spawn_empty().id();
+    let capsule = meshes.add(Capsule3d::new(0.3, 1.2));
+    let capsule_material = materials.add(Color::srgb(0.7, 0.7, 0.75));
+    let character = CharacterBuilder::new(asset_server.load("models/gltf/character.glb"))
+        .with_name("SandboxCharacter")
+        .with_foot_ik()
+        .with_placeholder(capsule, capsule_material, Transform::from_xyz(0.0, 0.9, 0.0))
+        .spawn(&mut commands)
+        .insert((
+            SandboxCharacter::new(camera),
+            WeaponSpread::default(),
+            Hitboxes::humanoid(),
+        ))
+        .id();
+    commands.entity(camera).insert((
+        Name::new("SandboxCamera"),
+        Camera3d::default(),
+        CameraRig::new(character),
+        CameraKick::default(),
+        Transform::default(),
+    ));
+
+    commands.spawn((
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    // Ground to walk on, and some crates to shoot at.
+    let mut ground = commands.spawn((
+        Name::new("SandboxGround"),
+        Mesh3d(meshes.add(Cuboid::new(40.0, 0.2, 40.0))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.5, 0.3))),
+        Transform::from_xyz(0.0, -0.1, 0.0),
+        SurfaceKind::Dirt,
+    ));
+    #[cfg(not(feature = "avian"))]
+    ground.insert(Collider::cuboid(20.0, 0.1, 20.0));
+    #[cfg(feature = "avian")]
+    ground.insert((
+        avian3d::prelude::RigidBody::Static,
+        avian3d::prelude::Collider::cuboid(40.0, 0.2, 40.0),
+    ));
+    let crate_mesh = meshes.add(Cuboid::from_length(1.0));
+    let crate_material = materials.add(Color::srgb(0.55, 0.4, 0.25));
+    for x in [-3.0, 0.0, 3.0] {
+        let mut target = commands.spawn((
+            Name::new("SandboxCrate"),
+            Mesh3d(crate_mesh.clone()),
+            MeshMaterial3d(crate_material.clone()),
+            Transform::from_xyz(x, 0.5, 8.0),
+            SurfaceKind::Wood,
+        ));
+        #[cfg(not(feature = "avian"))]
+        target.insert(Collider::cuboid(0.5, 0.5, 0.5));
+        #[cfg(feature = "avian")]
+        target.insert((
+            avian3d::prelude::RigidBody::Static,
+            avian3d::prelude::Collider::cuboid(1.0, 1.0, 1.0),
+        ));
+    }
+}
+
+fn look_sandbox_rigs(
+    characters: Query<&SandboxCharacter>,
+    mut rigs: Query<&mut CameraRig>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mouse: Res<AccumulatedMouseMotion>,
+    input: Res<SandboxInput>,
+) {
+    let grabbed = windows
+        .single()
+        .is_ok_and(|window| window.cursor_options.grab_mode != CursorGrabMode::None);
+    if !grabbed {
+        return;
+    }
+    let delta = mouse.delta * input.mouse_sensitivity;
+    for character in characters.iter() {
+        if let Ok(mut rig) = rigs.get_mut(character.camera) {
+            // The mouse's +Y is down.
+            rig.add_look(Vec2::new(delta.x, -delta.y));
+        }
+    }
+}
+
+fn move_sandbox_characters(
+    mut characters: Query<(Entity, &mut SandboxCharacter, &mut Transform)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    rigs: Query<&CameraRig>,
+    children: Query<&Children>,
+    keys: Res<ButtonInput<KeyCode>>,
+    input: Res<SandboxInput>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for (root, mut character, mut transform) in characters.iter_mut() {
+        let Ok(rig) = rigs.get(character.camera) else {
+            continue;
+        };
+        let local_movement_direction = utils::unit_vector_from_bools(
+            keys.pressed(input.forward),
+            keys.pressed(input.back),
+            keys.pressed(input.left),
+            keys.pressed(input.right),
+        );
+
+        let is_grounded = transform.translation.y <= character.ground_height
+            && character.vertical_speed <= 0.0;
+        let just_jumped = is_grounded && keys.just_pressed(input.jump);
+        let is_sprinting = is_grounded
+            && keys.pressed(input.sprint)
+            && utils::most_aligned(local_movement_direction) == IVec2::Y;
+
+        // Move along the ground relative to the view. The animation state turns
+        // the body.
+        let speed = match is_sprinting {
+            true => character.sprint_speed,
+            false => character.walk_speed,
+        };
+        let movement = Vec3::new(-local_movement_direction.x, 0.0, local_movement_direction.y);
+        transform.translation += Quat::from_rotation_y(rig.yaw()) * movement * speed * dt;
+
+        if just_jumped {
+            character.vertical_speed = character.jump_speed;
+        }
+        if !is_grounded || just_jumped {
+            character.vertical_speed -= character.gravity * dt;
+            transform.translation.y += character.vertical_speed * dt;
+        }
+        // As it touches down, for the landing to pick how hard it was.
+        let vertical_speed = character.vertical_speed;
+        if transform.translation.y <= character.ground_height {
+            transform.translation.y = character.ground_height;
+            character.vertical_speed = character.vertical_speed.max(0.0);
+        }
+
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        states
+            .get_mut(state_entity)
+            .unwrap()
+            .set_input(PlayerAnimationInput {
+                local_movement_direction,
+                is_sprinting,
+                look_y: rig.yaw(),
+                look_x: rig.pitch(),
+                just_jumped,
+                is_grounded,
+                caution: 0.0,
+                vertical_speed,
+                wants_roll: keys.just_pressed(input.roll),
+            });
+    }
+}
+
+/// Fires from the camera, so shots land under the crosshair, with the tracer
+/// drawn from the character's muzzle.
+fn fire_sandbox_weapons(
+    mut characters: Query<(Entity, &SandboxCharacter, &mut WeaponSpread)>,
+    states: Query<&PlayerAnimationState>,
+    cameras: Query<&Transform, With<CameraRig>>,
+    global_transforms: Query<&GlobalTransform>,
+    children: Query<&Children>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    input: Res<SandboxInput>,
+    mut hitscan: Hitscan,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    if !buttons.just_pressed(input.fire) {
+        return;
+    }
+    for (root, character, mut spread) in characters.iter_mut() {
+        let Ok(camera) = cameras.get(character.camera) else {
+            continue;
+        };
+        let Some(state) = children
+            .iter_descendants(root)
+            .find_map(|e| states.get(e).ok())
+        else {
+            continue;
+        };
+        let muzzle = state.proc_targets.bullet_point;
+        let direction = spread.fire(camera.forward().as_vec3(), &mut effect_rng);
+        hitscan.fire_ray(Shot {
+            visual_origin: global_transforms
+                .get(muzzle)
+                .ok()
+                .map(GlobalTransform::translation),
+            shooter: Some(root),
+            muzzle: Some(muzzle),
+            ..Shot::new(camera.translation, direction)
+        });
+    }
+}
@@ -0,0 +1,134 @@
+//! Versioned RON assets. Every file has a top level `version` field, and files
+//! written for an older version are migrated one version at a time when they
+//! are loaded, so upgrading the crate doesn't require hand editing assets.
+
+use std::{fmt, marker::PhantomData};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use ron::{value::Map, Value};
+use serde::de::DeserializeOwned;
+
+pub trait VersionedAsset: Asset + DeserializeOwned {
+    /// The version written by this version of the crate.
+    const CURRENT_VERSION: u32;
+    /// File extensions, e.g. `tracer.ron`.
+    const EXTENSIONS: &'static [&'static str];
+
+    /// Migrates the fields of a file from `from_version` to `from_version + 1`.
+    /// The `version` field has already been removed.
+    fn migrate(from_version: u32, fields: &mut Map) -> Result<(), SchemaError>;
+
+    /// Checks the values of a loaded file, e.g. that no duration is negative.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Deserialize(ron::Error),
+    /// The top level of the file isn't a struct or map.
+    NotAStruct,
+    /// The file is newer than this version of the crate understands.
+    UnsupportedVersion { found: u32, current: u32 },
+    /// A migration couldn't be applied.
+    Migration { from_version: u32, reason: String },
+    /// The file has a value that can't be used, see [`VersionedAsset::validate`].
+    Invalid(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read asset: {e}"),
+            Self::Parse(e) => write!(f, "could not parse asset: {e}"),
+            Self::Deserialize(e) => write!(f, "invalid asset: {e}"),
+            Self::NotAStruct => write!(f, "asset must be a struct"),
+            Self::UnsupportedVersion { found, current } => write!(
+                f,
+                "asset version {found} is newer than the supported version {current}"
+            ),
+            Self::Migration {
+                from_version,
+                reason,
+            } => write!(f, "could not migrate from version {from_version}: {reason}"),
+            Self::Invalid(reason) => write!(f, "invalid value in asset: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// Parses a versioned asset, migrating it to the current version if needed,
+/// and validates it. Files without a `version` field are treated as version 0.
+pub fn parse_versioned<T: VersionedAsset>(bytes: &[u8]) -> Result<T, SchemaError> {
+    let value: Value = ron::de::from_bytes(bytes).map_err(SchemaError::Parse)?;
+    let Value::Map(mut fields) = value else {
+        return Err(SchemaError::NotAStruct);
+    };
+
+    let version = match fields.remove(&Value::String("version".into())) {
+        Some(Value::Number(n)) => n.as_i64().unwrap_or(0) as u32,
+        _ => 0,
+    };
+    if version > T::CURRENT_VERSION {
+        return Err(SchemaError::UnsupportedVersion {
+            found: version,
+            current: T::CURRENT_VERSION,
+        });
+    }
+    for from_version in version..T::CURRENT_VERSION {
+        T::migrate(from_version, &mut fields)?;
+    }
+
+    let asset: T = Value::Map(fields)
+        .into_rust()
+        .map_err(SchemaError::Deserialize)?;
+    asset.validate().map_err(SchemaError::Invalid)?;
+    Ok(asset)
+}
+
+/// Renames a field during a migration, if it's present.
+pub fn rename_field(fields: &mut Map, from: &str, to: &str) {
+    if let Some(value) = fields.remove(&Value::String(from.into())) {
+        fields.insert(Value::String(to.into()), value);
+    }
+}
+
+/// Loads any [`VersionedAsset`] from RON.
+pub struct VersionedRonLoader<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for VersionedRonLoader<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: VersionedAsset> AssetLoader for VersionedRonLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = SchemaError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<T, SchemaError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(SchemaError::Io)?;
+        parse_versioned(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
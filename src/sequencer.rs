@@ -0,0 +1,214 @@
+use bevy::{math::curve::UnevenSampleAutoCurve, prelude::*};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::damping::{update_anim_params, AnimParams};
+use crate::montage::Montage;
+use crate::state::{PlayerAnimationInput, PlayerAnimationState};
+use crate::velocity::drive_animation_from_velocity;
+
+/// Lets timeline tools drive characters in cutscenes. A [`Timeline`] on a
+/// character root holds tracks of montages, parameter curves and look-at
+/// targets keyed at absolute times, and the tool tells it the time every
+/// frame with [`Timeline::set_time`], or jumps with [`Timeline::scrub`].
+pub struct SequencerPlugin;
+
+impl Plugin for SequencerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Timeline>();
+        app.add_systems(
+            Update,
+            play_timelines
+                .in_set(CharAnimSet::ModifyInput)
+                .after(drive_animation_from_velocity)
+                .before(update_anim_params),
+        );
+    }
+}
+
+/// A montage that starts at `start` seconds on the timeline.
+#[derive(Reflect, Clone, Debug)]
+pub struct MontageKey {
+    pub start: f32,
+    pub montage: Montage,
+}
+
+/// Sets an `AnimParams` parameter from a curve over the timeline. The
+/// parameter holds the first and last keys outside the curve.
+#[derive(Reflect, Clone, Debug)]
+pub struct ParamTrack {
+    pub name: &'static str,
+    pub curve: UnevenSampleAutoCurve<f32>,
+}
+
+impl ParamTrack {
+    /// A curve through `(time, value)` keys, linear in between. Needs at
+    /// least two keys at different times.
+    pub fn new(name: &'static str, keys: impl IntoIterator<Item = (f32, f32)>) -> Option<Self> {
+        Some(Self {
+            name,
+            curve: UnevenSampleAutoCurve::new(keys).ok()?,
+        })
+    }
+}
+
+/// From `time` seconds on the timeline, the character looks at `target` in
+/// global world space, or is left to the game's input if none.
+#[derive(Reflect, Clone, Copy, Debug)]
+pub struct LookAtKey {
+    pub time: f32,
+    pub target: Option<Vec3>,
+}
+
+/// The cutscene tracks of a character. Add to the character root.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct Timeline {
+    pub montages: Vec<MontageKey>,
+    pub params: Vec<ParamTrack>,
+    pub look_at: Vec<LookAtKey>,
+    /// The time on the timeline in seconds.
+    time: f32,
+    /// Whether the time jumped since the last update, rather than played.
+    jumped: bool,
+    /// The index of the montage key that was started, if it's still playing.
+    playing: Option<usize>,
+}
+
+impl Timeline {
+    pub fn with_montage(mut self, start: f32, montage: Montage) -> Self {
+        self.montages.push(MontageKey { start, montage });
+        self
+    }
+
+    pub fn with_param(mut self, track: ParamTrack) -> Self {
+        self.params.push(track);
+        self
+    }
+
+    pub fn with_look_at(mut self, time: f32, target: Option<Vec3>) -> Self {
+        self.look_at.push(LookAtKey { time, target });
+        self
+    }
+
+    /// Plays the timeline on to `time`, e.g. every frame of playback. The
+    /// montages' notifies and root motion up to it happen as usual.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Jumps the timeline to `time`, e.g. when the tool's playhead is dragged.
+    /// Montages are seeked to where they'd be, skipping their notifies and
+    /// root motion.
+    pub fn scrub(&mut self, time: f32) {
+        self.time = time;
+        self.jumped = true;
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// The montage key playing at `time` given the clips' durations, the
+    /// latest to start if they overlap.
+    fn montage_at(&self, time: f32, clips: &Assets<AnimationClip>) -> Option<usize> {
+        self.montages
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| key.start <= time)
+            .filter(|(_, key)| {
+                // Keep a montage whose clip hasn't loaded, it waits to start.
+                clips.get(&key.montage.clip).is_none_or(|clip| {
+                    time < key.start + clip.duration() / key.montage.speed.max(f32::EPSILON)
+                })
+            })
+            .max_by(|(_, a), (_, b)| a.start.total_cmp(&b.start))
+            .map(|(index, _)| index)
+    }
+
+    fn look_target_at(&self, time: f32) -> Option<Vec3> {
+        self.look_at
+            .iter()
+            .filter(|key| key.time <= time)
+            .max_by(|a, b| a.time.total_cmp(&b.time))
+            .and_then(|key| key.target)
+    }
+}
+
+fn play_timelines(
+    mut timelines: Query<(Entity, &mut Timeline, &GlobalTransform, Option<&RigMap>)>,
+    mut states: Query<(&mut PlayerAnimationState, Option<&mut AnimParams>)>,
+    children: Query<&Children>,
+    global_transforms: Query<&GlobalTransform>,
+    clips: Res<Assets<AnimationClip>>,
+) {
+    for (root, mut timeline, root_global, rig) in timelines.iter_mut() {
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let (mut state, params) = states.get_mut(state_entity).unwrap();
+        let time = timeline.time;
+        let jumped = std::mem::take(&mut timeline.jumped);
+
+        // Something else, e.g. a dodge, may have replaced the montage.
+        if let Some(index) = timeline.playing {
+            let name = timeline.montages[index].montage.name;
+            if state.montage().is_none_or(|montage| montage.name != name) {
+                timeline.playing = None;
+            }
+        }
+        let target = timeline.montage_at(time, &clips);
+        match target {
+            Some(index) if timeline.playing != Some(index) => {
+                let key = &timeline.montages[index];
+                state.play_montage(key.montage.clone());
+                // Started late, e.g. scrubbed into the middle of it.
+                let offset = (time - key.start) * key.montage.speed;
+                if offset > 0.0 {
+                    state.seek_montage(offset);
+                }
+                timeline.playing = Some(index);
+            }
+            Some(index) if jumped => {
+                let key = &timeline.montages[index];
+                state.seek_montage((time - key.start) * key.montage.speed);
+            }
+            None => {
+                if timeline.playing.take().is_some() {
+                    state.stop_montage();
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(mut params) = params {
+            for track in timeline.params.iter() {
+                params.set_float(track.name, track.curve.sample_clamped(time));
+            }
+        }
+
+        let Some(target) = timeline.look_target_at(time) else {
+            continue;
+        };
+        let eyes = rig
+            .and_then(|rig| rig.get(RigBone::Head))
+            .and_then(|head| global_transforms.get(head).ok())
+            .unwrap_or(root_global)
+            .translation();
+        let Some(direction) = (target - eyes).try_normalize() else {
+            continue;
+        };
+        if state.input().is_none() {
+            state.set_input(PlayerAnimationInput {
+                is_grounded: true,
+                ..default()
+            });
+        }
+        let input = state.input_mut().unwrap();
+        input.look_y = direction.x.atan2(direction.z);
+        input.look_x = (-direction.y).atan2(direction.xz().length());
+    }
+}
@@ -0,0 +1,271 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Collider;
+
+use crate::anim::CharAnimSet;
+use crate::events::{EventMeta, EventRouting, SlideEvent, SlidePhase};
+use crate::hitscan::Hitscan;
+use crate::montage::Montage;
+use crate::netsync::FixedRootMotion;
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+use crate::velocity::drive_animation_from_velocity;
+
+/// Sliding. Pressing crouch while sprinting drops a character with [`Slide`]
+/// into a slide pose and shrinks its capsule, and friction slows it down until
+/// it gets up, or stays in a crouched pose while crouch is held or there's no
+/// room to stand. `SlideEvent`s are sent when it starts and ends.
+pub struct SlidePlugin;
+
+impl Plugin for SlidePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Slide>();
+        app.add_systems(
+            Update,
+            slide
+                .after(drive_animation_from_velocity)
+                .in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+const GRAVITY: f32 = 9.81;
+
+#[derive(Reflect, Clone, Copy, Debug)]
+struct ActiveSlide {
+    /// The horizontal direction of the slide in global world space.
+    direction: Vec3,
+    /// The current speed in m/s.
+    speed: f32,
+}
+
+/// Lets a character slide. Add to the character root. A capsule `Collider` on
+/// the root is shrunk from the bottom while sliding.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct Slide {
+    /// The looping full body slide pose.
+    pub pose: Handle<AnimationClip>,
+    /// Played when getting up at the end of the slide, if set.
+    pub stand_up: Option<Montage>,
+    /// The looping full body pose held while staying crouched after a slide,
+    /// or the slide pose if none.
+    pub crouch_pose: Option<Handle<AnimationClip>>,
+    /// The coefficient of friction. The slide slows down by this times
+    /// gravity, so its duration is the entry speed over that.
+    pub friction: f32,
+    /// The slowest sprint in m/s that slides rather than just crouching.
+    pub min_entry_speed: f32,
+    /// The speed in m/s at which the slide ends.
+    pub exit_speed: f32,
+    /// The height of the capsule while sliding, as a fraction of standing.
+    pub capsule_scale: f32,
+    /// How much the spine aims while sliding, so the character can shoot.
+    pub aim_weight: f32,
+    crouch: bool,
+    last_crouch: bool,
+    last_position: Option<Vec3>,
+    active: Option<ActiveSlide>,
+    /// Whether it stayed crouched after the last slide.
+    crouched: bool,
+    #[reflect(ignore)]
+    standing_collider: Option<Collider>,
+}
+
+impl Slide {
+    pub fn new(pose: Handle<AnimationClip>) -> Self {
+        Self {
+            pose,
+            stand_up: None,
+            crouch_pose: None,
+            friction: 0.35,
+            min_entry_speed: 4.0,
+            exit_speed: 1.5,
+            capsule_scale: 0.5,
+            aim_weight: 0.5,
+            crouch: false,
+            last_crouch: false,
+            last_position: None,
+            active: None,
+            crouched: false,
+            standing_collider: None,
+        }
+    }
+
+    /// Sets whether crouch is held. Pressing it while sprinting slides.
+    pub fn set_crouch(&mut self, crouch: bool) {
+        self.crouch = crouch;
+    }
+
+    pub fn is_sliding(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Whether it stayed crouched after a slide.
+    pub fn is_crouched(&self) -> bool {
+        self.crouched
+    }
+
+    /// The current slide speed in m/s, 0 when not sliding.
+    pub fn speed(&self) -> f32 {
+        self.active.map_or(0.0, |active| active.speed)
+    }
+
+    fn crouch_override(&self) -> LocomotionOverride {
+        let pose = self.crouch_pose.as_ref().unwrap_or(&self.pose);
+        LocomotionOverride {
+            clips: vec![LocomotionClip::new(pose.clone(), 1.0)],
+            pitch: 0.0,
+            yaw: None,
+            aim_weight: 1.0,
+        }
+    }
+}
+
+/// The capsule shrunk to `scale` of its height, keeping its bottom in place.
+fn crouched_capsule(collider: &Collider, scale: f32) -> Option<Collider> {
+    let capsule = collider.as_capsule()?;
+    let (a, b) = (capsule.segment().a(), capsule.segment().b());
+    let (bottom, top) = if a.y <= b.y { (a, b) } else { (b, a) };
+    let radius = capsule.radius();
+    // Scale the whole height, caps included, down to at least a ball.
+    let height = (top.y - bottom.y) + 2.0 * radius;
+    let segment = (height * scale - 2.0 * radius).max(0.0);
+    let top = bottom + (top - bottom).normalize_or_zero() * segment;
+    Some(Collider::capsule(bottom, top, radius))
+}
+
+/// The top of a capsule in the collider's space.
+fn capsule_top(collider: &Collider) -> Option<f32> {
+    let capsule = collider.as_capsule()?;
+    Some(capsule.segment().a().y.max(capsule.segment().b().y) + capsule.radius())
+}
+
+fn slide(
+    mut characters: Query<(
+        Entity,
+        &mut Slide,
+        &mut Transform,
+        Option<&mut Collider>,
+        Option<&mut FixedRootMotion>,
+    )>,
+    mut states: Query<&mut PlayerAnimationState>,
+    mut events: EventWriter<SlideEvent>,
+    children: Query<&Children>,
+    hitscan: Hitscan,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (root, mut slide, mut transform, mut collider, fixed) in characters.iter_mut() {
+        let position = transform.translation;
+        let velocity = slide
+            .last_position
+            .replace(position)
+            .map_or(Vec3::ZERO, |last| (position - last).with_y(0.0) / dt);
+        let pressed = slide.crouch && !slide.last_crouch;
+        slide.last_crouch = slide.crouch;
+
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let grounded = state.input().is_some_and(|input| input.is_grounded);
+        let slide = slide.as_mut();
+        // Whether the standing capsule would hit a ceiling.
+        let blocked = |slide: &Slide, collider: Option<&Collider>| {
+            let standing_top = slide.standing_collider.as_ref().and_then(capsule_top);
+            let crouched_top = collider.and_then(capsule_top);
+            let (Some(standing_top), Some(crouched_top)) = (standing_top, crouched_top) else {
+                return false;
+            };
+            let from = position + Vec3::Y * crouched_top;
+            let headroom = standing_top - crouched_top;
+            hitscan
+                .cast_ray(from, Dir3::Y, headroom, Some(root))
+                .is_some()
+        };
+
+        if slide.active.is_none() {
+            // Stayed crouched after a slide, stand once crouch is let go.
+            if slide.crouched && !slide.crouch && !blocked(slide, collider.as_deref()) {
+                slide.crouched = false;
+                if let (Some(collider), Some(standing)) =
+                    (collider.as_deref_mut(), slide.standing_collider.take())
+                {
+                    *collider = standing;
+                }
+            }
+
+            let speed = velocity.length();
+            if !(pressed && grounded && state.is_sprinting() && speed >= slide.min_entry_speed) {
+                if slide.crouched {
+                    state.override_locomotion(slide.crouch_override());
+                }
+                continue;
+            }
+            slide.crouched = false;
+            slide.active = Some(ActiveSlide {
+                direction: velocity / speed,
+                speed,
+            });
+            // Already crouched from the last slide if there wasn't room to stand.
+            if let (Some(collider), None) = (collider.as_deref_mut(), &slide.standing_collider) {
+                if let Some(crouched) = crouched_capsule(collider, slide.capsule_scale) {
+                    slide.standing_collider = Some(std::mem::replace(collider, crouched));
+                }
+            }
+            if routing.emits::<SlideEvent>() {
+                events.write(SlideEvent {
+                    meta: EventMeta::new(root, &time, position),
+                    phase: SlidePhase::Started,
+                });
+            }
+        }
+
+        let active = slide.active.as_mut().unwrap();
+        active.speed -= slide.friction * GRAVITY * dt;
+        if active.speed > slide.exit_speed && grounded {
+            let step = active.direction * active.speed * dt;
+            match fixed {
+                Some(mut fixed) => fixed.pending += step,
+                None => transform.translation += step,
+            }
+            state.override_locomotion(LocomotionOverride {
+                clips: vec![LocomotionClip::new(slide.pose.clone(), 1.0)],
+                pitch: 0.0,
+                yaw: Some(active.direction.x.atan2(active.direction.z)),
+                aim_weight: slide.aim_weight,
+            });
+            continue;
+        }
+
+        // Stand up if there's room above the crouched capsule.
+        slide.active = None;
+        let crouched = slide.crouch || blocked(slide, collider.as_deref());
+        slide.crouched = crouched;
+        if crouched {
+            state.override_locomotion(slide.crouch_override());
+        } else {
+            if let (Some(collider), Some(standing)) =
+                (collider.as_deref_mut(), slide.standing_collider.take())
+            {
+                *collider = standing;
+            }
+            if let Some(stand_up) = slide.stand_up.clone() {
+                state.play_montage(stand_up);
+            }
+        }
+        if routing.emits::<SlideEvent>() {
+            events.write(SlideEvent {
+                meta: EventMeta::new(root, &time, position),
+                phase: SlidePhase::Ended { crouched },
+            });
+        }
+    }
+}
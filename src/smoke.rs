@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+#[cfg(feature = "hanabi")]
+use bevy_hanabi::prelude::*;
+
+#[cfg(feature = "hanabi")]
+use crate::effect_rng::{EffectRng, MUZZLE_SMOKE_STREAM};
+use crate::events::{CharAnimEventsPlugin, FireEvent};
+#[cfg(feature = "hanabi")]
+use crate::vfx::VfxQuality;
+use crate::vfx::{add_particle_plugin, EffectLayers, ParticleKind, VfxQualityPlugin};
+
+/// Barrel smoke after sustained fire. Add a [`WeaponHeat`] to the muzzle (e.g.
+/// the muzzle socket) and fire from it with `SpawnTracer::muzzle` set, or call
+/// [`WeaponHeat::shot`] for shots fired some other way. Once enough shots are
+/// fired in a short window, smoke trails from the muzzle until the barrel
+/// cools down. This is separate from the per shot muzzle flash.
+pub struct MuzzleSmokePlugin;
+
+impl Plugin for MuzzleSmokePlugin {
+    fn build(&self, app: &mut App) {
+        add_particle_plugin(app);
+        if !app.is_plugin_added::<VfxQualityPlugin>() {
+            app.add_plugins(VfxQualityPlugin);
+        }
+        if !app.is_plugin_added::<CharAnimEventsPlugin>() {
+            app.add_plugins(CharAnimEventsPlugin);
+        }
+        app.register_type::<WeaponHeat>();
+        app.add_systems(Update, (heat_fired_muzzles, update_muzzle_smoke).chain());
+        #[cfg(feature = "hanabi")]
+        app.add_systems(Startup, setup_muzzle_smoke_particle_system)
+            .add_systems(
+                Update,
+                rebuild_muzzle_smoke_effect
+                    .run_if(resource_changed::<VfxQuality>.and(not(resource_added::<VfxQuality>))),
+            );
+    }
+}
+
+/// The name of the effect property that scales the smoke, in [0, 1].
+#[cfg(feature = "hanabi")]
+const HEAT_PROPERTY: &str = "heat";
+
+#[cfg(feature = "hanabi")]
+#[derive(Resource, Deref)]
+struct MuzzleSmokeEffect(Handle<EffectAsset>);
+
+/// How hot a weapon's barrel is. Add to the muzzle entity.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeaponHeat {
+    /// How many shots within `window_secs` start the smoke.
+    pub shots_to_smoke: usize,
+    pub window_secs: f32,
+    /// How much heat each shot adds, heat is in [0, 1].
+    pub heat_per_shot: f32,
+    /// How much heat is lost per second. The smoke lingers until the heat is
+    /// gone, e.g. 0.3 lingers for a bit over three seconds after a long burst.
+    pub cooling: f32,
+    heat: f32,
+    /// Seconds since each shot in the window.
+    recent_shots: Vec<f32>,
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    smoke: Option<Entity>,
+}
+
+impl Default for WeaponHeat {
+    fn default() -> Self {
+        Self {
+            shots_to_smoke: 5,
+            window_secs: 1.0,
+            heat_per_shot: 0.15,
+            cooling: 0.3,
+            heat: 0.0,
+            recent_shots: Vec::new(),
+            smoke: None,
+        }
+    }
+}
+
+impl WeaponHeat {
+    /// Records a shot.
+    pub fn shot(&mut self) {
+        self.heat = (self.heat + self.heat_per_shot).min(1.0);
+        self.recent_shots.push(0.0);
+    }
+
+    pub fn heat(&self) -> f32 {
+        self.heat
+    }
+
+    /// Whether the muzzle is smoking.
+    pub fn smoking(&self) -> bool {
+        self.smoke.is_some()
+    }
+}
+
+/// Heats the muzzles of the shots fired.
+fn heat_fired_muzzles(mut fires: EventReader<FireEvent>, mut muzzles: Query<&mut WeaponHeat>) {
+    for fire in fires.read() {
+        if let Some(mut heat) = fire.muzzle.and_then(|muzzle| muzzles.get_mut(muzzle).ok()) {
+            heat.shot();
+        }
+    }
+}
+
+fn update_muzzle_smoke(
+    mut commands: Commands,
+    mut muzzles: Query<(Entity, &mut WeaponHeat)>,
+    #[cfg(feature = "hanabi")] mut properties: Query<&mut EffectProperties>,
+    #[cfg(feature = "hanabi")] smoke_effect: Res<MuzzleSmokeEffect>,
+    layers: Query<&EffectLayers>,
+    parents: Query<&ChildOf>,
+    #[cfg(feature = "hanabi")] mut effect_rng: ResMut<EffectRng>,
+    time: Res<Time>,
+) {
+    let delta_secs = time.delta_secs();
+    for (muzzle, mut heat) in muzzles.iter_mut() {
+        let heat = &mut *heat;
+        let window_secs = heat.window_secs;
+        for age in heat.recent_shots.iter_mut() {
+            *age += delta_secs;
+        }
+        heat.recent_shots.retain(|age| *age <= window_secs);
+        heat.heat = (heat.heat - heat.cooling * delta_secs).max(0.0);
+
+        match heat.smoke {
+            Some(smoke) if heat.heat <= 0.0 => {
+                commands.entity(smoke).despawn();
+                heat.smoke = None;
+            }
+            #[cfg(feature = "hanabi")]
+            Some(smoke) => {
+                if let Ok(mut properties) = properties.get_mut(smoke) {
+                    properties.set(HEAT_PROPERTY, heat.heat.into());
+                }
+            }
+            #[cfg(not(feature = "hanabi"))]
+            Some(_) => {}
+            None if heat.recent_shots.len() >= heat.shots_to_smoke => {
+                let mut smoke = commands.spawn((
+                    ParticleKind::MuzzleSmoke,
+                    Transform::default(),
+                    EffectLayers::find(muzzle, &layers, &parents).unwrap_or_default(),
+                    ChildOf(muzzle),
+                ));
+                #[cfg(feature = "hanabi")]
+                smoke.insert((
+                    effect_rng.particle_effect(MUZZLE_SMOKE_STREAM, smoke_effect.0.clone()),
+                    EffectProperties::default()
+                        .with_properties([(HEAT_PROPERTY.to_string(), heat.heat.into())]),
+                ));
+                heat.smoke = Some(smoke.id());
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(feature = "hanabi")]
+fn setup_muzzle_smoke_particle_system(
+    mut effects: ResMut<Assets<EffectAsset>>,
+    mut commands: Commands,
+    quality: Res<VfxQuality>,
+) {
+    let handle = effects.add(muzzle_smoke_effect(&quality));
+    commands.insert_resource(MuzzleSmokeEffect(handle));
+}
+
+/// Rebuilds the smoke in place when the quality changes, so smoking muzzles
+/// keep their effect.
+#[cfg(feature = "hanabi")]
+fn rebuild_muzzle_smoke_effect(
+    mut effects: ResMut<Assets<EffectAsset>>,
+    smoke_effect: Res<MuzzleSmokeEffect>,
+    quality: Res<VfxQuality>,
+) {
+    effects.insert(&smoke_effect.0, muzzle_smoke_effect(&quality));
+}
+
+#[cfg(feature = "hanabi")]
+fn muzzle_smoke_effect(quality: &VfxQuality) -> EffectAsset {
+    let writer = ExprWriter::new();
+    let heat = writer.add_property(HEAT_PROPERTY, 0.0.into());
+
+    // Start just around the muzzle.
+    let init_pos = SetPositionSphereModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        radius: writer.lit(0.02).expr(),
+        dimension: ShapeDimension::Volume,
+    };
+
+    let init_age = SetAttributeModifier::new(Attribute::AGE, writer.lit(0.0).expr());
+    let init_lifetime = SetAttributeModifier::new(
+        Attribute::LIFETIME,
+        (writer.rand(ScalarType::Float) * writer.lit(1.0) + writer.lit(1.5)).expr(),
+    );
+
+    // Drift up slowly, the hotter the faster.
+    let init_velocity = SetAttributeModifier::new(
+        Attribute::VELOCITY,
+        (writer.rand(VectorType::VEC3F) * writer.lit(0.1) - writer.lit(0.05)
+            + writer.lit(Vec3::Y * 0.25) * writer.prop(heat))
+        .expr(),
+    );
+
+    // The smoke is thicker and puffier the hotter the barrel.
+    let init_size = SetAttributeModifier::new(
+        Attribute::F32_0,
+        (writer.lit(0.02) + writer.lit(0.06) * writer.prop(heat)).expr(),
+    );
+    let update_size = SetAttributeModifier::new(
+        Attribute::SIZE,
+        writer
+            .attr(Attribute::F32_0)
+            .mul(writer.lit(1.0).add(writer.attr(Attribute::AGE).mul(writer.lit(1.5))))
+            .expr(),
+    );
+
+    let mut color = Gradient::new();
+    color.add_key(0.0, Vec4::new(0.6, 0.6, 0.6, 0.0));
+    color.add_key(0.1, Vec4::new(0.6, 0.6, 0.6, 0.35));
+    color.add_key(1.0, Vec4::new(0.7, 0.7, 0.7, 0.0));
+
+    let module = writer.finish();
+
+    // Simulated in world space so the smoke trails behind a moving weapon.
+    EffectAsset::new(256, SpawnerSettings::rate(quality.particles(24.0).into()), module)
+        .with_simulation_space(SimulationSpace::Global)
+        .with_name("muzzle smoke")
+        .init(init_pos)
+        .init(init_age)
+        .init(init_lifetime)
+        .init(init_velocity)
+        .init(init_size)
+        .update(update_size)
+        .render(ColorOverLifetimeModifier::new(color))
+}
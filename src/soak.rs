@@ -0,0 +1,228 @@
+//! A soak test for catching leaks before they show up in long play sessions.
+//! It spawns and despawns characters, drives them with random input, fires
+//! tracers and makes noise, and panics if entity or asset counts keep growing
+//! once everything has warmed up.
+//!
+//! Run it with `cargo run --release --features soak`.
+
+use bevy::{ecs::entity::Entities, prelude::*};
+use rand::Rng;
+
+use crate::anim::CharAnimSet;
+use crate::character::CharacterBuilder;
+use crate::events::{EventMeta, NoiseEvent};
+use crate::state::{PlayerAnimationInput, PlayerAnimationState};
+use crate::tracer::{SpawnTracer, Tracer};
+use crate::vfx::ParticleKind;
+
+#[derive(Default)]
+pub struct SoakPlugin {
+    pub config: SoakConfig,
+}
+
+impl Plugin for SoakPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.config.clone());
+        app.init_resource::<SoakBounds>();
+        app.register_type::<SoakConfig>();
+        app.add_systems(
+            Update,
+            (
+                cycle_soak_characters,
+                drive_soak_characters.in_set(CharAnimSet::Input),
+            ),
+        );
+        app.add_systems(Last, check_soak_bounds);
+    }
+}
+
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct SoakConfig {
+    /// How many characters to keep alive at once.
+    pub characters: usize,
+    /// How long each character lives before it's despawned and replaced.
+    pub character_lifetime: f32,
+    /// The chance per frame that each character fires a tracer.
+    pub fire_chance: f32,
+    /// The chance per frame that a loud noise goes off somewhere.
+    pub noise_chance: f32,
+    /// Seconds to run before the counts are expected to stop growing. Should
+    /// cover a few character lifetimes so every subsystem has been exercised.
+    pub warmup_secs: f32,
+    /// How far past its high water mark from the warmup a count may grow
+    /// before it's considered a leak, as a fraction of the mark.
+    pub growth_tolerance: f32,
+    /// Seconds between checks.
+    pub check_interval: f32,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            characters: 8,
+            character_lifetime: 20.0,
+            fire_chance: 0.05,
+            noise_chance: 0.005,
+            warmup_secs: 120.0,
+            growth_tolerance: 0.5,
+            check_interval: 5.0,
+        }
+    }
+}
+
+/// A character spawned by the soak test and when to despawn it.
+#[derive(Component)]
+struct SoakCharacter {
+    despawn_at: f32,
+}
+
+/// The highest count of each tracked thing seen during the warmup.
+#[derive(Resource, Default)]
+struct SoakBounds {
+    high_water: Vec<(&'static str, usize)>,
+    next_check: f32,
+}
+
+fn cycle_soak_characters(
+    mut commands: Commands,
+    characters: Query<(Entity, &SoakCharacter)>,
+    config: Res<SoakConfig>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    let mut alive = 0;
+    for (entity, character) in characters.iter() {
+        if now >= character.despawn_at {
+            commands.entity(entity).despawn();
+        } else {
+            alive += 1;
+        }
+    }
+
+    let mut rng = rand::thread_rng();
+    for i in alive..config.characters {
+        let position = Vec3::new(rng.gen_range(-10.0..10.0), 0.0, rng.gen_range(-10.0..10.0));
+        // Stagger the lifetimes so characters aren't all replaced at once.
+        let lifetime = config.character_lifetime * rng.gen_range(0.5..1.5);
+        CharacterBuilder::new(asset_server.load("models/gltf/character.glb"))
+            .with_name(format!("Soak character {i}"))
+            .with_transform(Transform::from_translation(position))
+            .with_rig_autodetect()
+            .spawn(&mut commands)
+            .insert(SoakCharacter {
+                despawn_at: now + lifetime,
+            });
+    }
+}
+
+/// Gives random input to every character that wasn't given any this frame,
+/// including the player.
+fn drive_soak_characters(
+    mut states: Query<&mut PlayerAnimationState>,
+    global_transforms: Query<&GlobalTransform>,
+    config: Res<SoakConfig>,
+    time: Res<Time>,
+    mut tracers: EventWriter<SpawnTracer>,
+    mut noises: EventWriter<NoiseEvent>,
+) {
+    let mut rng = rand::thread_rng();
+    for mut state in states.iter_mut() {
+        if state.input().is_some() {
+            continue;
+        }
+        let sprint = rng.gen_bool(0.2);
+        let direction = if sprint {
+            Vec2::Y
+        } else {
+            Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0))
+        };
+        state.set_input(PlayerAnimationInput {
+            local_movement_direction: direction,
+            is_sprinting: sprint,
+            look_y: rng.gen_range(-1.0..1.0),
+            look_x: rng.gen_range(-0.5..0.5),
+            just_jumped: rng.gen_bool(0.01),
+            is_grounded: rng.gen_bool(0.98),
+            caution: rng.gen_range(0.0..1.0),
+            vertical_speed: rng.gen_range(-15.0..0.0),
+            wants_roll: rng.gen_bool(0.05),
+        });
+
+        if !rng.gen_bool(config.fire_chance as f64) {
+            continue;
+        }
+        let Ok(muzzle) = global_transforms.get(state.proc_targets.bullet_point) else {
+            continue;
+        };
+        tracers.write(SpawnTracer {
+            start: muzzle.translation(),
+            end: muzzle.translation() + muzzle.rotation() * Vec3::Z * 10.0,
+            logical_start: None,
+            profile: None,
+            user_data: None,
+            muzzle: Some(state.proc_targets.bullet_point),
+            ammo: None,
+        });
+    }
+
+    if rng.gen_bool(config.noise_chance as f64) {
+        let position = Vec3::new(rng.gen_range(-10.0..10.0), 0.0, rng.gen_range(-10.0..10.0));
+        noises.write(NoiseEvent {
+            meta: EventMeta::new(Entity::PLACEHOLDER, &time, position),
+            radius: 15.0,
+        });
+    }
+}
+
+fn check_soak_bounds(
+    mut bounds: ResMut<SoakBounds>,
+    config: Res<SoakConfig>,
+    time: Res<Time>,
+    entities: &Entities,
+    tracers: Query<(), With<Tracer>>,
+    effects: Query<(), With<ParticleKind>>,
+    meshes: Res<Assets<Mesh>>,
+    materials: Res<Assets<StandardMaterial>>,
+    graphs: Res<Assets<AnimationGraph>>,
+) {
+    let now = time.elapsed_secs();
+    if now < bounds.next_check {
+        return;
+    }
+    bounds.next_check = now + config.check_interval;
+
+    let counts = [
+        ("entities", entities.len() as usize),
+        ("tracers", tracers.iter().count()),
+        ("particle effects", effects.iter().count()),
+        ("meshes", meshes.len()),
+        ("materials", materials.len()),
+        ("animation graphs", graphs.len()),
+    ];
+    info!("soak at {now:.0}s: {counts:?}");
+
+    if now < config.warmup_secs {
+        bounds.high_water = counts
+            .iter()
+            .map(|&(name, count)| {
+                let mark = bounds
+                    .high_water
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map_or(0, |(_, mark)| *mark);
+                (name, count.max(mark))
+            })
+            .collect();
+        return;
+    }
+
+    for ((name, count), (_, mark)) in counts.iter().zip(bounds.high_water.iter()) {
+        let limit = (*mark as f32 * (1.0 + config.growth_tolerance)).ceil() as usize;
+        assert!(
+            *count <= limit.max(1),
+            "soak: {name} grew to {count} after {now:.0}s, the most during warmup was {mark}"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use rand::Rng;
+
+use bevy::prelude::*;
+
+use crate::effect_rng::{EffectRng, SPREAD_STREAM};
+
+/// Weapon spread and recoil. Add a [`WeaponSpread`] to whatever fires (e.g. the
+/// character root) and call [`WeaponSpread::fire`] for each shot to get the
+/// direction that both the hit ray and the `SpawnTracer` should use, so the
+/// tracer always shows where the shot really went. The scatter comes from the
+/// `EffectRng`, so seeded apps spray alike.
+pub struct SpreadPlugin;
+
+impl Plugin for SpreadPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WeaponSpread>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(Update, recover_spread);
+    }
+}
+
+/// Random spread in a cone that blooms as the weapon is fired and recovers
+/// when it isn't. Angles are half angles of the cone in radians.
+#[derive(Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpreadPattern {
+    /// The spread of a first, rested shot.
+    pub base: f32,
+    /// How much the spread grows per shot.
+    pub bloom_per_shot: f32,
+    /// The most the spread grows over the base.
+    pub max_bloom: f32,
+    /// How fast the bloom shrinks in radians per second.
+    pub recovery: f32,
+}
+
+impl Default for SpreadPattern {
+    fn default() -> Self {
+        Self {
+            base: 0.2f32.to_radians(),
+            bloom_per_shot: 0.4f32.to_radians(),
+            max_bloom: 4f32.to_radians(),
+            recovery: 8f32.to_radians(),
+        }
+    }
+}
+
+/// A fixed sequence of kicks that moves the aim the same way every spray, so
+/// players can learn to pull against it.
+#[derive(Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecoilPattern {
+    /// The kick of each shot in radians, x is yaw to the right and y is pitch
+    /// up. Shots past the end repeat the last kick.
+    pub kicks: Vec<Vec2>,
+    /// Seconds without firing after which the pattern starts over.
+    pub reset_secs: f32,
+    /// How fast the accumulated kick returns to the aim in radians per second.
+    pub recovery: f32,
+}
+
+impl Default for RecoilPattern {
+    fn default() -> Self {
+        Self {
+            kicks: vec![Vec2::new(0.0, 0.6f32.to_radians())],
+            reset_secs: 0.4,
+            recovery: 6f32.to_radians(),
+        }
+    }
+}
+
+/// The spread and recoil state of a weapon.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeaponSpread {
+    pub spread: SpreadPattern,
+    pub recoil: RecoilPattern,
+    bloom: f32,
+    /// The index of the next shot in the recoil pattern.
+    shot: usize,
+    /// The accumulated kick in radians, x is yaw to the right and y is pitch
+    /// up.
+    kick: Vec2,
+    since_shot: f32,
+    /// How much the spread is tightened, in [0, 1], e.g. while aiming down
+    /// sights.
+    tightness: f32,
+}
+
+impl WeaponSpread {
+    pub fn new(spread: SpreadPattern, recoil: RecoilPattern) -> Self {
+        Self {
+            spread,
+            recoil,
+            ..default()
+        }
+    }
+
+    /// Fires a shot aimed along `aim` in global world space and returns the
+    /// direction it actually goes in after recoil and spread.
+    pub fn fire(&mut self, aim: Vec3, rng: &mut EffectRng) -> Vec3 {
+        let Some(aim) = aim.try_normalize() else {
+            return aim;
+        };
+        if self.since_shot > self.recoil.reset_secs {
+            self.shot = 0;
+        }
+        if let Some(kick) = self
+            .recoil
+            .kicks
+            .get(self.shot)
+            .or(self.recoil.kicks.last())
+        {
+            self.kick += *kick;
+        }
+
+        // Kick the aim, then scatter around it.
+        let right = aim
+            .cross(Vec3::Y)
+            .try_normalize()
+            .unwrap_or_else(|| aim.any_orthonormal_vector());
+        let up = right.cross(aim);
+        let kicked = Quat::from_axis_angle(Vec3::Y, -self.kick.x)
+            * Quat::from_axis_angle(right, self.kick.y)
+            * aim;
+        let rng = rng.stream(SPREAD_STREAM);
+        let cone = self.current_spread();
+        // Uniform over the disc at the end of the cone.
+        let radius = cone.tan() * rng.gen::<f32>().sqrt();
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let offset = (right * angle.cos() + up * angle.sin()) * radius;
+        let direction = (kicked + offset).normalize();
+
+        self.bloom = (self.bloom + self.spread.bloom_per_shot).min(self.spread.max_bloom);
+        self.shot += 1;
+        self.since_shot = 0.0;
+        direction
+    }
+
+    /// The half angle in radians of the cone the next shot lands in.
+    pub fn current_spread(&self) -> f32 {
+        (self.spread.base + self.bloom) * (1.0 - self.tightness)
+    }
+
+    /// Tightens the spread, from 0 for the full cone to 1 for none.
+    pub fn set_tightness(&mut self, tightness: f32) {
+        self.tightness = tightness.clamp(0.0, 1.0);
+    }
+
+    /// The accumulated kick in radians, x is yaw to the right and y is pitch
+    /// up. Cameras can add this to the view so the crosshair follows the
+    /// recoil.
+    pub fn kick(&self) -> Vec2 {
+        self.kick
+    }
+
+    /// Drops the accumulated kick and bloom and starts the recoil pattern
+    /// over, e.g. when the weapon is swapped.
+    pub fn reset(&mut self) {
+        self.bloom = 0.0;
+        self.shot = 0;
+        self.kick = Vec2::ZERO;
+        self.since_shot = 0.0;
+    }
+}
+
+fn recover_spread(mut weapons: Query<&mut WeaponSpread>, time: Res<Time>) {
+    let delta_secs = time.delta_secs();
+    for mut weapon in weapons.iter_mut() {
+        let weapon = &mut *weapon;
+        weapon.since_shot += delta_secs;
+        weapon.bloom = (weapon.bloom - weapon.spread.recovery * delta_secs).max(0.0);
+        let recovery = weapon.recoil.recovery * delta_secs;
+        weapon.kick = weapon.kick.move_towards(Vec2::ZERO, recovery);
+    }
+}
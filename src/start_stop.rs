@@ -0,0 +1,324 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::anim::CharAnimSet;
+use crate::state::{LocomotionClip, LocomotionOverride, LowerBodyState, PlayerAnimationState};
+use crate::velocity::drive_animation_from_velocity;
+
+/// Start and stop transitions. Instead of blending straight between the idle
+/// and the run, a character with [`StartStop`] plants into a start clip when
+/// it accelerates from standing and skids through a stop clip when it brakes.
+///
+/// The clips are distance matched rather than played at their own pace: their
+/// time follows how far the character has moved since starting, or how far it
+/// has left until it stops, so the feet don't slide whatever the controller's
+/// acceleration and the character comes to rest exactly where it stops.
+pub struct StartStopPlugin;
+
+impl Plugin for StartStopPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StartStop>();
+        app.add_systems(
+            Update,
+            play_starts_and_stops
+                .after(drive_animation_from_velocity)
+                .in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+/// A start or stop clip, authored in place.
+#[derive(Reflect, Clone, Debug)]
+pub struct StartStopClip {
+    pub clip: Handle<AnimationClip>,
+    /// The direction the clip moves in, in character space, +Y is forward and
+    /// +X is right as in `PlayerAnimationInput`.
+    pub direction: Vec2,
+    /// The speed in m/s that a start gets up to, or that a stop brakes from.
+    pub speed: f32,
+    /// How far the root travels over the clip in meters, as it was authored.
+    /// The clip is assumed to accelerate or brake evenly over that distance.
+    pub distance: f32,
+}
+
+impl StartStopClip {
+    pub fn new(clip: Handle<AnimationClip>, direction: Vec2, speed: f32, distance: f32) -> Self {
+        Self {
+            clip,
+            direction,
+            speed,
+            distance,
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+enum Transition {
+    Start,
+    Stop,
+}
+
+#[derive(Reflect, Clone, Debug)]
+struct ActiveTransition {
+    transition: Transition,
+    clip: usize,
+    /// The horizontal direction of travel in global world space.
+    direction: Vec3,
+    /// Where a start started or where a stop ends, in global world space.
+    anchor: Vec3,
+}
+
+/// Lets a character play start and stop clips. Add to the character root. The
+/// velocity is read from a rapier `Velocity` if there is one, otherwise from
+/// how far the `Transform` moved since last frame.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct StartStop {
+    /// Clips from standing to moving. Usually 4 or 8 directions, the closest
+    /// one to the movement is played, and of those the closest in speed.
+    pub starts: Vec<StartStopClip>,
+    /// Clips from moving to standing, picked the same way.
+    pub stops: Vec<StartStopClip>,
+    /// The acceleration in m/s² from standing at or above which a start is
+    /// played. Slower starts blend into the locomotion as usual.
+    pub start_acceleration: f32,
+    /// How long in seconds after setting off from standing a start can still
+    /// be played, as the smoothed acceleration takes a few frames to build up.
+    pub start_window: f32,
+    /// The deceleration in m/s² at or above which a stop is played.
+    pub stop_deceleration: f32,
+    /// Above this speed in m/s the character sets off from standing, and below
+    /// half of it it's standing again, so noise around it doesn't flicker.
+    pub moving_threshold: f32,
+    /// The time constant in seconds that the velocity is smoothed over.
+    pub smoothing: f32,
+
+    last_position: Option<Vec3>,
+    velocity: Vec3,
+    /// How fast the speed is changing in m/s², negative when slowing down.
+    acceleration: f32,
+    /// How long the character has been moving since it last stood, if it is.
+    moving_secs: Option<f32>,
+    /// Where the controller said the character stops, if it did.
+    stop_target: Option<Vec3>,
+    active: Option<ActiveTransition>,
+}
+
+impl StartStop {
+    pub fn new(starts: Vec<StartStopClip>, stops: Vec<StartStopClip>) -> Self {
+        Self {
+            starts,
+            stops,
+            start_acceleration: 6.0,
+            start_window: 0.25,
+            stop_deceleration: 6.0,
+            moving_threshold: 0.2,
+            smoothing: 0.05,
+            last_position: None,
+            velocity: Vec3::ZERO,
+            acceleration: 0.0,
+            moving_secs: None,
+            stop_target: None,
+            active: None,
+        }
+    }
+
+    /// Tells where the character is going to stop, in global world space, e.g.
+    /// the end of its path. The next stop ends exactly there. Without it, the
+    /// stop point is predicted from the speed and deceleration.
+    pub fn stop_at(&mut self, position: Vec3) {
+        self.stop_target = Some(position);
+    }
+
+    /// The smoothed horizontal speed in m/s.
+    pub fn speed(&self) -> f32 {
+        self.velocity.length()
+    }
+
+    /// Whether a start is playing.
+    pub fn is_starting(&self) -> bool {
+        self.active
+            .as_ref()
+            .is_some_and(|active| active.transition == Transition::Start)
+    }
+
+    /// Whether a stop is playing.
+    pub fn is_stopping(&self) -> bool {
+        self.active
+            .as_ref()
+            .is_some_and(|active| active.transition == Transition::Stop)
+    }
+
+    fn clips(&self, transition: Transition) -> &[StartStopClip] {
+        match transition {
+            Transition::Start => &self.starts,
+            Transition::Stop => &self.stops,
+        }
+    }
+
+    /// Gets the clip that moves closest to `direction`, and of the clips that
+    /// move that way, the one closest to `speed`.
+    fn clip_for(&self, transition: Transition, direction: Vec2, speed: f32) -> Option<usize> {
+        let clips = self.clips(transition);
+        let alignment = |clip: &StartStopClip| clip.direction.normalize_or_zero().dot(direction);
+        let best = clips.iter().map(alignment).reduce(f32::max)?;
+        clips
+            .iter()
+            .enumerate()
+            .filter(|(_, clip)| alignment(clip) >= best - 0.01)
+            .min_by(|(_, a), (_, b)| (a.speed - speed).abs().total_cmp(&(b.speed - speed).abs()))
+            .map(|(index, _)| index)
+    }
+
+    /// Picks the clip for the current movement and where it's anchored.
+    /// `from` is where the character was last frame.
+    fn begin(
+        &mut self,
+        transition: Transition,
+        transform: &GlobalTransform,
+        from: Vec3,
+    ) -> Option<ActiveTransition> {
+        let direction = self.velocity.normalize_or_zero();
+        let speed = self.speed();
+        let local = transform.rotation().inverse() * direction;
+        let clip = self.clip_for(transition, Vec2::new(-local.x, local.z), speed)?;
+        let position = transform.translation();
+        let anchor = match transition {
+            Transition::Start => from,
+            Transition::Stop => match self.stop_target.take() {
+                Some(target) => position + direction * (target - position).dot(direction),
+                // Braking evenly from `speed` covers v² / 2a.
+                None => {
+                    let deceleration = (-self.acceleration).max(f32::EPSILON);
+                    position + direction * speed * speed / (2.0 * deceleration)
+                }
+            },
+        };
+        Some(ActiveTransition {
+            transition,
+            clip,
+            direction,
+            anchor,
+        })
+    }
+
+    /// The normalized time of the active clip with the root at `position`.
+    /// Starts accelerate evenly, so the distance covered grows with the time
+    /// squared, and stops brake evenly, so the distance left shrinks with the
+    /// time left squared.
+    fn normalized_time(&self, active: &ActiveTransition, position: Vec3) -> f32 {
+        let distance = self.clips(active.transition)[active.clip]
+            .distance
+            .max(f32::EPSILON);
+        let along = (position - active.anchor).dot(active.direction);
+        let t = match active.transition {
+            Transition::Start => (along / distance).max(0.0).sqrt(),
+            Transition::Stop => 1.0 - (-along / distance).max(0.0).sqrt(),
+        };
+        // The override wraps the time around, so hold just short of the end.
+        t.clamp(0.0, 0.999)
+    }
+}
+
+fn play_starts_and_stops(
+    mut characters: Query<(Entity, &mut StartStop, &GlobalTransform, Option<&Velocity>)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (root, mut start_stop, transform, physics_velocity) in characters.iter_mut() {
+        let position = transform.translation();
+        let raw_velocity = match (physics_velocity, start_stop.last_position) {
+            (Some(velocity), _) => velocity.linvel,
+            (None, Some(last_position)) => (position - last_position) / dt,
+            (None, None) => Vec3::ZERO,
+        };
+        let from = start_stop.last_position.replace(position).unwrap_or(position);
+        let alpha = 1.0 - (-dt / start_stop.smoothing.max(f32::EPSILON)).exp();
+        let last_speed = start_stop.speed();
+        let was_moving = start_stop.moving_secs.is_some();
+        start_stop.velocity = start_stop.velocity.lerp(raw_velocity.with_y(0.0), alpha);
+        let speed = start_stop.speed();
+        let acceleration = (speed - last_speed) / dt;
+        start_stop.acceleration = start_stop.acceleration.lerp(acceleration, alpha);
+        let threshold = match was_moving {
+            true => start_stop.moving_threshold * 0.5,
+            false => start_stop.moving_threshold,
+        };
+        let moving = speed > threshold;
+        let moving_secs = start_stop.moving_secs.map_or(0.0, |secs| secs + dt);
+        start_stop.moving_secs = moving.then_some(moving_secs);
+
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let busy = state.montage().is_some()
+            || state.scrubbing().is_some()
+            || state.locomotion_override().is_some()
+            || !matches!(
+                state.lower_body_state(),
+                LowerBodyState::Idle
+                    | LowerBodyState::Forward
+                    | LowerBodyState::Back
+                    | LowerBodyState::Left
+                    | LowerBodyState::Right
+            );
+        if busy {
+            start_stop.active = None;
+            continue;
+        }
+
+        start_stop.active = match start_stop.active.take() {
+            // Braking or turning away hands a start back to the locomotion.
+            Some(active) if active.transition == Transition::Start => {
+                let t = start_stop.normalized_time(&active, position);
+                let turned = start_stop.velocity.normalize_or_zero().dot(active.direction) < 0.7;
+                (moving && !turned && start_stop.acceleration > 0.0 && t < 0.999)
+                    .then_some(active)
+            }
+            // Speeding up again cancels a stop, otherwise it holds its last
+            // pose until the character is standing.
+            Some(active) => {
+                let t = start_stop.normalized_time(&active, position);
+                let speeding_up = moving && start_stop.acceleration > 0.0;
+                (!speeding_up && (moving || t < 0.999)).then_some(active)
+            }
+            None if start_stop
+                .moving_secs
+                .is_some_and(|secs| secs <= start_stop.start_window)
+                && start_stop.acceleration >= start_stop.start_acceleration =>
+            {
+                start_stop.begin(Transition::Start, transform, from)
+            }
+            None if moving && -start_stop.acceleration >= start_stop.stop_deceleration => {
+                start_stop.begin(Transition::Stop, transform, from)
+            }
+            None => None,
+        };
+        if !moving && !start_stop.is_stopping() {
+            start_stop.stop_target = None;
+        }
+
+        let Some(active) = start_stop.active.as_ref() else {
+            continue;
+        };
+        let clip = &start_stop.clips(active.transition)[active.clip];
+        let mut clip = LocomotionClip::new(clip.clip.clone(), 1.0);
+        clip.normalized_time = Some(start_stop.normalized_time(active, position));
+        state.override_locomotion(LocomotionOverride {
+            clips: vec![clip],
+            pitch: 0.0,
+            yaw: None,
+            aim_weight: 1.0,
+        });
+    }
+}
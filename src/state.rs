@@ -1,11 +1,23 @@
-use std::f32::consts::FRAC_PI_2;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 
-use bevy::animation::ActiveAnimation;
+use bevy::animation::{ActiveAnimation, RepeatAnimation};
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 
-use crate::anim::{AnimationName, PlayerAnimations, PlayerProceduralAnimationTargets};
+use crate::anim::{
+    AnimationName, PlayerAnimations, PlayerProceduralAnimationTargets, LOWER_BODY_MASK,
+};
+use crate::events::{
+    EventMeta, EventRouting, LandedEvent, LandingKind, NotifyEvent, NotifyWindowEvent,
+    StateChangeEvent,
+};
+use crate::gesture::{ActiveGesture, Gesture};
+use crate::knockback::Ragdolled;
+use crate::montage::{ActiveMontage, Montage, MontageUpdate};
+use crate::netsync::FixedRootMotion;
+use crate::pose_authority::PoseAuthority;
 use crate::utils;
-use crate::Player;
+use crate::character::{Player, RigMap};
 
 pub fn run_player_animations(
     mut states: Query<(
@@ -13,34 +25,148 @@ pub fn run_player_animations(
         &mut PlayerAnimationState,
         &mut AnimationPlayer,
         &AnimationGraphHandle,
+        Option<&PoseAuthority>,
     )>,
     parents: Query<&ChildOf>,
     players: Query<&Player>,
+    ragdolled: Query<(), With<Ragdolled>>,
+    rigs: Query<&RigMap>,
     mut transforms: Query<&mut Transform>,
     global_transforms: Query<&GlobalTransform>,
+    mut fixed_root_motion: Query<&mut FixedRootMotion>,
     mut anim_graphs: ResMut<Assets<AnimationGraph>>,
+    clips: Res<Assets<AnimationClip>>,
+    time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut state_changes: EventWriter<StateChangeEvent>,
+    mut landings: EventWriter<LandedEvent>,
+    mut notifies: EventWriter<NotifyEvent>,
+    mut notify_windows: EventWriter<NotifyWindowEvent>,
 ) {
-    for (entity, mut state, mut player, graph) in states.iter_mut() {
+    for (entity, mut state, mut player, graph, authority) in states.iter_mut() {
         let Some(graph) = anim_graphs.get_mut(graph) else {
             continue;
         };
-        state.transition(&player);
-        state.update_player(&mut player, graph);
         let Some((root_entity, _)) = utils::find_upwards(entity, &parents, &players) else {
             error!("player animation state not attached to child of player");
             continue;
         };
+
+        if let Some(normalized_time) = state.scrubbing {
+            state.hold_animations(&mut player);
+            scrub_animations(&mut player, graph, &clips, normalized_time);
+            state.consume_input();
+            continue;
+        }
+        if authority.is_some_and(PoseAuthority::is_external) || ragdolled.contains(root_entity) {
+            // Hold the pose until the skeleton is handed back, or the ragdoll
+            // gets back up.
+            state.hold_animations(&mut player);
+            state.consume_input();
+            continue;
+        }
+        // Scrubbing, an external pose or a ragdoll may just have stopped.
+        state.release_animations(&mut player);
+
+        let position = global_transforms
+            .get(root_entity)
+            .map(GlobalTransform::translation)
+            .unwrap_or_default();
+        let delta_secs = time.delta_secs() * state.time_scale;
+        let from = state.lower_body;
+        state.transition(&player, delta_secs);
+        if from != state.lower_body && routing.emits::<StateChangeEvent>() {
+            state_changes.write(StateChangeEvent {
+                meta: EventMeta::new(root_entity, &time, position),
+                from,
+                to: state.lower_body,
+            });
+        }
+        if from == LowerBodyState::Falling && state.lower_body == LowerBodyState::Land {
+            if let (Some((kind, impact_speed)), true) =
+                (state.landing, routing.emits::<LandedEvent>())
+            {
+                landings.write(LandedEvent {
+                    meta: EventMeta::new(root_entity, &time, position),
+                    kind,
+                    impact_speed,
+                });
+            }
+        }
+
+        state.update_upper_body(&mut player, graph, &clips, delta_secs);
+        let montage = state.update_montage(
+            root_entity,
+            &mut player,
+            graph,
+            &clips,
+            &transforms,
+            &global_transforms,
+            rigs.get(root_entity).ok(),
+        );
+        // Servers move the root in `FixedUpdate` instead.
+        if let Ok(mut fixed) = fixed_root_motion.get_mut(root_entity) {
+            fixed.pending += montage.root_motion;
+        } else if let Ok(mut root) = transforms.get_mut(root_entity) {
+            root.translation += montage.root_motion;
+        }
+        let locomotion = state.locomotion_override.take();
+        match locomotion {
+            _ if montage.playing => state.fade_out_locomotion_override(&mut player, &[]),
+            Some(ref locomotion) if !locomotion.clips.is_empty() => {
+                state.update_locomotion_override(&mut player, graph, &clips, locomotion)
+            }
+            _ => {
+                state.fade_out_locomotion_override(&mut player, &[]);
+                state.update_player(&mut player, graph);
+            }
+        }
+        state.locomotion_override = locomotion;
+        let meta = EventMeta::new(root_entity, &time, position);
+        if routing.emits::<NotifyEvent>() {
+            notifies.write_batch(
+                montage
+                    .notifies
+                    .into_iter()
+                    .map(|name| NotifyEvent { meta, name }),
+            );
+        }
+        if routing.emits::<NotifyWindowEvent>() {
+            notify_windows.write_batch(montage.windows.into_iter().map(|(name, open)| {
+                NotifyWindowEvent { meta, name, open }
+            }));
+        }
         state.update_transforms(root_entity, &mut transforms, &global_transforms, &player);
-        state.input = None;
+        state.consume_input();
+    }
+}
+
+/// Clears the input and the locomotion override once a step has read them, so
+/// characters nothing sets them for hold still. Until then they're kept, so
+/// every step of a state machine that runs in `FixedUpdate` reads them, and a
+/// frame without steps doesn't lose them.
+pub fn clear_consumed_input(mut states: Query<&mut PlayerAnimationState>) {
+    for mut state in states.iter_mut() {
+        if state.input_consumed {
+            state.input = None;
+            state.locomotion_override = None;
+            state.input_consumed = false;
+        }
     }
 }
 
 /// An authoritative input that changes the animation. This should be valid, e.g.
 /// sending is_sprinting with !is_grounded could have weird animation effects if
 /// you can't sprint while airborne.
-#[derive(Default)]
+///
+/// Characters face +Z with +Y up, so their right is -X. Yawing right and
+/// pitching up are negative rotations about Y and X, and rolling right is a
+/// positive rotation about Z. The look is such a yaw and pitch, so `look_y`
+/// grows turning left and `look_x` grows looking down.
+#[derive(Reflect, Default, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct PlayerAnimationInput {
-    /// +Y is forward
+    /// +Y is forward and +X is right.
     pub local_movement_direction: Vec2,
     pub is_sprinting: bool,
     pub look_y: f32,
@@ -48,13 +174,28 @@ pub struct PlayerAnimationInput {
 
     pub just_jumped: bool,
     pub is_grounded: bool,
+
+    /// How cautiously to move in [0, 1], e.g. when near a ledge or wall. This
+    /// slows down locomotion and prevents sprinting.
+    pub caution: f32,
+
+    /// The vertical speed of the character, negative when falling. Used to pick
+    /// how to land.
+    pub vertical_speed: f32,
+    /// Whether the player wants to roll, e.g. the roll button was pressed. A
+    /// roll is done on landing if this was set shortly before touching down.
+    pub wants_roll: bool,
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component, from_reflect = false)]
 pub struct PlayerAnimationState {
+    #[reflect(ignore)]
     anims: PlayerAnimations,
     lower_body: LowerBodyState,
     input: Option<PlayerAnimationInput>,
+    /// Whether a step has read the input since it was set.
+    input_consumed: bool,
     pub proc_targets: PlayerProceduralAnimationTargets,
 
     lower_body_y: f32,
@@ -62,10 +203,63 @@ pub struct PlayerAnimationState {
     upper_body_y: f32,
 
     is_sprinting: bool,
+    caution: f32,
+    /// The normalized time that animations are paused at, if scrubbing.
+    scrubbing: Option<f32>,
+    /// The fastest the player has fallen since leaving the ground.
+    fall_speed: f32,
+    /// Seconds since a roll was requested while falling.
+    roll_requested: Option<f32>,
+    /// How the player last landed, and the speed they hit the ground at.
+    landing: Option<(LandingKind, f32)>,
+    /// Seconds left until the player recovers from a hard landing.
+    land_recovery: f32,
+    #[reflect(ignore)]
+    montage: Option<ActiveMontage>,
+    /// Montages stopped early, which are cleaned up on the next update.
+    #[reflect(ignore)]
+    interrupted_montages: Vec<ActiveMontage>,
+    /// Graph nodes for montage and locomotion override clips, added the first
+    /// time each clip is played.
+    #[reflect(ignore)]
+    full_body_nodes: HashMap<AssetId<AnimationClip>, AnimationNodeIndex>,
+    /// Replaces the locomotion state machine until a step has read it, if set.
+    #[reflect(ignore)]
+    locomotion_override: Option<LocomotionOverride>,
+    /// The nodes played by locomotion overrides that haven't faded out yet.
+    #[reflect(ignore)]
+    override_nodes: Vec<AnimationNodeIndex>,
+    /// The gesture being played last, after any that are still blending out.
+    #[reflect(ignore)]
+    gestures: Vec<ActiveGesture>,
+    /// Graph nodes for gesture and pose clips, added the first time each clip
+    /// is played.
+    #[reflect(ignore)]
+    upper_body_nodes: HashMap<AssetId<AnimationClip>, AnimationNodeIndex>,
+    /// The clip that replaces the upper body idle, e.g. a carry pose.
+    #[reflect(ignore)]
+    upper_body_pose: Option<Handle<AnimationClip>>,
+    /// The nodes of the current and previous poses and their blend weights.
+    #[reflect(ignore)]
+    upper_body_poses: Vec<(AnimationNodeIndex, f32)>,
+    /// How fast this character animates relative to the game.
+    time_scale: f32,
+    /// The speeds of the playing animations before the time scale was applied
+    /// for this frame's advance.
+    #[reflect(ignore)]
+    unscaled_speeds: Vec<(AnimationNodeIndex, f32)>,
+    /// The animations that were playing when scrubbing, an external pose or a
+    /// ragdoll paused them, which are resumed once it stops. Animations paused
+    /// before then stay paused.
+    #[reflect(ignore)]
+    held_nodes: Option<Vec<AnimationNodeIndex>>,
     nodes: AnimationNodes,
     config: AnimationStateConfig,
 }
 
+#[derive(Reflect)]
+#[reflect(from_reflect = false)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnimationStateConfig {
     /// The rate that's used to blend between animations. Must be in (0, 1].
     pub blend_rate: f32,
@@ -76,6 +270,11 @@ pub struct AnimationStateConfig {
     /// point the bullet point forward. This is used when blending from sprinting
     /// (where the spine is controlled fully by the animation) and a normal pose
     /// (where the spine is controlled fully by the input). 
+    #[reflect(ignore)]
+    #[cfg_attr(
+        feature = "serialize",
+        serde(skip, default = "default_sprint_reaim_max_angle")
+    )]
     pub sprint_reaim_max_angle: fn(Option<&ActiveAnimation>) -> f32,
     /// The elapsed time for which the landing animation is in the impact phase
     /// and can be cancelled to play other animations.
@@ -89,6 +288,66 @@ pub struct AnimationStateConfig {
     /// The max angle per frame to rotate from the player's look position (which 
     /// is done by rotating the spine) to the sprint animation spine position.
     pub spine1_into_sprint_max_angle: f32,
+    /// The lower body animation speed multiplier when fully cautious.
+    pub cautious_speed: f32,
+    /// The caution above which the player is not allowed to sprint.
+    pub cautious_sprint_threshold: f32,
+    /// The fall speed at or above which landing is hard and needs recovery.
+    pub hard_land_speed: f32,
+    /// Seconds after a hard landing before the player can move again.
+    pub hard_land_recovery: f32,
+    /// How many seconds before touching down a roll can be requested.
+    pub roll_input_window: f32,
+    /// The montage played when rolling on landing. If none, the player never
+    /// rolls.
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub landing_roll: Option<Montage>,
+    /// Seconds to blend between upper body poses.
+    pub pose_blend_secs: f32,
+    /// Added to the aim in radians, x is yaw to the right and y is pitch up,
+    /// for upper body poses that hold the weapon off the bullet point's axis.
+    pub aim_offset: Vec2,
+    /// Which way the body turns while moving.
+    pub facing: FacingMode,
+    /// How far in radians the upper body twists from the lower body to aim
+    /// while moving, e.g. when running away from the look direction with
+    /// [`FacingMode::OrientToMovement`].
+    pub max_upper_body_yaw: f32,
+    /// How much longer the character's strides are than those of the body the
+    /// animations were made for. Root motion is scaled by it, and the
+    /// locomotion slowed to match so the feet don't slide.
+    pub stride_scale: f32,
+}
+
+/// How the body faces while moving. The upper body twists to aim at the look
+/// direction as far as `max_upper_body_yaw` allows, and standing still the
+/// body turns in place towards it once it's past `stationary_turn_threshold`.
+#[derive(Reflect, Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum FacingMode {
+    /// Faces the look direction and strafes, e.g. for shooters.
+    #[default]
+    OrientToAim,
+    /// Faces where it's going and runs forward, e.g. for exploration.
+    OrientToMovement,
+    /// Faces where it's going while that's within `max_angle` radians of the
+    /// look direction, and faces the look direction and strafes or backpedals
+    /// beyond it.
+    Hybrid { max_angle: f32 },
+}
+
+impl FacingMode {
+    /// The yaw the body turns to while moving in `local_direction`, relative to
+    /// the look yaw `look_y` as in `PlayerAnimationInput`.
+    pub fn body_yaw(self, look_y: f32, local_direction: Vec2) -> f32 {
+        let offset = (-local_direction.x).atan2(local_direction.y);
+        match self {
+            Self::OrientToAim => look_y,
+            Self::OrientToMovement => look_y + offset,
+            Self::Hybrid { max_angle } if offset.abs() <= max_angle => look_y + offset,
+            Self::Hybrid { .. } => look_y,
+        }
+    }
 }
 
 fn sprint_reaim_max_angle(anim: Option<&ActiveAnimation>) -> f32 {
@@ -99,6 +358,11 @@ fn sprint_reaim_max_angle(anim: Option<&ActiveAnimation>) -> f32 {
     }
 }
 
+#[cfg(feature = "serialize")]
+fn default_sprint_reaim_max_angle() -> fn(Option<&ActiveAnimation>) -> f32 {
+    sprint_reaim_max_angle
+}
+
 impl Default for AnimationStateConfig {
     fn default() -> Self {
         Self {
@@ -109,13 +373,67 @@ impl Default for AnimationStateConfig {
             stationary_turn_threshold: 45f32.to_radians(),
             stationary_turn_lerp_speed: 0.05,
             spine1_into_sprint_max_angle: 0.01,
+            cautious_speed: 0.6,
+            cautious_sprint_threshold: 0.5,
+            hard_land_speed: 10.0,
+            hard_land_recovery: 0.6,
+            roll_input_window: 0.3,
+            landing_roll: None,
+            pose_blend_secs: 0.3,
+            aim_offset: Vec2::ZERO,
+            facing: FacingMode::OrientToAim,
+            max_upper_body_yaw: 90f32.to_radians(),
+            stride_scale: 1.0,
         }
     }
 }
 
+/// Full body clips that replace the locomotion state machine for a frame, e.g.
+/// to swim or climb. The state machine keeps transitioning underneath, so it
+/// picks up where it should when the override stops.
+///
+/// Without clips the state machine keeps animating, and only the pitch, yaw and
+/// aim are overridden.
+#[derive(Clone, Debug, Default)]
+pub struct LocomotionOverride {
+    pub clips: Vec<LocomotionClip>,
+    /// The pitch of the whole body in radians, positive is nose up.
+    pub pitch: f32,
+    /// The yaw to face instead of the look direction, e.g. to face a ladder.
+    pub yaw: Option<f32>,
+    /// How much the spine aims in the look direction, in [0, 1]. At 0 the
+    /// clips have full control of the spine.
+    pub aim_weight: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct LocomotionClip {
+    pub clip: Handle<AnimationClip>,
+    /// The blend weight relative to the other clips of the override.
+    pub weight: f32,
+    pub speed: f32,
+    /// The normalized time to hold the clip at instead of playing it, e.g. to
+    /// keep a climbing loop in step with how far up a ladder the character is.
+    pub normalized_time: Option<f32>,
+}
+
+impl LocomotionClip {
+    pub fn new(clip: Handle<AnimationClip>, weight: f32) -> Self {
+        Self {
+            clip,
+            weight,
+            speed: 1.0,
+            normalized_time: None,
+        }
+    }
+}
+
+#[derive(Reflect)]
 pub struct AnimationNodes {
     /// The node that adds upper and lower body anims.
     pub upper_lower_add: AnimationNodeIndex,
+    /// The node that blends upper body anims, i.e. the idle and gestures.
+    pub upper_body: AnimationNodeIndex,
     /// The node that blends between `upper_lower_add` and full body animations.
     pub full_body: AnimationNodeIndex,
 }
@@ -130,12 +448,31 @@ impl PlayerAnimationState {
             anims,
             lower_body: LowerBodyState::Idle,
             input: None,
+            input_consumed: false,
             proc_targets,
 
             lower_body_y: 0.0,
             lower_body_target_y: 0.0,
             upper_body_y: 0.0,
             is_sprinting: false,
+            caution: 0.0,
+            scrubbing: None,
+            fall_speed: 0.0,
+            roll_requested: None,
+            landing: None,
+            land_recovery: 0.0,
+            montage: None,
+            interrupted_montages: Vec::new(),
+            full_body_nodes: HashMap::default(),
+            locomotion_override: None,
+            override_nodes: Vec::new(),
+            gestures: Vec::new(),
+            upper_body_nodes: HashMap::default(),
+            upper_body_pose: None,
+            upper_body_poses: Vec::new(),
+            time_scale: 1.0,
+            unscaled_speeds: Vec::new(),
+            held_nodes: None,
             nodes,
             config: AnimationStateConfig::default(),
         }
@@ -143,14 +480,223 @@ impl PlayerAnimationState {
 }
 
 impl PlayerAnimationState {
-    pub fn set_input(&mut self, input: PlayerAnimationInput) {
+    /// Sets the input that the state machine steps with until a step has
+    /// read it and it's cleared in the next `Update`. Jumps and rolls from an
+    /// input no step has read yet are carried over, so they aren't dropped.
+    pub fn set_input(&mut self, mut input: PlayerAnimationInput) {
+        if let (Some(pending), false) = (&self.input, self.input_consumed) {
+            input.just_jumped |= pending.just_jumped;
+            input.wants_roll |= pending.wants_roll;
+        }
         self.input = Some(input);
+        self.input_consumed = false;
+    }
+
+    /// Marks the input as read by a step. Later steps keep reading it, but
+    /// only the first acts on its jumps and rolls.
+    fn consume_input(&mut self) {
+        if let Some(input) = self.input.as_mut() {
+            input.just_jumped = false;
+            input.wants_roll = false;
+        }
+        self.input_consumed = true;
+    }
+
+    pub fn lower_body_state(&self) -> LowerBodyState {
+        self.lower_body
+    }
+
+    pub fn is_sprinting(&self) -> bool {
+        self.is_sprinting
+    }
+
+    pub fn anims(&self) -> &PlayerAnimations {
+        &self.anims
+    }
+
+    pub fn nodes(&self) -> &AnimationNodes {
+        &self.nodes
+    }
+
+    pub fn config_mut(&mut self) -> &mut AnimationStateConfig {
+        &mut self.config
+    }
+
+    /// Scales how fast this character animates, e.g. 0 for a frozen enemy or
+    /// 2 for a hasted one. Applies to the state machine, montages and their
+    /// root motion, gestures, and the tracers fired from its weapons.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
     }
 
-    pub fn transition(&mut self, player: &AnimationPlayer) {
+    /// Pauses the animations and seeks every playing clip to `normalized_time`
+    /// in [0, 1] of its duration. Input is ignored until [`Self::stop_scrubbing`].
+    pub fn scrub(&mut self, normalized_time: f32) {
+        self.scrubbing = Some(normalized_time.clamp(0.0, 1.0));
+    }
+
+    pub fn stop_scrubbing(&mut self) {
+        self.scrubbing = None;
+    }
+
+    pub fn scrubbing(&self) -> Option<f32> {
+        self.scrubbing
+    }
+
+    /// Pauses every animation, remembering which were playing the first time.
+    fn hold_animations(&mut self, player: &mut AnimationPlayer) {
+        if self.held_nodes.is_none() {
+            let playing = player
+                .playing_animations()
+                .filter(|(_, anim)| !anim.is_paused())
+                .map(|(index, _)| *index)
+                .collect();
+            self.held_nodes = Some(playing);
+        }
+        player.pause_all();
+    }
+
+    /// Resumes the animations paused by [`Self::hold_animations`], if any.
+    fn release_animations(&mut self, player: &mut AnimationPlayer) {
+        for index in self.held_nodes.take().into_iter().flatten() {
+            if let Some(anim) = player.animation_mut(index) {
+                anim.resume();
+            }
+        }
+    }
+
+    /// Plays a montage, replacing the current one if any.
+    pub fn play_montage(&mut self, montage: Montage) {
+        self.stop_montage();
+        self.montage = Some(ActiveMontage::new(montage));
+    }
+
+    pub fn stop_montage(&mut self) {
+        if let Some(active) = self.montage.take() {
+            self.interrupted_montages.push(active);
+        }
+    }
+
+    /// Sets the point in global world space that the target match during the
+    /// window named `window` of the current montage reaches for.
+    pub fn set_match_target(&mut self, window: &'static str, target: Vec3) {
+        let Some(active) = self.montage.as_mut() else {
+            return;
+        };
+        active.match_targets.retain(|(name, _)| *name != window);
+        active.match_targets.push((window, target));
+    }
+
+    /// Jumps the current montage to `secs` into its clip, e.g. when a timeline
+    /// is scrubbed. The root motion, notifies and windows in between are
+    /// skipped.
+    pub fn seek_montage(&mut self, secs: f32) {
+        if let Some(active) = self.montage.as_mut() {
+            active.seek = Some(secs.max(0.0));
+        }
+    }
+
+    /// Whether the current montage has a window named `name` that is open.
+    pub fn montage_window_open(&self, name: &str) -> bool {
+        self.montage
+            .as_ref()
+            .is_some_and(|active| active.window_open(name))
+    }
+
+    pub fn montage(&self) -> Option<&Montage> {
+        self.montage.as_ref().map(|active| &active.montage)
+    }
+
+    /// Plays a gesture over the upper body, blending out the current one if any.
+    pub fn play_gesture(&mut self, gesture: Gesture) {
+        self.stop_gesture();
+        self.gestures.push(ActiveGesture::new(gesture));
+    }
+
+    /// Blends out the current gesture.
+    pub fn stop_gesture(&mut self) {
+        for active in self.gestures.iter_mut() {
+            active.stopping = true;
+        }
+    }
+
+    /// The gesture being played, unless it's already blending out.
+    pub fn gesture(&self) -> Option<&Gesture> {
+        self.gestures
+            .last()
+            .filter(|active| !active.stopping)
+            .map(|active| &active.gesture)
+    }
+
+    /// How much gestures replace the upper body idle, in [0, 1].
+    fn gesture_weight(&self) -> f32 {
+        self.gestures
+            .iter()
+            .map(|active| active.weight)
+            .sum::<f32>()
+            .min(1.0)
+    }
+
+    /// Replaces the upper body idle with a looping pose, e.g. for carrying, or
+    /// goes back to the idle if none. Gestures still play over the pose.
+    pub fn set_upper_body_pose(&mut self, pose: Option<Handle<AnimationClip>>) {
+        self.upper_body_pose = pose;
+    }
+
+    pub fn upper_body_pose(&self) -> Option<&Handle<AnimationClip>> {
+        self.upper_body_pose.as_ref()
+    }
+
+    /// How much poses replace the upper body idle, in [0, 1].
+    fn upper_body_pose_weight(&self) -> f32 {
+        self.upper_body_poses
+            .iter()
+            .map(|(_, weight)| weight)
+            .sum::<f32>()
+            .min(1.0)
+    }
+
+    /// How much the spine aims in the look direction given the gestures playing.
+    fn gesture_aim_weight(&self) -> f32 {
+        self.gestures
+            .iter()
+            .map(|active| 1.0 - active.weight * (1.0 - active.gesture.aim_weight))
+            .product()
+    }
+
+    /// Replaces the locomotion state machine until the next `Update` after a
+    /// step, like the input. Montages still play over the override.
+    pub fn override_locomotion(&mut self, locomotion: LocomotionOverride) {
+        self.locomotion_override = Some(locomotion);
+    }
+
+    pub fn locomotion_override(&self) -> Option<&LocomotionOverride> {
+        self.locomotion_override.as_ref()
+    }
+
+    /// How the player last landed, and the speed they hit the ground at.
+    pub fn landing(&self) -> Option<(LandingKind, f32)> {
+        self.landing
+    }
+
+    /// The input for this frame, if it has been set yet.
+    pub fn input(&self) -> Option<&PlayerAnimationInput> {
+        self.input.as_ref()
+    }
+
+    pub fn input_mut(&mut self) -> Option<&mut PlayerAnimationInput> {
+        self.input.as_mut()
+    }
+
+    pub fn transition(&mut self, player: &AnimationPlayer, delta_secs: f32) {
         let Some(ref input) = self.input else {
             return;
         };
+        self.land_recovery = (self.land_recovery - delta_secs).max(0.0);
         let is_finished = |anim| {
             player
                 .animation(anim)
@@ -158,10 +704,12 @@ impl PlayerAnimationState {
                 .unwrap_or(true)
         };
 
-        self.is_sprinting = input.is_sprinting;
+        self.caution = input.caution.clamp(0.0, 1.0);
+        self.is_sprinting =
+            input.is_sprinting && self.caution < self.config.cautious_sprint_threshold;
         self.lower_body = match self.lower_body {
             LowerBodyState::Land => {
-                if is_finished(self.anims.get(AnimationName::Land)) {
+                if is_finished(self.anims.get(AnimationName::Land)) && self.land_recovery <= 0.0 {
                     LowerBodyState::Idle
                 } else {
                     LowerBodyState::Land
@@ -172,7 +720,14 @@ impl PlayerAnimationState {
                 LowerBodyState::Falling
             }
             LowerBodyState::Falling => {
+                self.fall_speed = self.fall_speed.max(-input.vertical_speed);
+                self.roll_requested = match self.roll_requested {
+                    _ if input.wants_roll => Some(0.0),
+                    Some(age) => Some(age + delta_secs),
+                    None => None,
+                };
                 if input.is_grounded {
+                    self.land();
                     LowerBodyState::Land
                 } else {
                     LowerBodyState::Falling
@@ -187,13 +742,57 @@ impl PlayerAnimationState {
                     LowerBodyState::Left,
                     LowerBodyState::Right,
                 ],
-                input.local_movement_direction,
+                self.body_movement_direction(input),
             ),
         };
     }
 
+    /// The movement input relative to where the body faces rather than where
+    /// it looks, which is what the locomotion blends by.
+    fn body_movement_direction(&self, input: &PlayerAnimationInput) -> Vec2 {
+        if self.config.facing == FacingMode::OrientToAim {
+            return input.local_movement_direction;
+        }
+        // Rotated about Y by the yaw from the body to the look direction, with
+        // the input's +X being the character's -X.
+        let yaw = input.look_y - self.lower_body_y;
+        let (sin, cos) = yaw.sin_cos();
+        let direction = input.local_movement_direction;
+        Vec2::new(
+            direction.x * cos - direction.y * sin,
+            direction.y * cos + direction.x * sin,
+        )
+    }
+
+    /// Picks how to land based on the fall speed and whether a roll was
+    /// requested just before touching down.
+    fn land(&mut self) {
+        let roll_in_time = self
+            .roll_requested
+            .is_some_and(|age| age <= self.config.roll_input_window);
+        let kind = match self.config.landing_roll {
+            Some(ref roll) if roll_in_time => {
+                self.play_montage(roll.clone());
+                LandingKind::Roll
+            }
+            _ if self.fall_speed >= self.config.hard_land_speed => {
+                self.land_recovery = self.config.hard_land_recovery;
+                LandingKind::Hard
+            }
+            _ => LandingKind::Light,
+        };
+        self.landing = Some((kind, self.fall_speed));
+        self.fall_speed = 0.0;
+        self.roll_requested = None;
+    }
+
     fn get_lower_body_anim_from_state(&self) -> AnimationNodeIndex {
-        match self.lower_body {
+        self.lower_body_anim(self.lower_body)
+    }
+
+    /// Gets the animation that is played for a lower body state.
+    pub fn lower_body_anim(&self, state: LowerBodyState) -> AnimationNodeIndex {
+        match state {
             LowerBodyState::Idle => self.anims.get(AnimationName::IdleLowerBody),
             LowerBodyState::Forward => self.anims.get(AnimationName::Forward),
             LowerBodyState::Back => self.anims.get(AnimationName::Back),
@@ -219,7 +818,8 @@ impl PlayerAnimationState {
             && player
                 .animation(self.anims.get(AnimationName::Land))
                 .is_none();
-        let not_just_landed = self.lower_body != LowerBodyState::Land || mostly_landed;
+        let not_just_landed = self.lower_body != LowerBodyState::Land
+            || (mostly_landed && self.land_recovery <= 0.0);
 
         // This statement should read: play the sprinting animation if we are sprinting,
         // haven't just landed, and aren't about to start the landing animation.
@@ -254,6 +854,238 @@ impl PlayerAnimationState {
         upper_lower_add.weight = (upper_lower_add.weight / rate).clamp(threshold, 1.0);
     }
 
+    /// Plays the gestures and poses on the upper body node, blending them in and
+    /// out. Gestures play over the poses.
+    fn update_upper_body(
+        &mut self,
+        player: &mut AnimationPlayer,
+        graph: &mut AnimationGraph,
+        clips: &Assets<AnimationClip>,
+        delta_secs: f32,
+    ) {
+        let upper_body = self.nodes.upper_body;
+        self.gestures.retain_mut(|active| {
+            let node = *self
+                .upper_body_nodes
+                .entry(active.gesture.clip.id())
+                .or_insert_with(|| {
+                    graph.add_clip_with_mask(
+                        active.gesture.clip.clone(),
+                        LOWER_BODY_MASK,
+                        1.0,
+                        upper_body,
+                    )
+                });
+            // Wait for the clip to load, unless it was stopped before it started.
+            let Some(duration) = utils::clip_duration(graph, node, clips) else {
+                return !active.stopping;
+            };
+
+            if active.node.is_none() {
+                player
+                    .start(node)
+                    .set_speed(active.gesture.speed)
+                    .set_repeat(RepeatAnimation::Never);
+                active.node = Some(node);
+            }
+            let Some(anim) = player.animation_mut(node) else {
+                return false;
+            };
+            let remaining_secs =
+                (duration - anim.seek_time()).max(0.0) / active.gesture.speed.max(f32::EPSILON);
+            let done = active.blend(remaining_secs, delta_secs);
+            anim.set_weight(active.weight);
+            if done {
+                player.stop(node);
+            }
+            !done
+        });
+
+        let target = self.upper_body_pose.as_ref().map(|pose| {
+            *self.upper_body_nodes.entry(pose.id()).or_insert_with(|| {
+                graph.add_clip_with_mask(pose.clone(), LOWER_BODY_MASK, 1.0, upper_body)
+            })
+        });
+        if let Some(target) = target {
+            if !self.upper_body_poses.iter().any(|(node, _)| *node == target) {
+                self.upper_body_poses.push((target, 0.0));
+            }
+        }
+        let step = delta_secs / self.config.pose_blend_secs.max(f32::EPSILON);
+        let gesture_weight = self.gesture_weight();
+        self.upper_body_poses.retain_mut(|(node, weight)| {
+            let fading_in = Some(*node) == target;
+            *weight = if fading_in {
+                (*weight + step).min(1.0)
+            } else {
+                (*weight - step).max(0.0)
+            };
+            if *weight <= 0.0 && !fading_in {
+                player.stop(*node);
+                return false;
+            }
+            player
+                .play(*node)
+                .repeat()
+                .set_weight(*weight * (1.0 - gesture_weight));
+            true
+        });
+    }
+
+    /// Plays the current montage as a full body animation and works out its
+    /// root motion. When no montage is playing the state machine drives the
+    /// animation instead.
+    fn update_montage(
+        &mut self,
+        root_entity: Entity,
+        player: &mut AnimationPlayer,
+        graph: &mut AnimationGraph,
+        clips: &Assets<AnimationClip>,
+        transforms: &Query<&mut Transform>,
+        global_transforms: &Query<&GlobalTransform>,
+        rig: Option<&RigMap>,
+    ) -> MontageUpdate {
+        let mut update = MontageUpdate::default();
+        for interrupted in self.interrupted_montages.drain(..) {
+            interrupted.interrupt(&mut update);
+            if let Some(node) = interrupted.node {
+                player.stop(node);
+            }
+        }
+
+        let Some(active) = self.montage.as_mut() else {
+            return update;
+        };
+        let full_body = self.nodes.full_body;
+        let node = *self
+            .full_body_nodes
+            .entry(active.montage.clip.id())
+            .or_insert_with(|| graph.add_clip(active.montage.clip.clone(), 1.0, full_body));
+        // Wait for the clip to load before starting so no root motion is lost.
+        let Some(duration) = utils::clip_duration(graph, node, clips) else {
+            return update;
+        };
+        update.playing = true;
+
+        if active.node.is_none() {
+            player
+                .start(node)
+                .set_speed(active.montage.speed)
+                .set_repeat(RepeatAnimation::Never);
+            active.node = Some(node);
+        }
+        let Some(anim) = player.animation_mut(node) else {
+            active.interrupt(&mut update);
+            self.montage = None;
+            return update;
+        };
+        anim.set_weight(1.0);
+        if let Some(seek) = active.seek.take() {
+            anim.seek_to(seek);
+            active.progress = Some((seek / duration).clamp(0.0, 1.0));
+        }
+        let finished = anim.is_finished();
+        let progress = (anim.seek_time() / duration).clamp(0.0, 1.0);
+        let progress = if finished { 1.0 } else { progress };
+
+        let last = active.progress.unwrap_or(0.0);
+        let root_motion = active.advance(progress, &mut update);
+        if let Ok(root) = transforms.get(root_entity) {
+            let root_motion = root.rotation * root_motion * self.config.stride_scale;
+            update.root_motion += root_motion;
+
+            // Bone transforms are from last frame, so account for the root motion
+            // that was just applied.
+            for (bone, target, fraction) in active.target_match_steps(last, progress) {
+                let Some(bone) = rig.and_then(|rig| rig.get(bone)) else {
+                    continue;
+                };
+                let Ok(bone_global) = global_transforms.get(bone) else {
+                    continue;
+                };
+                let bone_position = bone_global.translation() + root_motion;
+                update.root_motion += (target - bone_position) * fraction;
+            }
+        }
+
+        if finished && !active.montage.hold {
+            player.stop(node);
+            self.montage = None;
+        }
+
+        // Montages replace sprinting, which shares the full body node.
+        let sprint = self.anims.get(AnimationName::Sprint);
+        if player.is_playing_animation(sprint) {
+            let rate = self.config.blend_rate;
+            let threshold = self.config.blend_threshold;
+            fade_out_animations(player, vec![sprint], rate, threshold);
+        }
+        self.fade_in_full_body(graph);
+        update
+    }
+
+    /// Plays the clips of a locomotion override on the full body node.
+    fn update_locomotion_override(
+        &mut self,
+        player: &mut AnimationPlayer,
+        graph: &mut AnimationGraph,
+        clips: &Assets<AnimationClip>,
+        locomotion: &LocomotionOverride,
+    ) {
+        let full_body = self.nodes.full_body;
+        let mut playing = Vec::with_capacity(locomotion.clips.len());
+        for clip in locomotion.clips.iter() {
+            let node = *self
+                .full_body_nodes
+                .entry(clip.clip.id())
+                .or_insert_with(|| graph.add_clip(clip.clip.clone(), 1.0, full_body));
+            let anim = player.play(node).repeat().set_weight(clip.weight);
+            match clip.normalized_time {
+                Some(normalized_time) => {
+                    anim.set_speed(0.0);
+                    if let Some(duration) = utils::clip_duration(graph, node, clips) {
+                        anim.seek_to(normalized_time.rem_euclid(1.0) * duration);
+                    }
+                }
+                None => {
+                    anim.set_speed(clip.speed);
+                }
+            }
+            if !self.override_nodes.contains(&node) {
+                self.override_nodes.push(node);
+            }
+            playing.push(node);
+        }
+        self.fade_out_locomotion_override(player, &playing);
+
+        let sprint = self.anims.get(AnimationName::Sprint);
+        if player.is_playing_animation(sprint) {
+            let rate = self.config.blend_rate;
+            let threshold = self.config.blend_threshold;
+            fade_out_animations(player, vec![sprint], rate, threshold);
+        }
+        self.fade_in_full_body(graph);
+    }
+
+    /// Fades out the override clips that aren't in `keep`.
+    fn fade_out_locomotion_override(
+        &mut self,
+        player: &mut AnimationPlayer,
+        keep: &[AnimationNodeIndex],
+    ) {
+        let rate = self.config.blend_rate;
+        let threshold = self.config.blend_threshold;
+        let fading = self
+            .override_nodes
+            .iter()
+            .copied()
+            .filter(|node| !keep.contains(node) && player.is_playing_animation(*node))
+            .collect();
+        fade_out_animations(player, fading, rate, threshold);
+        self.override_nodes
+            .retain(|node| player.is_playing_animation(*node));
+    }
+
     pub fn update_player(&self, player: &mut AnimationPlayer, graph: &mut AnimationGraph) {
         let rate = self.config.blend_rate;
         let threshold = self.config.blend_threshold;
@@ -273,19 +1105,27 @@ impl PlayerAnimationState {
 
         // Only fade lower body animations excluding the one that's being faded in.
         let filter = |(ix, _): (&AnimationNodeIndex, &ActiveAnimation)| {
-            (self.anims.get_name(*ix).is_lower_body() && *ix != target_lower_body_anim)
-                .then_some(*ix)
+            // Gestures aren't part of the state machine.
+            let is_lower_body = self.anims.find_name(*ix).is_some_and(|n| n.is_lower_body());
+            (is_lower_body && *ix != target_lower_body_anim).then_some(*ix)
         };
         let animations_to_fade = player.playing_animations().filter_map(filter).collect();
 
         fade_out_animations(player, animations_to_fade, rate, threshold);
         let lower_body_anim =
             fade_in_animation(player, target_lower_body_anim, 1.0 / rate, threshold);
-        self.anims
+        let lower_body_anim = self
+            .anims
             .apply_defaults(target_lower_body_anim, lower_body_anim);
+        // Near ledges and walls the locomotion slows into a cautious walk.
+        let cautious_speed = 1f32.lerp(self.config.cautious_speed, self.caution);
+        let stride_scale = self.config.stride_scale.max(f32::EPSILON);
+        lower_body_anim.set_speed(lower_body_anim.speed() * cautious_speed / stride_scale);
 
         let target_upper_body_anim = self.anims.get(AnimationName::IdleUpperBody);
-        let active_anim = player.play(target_upper_body_anim).set_weight(1.0);
+        let active_anim = player
+            .play(target_upper_body_anim)
+            .set_weight((1.0 - self.gesture_weight()) * (1.0 - self.upper_body_pose_weight()));
         self.anims
             .apply_defaults(target_upper_body_anim, active_anim);
     }
@@ -303,7 +1143,17 @@ impl PlayerAnimationState {
             return;
         };
 
-        if input.local_movement_direction.length() < 0.1 && input.is_grounded && !input.is_sprinting
+        let (pitch, yaw, override_aim_weight) = self.locomotion_override.as_ref().map_or(
+            (0.0, None, 1.0),
+            |locomotion| (locomotion.pitch, locomotion.yaw, locomotion.aim_weight),
+        );
+        if let Some(yaw) = yaw {
+            self.lower_body_y = yaw;
+            self.lower_body_target_y = yaw;
+            self.upper_body_y = input.look_y - yaw;
+        } else if input.local_movement_direction.length() < 0.1
+            && input.is_grounded
+            && !input.is_sprinting
         {
             if self.upper_body_y > self.config.stationary_turn_threshold {
                 self.lower_body_target_y += self.config.stationary_turn_threshold;
@@ -317,14 +1167,23 @@ impl PlayerAnimationState {
             );
             self.upper_body_y = input.look_y - self.lower_body_y;
         } else {
+            let body_y = self
+                .config
+                .facing
+                .body_yaw(input.look_y, input.local_movement_direction);
+            // Turn the short way round.
+            let body_y = self.lower_body_y + wrap_angle(body_y - self.lower_body_y);
             self.lower_body_y = self
                 .lower_body_y
-                .lerp(input.look_y, self.config.stationary_turn_lerp_speed);
-            self.lower_body_target_y = input.look_y;
-            self.upper_body_y = input.look_y - self.lower_body_y;
+                .lerp(body_y, self.config.stationary_turn_lerp_speed);
+            self.lower_body_target_y = body_y;
+            let max_yaw = self.config.max_upper_body_yaw;
+            self.upper_body_y =
+                wrap_angle(input.look_y - self.lower_body_y).clamp(-max_yaw, max_yaw);
         }
 
-        root_local.rotation = Quat::from_axis_angle(Vec3::Y, self.lower_body_y);
+        root_local.rotation = Quat::from_axis_angle(Vec3::Y, self.lower_body_y)
+            * Quat::from_axis_angle(Vec3::X, -pitch);
 
         let bullet_point_global = global_transforms
             .get(self.proc_targets.bullet_point)
@@ -333,17 +1192,18 @@ impl PlayerAnimationState {
         let mut spine1_local = transforms.get_mut(self.proc_targets.spine1).unwrap();
         let root_global = global_transforms.get(root_entity).unwrap();
 
-        if !self.is_sprinting {
+        if !self.is_sprinting && self.montage.is_none() && override_aim_weight > 0.0 {
             let anim = player.animation(self.anims.get(AnimationName::Sprint));
             let max_angle = (self.config.sprint_reaim_max_angle)(anim);
+            let aim_weight = self.gesture_aim_weight() * override_aim_weight;
 
             rotate_spine_to_x(
                 root_global,
                 bullet_point_global,
                 spine1_global,
                 &mut spine1_local,
-                input.look_x,
-                self.upper_body_y,
+                (input.look_x - self.config.aim_offset.y) * aim_weight,
+                (self.upper_body_y - self.config.aim_offset.x) * aim_weight,
                 max_angle,
             );
         } else {
@@ -354,6 +1214,55 @@ impl PlayerAnimationState {
     }
 }
 
+/// Scales the speeds of the playing animations by their character's time scale
+/// just for the animations' advance, so the speeds the state machine sets are
+/// left as they are.
+pub(crate) fn scale_animation_speeds(
+    mut states: Query<(&mut PlayerAnimationState, &mut AnimationPlayer)>,
+) {
+    for (mut state, mut player) in states.iter_mut() {
+        if state.time_scale == 1.0 {
+            continue;
+        }
+        let time_scale = state.time_scale;
+        for (node, anim) in player.playing_animations_mut() {
+            state.unscaled_speeds.push((*node, anim.speed()));
+            anim.set_speed(anim.speed() * time_scale);
+        }
+    }
+}
+
+/// Puts back the speeds [`scale_animation_speeds`] scaled, once the animations
+/// have advanced.
+pub(crate) fn restore_animation_speeds(
+    mut states: Query<(&mut PlayerAnimationState, &mut AnimationPlayer)>,
+) {
+    for (mut state, mut player) in states.iter_mut() {
+        for (node, speed) in state.unscaled_speeds.drain(..) {
+            if let Some(anim) = player.animation_mut(node) {
+                anim.set_speed(speed);
+            }
+        }
+    }
+}
+
+/// Pauses all playing animations at the same normalized time. Seeking is used
+/// rather than setting the time directly so that animation events between the
+/// old and new time still fire.
+fn scrub_animations(
+    player: &mut AnimationPlayer,
+    graph: &AnimationGraph,
+    clips: &Assets<AnimationClip>,
+    normalized_time: f32,
+) {
+    player.pause_all();
+    for (index, anim) in player.playing_animations_mut() {
+        if let Some(duration) = utils::clip_duration(graph, *index, clips) {
+            anim.seek_to(normalized_time * duration);
+        }
+    }
+}
+
 /// Fades a number of animations out for a single timestep. Stops animations
 /// where the weight is less than `threshold`. Fade done by multiplying the weight
 /// by `rate`.
@@ -395,8 +1304,9 @@ fn fade_in_animation(
     target_anim
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum LowerBodyState {
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum LowerBodyState {
     Idle,
     Forward,
     Back,
@@ -407,6 +1317,24 @@ enum LowerBodyState {
     Land,
 }
 
+impl LowerBodyState {
+    pub const ALL: [Self; 8] = [
+        Self::Idle,
+        Self::Forward,
+        Self::Back,
+        Self::Left,
+        Self::Right,
+        Self::Jump,
+        Self::Falling,
+        Self::Land,
+    ];
+}
+
+/// Wraps an angle in radians into [-PI, PI].
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
 /// Rotates the spine bone to the target rotation about the player x-axis.
 fn rotate_spine_to_x(
     player_global: &GlobalTransform,
@@ -450,3 +1378,155 @@ fn rotate_spine_to_x(
     let spine1_rotated = spine1_rotation.rotate_towards(target_pulled_up, max_angle);
     spine1_local.rotation = spine1_rotated;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{AnimationTestApp, TestBone};
+
+    fn grounded(local_movement_direction: Vec2) -> PlayerAnimationInput {
+        PlayerAnimationInput {
+            local_movement_direction,
+            is_grounded: true,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn stays_idle_without_movement() {
+        let mut test = AnimationTestApp::new();
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        test.tick_with_input(rig, grounded(Vec2::ZERO), 5);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Idle));
+    }
+
+    #[test]
+    fn walks_in_the_input_direction() {
+        let mut test = AnimationTestApp::new();
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        test.record_events::<StateChangeEvent>();
+
+        test.tick_with_input(rig, grounded(Vec2::Y), 3);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Forward));
+        test.tick_with_input(rig, grounded(Vec2::NEG_X), 3);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Left));
+
+        let changes: Vec<_> = test
+            .recorded_events::<StateChangeEvent>()
+            .iter()
+            .map(|change| (change.from, change.to))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (LowerBodyState::Idle, LowerBodyState::Forward),
+                (LowerBodyState::Forward, LowerBodyState::Left),
+            ]
+        );
+    }
+
+    #[test]
+    fn lands_hard_after_a_fast_fall() {
+        let mut test = AnimationTestApp::new();
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        test.record_events::<LandedEvent>();
+
+        let jump = PlayerAnimationInput {
+            just_jumped: true,
+            ..grounded(Vec2::ZERO)
+        };
+        test.tick_with_input(rig, jump, 1);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Jump));
+
+        let falling = PlayerAnimationInput {
+            vertical_speed: -12.0,
+            ..default()
+        };
+        test.tick_with_input(rig, falling, 3);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Falling));
+
+        test.tick_with_input(rig, grounded(Vec2::ZERO), 1);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Land));
+        let landings = test.recorded_events::<LandedEvent>();
+        assert_eq!(landings.len(), 1);
+        assert_eq!(landings[0].kind, LandingKind::Hard);
+        assert_eq!(landings[0].impact_speed, 12.0);
+    }
+
+    #[test]
+    fn holds_the_state_without_input() {
+        let mut test = AnimationTestApp::new();
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        test.tick_with_input(rig, grounded(Vec2::Y), 2);
+        test.tick(3);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Forward));
+    }
+
+    #[test]
+    fn keeps_jumps_until_a_step_reads_them() {
+        let mut test = AnimationTestApp::new();
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        let jump = PlayerAnimationInput {
+            just_jumped: true,
+            ..grounded(Vec2::ZERO)
+        };
+        test.set_input(rig, jump);
+        test.set_input(rig, grounded(Vec2::Y));
+        test.tick(1);
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Jump));
+    }
+
+    #[test]
+    fn keeps_the_feet_planted_while_the_upper_body_aims() {
+        let mut test = AnimationTestApp::new();
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        test.tick_with_input(rig, grounded(Vec2::ZERO), 3);
+        let hand = |test: &mut AnimationTestApp| {
+            test.bone_world_transform(rig, "mixamorig:RightHand")
+                .unwrap()
+                .translation()
+        };
+        let hand_before = hand(&mut test);
+
+        // Looking less than the stationary turn threshold to the side twists
+        // the spine, and leaves the legs where they are.
+        let aim = PlayerAnimationInput {
+            look_y: 0.3,
+            ..grounded(Vec2::ZERO)
+        };
+        test.tick_with_input(rig, aim, 30);
+        let hand_moved = hand(&mut test).distance(hand_before);
+        assert!(hand_moved > 0.1, "the hand only moved {hand_moved}");
+        assert_eq!(test.lower_body_state(rig), Some(LowerBodyState::Idle));
+        test.assert_bone_near(rig, "mixamorig:LeftFoot", Vec3::new(0.1, 0.05, 0.0), 0.01);
+        test.assert_bone_near(rig, "mixamorig:RightFoot", Vec3::new(-0.1, 0.05, 0.0), 0.01);
+    }
+
+    #[test]
+    fn leaves_paused_animations_paused_after_scrubbing() {
+        let mut test = AnimationTestApp::new();
+        let rig = test.spawn_rig(&TestBone::humanoid());
+        test.tick_with_input(rig, grounded(Vec2::ZERO), 2);
+
+        let world = test.app.world_mut();
+        let anims = &world.get::<PlayerAnimationState>(rig).unwrap().anims;
+        let upper = anims.get(AnimationName::IdleUpperBody);
+        let lower = anims.get(AnimationName::IdleLowerBody);
+        let mut player = world.get_mut::<AnimationPlayer>(rig).unwrap();
+        player.animation_mut(upper).unwrap().pause();
+        let mut state = world.get_mut::<PlayerAnimationState>(rig).unwrap();
+        state.scrub(0.5);
+        test.tick_with_input(rig, grounded(Vec2::ZERO), 2);
+
+        let world = test.app.world_mut();
+        world
+            .get_mut::<PlayerAnimationState>(rig)
+            .unwrap()
+            .stop_scrubbing();
+        test.tick_with_input(rig, grounded(Vec2::ZERO), 1);
+
+        let player = test.app.world().get::<AnimationPlayer>(rig).unwrap();
+        assert!(player.animation(upper).unwrap().is_paused());
+        assert!(!player.animation(lower).unwrap().is_paused());
+    }
+}
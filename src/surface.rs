@@ -0,0 +1,556 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{
+    ecs::component::{ComponentHooks, HookContext, Immutable, StorageType},
+    gltf::GltfMaterialName,
+    prelude::*,
+    render::view::RenderLayers,
+};
+#[cfg(feature = "hanabi")]
+use bevy_hanabi::prelude::*;
+use ron::value::Map;
+use serde::Deserialize;
+
+use crate::effect_rng::{EffectRng, FOOTSTEP_PARTICLE_STREAM, IMPACT_PARTICLE_STREAM};
+use crate::events::{CharAnimEventsPlugin, FootstepEvent};
+use crate::hitscan::{find_surface, Hitscan, Surface};
+use crate::schema::{SchemaError, VersionedAsset, VersionedRonLoader};
+use crate::tracer::{validate_lifetime, DespawnAfter, EffectClock, EffectLifetimePlugin};
+use crate::vfx::{
+    add_particle_plugin, profile_layers, EffectLayers, EffectVisibility, LayeredEffect,
+    ParticleKind, VfxQuality, VfxQualityPlugin,
+};
+
+/// Impact and footstep effects per surface, defined in `surfaces.ron` rather
+/// than in code. The particles are rebuilt whenever the file changes, so with
+/// Bevy's `file_watcher` feature they can be tweaked while the game runs. The
+/// sounds are played by the audio plugin, when the `audio` feature is on.
+///
+/// Colliders are tagged with a [`SurfaceKind`], or by the auto-tagging pass
+/// from the names of imported meshes and materials, see
+/// [`SurfaceAutoTagging`].
+pub struct SurfacePlugin;
+
+impl Plugin for SurfacePlugin {
+    fn build(&self, app: &mut App) {
+        add_particle_plugin(app);
+        if !app.is_plugin_added::<VfxQualityPlugin>() {
+            app.add_plugins(VfxQualityPlugin);
+        }
+        if !app.is_plugin_added::<CharAnimEventsPlugin>() {
+            app.add_plugins(CharAnimEventsPlugin);
+        }
+        if !app.is_plugin_added::<EffectLifetimePlugin>() {
+            app.add_plugins(EffectLifetimePlugin::default());
+        }
+        app.add_event::<SpawnImpact>();
+        app.init_asset::<SurfaceLibrary>();
+        app.init_asset_loader::<VersionedRonLoader<SurfaceLibrary>>();
+        app.register_type::<SurfaceKind>();
+        app.init_resource::<SurfaceEffects>();
+        app.init_resource::<SurfaceAutoTagging>();
+        app.add_systems(Startup, load_surface_library);
+        app.add_systems(
+            Update,
+            (
+                auto_tag_surfaces.run_if(|tagging: Res<SurfaceAutoTagging>| tagging.enabled),
+                build_surface_effects,
+                (spawn_impact_particles, spawn_footstep_particles),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Requests the impact particles of whatever was hit. [`Hitscan::fire_ray`]
+/// and projectiles send one for every hit, whether or not `HitEvent`s are
+/// emitted. Points are in global world space.
+#[derive(Event, Reflect, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpawnImpact {
+    pub position: Vec3,
+    pub normal: Vec3,
+    /// The collider that was hit, whose `Surface` picks the particles.
+    pub target: Entity,
+    /// What made the impact, e.g. the shooter, whose `EffectLayers` the
+    /// particles are drawn on.
+    pub source: Entity,
+}
+
+/// The common kinds of [`Surface`]. Inserting one tags the entity with the
+/// matching `Surface`, whose name is what `surfaces.ron` is keyed by. Use a
+/// `Surface` directly for anything else.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum SurfaceKind {
+    Concrete,
+    Dirt,
+    Flesh,
+    Glass,
+    Grass,
+    Metal,
+    Water,
+    Wood,
+}
+
+impl SurfaceKind {
+    pub const ALL: [SurfaceKind; 8] = [
+        SurfaceKind::Concrete,
+        SurfaceKind::Dirt,
+        SurfaceKind::Flesh,
+        SurfaceKind::Glass,
+        SurfaceKind::Grass,
+        SurfaceKind::Metal,
+        SurfaceKind::Water,
+        SurfaceKind::Wood,
+    ];
+
+    /// The `Surface` name, e.g. "metal".
+    pub fn name(self) -> &'static str {
+        match self {
+            SurfaceKind::Concrete => "concrete",
+            SurfaceKind::Dirt => "dirt",
+            SurfaceKind::Flesh => "flesh",
+            SurfaceKind::Glass => "glass",
+            SurfaceKind::Grass => "grass",
+            SurfaceKind::Metal => "metal",
+            SurfaceKind::Water => "water",
+            SurfaceKind::Wood => "wood",
+        }
+    }
+}
+
+impl Component for SurfaceKind {
+    // Immutable so the `Surface` can't go stale; insert a new kind instead.
+    type Mutability = Immutable;
+
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        hooks.on_insert(|mut world, HookContext { entity, .. }| {
+            let name = world.get::<Self>(entity).unwrap().name();
+            world.commands().entity(entity).insert(Surface { name });
+        });
+    }
+}
+
+/// Infers a [`SurfaceKind`] for meshes spawned without a [`Surface`], e.g. from
+/// imported scenes, by looking for words in the names of the mesh entity, its
+/// ancestors, and its glTF material: "Crate_wood" and "metal_floor" are wood
+/// and metal. Only entities with a mesh are tagged, and hits on colliders find
+/// them by walking up from the collider.
+#[derive(Resource, Clone, Debug)]
+pub struct SurfaceAutoTagging {
+    pub enabled: bool,
+    /// Lowercase words and the surfaces they mean, checked in order.
+    pub words: Vec<(String, SurfaceKind)>,
+}
+
+impl Default for SurfaceAutoTagging {
+    fn default() -> Self {
+        let mut words: Vec<_> = SurfaceKind::ALL
+            .into_iter()
+            .map(|kind| (kind.name().to_string(), kind))
+            .collect();
+        words.extend(
+            [
+                ("steel", SurfaceKind::Metal),
+                ("iron", SurfaceKind::Metal),
+                ("stone", SurfaceKind::Concrete),
+                ("brick", SurfaceKind::Concrete),
+                ("asphalt", SurfaceKind::Concrete),
+                ("plank", SurfaceKind::Wood),
+                ("mud", SurfaceKind::Dirt),
+                ("sand", SurfaceKind::Dirt),
+                ("window", SurfaceKind::Glass),
+            ]
+            .map(|(word, kind)| (word.to_string(), kind)),
+        );
+        Self {
+            enabled: true,
+            words,
+        }
+    }
+}
+
+impl SurfaceAutoTagging {
+    /// The surface a name means, matching whole words split on anything that
+    /// isn't a letter, e.g. "Wall_Brick.001" means concrete.
+    pub fn infer(&self, name: &str) -> Option<SurfaceKind> {
+        let name = name.to_lowercase();
+        let words: Vec<_> = name.split(|c: char| !c.is_alphabetic()).collect();
+        self.words
+            .iter()
+            .find(|(word, _)| words.contains(&word.as_str()))
+            .map(|(_, kind)| *kind)
+    }
+}
+
+fn auto_tag_surfaces(
+    mut commands: Commands,
+    tagging: Res<SurfaceAutoTagging>,
+    meshes: Query<(Entity, Option<&GltfMaterialName>), (Added<Mesh3d>, Without<Surface>)>,
+    names: Query<&Name>,
+    parents: Query<&ChildOf>,
+) {
+    for (entity, material) in meshes.iter() {
+        let kind = material
+            .and_then(|material| tagging.infer(&material.0))
+            .or_else(|| {
+                std::iter::once(entity)
+                    .chain(parents.iter_ancestors(entity))
+                    .filter_map(|e| names.get(e).ok())
+                    .find_map(|name| tagging.infer(name))
+            });
+        if let Some(kind) = kind {
+            commands.entity(entity).insert(kind);
+        }
+    }
+}
+
+/// A particle burst, e.g. sparks off metal or dust off concrete.
+#[derive(Reflect, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[serde(default)]
+pub struct SurfaceParticles {
+    pub count: f32,
+    /// Linear RGBA colour, fading out over the lifetime.
+    pub color: [f32; 4],
+    pub size: f32,
+    /// How fast the particles fly off the surface.
+    pub speed: f32,
+    /// How much the particles spread out from the normal, 0 is straight out
+    /// and 1 is anywhere in the hemisphere.
+    pub spread: f32,
+    /// The acceleration in m/s², e.g. -9.81 for sparks and debris.
+    pub gravity: f32,
+    pub lifetime_secs: f32,
+    /// The render layers the particles are drawn on. If none, the layers of
+    /// the shooter's or walker's `EffectLayers` are used, or the default layer.
+    pub render_layers: Option<Vec<usize>>,
+}
+
+impl Default for SurfaceParticles {
+    fn default() -> Self {
+        Self {
+            count: 12.0,
+            color: [0.6, 0.55, 0.5, 1.0],
+            size: 0.03,
+            speed: 2.0,
+            spread: 0.5,
+            gravity: -9.81,
+            lifetime_secs: 0.6,
+            render_layers: None,
+        }
+    }
+}
+
+/// The effects of one surface.
+#[derive(Reflect, Deserialize, Clone, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[serde(default)]
+pub struct SurfaceEntry {
+    pub impact: Option<SurfaceParticles>,
+    pub footstep: Option<SurfaceParticles>,
+    /// Asset paths of the impact sounds, one is picked at random.
+    pub impact_sounds: Vec<String>,
+    pub footstep_sounds: Vec<String>,
+}
+
+/// The effects for each surface, by `Surface` name, loaded from
+/// `.surfaces.ron` files.
+#[derive(Asset, Reflect, Deserialize, Clone, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[serde(default)]
+pub struct SurfaceLibrary {
+    /// Used for surfaces that aren't listed, and colliders without a surface.
+    pub default: SurfaceEntry,
+    pub surfaces: HashMap<String, SurfaceEntry>,
+}
+
+impl VersionedAsset for SurfaceLibrary {
+    const CURRENT_VERSION: u32 = 1;
+    const EXTENSIONS: &'static [&'static str] = &["surfaces.ron"];
+
+    fn migrate(from_version: u32, _fields: &mut Map) -> Result<(), SchemaError> {
+        Err(SchemaError::Migration {
+            from_version,
+            reason: "unknown version".into(),
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let entries = std::iter::once(&self.default).chain(self.surfaces.values());
+        entries
+            .flat_map(|entry| [&entry.impact, &entry.footstep])
+            .flatten()
+            .try_for_each(|particles| validate_lifetime(particles.lifetime_secs))
+    }
+}
+
+#[derive(Resource)]
+struct SurfaceLibraryHandle(Handle<SurfaceLibrary>);
+
+/// The name of the effect property that particles fly out along.
+#[cfg(feature = "hanabi")]
+const NORMAL_PROPERTY: &str = "normal";
+
+/// The effects built from the loaded [`SurfaceLibrary`].
+#[derive(Resource, Default)]
+pub struct SurfaceEffects {
+    default: BuiltSurface,
+    surfaces: HashMap<String, BuiltSurface>,
+}
+
+#[derive(Default)]
+struct BuiltSurface {
+    impact: Option<BuiltParticles>,
+    footstep: Option<BuiltParticles>,
+    impact_sounds: Vec<Handle<AudioSource>>,
+    footstep_sounds: Vec<Handle<AudioSource>>,
+}
+
+struct BuiltParticles {
+    kind: ParticleKind,
+    #[cfg(feature = "hanabi")]
+    effect: Handle<EffectAsset>,
+    lifetime: Duration,
+    render_layers: Option<Vec<usize>>,
+}
+
+impl BuiltParticles {
+    /// The particles at `transform`, flying out along `normal`, seeded from
+    /// the `EffectRng` stream named `stream` and seen by the `spawner_layers`.
+    #[cfg_attr(not(feature = "hanabi"), allow(unused_variables))]
+    fn spawn(
+        &self,
+        commands: &mut Commands,
+        effect_rng: &mut EffectRng,
+        stream: &'static str,
+        normal: Vec3,
+        transform: Transform,
+        spawner_layers: Option<RenderLayers>,
+    ) {
+        let mut particles = commands.spawn((
+            self.kind,
+            transform.looking_to(normal, Vec3::Y),
+            DespawnAfter::new(self.lifetime, EffectClock::Virtual),
+        ));
+        #[cfg(feature = "hanabi")]
+        particles.insert((
+            effect_rng.particle_effect(stream, self.effect.clone()),
+            normal_property(normal),
+        ));
+        if let Some(layers) = profile_layers(&self.render_layers, spawner_layers) {
+            particles.insert((layers, LayeredEffect));
+        }
+    }
+}
+
+impl SurfaceEffects {
+    fn get(&self, surface: Option<&str>) -> &BuiltSurface {
+        surface
+            .and_then(|surface| self.surfaces.get(surface))
+            .unwrap_or(&self.default)
+    }
+
+    /// The impact sounds of a surface, falling back to the default ones.
+    pub fn impact_sounds(&self, surface: Option<&str>) -> &[Handle<AudioSource>] {
+        &self.get(surface).impact_sounds
+    }
+
+    /// The footstep sounds of a surface, falling back to the default ones.
+    pub fn footstep_sounds(&self, surface: Option<&str>) -> &[Handle<AudioSource>] {
+        &self.get(surface).footstep_sounds
+    }
+}
+
+fn load_surface_library(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SurfaceLibraryHandle(
+        asset_server.load("surfaces/default.surfaces.ron"),
+    ));
+}
+
+/// Rebuilds the effects whenever the library loads or changes, and when the
+/// quality changes.
+#[cfg_attr(not(feature = "hanabi"), allow(unused_mut))]
+fn build_surface_effects(
+    mut events: EventReader<AssetEvent<SurfaceLibrary>>,
+    handle: Res<SurfaceLibraryHandle>,
+    libraries: Res<Assets<SurfaceLibrary>>,
+    quality: Res<VfxQuality>,
+    mut surface_effects: ResMut<SurfaceEffects>,
+    #[cfg(feature = "hanabi")] mut effects: ResMut<Assets<EffectAsset>>,
+    asset_server: Res<AssetServer>,
+) {
+    let changed = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == handle.0.id()
+        }
+        _ => false,
+    });
+    if !changed && !quality.is_changed() {
+        return;
+    }
+    let Some(library) = libraries.get(&handle.0) else {
+        return;
+    };
+
+    let mut build = |entry: &SurfaceEntry| {
+        let mut particles = |particles: &Option<SurfaceParticles>, kind| {
+            particles.as_ref().map(|particles| BuiltParticles {
+                kind,
+                #[cfg(feature = "hanabi")]
+                effect: effects.add(surface_effect(particles, &quality)),
+                lifetime: Duration::from_secs_f32(particles.lifetime_secs),
+                render_layers: particles.render_layers.clone(),
+            })
+        };
+        BuiltSurface {
+            impact: particles(&entry.impact, ParticleKind::Impact),
+            footstep: particles(&entry.footstep, ParticleKind::Footstep),
+            impact_sounds: entry
+                .impact_sounds
+                .iter()
+                .map(|path| asset_server.load(path))
+                .collect(),
+            footstep_sounds: entry
+                .footstep_sounds
+                .iter()
+                .map(|path| asset_server.load(path))
+                .collect(),
+        }
+    };
+    *surface_effects = SurfaceEffects {
+        default: build(&library.default),
+        surfaces: library
+            .surfaces
+            .iter()
+            .map(|(name, entry)| (name.clone(), build(entry)))
+            .collect(),
+    };
+}
+
+fn spawn_impact_particles(
+    mut commands: Commands,
+    mut impacts: EventReader<SpawnImpact>,
+    surface_effects: Res<SurfaceEffects>,
+    surfaces: Query<&Surface>,
+    layers: Query<&EffectLayers>,
+    parents: Query<&ChildOf>,
+    visibility: EffectVisibility,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    for request in impacts.read() {
+        if !visibility.sees(request.position, 0.0) {
+            continue;
+        }
+        let surface = find_surface(request.target, &surfaces, &parents);
+        if let Some(particles) = &surface_effects.get(surface).impact {
+            particles.spawn(
+                &mut commands,
+                &mut effect_rng,
+                IMPACT_PARTICLE_STREAM,
+                request.normal,
+                Transform::from_translation(request.position),
+                EffectLayers::find(request.source, &layers, &parents),
+            );
+        }
+    }
+}
+
+/// Kicks up particles from the ground under each footstep.
+fn spawn_footstep_particles(
+    mut commands: Commands,
+    mut footsteps: EventReader<FootstepEvent>,
+    surface_effects: Res<SurfaceEffects>,
+    hitscan: Hitscan,
+    layers: Query<&EffectLayers>,
+    parents: Query<&ChildOf>,
+    visibility: EffectVisibility,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    for footstep in footsteps.read() {
+        if !visibility.sees(footstep.meta.position, 0.0) {
+            continue;
+        }
+        let ground = hitscan.cast_ray(
+            footstep.meta.position + Vec3::Y * 0.5,
+            Dir3::NEG_Y,
+            1.0,
+            Some(footstep.meta.entity),
+        );
+        let Some(ground) = ground else {
+            continue;
+        };
+        if let Some(footstep_particles) = &surface_effects.get(ground.surface).footstep {
+            footstep_particles.spawn(
+                &mut commands,
+                &mut effect_rng,
+                FOOTSTEP_PARTICLE_STREAM,
+                ground.normal,
+                Transform::from_translation(ground.point),
+                EffectLayers::find(footstep.meta.entity, &layers, &parents),
+            );
+        }
+    }
+}
+
+#[cfg(feature = "hanabi")]
+pub(crate) fn normal_property(normal: Vec3) -> EffectProperties {
+    EffectProperties::default().with_properties([(NORMAL_PROPERTY.to_string(), normal.into())])
+}
+
+/// A burst of particles flying off the surface along the normal property.
+#[cfg(feature = "hanabi")]
+pub(crate) fn surface_effect(particles: &SurfaceParticles, quality: &VfxQuality) -> EffectAsset {
+    let writer = ExprWriter::new();
+    let normal = writer.add_property(NORMAL_PROPERTY, Vec3::Y.into());
+
+    let init_pos = SetPositionSphereModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        radius: writer.lit(0.02).expr(),
+        dimension: ShapeDimension::Volume,
+    };
+    // Out along the normal, spread sideways by a random amount.
+    let sideways = writer.rand(VectorType::VEC3F) * writer.lit(2.0) - writer.lit(1.0);
+    let init_velocity = SetAttributeModifier::new(
+        Attribute::VELOCITY,
+        ((writer.prop(normal) + sideways * writer.lit(particles.spread))
+            .normalized()
+            * writer.lit(particles.speed)
+            * (writer.rand(ScalarType::Float) * writer.lit(0.5) + writer.lit(0.75)))
+        .expr(),
+    );
+    let init_age = SetAttributeModifier::new(Attribute::AGE, writer.lit(0.0).expr());
+    let init_lifetime = SetAttributeModifier::new(
+        Attribute::LIFETIME,
+        (writer.rand(ScalarType::Float) * writer.lit(particles.lifetime_secs * 0.5)
+            + writer.lit(particles.lifetime_secs * 0.5))
+        .expr(),
+    );
+    let init_size = SetAttributeModifier::new(Attribute::SIZE, writer.lit(particles.size).expr());
+    let gravity = AccelModifier::new(writer.lit(Vec3::Y * particles.gravity).expr());
+
+    let [r, g, b, a] = particles.color;
+    let mut color = Gradient::new();
+    color.add_key(0.0, Vec4::new(r, g, b, a));
+    color.add_key(1.0, Vec4::new(r, g, b, 0.0));
+
+    let module = writer.finish();
+
+    let count = quality.particles(particles.count);
+    // Simulated in world space, so gravity pulls down whichever way the
+    // surface faces.
+    EffectAsset::new(256, SpawnerSettings::once(count.into()), module)
+        .with_simulation_space(SimulationSpace::Global)
+        .with_name("surface impact")
+        .init(init_pos)
+        .init(init_velocity)
+        .init(init_age)
+        .init(init_lifetime)
+        .init(init_size)
+        .update(gravity)
+        .render(ColorOverLifetimeModifier::new(color))
+}
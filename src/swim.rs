@@ -0,0 +1,135 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::anim::CharAnimSet;
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+
+/// Swimming at the surface and underwater. Add a [`SwimSet`] to a character
+/// root to let it swim, and a [`Submerged`] while it's in water.
+pub struct SwimPlugin;
+
+impl Plugin for SwimPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SwimSet>();
+        app.register_type::<Submerged>();
+        app.add_systems(Update, swim.in_set(CharAnimSet::ModifyInput));
+    }
+}
+
+/// The clips and tuning of a swimming character. The clips form a blend space
+/// over depth (surface to underwater) and speed (idle to forward).
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct SwimSet {
+    pub surface_idle: Handle<AnimationClip>,
+    pub surface_forward: Handle<AnimationClip>,
+    pub underwater_idle: Handle<AnimationClip>,
+    pub underwater_forward: Handle<AnimationClip>,
+    /// The depth in meters over which the surface clips blend into the
+    /// underwater ones.
+    pub surface_depth: f32,
+    /// The speed in m/s of a full movement input, used to turn the vertical
+    /// speed into a pitch.
+    pub swim_speed: f32,
+    /// How far the body tilts in radians per unit of buoyancy when floating in
+    /// place. Buoyant characters drift head up, heavy ones head down.
+    pub buoyancy_tilt: f32,
+    /// The most the body pitches in radians.
+    pub max_pitch: f32,
+    /// How fast the pitch follows its target in radians per second.
+    pub pitch_speed: f32,
+    pitch: f32,
+}
+
+impl SwimSet {
+    pub fn new(
+        surface_idle: Handle<AnimationClip>,
+        surface_forward: Handle<AnimationClip>,
+        underwater_idle: Handle<AnimationClip>,
+        underwater_forward: Handle<AnimationClip>,
+    ) -> Self {
+        Self {
+            surface_idle,
+            surface_forward,
+            underwater_idle,
+            underwater_forward,
+            surface_depth: 0.6,
+            swim_speed: 1.5,
+            buoyancy_tilt: 0.3,
+            max_pitch: 70f32.to_radians(),
+            pitch_speed: 2.0,
+            pitch: 0.0,
+        }
+    }
+
+    /// The current pitch of the body in radians, positive is nose up.
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+}
+
+/// Set by the game on the character root while it's in water.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Submerged {
+    /// How far below the surface the character's root is in meters.
+    pub depth: f32,
+    /// The net upwards force on the character relative to gravity, e.g. 0 is
+    /// neutrally buoyant and 1 floats up as fast as it would fall in air.
+    pub buoyancy: f32,
+}
+
+fn swim(
+    mut swimmers: Query<(Entity, &mut SwimSet, Option<&Submerged>)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    for (root, mut swim, submerged) in swimmers.iter_mut() {
+        let Some(submerged) = submerged else {
+            swim.pitch = 0.0;
+            continue;
+        };
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let Some(input) = state.input() else {
+            continue;
+        };
+
+        let underwater = (submerged.depth / swim.surface_depth.max(f32::EPSILON)).clamp(0.0, 1.0);
+        let underwater = underwater * underwater * (3.0 - 2.0 * underwater);
+        let speed = input.local_movement_direction.length().min(1.0);
+
+        // Swimming pitches towards the direction of travel, floating in place
+        // tilts with buoyancy. Only underwater, at the surface the body is flat.
+        let forward_speed = (speed * swim.swim_speed).max(0.1);
+        let travel_pitch = input.vertical_speed.atan2(forward_speed);
+        let float_pitch = (submerged.buoyancy * swim.buoyancy_tilt).clamp(-FRAC_PI_2, FRAC_PI_2);
+        let target_pitch = (underwater * travel_pitch.lerp(float_pitch, 1.0 - speed))
+            .clamp(-swim.max_pitch, swim.max_pitch);
+        let max_step = swim.pitch_speed * time.delta_secs();
+        swim.pitch += (target_pitch - swim.pitch).clamp(-max_step, max_step);
+
+        state.override_locomotion(LocomotionOverride {
+            clips: vec![
+                LocomotionClip::new(
+                    swim.surface_idle.clone(),
+                    (1.0 - underwater) * (1.0 - speed),
+                ),
+                LocomotionClip::new(swim.surface_forward.clone(), (1.0 - underwater) * speed),
+                LocomotionClip::new(swim.underwater_idle.clone(), underwater * (1.0 - speed)),
+                LocomotionClip::new(swim.underwater_forward.clone(), underwater * speed),
+            ],
+            pitch: swim.pitch,
+            yaw: None,
+            aim_weight: 0.0,
+        });
+    }
+}
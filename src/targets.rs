@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+
+use crate::events::{EventMeta, EventRouting, HitEvent, TargetHitEvent};
+use crate::prop_anim::{PropAnimator, PropState};
+
+/// Shooting range targets. A [`PopUpTarget`] falls over when shot, through the
+/// `PropAnimator` on its root, and sends a `TargetHitEvent` scored by the
+/// [`TargetZone`] that was hit, e.g. more for the bullseye. It stays down
+/// until [`PopUpTarget::reset`], or pops back up by itself after a while. The
+/// [`RangeScore`] adds up the scores, for a tutorial or a test of the hit
+/// pipeline.
+pub struct TargetsPlugin;
+
+impl Plugin for TargetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PopUpTarget>();
+        app.register_type::<TargetZone>();
+        app.register_type::<RangeScore>();
+        app.init_resource::<RangeScore>();
+        app.add_systems(Update, (hit_targets, reset_targets).chain());
+    }
+}
+
+/// The prop state a target stands in.
+pub const TARGET_UP: &str = "up";
+/// The prop state a target falls over in.
+pub const TARGET_DOWN: &str = "down";
+
+/// Add to the root of a target, along with a `PropAnimator` with
+/// [`TARGET_UP`] and [`TARGET_DOWN`] states, e.g. from
+/// [`PopUpTarget::animator`]. Its colliders are under it.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct PopUpTarget {
+    /// The score of hits outside any [`TargetZone`].
+    pub score: u32,
+    /// Seconds after falling over that the target pops back up, or never if
+    /// none.
+    pub reset_secs: Option<f32>,
+    up: bool,
+    down_secs: f32,
+    reset: bool,
+}
+
+impl Default for PopUpTarget {
+    fn default() -> Self {
+        Self {
+            score: 1,
+            reset_secs: Some(3.0),
+            up: true,
+            down_secs: 0.0,
+            reset: false,
+        }
+    }
+}
+
+impl PopUpTarget {
+    /// An animator that pops the target up with `up` and knocks it over with
+    /// `down`, both holding their last frame.
+    pub fn animator(up: Handle<AnimationClip>, down: Handle<AnimationClip>) -> PropAnimator {
+        PropAnimator::new(vec![
+            PropState::new(TARGET_UP, up),
+            PropState::new(TARGET_DOWN, down),
+        ])
+        .expect("the target has states")
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.up
+    }
+
+    /// Pops the target back up.
+    pub fn reset(&mut self) {
+        self.reset = true;
+    }
+}
+
+/// A part of a target scored on its own, e.g. a ring. Add to the zone's
+/// collider, or an entity above it under the target.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct TargetZone {
+    pub name: String,
+    pub score: u32,
+}
+
+impl TargetZone {
+    pub fn new(name: impl Into<String>, score: u32) -> Self {
+        Self {
+            name: name.into(),
+            score,
+        }
+    }
+}
+
+/// The score of all the targets hit since the last reset.
+#[derive(Resource, Reflect, Clone, Debug, Default)]
+#[reflect(Resource)]
+pub struct RangeScore {
+    pub total: u32,
+    pub hits: u32,
+}
+
+impl RangeScore {
+    pub fn reset(&mut self) {
+        *self = default();
+    }
+}
+
+fn hit_targets(
+    mut hits: EventReader<HitEvent>,
+    mut targets: Query<(&mut PopUpTarget, &mut PropAnimator)>,
+    zones: Query<&TargetZone>,
+    parents: Query<&ChildOf>,
+    mut score: ResMut<RangeScore>,
+    mut events: EventWriter<TargetHitEvent>,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+) {
+    for hit in hits.read() {
+        let mut ancestors = std::iter::once(hit.target).chain(parents.iter_ancestors(hit.target));
+        // The closest zone on the way up to the target.
+        let mut zone = None;
+        let Some(root) = ancestors.find(|e| {
+            zone = zone.or(zones.get(*e).ok());
+            targets.contains(*e)
+        }) else {
+            continue;
+        };
+        let (mut target, mut animator) = targets.get_mut(root).unwrap();
+        if !target.up {
+            continue;
+        }
+        target.up = false;
+        target.down_secs = 0.0;
+        animator.set_state(TARGET_DOWN);
+
+        let points = zone.map_or(target.score, |zone| zone.score);
+        score.total += points;
+        score.hits += 1;
+        if routing.emits::<TargetHitEvent>() {
+            events.write(TargetHitEvent {
+                meta: EventMeta::new(root, &time, hit.meta.position),
+                shooter: (hit.meta.entity != hit.target).then_some(hit.meta.entity),
+                zone: zone.map(|zone| zone.name.clone()),
+                score: points,
+            });
+        }
+    }
+}
+
+fn reset_targets(mut targets: Query<(&mut PopUpTarget, &mut PropAnimator)>, time: Res<Time>) {
+    for (mut target, mut animator) in targets.iter_mut() {
+        if target.up {
+            target.reset = false;
+            continue;
+        }
+        target.down_secs += time.delta_secs();
+        let timed_out = target
+            .reset_secs
+            .is_some_and(|secs| target.down_secs >= secs);
+        if target.reset || timed_out {
+            target.reset = false;
+            target.up = true;
+            animator.set_state(TARGET_UP);
+        }
+    }
+}
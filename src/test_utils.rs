@@ -0,0 +1,269 @@
+//! Helpers for regression testing animation logic in a headless `App`, without
+//! a window or renderer.
+
+use std::time::Duration;
+
+use bevy::{
+    animation::{AnimationPlugin as BevyAnimationPlugin, AnimationTarget, AnimationTargetId},
+    ecs::system::SystemState,
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+
+use crate::anim::{self, PlayerAnimationPaths};
+use crate::character::Player;
+use crate::state::{LowerBodyState, PlayerAnimationInput, PlayerAnimationState};
+use crate::{events, ik, utils};
+
+/// The fixed timestep every tick advances time by, so tests are deterministic.
+pub const TEST_TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+
+pub struct AnimationTestApp {
+    pub app: App,
+}
+
+impl Default for AnimationTestApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A bone to spawn with [`AnimationTestApp::spawn_rig`].
+pub struct TestBone {
+    pub name: &'static str,
+    /// The index of the parent in the bone list, or none for the root bone.
+    pub parent: Option<usize>,
+    pub transform: Transform,
+}
+
+impl TestBone {
+    pub fn new(name: &'static str, parent: Option<usize>, translation: Vec3) -> Self {
+        Self {
+            name,
+            parent,
+            transform: Transform::from_translation(translation),
+        }
+    }
+
+    /// A minimal humanoid with the bones the default animation graph masks
+    /// and aims with, standing 1.8 units tall.
+    pub fn humanoid() -> Vec<Self> {
+        vec![
+            Self::new("mixamorig:Hips", None, Vec3::Y),
+            Self::new("mixamorig:Spine", Some(0), Vec3::Y * 0.1),
+            Self::new("mixamorig:Spine1", Some(1), Vec3::Y * 0.15),
+            Self::new("mixamorig:Spine2", Some(2), Vec3::Y * 0.15),
+            Self::new("mixamorig:Head", Some(3), Vec3::Y * 0.4),
+            Self::new("mixamorig:RightArm", Some(3), Vec3::new(-0.2, 0.25, 0.0)),
+            Self::new("mixamorig:RightForeArm", Some(5), Vec3::new(-0.3, 0.0, 0.0)),
+            Self::new("mixamorig:RightHand", Some(6), Vec3::new(-0.25, 0.0, 0.0)),
+            Self::new("BlasterN", Some(7), Vec3::new(0.0, 0.0, 0.3)),
+            Self::new("mixamorig:LeftUpLeg", Some(0), Vec3::new(0.1, -0.05, 0.0)),
+            Self::new("mixamorig:LeftLeg", Some(9), Vec3::Y * -0.45),
+            Self::new("mixamorig:LeftFoot", Some(10), Vec3::Y * -0.45),
+            Self::new("mixamorig:RightUpLeg", Some(0), Vec3::new(-0.1, -0.05, 0.0)),
+            Self::new("mixamorig:RightLeg", Some(12), Vec3::Y * -0.45),
+            Self::new("mixamorig:RightFoot", Some(13), Vec3::Y * -0.45),
+        ]
+    }
+}
+
+impl AnimationTestApp {
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            TransformPlugin,
+            BevyAnimationPlugin,
+        ))
+        .add_plugins((
+            events::CharAnimEventsPlugin,
+            anim::AnimationPlugin::default(),
+            ik::IkPlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TEST_TIMESTEP));
+        Self { app }
+    }
+
+    /// Spawns a character from a flat list of bones, with the same components
+    /// a glTF scene and the character plugin would give them. The bones must
+    /// include those of [`GraphBones::default`](anim::GraphBones), e.g.
+    /// [`TestBone::humanoid`]. Returns the rig root, which holds the
+    /// `AnimationPlayer` and the `PlayerAnimationState`, under a `Player`.
+    ///
+    /// The clips are never loaded, so the state machine transitions and poses
+    /// procedural bones while the clips leave the bones where they are.
+    pub fn spawn_rig(&mut self, bones: &[TestBone]) -> Entity {
+        let player = self
+            .app
+            .world_mut()
+            .spawn((Name::new("TestPlayer"), Player, Transform::default()))
+            .id();
+        let root = self.spawn_bones(player, bones);
+
+        let world = self.app.world_mut();
+        let mut system_state: SystemState<(
+            Commands,
+            Res<AssetServer>,
+            ResMut<Assets<AnimationGraph>>,
+            Query<&Children>,
+            Query<&Name>,
+            Query<&AnimationTarget>,
+            Query<&ChildOf>,
+        )> = SystemState::new(world);
+        let (mut commands, asset_server, mut graphs, children, names, targets, parents) =
+            system_state.get_mut(world);
+        let (anims, proc_targets, graph, nodes) = anim::load_player_animations(
+            root,
+            &PlayerAnimationPaths::default(),
+            &asset_server,
+            &children,
+            &names,
+            &targets,
+            commands.reborrow(),
+            &parents,
+        )
+        .expect("the test rig has every bone");
+        commands.entity(root).insert((
+            AnimationGraphHandle(graphs.add(graph)),
+            PlayerAnimationState::new(anims, proc_targets, nodes),
+        ));
+        system_state.apply(world);
+        root
+    }
+
+    /// Spawns the `AnimationPlayer` and bones of a rig under `parent`, as a
+    /// glTF scene would, without an animation graph. Returns the rig root.
+    pub fn spawn_bones(&mut self, parent: Entity, bones: &[TestBone]) -> Entity {
+        let world = self.app.world_mut();
+        let root = world
+            .spawn((
+                Name::new("TestRig"),
+                AnimationPlayer::default(),
+                Transform::default(),
+                ChildOf(parent),
+            ))
+            .id();
+
+        let mut entities: Vec<Entity> = Vec::with_capacity(bones.len());
+        for bone in bones {
+            let parent = bone.parent.map(|ix| entities[ix]).unwrap_or(root);
+            let name = Name::new(bone.name);
+            let entity = world
+                .spawn((
+                    AnimationTarget {
+                        id: AnimationTargetId::from_name(&name),
+                        player: root,
+                    },
+                    name,
+                    bone.transform,
+                    ChildOf(parent),
+                ))
+                .id();
+            entities.push(entity);
+        }
+        root
+    }
+
+    /// Sets the animation input of the rig spawned by [`Self::spawn_rig`], to
+    /// be read on the next tick.
+    pub fn set_input(&mut self, root: Entity, input: PlayerAnimationInput) {
+        self.app
+            .world_mut()
+            .get_mut::<PlayerAnimationState>(root)
+            .expect("spawn the rig with spawn_rig")
+            .set_input(input);
+    }
+
+    /// Sets `input` and runs `frames` updates, setting it again before each.
+    pub fn tick_with_input(&mut self, root: Entity, input: PlayerAnimationInput, frames: usize) {
+        for _ in 0..frames {
+            self.set_input(root, input.clone());
+            self.app.update();
+        }
+    }
+
+    /// Runs `frames` updates.
+    pub fn tick(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    /// Gets the propagated world transform of the bone named `name` under `root`.
+    pub fn bone_world_transform(&mut self, root: Entity, name: &str) -> Option<GlobalTransform> {
+        let bone = self.find_bone(root, name)?;
+        self.app.world().get::<GlobalTransform>(bone).copied()
+    }
+
+    /// Panics if the bone isn't within `tolerance` of `expected` in world space.
+    pub fn assert_bone_near(&mut self, root: Entity, name: &str, expected: Vec3, tolerance: f32) {
+        let actual = self
+            .bone_world_transform(root, name)
+            .unwrap_or_else(|| panic!("no bone named {name}"))
+            .translation();
+        assert!(
+            actual.distance(expected) <= tolerance,
+            "bone {name} at {actual}, expected {expected} (tolerance {tolerance})"
+        );
+    }
+
+    /// Gets the lower body state of the animation state on or under `root`.
+    pub fn lower_body_state(&mut self, root: Entity) -> Option<LowerBodyState> {
+        let world = self.app.world_mut();
+        let mut system_state: SystemState<(
+            Query<(Entity, &PlayerAnimationState)>,
+            Query<&ChildOf>,
+        )> = SystemState::new(world);
+        let (states, parents) = system_state.get(world);
+        states
+            .iter()
+            .find(|(entity, _)| {
+                *entity == root || parents.iter_ancestors(*entity).any(|e| e == root)
+            })
+            .map(|(_, state)| state.lower_body_state())
+    }
+
+    /// Starts recording events of type `E`, read them with [`Self::recorded_events`].
+    pub fn record_events<E: Event + Clone>(&mut self) {
+        self.app
+            .init_resource::<RecordedEvents<E>>()
+            .add_systems(Last, record_events::<E>);
+    }
+
+    /// All events of type `E` recorded since [`Self::record_events`].
+    pub fn recorded_events<E: Event + Clone>(&self) -> &[E] {
+        &self
+            .app
+            .world()
+            .get_resource::<RecordedEvents<E>>()
+            .expect("call record_events first")
+            .0
+    }
+
+    /// Finds a bone entity by name under `root`.
+    pub fn find_bone(&mut self, root: Entity, name: &str) -> Option<Entity> {
+        let world = self.app.world_mut();
+        let mut system_state: SystemState<(Query<&Children>, Query<&Name>)> =
+            SystemState::new(world);
+        let (children, names) = system_state.get(world);
+        utils::find_child_with_name(root, name, &children, &names)
+    }
+}
+
+#[derive(Resource)]
+struct RecordedEvents<E>(Vec<E>);
+
+impl<E> Default for RecordedEvents<E> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+fn record_events<E: Event + Clone>(
+    mut reader: EventReader<E>,
+    mut recorded: ResMut<RecordedEvents<E>>,
+) {
+    recorded.0.extend(reader.read().cloned());
+}
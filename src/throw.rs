@@ -0,0 +1,342 @@
+use bevy::{
+    ecs::component::{ComponentHooks, HookContext, Mutable, StorageType},
+    pbr::NotShadowCaster,
+    prelude::*,
+};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::events::{NotifyEvent, UserData};
+use crate::hitscan::Hitscan;
+use crate::montage::Montage;
+use crate::projectile::SpawnProjectile;
+use crate::state::PlayerAnimationState;
+use crate::tracer::{beam_transform, TracerParams, TracerProfile, TracerShader};
+
+/// Throwing, e.g. grenades. A [`Throw`] plays a wind-up montage, and when it
+/// passes its [`RELEASE_NOTIFY`] a `SpawnProjectile` is sent from the throwing
+/// hand along the aim. While aiming, the arc the throw would follow is
+/// previewed with the tracer material.
+pub struct ThrowPlugin;
+
+impl Plugin for ThrowPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Throw>();
+        app.add_systems(
+            Update,
+            (
+                start_throws.in_set(CharAnimSet::ModifyInput),
+                (release_throws, preview_trajectories)
+                    .chain()
+                    .after(CharAnimSet::StateMachine),
+            ),
+        );
+    }
+}
+
+/// The notify in the throw montage at which the projectile leaves the hand.
+pub const RELEASE_NOTIFY: &str = "release";
+
+/// The step in seconds that the preview traces the arc with.
+const PREVIEW_STEP_SECS: f32 = 1.0 / 30.0;
+
+/// How the arc of a throw is shown while aiming.
+#[derive(Reflect, Clone, Debug)]
+pub struct TrajectoryPreview {
+    /// The longest the arc is traced for, in seconds of flight.
+    pub max_secs: f32,
+    /// The radius of the line in meters.
+    pub radius: f32,
+    pub color: [f32; 4],
+    pub brightness: f32,
+}
+
+impl Default for TrajectoryPreview {
+    fn default() -> Self {
+        Self {
+            max_secs: 3.0,
+            radius: 0.01,
+            color: [1.0, 1.0, 1.0, 1.0],
+            brightness: 2.0,
+        }
+    }
+}
+
+/// Lets a character throw. Add to the character root with a `RigMap`.
+#[derive(Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Throw {
+    /// The wind-up and throw, with a [`RELEASE_NOTIFY`] notify.
+    pub montage: Montage,
+    /// The bone the projectile leaves from.
+    pub hand: RigBone,
+    /// The speed the projectile leaves the hand at, in m/s.
+    pub speed: f32,
+    /// How far above the aim the throw goes, in radians.
+    pub loft: f32,
+    pub gravity: Vec3,
+    pub drag: f32,
+    pub lifetime_secs: f32,
+    /// The look of the projectile, or the default tracer look if none.
+    pub profile: Option<Handle<TracerProfile>>,
+    /// Passed on to the `SpawnProjectile`.
+    #[reflect(ignore)]
+    pub user_data: Option<UserData>,
+    /// Shown while aiming, if set.
+    pub preview: Option<TrajectoryPreview>,
+    aiming: bool,
+    requested: bool,
+    #[reflect(ignore)]
+    preview_segments: Vec<Entity>,
+    #[reflect(ignore)]
+    preview_material: Option<Handle<TracerShader>>,
+}
+
+impl Component for Throw {
+    type Mutability = Mutable;
+
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        // The preview segments aren't under the character, so they go with
+        // the throw when it's removed or the character despawns.
+        hooks.on_replace(|mut world, HookContext { entity, .. }| {
+            let segments = world.get::<Self>(entity).unwrap().preview_segments.clone();
+            let mut commands = world.commands();
+            for segment in segments {
+                commands.entity(segment).try_despawn();
+            }
+        });
+    }
+}
+
+impl Throw {
+    pub fn new(montage: Montage) -> Self {
+        Self {
+            montage,
+            hand: RigBone::RightHand,
+            speed: 15.0,
+            loft: 10f32.to_radians(),
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            drag: 0.05,
+            lifetime_secs: 5.0,
+            profile: None,
+            user_data: None,
+            preview: Some(TrajectoryPreview::default()),
+            aiming: false,
+            requested: false,
+            preview_segments: Vec::new(),
+            preview_material: None,
+        }
+    }
+
+    /// Shows or hides the trajectory preview, e.g. while the throw button is
+    /// held.
+    pub fn set_aiming(&mut self, aiming: bool) {
+        self.aiming = aiming;
+    }
+
+    pub fn is_aiming(&self) -> bool {
+        self.aiming
+    }
+
+    /// Plays the throw. The projectile is sent when the montage passes its
+    /// release notify.
+    pub fn throw(&mut self) {
+        self.requested = true;
+        self.aiming = false;
+    }
+
+    /// The velocity the projectile leaves with when aiming along `look_y` and
+    /// `look_x`.
+    fn launch_velocity(&self, look_y: f32, look_x: f32) -> Vec3 {
+        let aim = Quat::from_axis_angle(Vec3::Y, look_y)
+            * Quat::from_axis_angle(Vec3::X, look_x - self.loft)
+            * Vec3::Z;
+        aim * self.speed
+    }
+}
+
+fn find_state<'a>(
+    root: Entity,
+    children: &Query<&Children>,
+    states: &'a Query<&PlayerAnimationState>,
+) -> Option<&'a PlayerAnimationState> {
+    children
+        .iter_descendants(root)
+        .find_map(|e| states.get(e).ok())
+}
+
+/// The launch point and velocity of a character's throw, if it's set up.
+fn launch(
+    root: Entity,
+    throw: &Throw,
+    rig: &RigMap,
+    states: &Query<&PlayerAnimationState>,
+    children: &Query<&Children>,
+    global_transforms: &Query<&GlobalTransform>,
+) -> Option<(Vec3, Vec3)> {
+    let input = find_state(root, children, states)?.input()?;
+    let hand = global_transforms.get(rig.get(throw.hand)?).ok()?;
+    let velocity = throw.launch_velocity(input.look_y, input.look_x);
+    Some((hand.translation(), velocity))
+}
+
+fn start_throws(
+    mut characters: Query<(Entity, &mut Throw)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+) {
+    for (root, mut throw) in characters.iter_mut() {
+        if !std::mem::take(&mut throw.requested) {
+            continue;
+        }
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        state.play_montage(throw.montage.clone());
+    }
+}
+
+fn release_throws(
+    mut notifies: EventReader<NotifyEvent>,
+    mut projectiles: EventWriter<SpawnProjectile>,
+    characters: Query<(&Throw, &RigMap)>,
+    states: Query<&PlayerAnimationState>,
+    children: Query<&Children>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    for notify in notifies.read() {
+        if notify.name != RELEASE_NOTIFY {
+            continue;
+        }
+        let root = notify.meta.entity;
+        let Ok((throw, rig)) = characters.get(root) else {
+            continue;
+        };
+        let throwing = find_state(root, &children, &states)
+            .and_then(|state| state.montage())
+            .is_some_and(|montage| montage.name == throw.montage.name);
+        if !throwing {
+            continue;
+        }
+        let Some((start, velocity)) =
+            launch(root, throw, rig, &states, &children, &global_transforms)
+        else {
+            continue;
+        };
+        projectiles.write(SpawnProjectile {
+            gravity: throw.gravity,
+            drag: throw.drag,
+            lifetime_secs: throw.lifetime_secs,
+            shooter: Some(root),
+            profile: throw.profile.clone(),
+            user_data: throw.user_data.clone(),
+            ..SpawnProjectile::new(start, velocity)
+        });
+    }
+}
+
+/// Traces the arc the way projectiles move, and stops it at the first hit.
+fn trace_arc(
+    start: Vec3,
+    mut velocity: Vec3,
+    throw: &Throw,
+    max_secs: f32,
+    shooter: Entity,
+    hitscan: &Hitscan,
+) -> Vec<Vec3> {
+    let mut points = vec![start];
+    let mut position = start;
+    let mut secs = 0.0;
+    while secs < max_secs {
+        velocity += throw.gravity * PREVIEW_STEP_SECS;
+        velocity *= (1.0 - throw.drag * PREVIEW_STEP_SECS).max(0.0);
+        let step = velocity * PREVIEW_STEP_SECS;
+        let Ok(direction) = Dir3::new(step) else {
+            break;
+        };
+        if let Some(hit) = hitscan.cast_ray(position, direction, step.length(), Some(shooter)) {
+            points.push(hit.point);
+            break;
+        }
+        position += step;
+        points.push(position);
+        secs += PREVIEW_STEP_SECS;
+    }
+    points
+}
+
+fn preview_trajectories(
+    mut commands: Commands,
+    mut characters: Query<(Entity, &mut Throw, &RigMap)>,
+    mut visibilities: Query<(&mut Transform, &mut Visibility)>,
+    states: Query<&PlayerAnimationState>,
+    children: Query<&Children>,
+    global_transforms: Query<&GlobalTransform>,
+    hitscan: Hitscan,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<TracerShader>>,
+    mut segment_mesh: Local<Option<Handle<Mesh>>>,
+) {
+    for (root, mut throw, rig) in characters.iter_mut() {
+        let points = match (throw.aiming, throw.preview.as_ref()) {
+            (true, Some(preview)) => {
+                launch(root, &throw, rig, &states, &children, &global_transforms).map(
+                    |(start, velocity)| {
+                        trace_arc(start, velocity, &throw, preview.max_secs, root, &hitscan)
+                    },
+                )
+            }
+            _ => None,
+        };
+        let points = points.unwrap_or_default();
+        let segments = points.len().saturating_sub(1);
+
+        let throw = throw.as_mut();
+        if let Some(preview) = throw.preview.as_ref() {
+            let mesh = segment_mesh
+                .get_or_insert_with(|| meshes.add(Cylinder::new(1.0, 1.0).mesh().build()))
+                .clone();
+            let material = throw.preview_material.get_or_insert_with(|| {
+                let color = LinearRgba::from_f32_array(preview.color);
+                materials.add(TracerShader {
+                    params: TracerParams::beam(color, preview.brightness),
+                })
+            });
+            while throw.preview_segments.len() < segments {
+                let segment = commands
+                    .spawn((
+                        Mesh3d(mesh.clone()),
+                        MeshMaterial3d(material.clone()),
+                        NotShadowCaster,
+                        Transform::default(),
+                        Visibility::Hidden,
+                    ))
+                    .id();
+                throw.preview_segments.push(segment);
+            }
+        }
+
+        let radius = throw.preview.as_ref().map_or(0.0, |preview| preview.radius);
+        for (index, segment) in throw.preview_segments.iter().enumerate() {
+            let Ok((mut transform, mut visibility)) = visibilities.get_mut(*segment) else {
+                continue;
+            };
+            let Some(&[from, to]) = points.get(index..index + 2) else {
+                *visibility = Visibility::Hidden;
+                continue;
+            };
+            let Some(beam) = beam_transform(from, to, radius) else {
+                *visibility = Visibility::Hidden;
+                continue;
+            };
+            *transform = beam;
+            *visibility = Visibility::Visible;
+        }
+    }
+}
@@ -1,36 +1,467 @@
-use bevy_hanabi::prelude::*;
-use std::time::Duration;
+#[cfg(feature = "hanabi")]
+use bevy_hanabi::{prelude::*, EffectSimulation, EffectSimulationTime};
+use std::{cmp::Reverse, collections::BinaryHeap, time::Duration};
 
 use bevy::{
     color::palettes::css::{WHITE, YELLOW},
-    ecs::component::{ComponentHooks, HookContext, Mutable, StorageType},
+    ecs::{
+        component::{ComponentHooks, HookContext, Mutable, StorageType},
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+        world::DeferredWorld,
+    },
     pbr::NotShadowCaster,
+    platform::collections::HashMap,
     prelude::*,
-    render::render_resource::{AsBindGroup, ShaderRef},
+    render::{
+        mesh::MeshTag,
+        render_resource::{AsBindGroup, ShaderRef, ShaderType},
+    },
 };
 
-const TRACER_RADIUS: f32 = 0.05;
-const TRACER_DURATION_MILLIS: u64 = 100;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use ron::value::Map;
+use serde::Deserialize;
 
-pub struct TracerPlugin;
+use crate::attachment::Suppressor;
+use crate::billboard::{BillboardPlugin, MuzzleFlashSprites, MUZZLE_FLASH_SPRITE_SIZE};
+use crate::effect_rng::{EffectRng, TRACER_MUZZLE_FLASH_STREAM};
+use crate::events::{CharAnimEventsPlugin, EventMeta, EventRouting, FireEvent, UserData};
+use crate::schema::{SchemaError, VersionedAsset, VersionedRonLoader};
+use crate::state::PlayerAnimationState;
+use crate::vfx::{
+    add_particle_plugin, profile_layers, EffectLayers, EffectLight, EffectVisibility,
+    LayeredEffect, ParticleKind, VfxQuality, VfxQualityPlugin,
+};
+
+/// Tracers, muzzle flashes and their effects. Loads the ammo gradients in
+/// `Startup` and runs in `Update`, unless moved with [`TracerPlugin::in_startup_schedule`] and
+/// [`TracerPlugin::in_schedule`], e.g. to run with the visuals in
+/// `PostUpdate`.
+pub struct TracerPlugin {
+    /// The schedule the ammo gradients are loaded in.
+    pub startup_schedule: InternedScheduleLabel,
+    /// The schedule tracers are spawned, updated and despawned in.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for TracerPlugin {
+    fn default() -> Self {
+        Self {
+            startup_schedule: Startup.intern(),
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl TracerPlugin {
+    pub fn in_startup_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.startup_schedule = schedule.intern();
+        self
+    }
+
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
 
 impl Plugin for TracerPlugin {
     fn build(&self, app: &mut App) {
-        if !app.is_plugin_added::<HanabiPlugin>() {
-            app.add_plugins(HanabiPlugin);
+        add_particle_plugin(app);
+        if !app.is_plugin_added::<VfxQualityPlugin>() {
+            app.add_plugins(VfxQualityPlugin);
+        }
+        if !app.is_plugin_added::<BillboardPlugin>() {
+            app.add_plugins(BillboardPlugin);
+        }
+        if !app.is_plugin_added::<CharAnimEventsPlugin>() {
+            app.add_plugins(CharAnimEventsPlugin);
+        }
+        if !app.is_plugin_added::<EffectLifetimePlugin>() {
+            app.add_plugins(EffectLifetimePlugin::default().in_schedule(self.schedule));
         }
         app.add_plugins(MaterialPlugin::<TracerShader>::default());
-        app.add_systems(Startup, setup_muzzle_flash_particle_system);
-        app.add_systems(Update, despawn_tracers);
+        app.init_asset::<TracerProfile>();
+        app.init_asset_loader::<VersionedRonLoader<TracerProfile>>();
+        app.init_asset::<TracerGradient>();
+        app.init_asset_loader::<VersionedRonLoader<TracerGradient>>();
+        app.register_type::<TracerGradient>();
+        app.add_event::<SpawnTracer>();
+        app.register_type::<Tracer>();
+        app.register_type::<TracerProfile>();
+        app.init_resource::<MuzzleFlashEffects>();
+        app.init_resource::<TracerMaterials>();
+        app.add_systems(self.startup_schedule, load_tracer_gradients);
+        app.add_systems(
+            self.schedule,
+            (
+                spawn_tracers.in_set(TracerSet::Spawn),
+                update_tracer_materials
+                    .after(tick_effect_clocks)
+                    .before(TracerSet::Despawn)
+                    .run_if(|paused: Res<EffectsPaused>| !paused.paused),
+            ),
+        );
+        #[cfg(feature = "hanabi")]
+        app.add_systems(
+            self.schedule,
+            (
+                pause_effect_simulation.run_if(resource_changed::<EffectsPaused>),
+                rebuild_muzzle_flash_effects
+                    .run_if(resource_changed::<VfxQuality>.and(not(resource_added::<VfxQuality>))),
+            ),
+        );
+    }
+}
+
+/// The effect clocks, [`EffectsPaused`] and [`DespawnAfter`] countdowns that
+/// tracers and the other one shot effects share. Every effect plugin adds it
+/// if it's missing, in `Update` unless moved with
+/// [`EffectLifetimePlugin::in_schedule`].
+pub struct EffectLifetimePlugin {
+    /// The schedule the clocks tick and expired effects are despawned in.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for EffectLifetimePlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl EffectLifetimePlugin {
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for EffectLifetimePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DespawnAfter>();
+        app.register_type::<EffectsPaused>();
+        app.init_resource::<EffectsPaused>();
+        app.init_resource::<EffectClocks>();
+        app.init_resource::<ExpiryQueue>();
+        app.init_resource::<TracerPool>();
+        app.add_systems(
+            self.schedule,
+            (
+                tick_effect_clocks,
+                despawn_expired.in_set(TracerSet::Despawn),
+            )
+                .chain()
+                .run_if(|paused: Res<EffectsPaused>| !paused.paused),
+        );
+    }
+}
+
+/// The stages of tracers in the [`TracerPlugin`]'s schedule, to order systems
+/// against.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TracerSet {
+    /// Spawns the tracers requested with [`SpawnTracer`]. Send them before
+    /// this to spawn them this frame.
+    Spawn,
+    /// Despawns tracers and effects that have expired.
+    Despawn,
+}
+
+/// Requests a tracer to be spawned. All points are in global world space.
+#[derive(Event, Reflect, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpawnTracer {
+    /// Where the tracer is drawn from, e.g. the muzzle.
+    pub start: Vec3,
+    pub end: Vec3,
+    /// Where the shot was traced from if not `start`, e.g. the camera in first
+    /// person. The tracer is still drawn from `start`, converging on `end`
+    /// where the shot really hit.
+    pub logical_start: Option<Vec3>,
+    /// The look of the tracer, or the default look if none.
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub profile: Option<Handle<TracerProfile>>,
+    /// Passed on to the spawned [`Tracer`] and the `FireEvent`.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub user_data: Option<UserData>,
+    /// The muzzle entity the tracer is fired from. Its attachments, e.g. a
+    /// `Suppressor`, change how the tracer looks, and its `WeaponHeat` heats
+    /// up.
+    pub muzzle: Option<Entity>,
+    /// Colors the tracer by its ammo, or by its profile if none.
+    pub ammo: Option<AmmoType>,
+}
+
+/// The kind of round fired, which picks the tracer's [`TracerGradient`].
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum AmmoType {
+    Standard,
+    Incendiary,
+    Plasma,
+}
+
+/// The colors of a tracer for an ammo type, loaded from `.gradient.ron` files.
+/// They replace the profile's colors, the rest of the profile still applies.
+#[derive(Asset, Reflect, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[reflect(Default)]
+#[serde(default)]
+pub struct TracerGradient {
+    /// Linear RGBA colour at the start of the tracer.
+    pub start_color: [f32; 4],
+    /// Linear RGBA colour at the end of the tracer, which the light takes too.
+    pub end_color: [f32; 4],
+    /// Scales the light intensity of the tracer.
+    pub glow: f32,
+}
+
+impl Default for TracerGradient {
+    fn default() -> Self {
+        Self {
+            start_color: LinearRgba::from(WHITE).to_f32_array(),
+            end_color: LinearRgba::from(YELLOW).to_f32_array(),
+            glow: 1.0,
+        }
+    }
+}
+
+impl TracerGradient {
+    pub fn apply(&self, profile: &mut TracerProfile) {
+        profile.start_color = self.start_color;
+        profile.end_color = self.end_color;
+        profile.light_intensity *= self.glow;
+    }
+}
+
+impl VersionedAsset for TracerGradient {
+    const CURRENT_VERSION: u32 = 1;
+    const EXTENSIONS: &'static [&'static str] = &["gradient.ron"];
+
+    fn migrate(from_version: u32, _fields: &mut Map) -> Result<(), SchemaError> {
+        match from_version {
+            // Unversioned files have the same fields as version 1.
+            0 => Ok(()),
+            _ => Err(SchemaError::Migration {
+                from_version,
+                reason: "unknown version".into(),
+            }),
+        }
     }
 }
 
-#[derive(Resource, Deref)]
-struct MuzzleFlashEffect(Handle<EffectAsset>);
+/// The gradients for each ammo type, loaded from `assets/tracers`.
+#[derive(Resource)]
+pub struct TracerGradients {
+    pub standard: Handle<TracerGradient>,
+    pub incendiary: Handle<TracerGradient>,
+    pub plasma: Handle<TracerGradient>,
+}
 
+impl TracerGradients {
+    pub fn get(&self, ammo: AmmoType) -> &Handle<TracerGradient> {
+        match ammo {
+            AmmoType::Standard => &self.standard,
+            AmmoType::Incendiary => &self.incendiary,
+            AmmoType::Plasma => &self.plasma,
+        }
+    }
+
+    /// Applies the gradient of an ammo type to a profile, if it has loaded.
+    pub(crate) fn apply(
+        &self,
+        ammo: Option<AmmoType>,
+        gradients: &Assets<TracerGradient>,
+        profile: &mut TracerProfile,
+    ) {
+        if let Some(gradient) = ammo.and_then(|ammo| gradients.get(self.get(ammo))) {
+            gradient.apply(profile);
+        }
+    }
+}
+
+/// The clock that an effect ages by.
+#[derive(Reflect, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+pub enum EffectClock {
+    /// Slows down and speeds up with `Time<Virtual>`, e.g. for slow motion.
+    #[default]
+    Virtual,
+    /// Always runs at wall clock speed, e.g. for UI effects that shouldn't slow
+    /// down in a kill cam.
+    Real,
+}
+
+/// Whether effects are frozen, e.g. while the game is paused. Tracers stop
+/// fading, particles stop simulating and `DespawnAfter` countdowns stop, on
+/// either clock, and all carry on where they left off once unpaused.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct EffectsPaused {
+    pub paused: bool,
+}
+
+/// Which muzzle flash a tracer is fired with.
+#[derive(Reflect, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+pub enum MuzzleFlash {
+    #[default]
+    Full,
+    /// A faint puff, e.g. for suppressed weapons.
+    Minimal,
+    /// A camera facing sprite rather than particles, see `BillboardPlugin`.
+    Sprite,
+    None,
+}
+
+/// How a tracer looks, loaded from `.tracer.ron` files.
+#[derive(Asset, Reflect, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[reflect(Default)]
+#[serde(default)]
+pub struct TracerProfile {
+    pub radius: f32,
+    pub lifetime_secs: f32,
+    /// Linear RGBA colour at the start of the tracer.
+    pub start_color: [f32; 4],
+    /// Linear RGBA colour at the end of the tracer.
+    pub end_color: [f32; 4],
+    /// Half the length of the visible streak, as a fraction of the whole
+    /// tracer.
+    pub tracer_length: f32,
+    /// Scales the streak colors, above 1 for HDR glow.
+    pub brightness: f32,
+    /// How far the streak moves along the tracer over its lifetime, 1 is from
+    /// the start to the end.
+    pub speed: f32,
+    /// How the streak fades out over its lifetime: 0 doesn't fade, 1 fades
+    /// linearly and higher values fade sooner.
+    pub fade_exponent: f32,
+    pub light_intensity: f32,
+    pub light_shadows: bool,
+    pub muzzle_flash: MuzzleFlash,
+    /// The clock the tracer fades and despawns by. Particles always follow
+    /// virtual time, as Hanabi simulates every effect on one clock.
+    pub clock: EffectClock,
+    /// The render layers the tracer and its flash and light are drawn on, so
+    /// only the cameras on those layers see them. If none, the shooter's
+    /// `EffectLayers` are used, or the default layer.
+    pub render_layers: Option<Vec<usize>>,
+}
+
+impl Default for TracerProfile {
+    fn default() -> Self {
+        Self {
+            radius: 0.05,
+            lifetime_secs: 0.1,
+            start_color: LinearRgba::from(WHITE).to_f32_array(),
+            end_color: LinearRgba::from(YELLOW).to_f32_array(),
+            tracer_length: 0.3,
+            brightness: 1.0,
+            speed: 1.0,
+            fade_exponent: 0.0,
+            light_intensity: 40_000.0,
+            light_shadows: true,
+            muzzle_flash: MuzzleFlash::Full,
+            clock: EffectClock::Virtual,
+            render_layers: None,
+        }
+    }
+}
+
+impl VersionedAsset for TracerProfile {
+    const CURRENT_VERSION: u32 = 1;
+    const EXTENSIONS: &'static [&'static str] = &["tracer.ron"];
+
+    fn migrate(from_version: u32, _fields: &mut Map) -> Result<(), SchemaError> {
+        match from_version {
+            // Unversioned files have the same fields as version 1.
+            0 => Ok(()),
+            _ => Err(SchemaError::Migration {
+                from_version,
+                reason: "unknown version".into(),
+            }),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_lifetime(self.lifetime_secs)
+    }
+}
+
+/// Checks that a lifetime in seconds can be made a `Duration`.
+pub(crate) fn validate_lifetime(lifetime_secs: f32) -> Result<(), String> {
+    if lifetime_secs.is_finite() && lifetime_secs >= 0.0 {
+        Ok(())
+    } else {
+        Err(format!("lifetime_secs must be at least 0, not {lifetime_secs}"))
+    }
+}
+
+/// The muzzle flash effects of a world. It's built when the plugin is added,
+/// so every world or sub-app the plugin is added to gets its own, made in its
+/// own effect assets. The CPU particles need no assets.
+#[derive(Resource)]
+#[cfg_attr(not(feature = "hanabi"), derive(Default))]
+pub(crate) struct MuzzleFlashEffects {
+    #[cfg(feature = "hanabi")]
+    full: Handle<EffectAsset>,
+    #[cfg(feature = "hanabi")]
+    minimal: Handle<EffectAsset>,
+}
+
+#[cfg(feature = "hanabi")]
+impl FromWorld for MuzzleFlashEffects {
+    fn from_world(world: &mut World) -> Self {
+        let quality = *world.resource::<VfxQuality>();
+        let mut effects = world.resource_mut::<Assets<EffectAsset>>();
+        Self {
+            full: effects.add(muzzle_flash_effect(quality.particles(16.0), 0.16, 0.07)),
+            minimal: effects.add(muzzle_flash_effect(quality.particles(4.0), 0.05, 0.03)),
+        }
+    }
+}
+
+impl MuzzleFlashEffects {
+    /// The particles of a `flash` seeded from `rng`, if it has any.
+    #[cfg_attr(not(feature = "hanabi"), allow(unused_variables))]
+    pub(crate) fn particles(&self, flash: MuzzleFlash, rng: &mut impl Rng) -> Option<impl Bundle> {
+        let kind = match flash {
+            MuzzleFlash::Full => ParticleKind::MuzzleFlash,
+            MuzzleFlash::Minimal => ParticleKind::MinimalMuzzleFlash,
+            MuzzleFlash::Sprite | MuzzleFlash::None => return None,
+        };
+        #[cfg(feature = "hanabi")]
+        let effect = ParticleEffect {
+            prng_seed: Some(rng.gen()),
+            ..ParticleEffect::new(match flash {
+                MuzzleFlash::Full => self.full.clone(),
+                _ => self.minimal.clone(),
+            })
+        };
+        #[cfg(not(feature = "hanabi"))]
+        let effect = ();
+        Some((kind, effect))
+    }
+}
+
+#[derive(Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tracer {
     /// End is in global world space.
     pub end: Vec3,
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub profile: Option<Handle<TracerProfile>>,
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub user_data: Option<UserData>,
+    pub muzzle: Option<Entity>,
+    pub ammo: Option<AmmoType>,
 }
 
 impl Component for Tracer {
@@ -39,7 +470,9 @@ impl Component for Tracer {
     const STORAGE_TYPE: StorageType = StorageType::Table;
 
     fn register_component_hooks(hooks: &mut ComponentHooks) {
-        hooks.on_add(
+        // On insert rather than on add, so a pooled tracer is rebuilt when it
+        // is fired again with a new `Tracer`.
+        hooks.on_insert(
             |mut world,
              HookContext {
                  entity,
@@ -47,122 +480,689 @@ impl Component for Tracer {
                  caller: _,
                  relationship_hook_mode: _,
              }: HookContext| {
-                let current_time = world.resource::<Time>().elapsed();
-                let lifetime = Duration::from_millis(TRACER_DURATION_MILLIS);
-                let despawn_after = DespawnAfter {
-                    spawned_at: current_time,
-                    lifetime,
-                };
-
                 let tracer = world.get::<Self>(entity).unwrap();
+                let tracer_end = tracer.end;
+                let mut profile = tracer
+                    .profile
+                    .as_ref()
+                    .and_then(|handle| world.resource::<Assets<TracerProfile>>().get(handle))
+                    .cloned()
+                    .unwrap_or_default();
+                // Worlds that were never started, e.g. a preview world, have no
+                // gradients.
+                if let Some(gradients) = world.get_resource::<TracerGradients>() {
+                    gradients.apply(
+                        tracer.ammo,
+                        world.resource::<Assets<TracerGradient>>(),
+                        &mut profile,
+                    );
+                }
+                if let Some(suppressor) = tracer.muzzle.and_then(|e| world.get::<Suppressor>(e)) {
+                    suppressor.apply(&mut profile);
+                }
+                // Tracers from a slowed or hasted character play out at its pace,
+                // and from a frozen one as slowly as a tenth. The lifetime is part
+                // of the material key, so the pace is rounded to a tenth between
+                // 0.1 and 10, and there are only so many materials per profile.
+                if let Some(time_scale) = tracer.muzzle.map(|e| character_time_scale(&world, e)) {
+                    let pace = (time_scale.clamp(0.1, 10.0) * 10.0).round() / 10.0;
+                    profile.lifetime_secs /= pace;
+                }
+                let vfx = world.resource::<VfxQuality>().settings();
+                let mut shooter = tracer.muzzle;
+                let mut shooter_layers = None;
+                while let Some(e) = shooter {
+                    if let Some(layers) = world.get::<EffectLayers>(e) {
+                        shooter_layers = Some(layers.0.clone());
+                        break;
+                    }
+                    shooter = world.get::<ChildOf>(e).map(ChildOf::parent);
+                }
+                let layers =
+                    profile_layers(&profile.render_layers, shooter_layers).unwrap_or_default();
+
+                let lifetime = Duration::from_secs_f32(profile.lifetime_secs);
+                let despawn_after = DespawnAfter::new(lifetime, profile.clock);
+
                 let tracer_start = world.get::<Transform>(entity).unwrap().translation;
-                let asset_server = world.resource::<AssetServer>();
-                let muzzle_flash = world.resource::<MuzzleFlashEffect>();
-                let muzzle_flash_handle = muzzle_flash.0.clone();
+                let mut flash_rng = world
+                    .get_resource_mut::<EffectRng>()
+                    .map_or_else(StdRng::from_entropy, |mut rng| {
+                        rng.fork(TRACER_MUZZLE_FLASH_STREAM)
+                    });
+                let muzzle_flash = world
+                    .get_resource::<MuzzleFlashEffects>()
+                    .and_then(|effects| effects.particles(profile.muzzle_flash, &mut flash_rng));
+                let sprite = world
+                    .get_resource::<MuzzleFlashSprites>()
+                    .filter(|_| profile.muzzle_flash == MuzzleFlash::Sprite)
+                    .map(|sprites| {
+                        sprites.sprite(Vec3::ZERO, MUZZLE_FLASH_SPRITE_SIZE, &mut flash_rng)
+                    });
+                // The spawn time goes in the mesh tag, so that tracers with the
+                // same profile can share one material and be batched.
+                let spawned_at = world.resource::<EffectClocks>().millis(profile.clock);
+                let asset_server = world.resource::<AssetServer>().clone();
+                let (tracer_mesh, tracer_material) = world
+                    .resource_mut::<TracerMaterials>()
+                    .get_or_add(&profile, &asset_server);
 
-                // Calculate the midpoint and rotation for the tracer
-                let direction = tracer.end - tracer_start;
+                // Calculate the midpoint and rotation for the tracer. The mesh
+                // is a unit cylinder, scaled to the tracer's radius and length.
+                let direction = tracer_end - tracer_start;
                 let distance = direction.length();
-                let cylinder = Cylinder::new(TRACER_RADIUS, distance).mesh().build();
-                let tracer_mesh = asset_server.add(cylinder);
-                let tracer_material = asset_server.add(TracerShader {
-                    tracer_start: WHITE.into(),
-                    tracer_end: YELLOW.into(),
-                    time_spawned: current_time.as_secs_f32(),
-                    time_alive: lifetime.as_secs_f32(),
-                    tracer_length: 0.3,
-                });
-
-                // Calculate the rotation to align the tracer with the direction vector
                 let rotation = Quat::from_rotation_arc(Vec3::Y, direction.normalize());
-                let mut transform = Transform::from_rotation(rotation);
+                let mut transform = Transform::from_rotation(rotation).with_scale(Vec3::new(
+                    profile.radius,
+                    distance,
+                    profile.radius,
+                ));
                 transform.translation += direction / 2.0;
 
                 let particle_rotation = Quat::from_rotation_arc(Vec3::NEG_Z, direction.normalize());
+                let light = vfx.tracer_lights.then(|| PointLight {
+                    color: LinearRgba::from_f32_array(profile.end_color).into(),
+                    shadows_enabled: profile.light_shadows && vfx.tracer_shadows,
+                    intensity: profile.light_intensity,
+                    ..default()
+                });
+                let mesh_bundle = (
+                    Mesh3d(tracer_mesh),
+                    MeshMaterial3d(tracer_material),
+                    MeshTag(spawned_at),
+                    transform,
+                    layers.clone(),
+                );
 
-                world
-                    .commands()
+                let parts = world.get::<TracerParts>(entity).copied();
+                let mut commands = world.commands();
+                commands
                     .entity(entity)
-                    .insert(despawn_after)
-                    .insert(Visibility::default())
-                    .with_children(|parent| {
-                        parent.spawn((
-                            Mesh3d(tracer_mesh),
-                            MeshMaterial3d(tracer_material),
-                            NotShadowCaster,
-                            transform,
-                            Visibility::default(),
-                        ));
-                        parent.spawn((
-                            PointLight {
-                                color: YELLOW.into(),
-                                shadows_enabled: true,
-                                intensity: 40_000.0,
-                                ..default()
-                            },
-                            Transform::default(),
-                            Visibility::default(),
-                        ));
-                        parent.spawn((
-                            ParticleEffect::new(muzzle_flash_handle),
-                            Transform::from_rotation(particle_rotation),
-                        ));
-                    });
+                    .insert((
+                        despawn_after,
+                        Visibility::Inherited,
+                        layers.clone(),
+                        LayeredEffect,
+                    ))
+                    .remove::<PooledTracer>();
+                let parts = match parts {
+                    // A pooled tracer, whose hierarchy only needs updating.
+                    Some(mut parts) => {
+                        commands.entity(parts.mesh).insert(mesh_bundle);
+                        match (parts.light, light) {
+                            (Some(old), Some(light)) => {
+                                commands.entity(old).insert((light, layers.clone()));
+                            }
+                            (Some(old), None) => {
+                                commands.entity(old).despawn();
+                                parts.light = None;
+                            }
+                            (None, Some(light)) => {
+                                let light = commands
+                                    .spawn((
+                                        light,
+                                        EffectLight::default(),
+                                        layers.clone(),
+                                        ChildOf(entity),
+                                    ))
+                                    .id();
+                                parts.light = Some(light);
+                            }
+                            (None, None) => {}
+                        }
+                        parts
+                    }
+                    None => {
+                        let mesh = commands
+                            .spawn((mesh_bundle, NotShadowCaster, ChildOf(entity)))
+                            .id();
+                        let light = light.map(|light| {
+                            commands
+                                .spawn((
+                                    light,
+                                    EffectLight::default(),
+                                    Transform::default(),
+                                    layers.clone(),
+                                    ChildOf(entity),
+                                ))
+                                .id()
+                        });
+                        TracerParts {
+                            mesh,
+                            light,
+                            flash: None,
+                        }
+                    }
+                };
+                // The flash is spawned fresh each time to restart its burst.
+                let flash = match (muzzle_flash, sprite) {
+                    (Some(muzzle_flash), _) => Some(
+                        commands
+                            .spawn((
+                                muzzle_flash,
+                                Transform::from_rotation(particle_rotation),
+                                layers.clone(),
+                                ChildOf(entity),
+                            ))
+                            .id(),
+                    ),
+                    (None, Some(sprite)) => {
+                        Some(commands.spawn((sprite, layers, ChildOf(entity))).id())
+                    }
+                    (None, None) => None,
+                };
+                commands.entity(entity).insert(TracerParts { flash, ..parts });
             },
         );
     }
 }
 
+/// The time scale of the character that `entity` is part of, or 1 if none.
+fn character_time_scale(world: &DeferredWorld, entity: Entity) -> f32 {
+    let mut search = entity;
+    loop {
+        if let Some(state) = world.get::<PlayerAnimationState>(search) {
+            return state.time_scale();
+        }
+        let Some(parent) = world.get::<ChildOf>(search) else {
+            return 1.0;
+        };
+        search = parent.parent();
+    }
+}
+
+/// The children of a tracer, kept so a pooled tracer can be reused.
+#[derive(Component, Clone, Copy)]
+struct TracerParts {
+    mesh: Entity,
+    light: Option<Entity>,
+    flash: Option<Entity>,
+}
+
+/// Marks a finished tracer that is hidden in the [`TracerPool`], waiting to be
+/// fired again.
+#[derive(Component)]
+pub struct PooledTracer;
+
+/// Finished tracers are hidden and kept here rather than despawned, and the
+/// next tracers reuse them, which saves spawning and despawning a hierarchy
+/// per shot.
+#[derive(Resource)]
+pub struct TracerPool {
+    /// The most finished tracers kept. Tracers finishing once the pool is
+    /// full are despawned.
+    pub max_size: usize,
+    idle: Vec<Entity>,
+    stats: TracerPoolStats,
+}
+
+impl Default for TracerPool {
+    fn default() -> Self {
+        Self {
+            max_size: 64,
+            idle: Vec::new(),
+            stats: TracerPoolStats::default(),
+        }
+    }
+}
+
+impl TracerPool {
+    /// How many finished tracers are waiting to be reused.
+    pub fn idle(&self) -> usize {
+        self.idle.len()
+    }
+
+    pub fn stats(&self) -> TracerPoolStats {
+        self.stats
+    }
+}
+
+/// Running totals of the tracer pool.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracerPoolStats {
+    /// Tracers spawned because the pool was empty.
+    pub spawned: u64,
+    /// Tracers fired from the pool.
+    pub reused: u64,
+    /// Finished tracers put back in the pool.
+    pub recycled: u64,
+    /// Finished tracers despawned because the pool was full.
+    pub despawned: u64,
+}
+
+/// The shader the tracer material draws with. A custom shader replacing it
+/// must declare `TracerParams` as in this file, bound to `@group(2)
+/// @binding(0)`.
+pub const TRACER_SHADER_PATH: &str = "shaders/tracer.wgsl";
+
+/// The tracer material. Tracers with the same look share one, so they batch,
+/// and each tracer's spawn time is passed per instance in its `MeshTag`, in
+/// milliseconds on its clock.
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-struct TracerShader {
+pub struct TracerShader {
+    /// All the parameters are in one uniform buffer, as WebGL2 only allows a
+    /// handful of uniform buffers per shader stage.
     #[uniform(0)]
-    tracer_start: LinearRgba,
-    #[uniform(1)]
-    tracer_end: LinearRgba,
+    pub params: TracerParams,
+}
+
+/// Matches `TracerParams` in `tracer.wgsl`, field for field, so keep the two
+/// in sync. The fields other than `now_millis` come from the
+/// [`TracerProfile`].
+#[derive(ShaderType, Clone, Debug)]
+pub struct TracerParams {
+    pub tracer_start: LinearRgba,
+    pub tracer_end: LinearRgba,
+    /// The current time in milliseconds on the tracer's clock. A tracer's age
+    /// is this minus its mesh tag.
+    pub now_millis: u32,
+    pub time_alive: f32,
+    pub tracer_length: f32,
+    pub brightness: f32,
+    pub speed: f32,
+    pub fade_exponent: f32,
+}
+
+impl TracerParams {
+    pub fn new(profile: &TracerProfile) -> Self {
+        Self {
+            tracer_start: LinearRgba::from_f32_array(profile.start_color),
+            tracer_end: LinearRgba::from_f32_array(profile.end_color),
+            now_millis: 0,
+            time_alive: profile.lifetime_secs,
+            tracer_length: profile.tracer_length,
+            brightness: profile.brightness,
+            speed: profile.speed,
+            fade_exponent: profile.fade_exponent,
+        }
+    }
+
+    /// A still, unfading streak of one color that's longer than the mesh, so
+    /// the whole cylinder is drawn, e.g. for a rope or an aim line.
+    pub fn beam(color: LinearRgba, brightness: f32) -> Self {
+        Self {
+            tracer_start: color,
+            tracer_end: color,
+            speed: 0.0,
+            tracer_length: 1.0,
+            brightness,
+            fade_exponent: 0.0,
+            ..Self::new(&TracerProfile::default())
+        }
+    }
+}
 
-    #[uniform(2)]
-    time_spawned: f32,
-    #[uniform(3)]
-    time_alive: f32,
-    #[uniform(4)]
-    tracer_length: f32,
+/// The transform that stretches the unit tracer cylinder, which is a unit
+/// tall along Y and centered, from `from` to `to`. None if they're the same.
+pub(crate) fn beam_transform(from: Vec3, to: Vec3, radius: f32) -> Option<Transform> {
+    let direction = to - from;
+    let axis = direction.try_normalize()?;
+    Some(
+        Transform::from_translation((from + to) / 2.0)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Y, axis))
+            .with_scale(Vec3::new(radius, direction.length(), radius)),
+    )
 }
 
 impl Material for TracerShader {
     fn fragment_shader() -> ShaderRef {
-        "shaders/tracer.wgsl".into()
+        TRACER_SHADER_PATH.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        // Only fading tracers need blending.
+        if self.params.fade_exponent > 0.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        }
     }
 }
 
-#[derive(Component, Clone)]
-struct DespawnAfter {
-    spawned_at: Duration,
+/// How long each clock has run for effects, which stops while they're paused.
+#[derive(Resource, Default)]
+struct EffectClocks {
+    virtual_elapsed: Duration,
+    real_elapsed: Duration,
+}
+
+impl EffectClocks {
+    fn elapsed(&self, clock: EffectClock) -> Duration {
+        match clock {
+            EffectClock::Virtual => self.virtual_elapsed,
+            EffectClock::Real => self.real_elapsed,
+        }
+    }
+
+    /// The elapsed time in wrapping milliseconds, which the shader subtracts
+    /// exactly however long the game runs.
+    fn millis(&self, clock: EffectClock) -> u32 {
+        self.elapsed(clock).as_millis() as u32
+    }
+}
+
+/// The shared tracer mesh and a material per tracer look and clock.
+#[derive(Resource, Default)]
+struct TracerMaterials {
+    mesh: Option<Handle<Mesh>>,
+    materials: HashMap<[u32; 14], (EffectClock, Handle<TracerShader>)>,
+}
+
+impl TracerMaterials {
+    fn get_or_add(
+        &mut self,
+        profile: &TracerProfile,
+        asset_server: &AssetServer,
+    ) -> (Handle<Mesh>, Handle<TracerShader>) {
+        let mesh = self
+            .mesh
+            .get_or_insert_with(|| asset_server.add(Cylinder::new(1.0, 1.0).mesh().build()))
+            .clone();
+        // Keyed by everything the material takes from the profile.
+        let mut key = [profile.clock as u32; 14];
+        for (bits, value) in key.iter_mut().zip(
+            profile.start_color.into_iter().chain(profile.end_color).chain([
+                profile.lifetime_secs,
+                profile.tracer_length,
+                profile.brightness,
+                profile.speed,
+                profile.fade_exponent,
+            ]),
+        ) {
+            *bits = value.to_bits();
+        }
+        let (_, material) = self.materials.entry(key).or_insert_with(|| {
+            let material = asset_server.add(TracerShader {
+                params: TracerParams::new(profile),
+            });
+            (profile.clock, material)
+        });
+        (mesh, material.clone())
+    }
+}
+
+/// Despawns an entity once it has been alive for `lifetime` on its clock, e.g.
+/// a one shot effect. The countdown starts when the component is inserted and
+/// restarts if it's inserted again.
+#[derive(Reflect, Clone)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct DespawnAfter {
     lifetime: Duration,
+    clock: EffectClock,
+    /// When to despawn on the effect clock, set on insert.
+    deadline: Duration,
+}
+
+impl DespawnAfter {
+    pub fn new(lifetime: Duration, clock: EffectClock) -> Self {
+        Self {
+            lifetime,
+            clock,
+            deadline: Duration::ZERO,
+        }
+    }
+}
+
+impl Component for DespawnAfter {
+    type Mutability = Mutable;
+
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        hooks.on_insert(|mut world, HookContext { entity, .. }| {
+            let despawn_after = world.get::<Self>(entity).unwrap();
+            let clock = despawn_after.clock;
+            let lifetime = despawn_after.lifetime;
+            let deadline = world.resource::<EffectClocks>().elapsed(clock) + lifetime;
+            world.get_mut::<Self>(entity).unwrap().deadline = deadline;
+            world.resource_mut::<ExpiryQueue>().push(clock, deadline, entity);
+        });
+    }
+}
+
+/// The entities with a [`DespawnAfter`], soonest deadline first, so only the
+/// expired ones are looked at each frame. Entries whose entity has been
+/// despawned, lost its `DespawnAfter` or got a new deadline are skipped when
+/// they come up.
+#[derive(Resource, Default)]
+struct ExpiryQueue {
+    virtual_clock: BinaryHeap<Reverse<(Duration, Entity)>>,
+    real_clock: BinaryHeap<Reverse<(Duration, Entity)>>,
 }
 
-fn despawn_tracers(
+impl ExpiryQueue {
+    fn push(&mut self, clock: EffectClock, deadline: Duration, entity: Entity) {
+        self.heap(clock).push(Reverse((deadline, entity)));
+    }
+
+    fn heap(&mut self, clock: EffectClock) -> &mut BinaryHeap<Reverse<(Duration, Entity)>> {
+        match clock {
+            EffectClock::Virtual => &mut self.virtual_clock,
+            EffectClock::Real => &mut self.real_clock,
+        }
+    }
+
+    /// Pops the entries that are due by `now`.
+    fn pop_expired(&mut self, clock: EffectClock, now: Duration, expired: &mut Vec<Entity>) {
+        let heap = self.heap(clock);
+        while let Some(Reverse((deadline, entity))) = heap.peek().copied() {
+            if deadline > now {
+                break;
+            }
+            heap.pop();
+            expired.push(entity);
+        }
+    }
+}
+
+fn spawn_tracers(
     mut commands: Commands,
-    tracers: Query<(Entity, &DespawnAfter)>,
+    mut events: EventReader<SpawnTracer>,
+    mut pool: ResMut<TracerPool>,
+    pooled: Query<(), With<PooledTracer>>,
+    visibility: EffectVisibility,
     time: Res<Time>,
+    routing: Res<EventRouting>,
+    mut fire_events: EventWriter<FireEvent>,
+) {
+    for event in events.read() {
+        if !visibility.sees_segment(event.start, event.end, 0.0) {
+            if routing.emits::<FireEvent>() {
+                fire_events.write(FireEvent {
+                    meta: EventMeta::new(Entity::PLACEHOLDER, &time, event.start),
+                    end: event.end,
+                    muzzle: event.muzzle,
+                    user_data: event.user_data.clone(),
+                });
+            }
+            continue;
+        }
+        // A hit between the camera and the muzzle would draw the tracer
+        // backwards, so draw it from the logical start instead.
+        let start = match event.logical_start {
+            Some(logical_start)
+                if (event.end - event.start).dot(event.end - logical_start) <= 0.0 =>
+            {
+                logical_start
+            }
+            _ => event.start,
+        };
+        let bundle = (
+            Tracer {
+                end: event.end,
+                profile: event.profile.clone(),
+                user_data: event.user_data.clone(),
+                muzzle: event.muzzle,
+                ammo: event.ammo,
+            },
+            Transform::from_translation(start),
+        );
+        // Skip pooled tracers that were despawned by something else.
+        let mut idle = None;
+        while let Some(tracer) = pool.idle.pop() {
+            if pooled.contains(tracer) {
+                idle = Some(tracer);
+                break;
+            }
+        }
+        let tracer = match idle {
+            Some(tracer) => {
+                pool.stats.reused += 1;
+                commands.entity(tracer).insert(bundle);
+                tracer
+            }
+            None => {
+                pool.stats.spawned += 1;
+                commands.spawn(bundle).id()
+            }
+        };
+        if routing.emits::<FireEvent>() {
+            fire_events.write(FireEvent {
+                meta: EventMeta::new(tracer, &time, event.start),
+                end: event.end,
+                muzzle: event.muzzle,
+                user_data: event.user_data.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(feature = "hanabi")]
+fn pause_effect_simulation(
+    paused: Res<EffectsPaused>,
+    simulation: Option<ResMut<Time<EffectSimulation>>>,
+) {
+    // There's no Hanabi simulation with the CPU particle fallback.
+    let Some(mut simulation) = simulation else {
+        return;
+    };
+    if paused.paused {
+        simulation.pause();
+    } else {
+        simulation.unpause();
+    }
+}
+
+fn tick_effect_clocks(
+    mut clocks: ResMut<EffectClocks>,
+    virtual_time: Res<Time<Virtual>>,
+    real_time: Res<Time<Real>>,
 ) {
-    for (entity, tracer) in tracers.iter() {
-        if tracer.spawned_at + tracer.lifetime < time.elapsed() {
-            commands.entity(entity).despawn_recursive();
+    clocks.virtual_elapsed += virtual_time.delta();
+    clocks.real_elapsed += real_time.delta();
+}
+
+/// Feeds the effect clocks to the tracer materials, rather than letting the
+/// shader read the global time, so tracers can fade on either clock.
+fn update_tracer_materials(
+    tracer_materials: Res<TracerMaterials>,
+    clocks: Res<EffectClocks>,
+    mut materials: ResMut<Assets<TracerShader>>,
+) {
+    for (clock, handle) in tracer_materials.materials.values() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.params.now_millis = clocks.millis(*clock);
         }
     }
 }
 
-fn setup_muzzle_flash_particle_system(
-    mut effects: ResMut<Assets<EffectAsset>>,
+/// Pops the expired entities off the [`ExpiryQueue`] and despawns them in one
+/// command, or puts them back in the [`TracerPool`] if they're tracers.
+fn despawn_expired(
     mut commands: Commands,
+    mut queue: ResMut<ExpiryQueue>,
+    clocks: Res<EffectClocks>,
+    effects: Query<(&DespawnAfter, Option<&TracerParts>)>,
+    mut pool: ResMut<TracerPool>,
+    mut expired: Local<Vec<Entity>>,
 ) {
+    expired.clear();
+    for clock in [EffectClock::Virtual, EffectClock::Real] {
+        queue.pop_expired(clock, clocks.elapsed(clock), &mut expired);
+    }
+    if expired.is_empty() {
+        return;
+    }
+
+    let mut despawn = Vec::new();
+    let mut recycle = Vec::new();
+    for &entity in expired.iter() {
+        let Ok((effect, parts)) = effects.get(entity) else {
+            continue;
+        };
+        // A newer deadline has its own entry.
+        if effect.deadline > clocks.elapsed(effect.clock) {
+            continue;
+        }
+        match parts {
+            Some(parts) if pool.idle.len() < pool.max_size => {
+                pool.idle.push(entity);
+                pool.stats.recycled += 1;
+                recycle.push((entity, parts.flash));
+            }
+            Some(_) => {
+                pool.stats.despawned += 1;
+                despawn.push(entity);
+            }
+            None => despawn.push(entity),
+        }
+    }
+    commands.queue(move |world: &mut World| {
+        for entity in despawn {
+            world.despawn(entity);
+        }
+        // Hide the tracers and stop their countdowns until they're reused.
+        for (entity, flash) in recycle {
+            if let Some(flash) = flash {
+                world.despawn(flash);
+            }
+            if let Ok(mut tracer) = world.get_entity_mut(entity) {
+                tracer
+                    .insert((Visibility::Hidden, PooledTracer))
+                    .remove::<DespawnAfter>();
+            }
+        }
+    });
+}
+
+fn load_tracer_gradients(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TracerGradients {
+        standard: asset_server.load("tracers/standard.gradient.ron"),
+        incendiary: asset_server.load("tracers/incendiary.gradient.ron"),
+        plasma: asset_server.load("tracers/plasma.gradient.ron"),
+    });
+}
+
+/// Rebuilds the muzzle flashes in place when the quality changes, so the
+/// handles held elsewhere stay valid.
+#[cfg(feature = "hanabi")]
+fn rebuild_muzzle_flash_effects(
+    mut effects: ResMut<Assets<EffectAsset>>,
+    muzzle_flashes: Res<MuzzleFlashEffects>,
+    quality: Res<VfxQuality>,
+) {
+    effects.insert(
+        &muzzle_flashes.full,
+        muzzle_flash_effect(quality.particles(16.0), 0.16, 0.07),
+    );
+    effects.insert(
+        &muzzle_flashes.minimal,
+        muzzle_flash_effect(quality.particles(4.0), 0.05, 0.03),
+    );
+}
+
+/// A burst of `count` particles within `radius` of the muzzle, each about
+/// `size` big.
+#[cfg(feature = "hanabi")]
+fn muzzle_flash_effect(count: f32, radius: f32, size: f32) -> EffectAsset {
     let writer = ExprWriter::new();
 
     // Position the particle laterally within a small radius.
     let init_xz_pos = SetPositionCircleModifier {
         center: writer.lit(Vec3::ZERO).expr(),
         axis: writer.lit(Vec3::Z).expr(),
-        radius: writer.lit(0.16).expr(),
+        radius: writer.lit(radius).expr(),
         dimension: ShapeDimension::Volume,
     };
 
@@ -173,7 +1173,7 @@ fn setup_muzzle_flash_particle_system(
     // Vary the size a bit.
     let init_size = SetAttributeModifier::new(
         Attribute::F32_0,
-        (writer.rand(ScalarType::Float) * writer.lit(0.05) + writer.lit(0.07)).expr(),
+        (writer.rand(ScalarType::Float) * writer.lit(size * 0.7) + writer.lit(size)).expr(),
     );
 
     // Make the particles move backwards at a constant speed.
@@ -198,17 +1198,40 @@ fn setup_muzzle_flash_particle_system(
 
     let module = writer.finish();
 
-    // Add the effect.
-    let handle = effects.add(
-        EffectAsset::new(256, SpawnerSettings::burst(16.0.into(), 0.45.into()), module)
-            .with_simulation_space(SimulationSpace::Local)
-            .with_name("cartoon explosion")
-            .init(init_xz_pos)
-            .init(init_age)
-            .init(init_lifetime)
-            .init(init_size)
-            .init(init_velocity)
-            .update(update_size),
-    );
-    commands.insert_resource(MuzzleFlashEffect(handle));
+    EffectAsset::new(256, SpawnerSettings::burst(count.into(), 0.45.into()), module)
+        .with_simulation_space(SimulationSpace::Local)
+        .with_name("cartoon explosion")
+        .init(init_xz_pos)
+        .init(init_age)
+        .init(init_lifetime)
+        .init(init_size)
+        .init(init_velocity)
+        .update(update_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::parse_versioned;
+
+    #[test]
+    fn loads_unversioned_and_current_tracer_profiles() {
+        for version in ["", "version: 1,"] {
+            let source = format!("({version} radius: 0.1, lifetime_secs: 0.5, muzzle_flash: None)");
+            let profile = parse_versioned::<TracerProfile>(source.as_bytes()).unwrap();
+            assert_eq!(profile.radius, 0.1);
+            assert_eq!(profile.lifetime_secs, 0.5);
+            assert_eq!(profile.muzzle_flash, MuzzleFlash::None);
+        }
+    }
+
+    #[test]
+    fn loads_unversioned_and_current_tracer_gradients() {
+        for version in ["", "version: 1,"] {
+            let source = format!("({version} end_color: (0.1, 0.5, 1.0, 1.0), glow: 2.0)");
+            let gradient = parse_versioned::<TracerGradient>(source.as_bytes()).unwrap();
+            assert_eq!(gradient.end_color, [0.1, 0.5, 1.0, 1.0]);
+            assert_eq!(gradient.glow, 2.0);
+        }
+    }
 }
@@ -0,0 +1,265 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::{app::Animation, prelude::*};
+
+use crate::events::{EventMeta, EventRouting, OnTargetEvent};
+use crate::hitscan::Shot;
+
+/// Turret aiming. A [`Turret`] yaws its base and pitches its barrel towards
+/// its target, within angle limits and at capped speeds, leading moving
+/// targets by its rounds' flight time. An `OnTargetEvent` is sent when it
+/// comes onto or goes off target, and [`Turret::shot`] fires from its muzzle,
+/// so its shots and tracers go through the hitscan like a character's.
+pub struct TurretPlugin;
+
+impl Plugin for TurretPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Turret>();
+        app.add_systems(
+            PostUpdate,
+            (
+                aim_turrets
+                    .after(Animation)
+                    .before(TransformSystem::TransformPropagate),
+                record_muzzles.after(TransformSystem::TransformPropagate),
+            ),
+        );
+    }
+}
+
+/// Iterations of the lead solve, each refining the flight time.
+const LEAD_ITERATIONS: usize = 3;
+
+/// What a turret aims at.
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum TurretTarget {
+    /// Tracks an entity, leading it if it moves.
+    Entity(Entity),
+    /// A fixed point in global world space.
+    Point(Vec3),
+}
+
+/// Add to the turret root. The base and barrel face +Z at rest like
+/// characters. The base yaws about its Y, and the barrel, a descendant of the
+/// base, pitches about its X. The muzzle fires along its +Z.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Turret {
+    pub base: Entity,
+    pub barrel: Entity,
+    pub muzzle: Entity,
+    /// The yaw range from rest in radians, or all the way round if none.
+    pub yaw_limits: Option<(f32, f32)>,
+    /// The pitch range from rest in radians, positive up.
+    pub pitch_limits: (f32, f32),
+    /// The fastest the base turns, in radians per second.
+    pub yaw_speed: f32,
+    /// The fastest the barrel turns, in radians per second.
+    pub pitch_speed: f32,
+    /// The speed of the turret's rounds in m/s, to lead moving targets by, or
+    /// none for hitscan weapons that don't need leading.
+    pub projectile_speed: Option<f32>,
+    /// How far off the aim can be in radians and still be on target.
+    pub tolerance: f32,
+    target: Option<TurretTarget>,
+    yaw: f32,
+    pitch: f32,
+    /// The rotations of the base and barrel at rest.
+    rest: Option<(Quat, Quat)>,
+    last_target_position: Option<Vec3>,
+    target_velocity: Vec3,
+    on_target: bool,
+    muzzle_transform: GlobalTransform,
+}
+
+impl Turret {
+    pub fn new(base: Entity, barrel: Entity, muzzle: Entity) -> Self {
+        Self {
+            base,
+            barrel,
+            muzzle,
+            yaw_limits: None,
+            pitch_limits: (-10f32.to_radians(), 60f32.to_radians()),
+            yaw_speed: 90f32.to_radians(),
+            pitch_speed: 60f32.to_radians(),
+            projectile_speed: None,
+            tolerance: 2f32.to_radians(),
+            target: None,
+            yaw: 0.0,
+            pitch: 0.0,
+            rest: None,
+            last_target_position: None,
+            target_velocity: Vec3::ZERO,
+            on_target: false,
+            muzzle_transform: GlobalTransform::IDENTITY,
+        }
+    }
+
+    /// Aims at a target, or goes back to rest if none.
+    pub fn set_target(&mut self, target: Option<TurretTarget>) {
+        if self.target != target {
+            self.last_target_position = None;
+            self.target_velocity = Vec3::ZERO;
+        }
+        self.target = target;
+    }
+
+    pub fn target(&self) -> Option<TurretTarget> {
+        self.target
+    }
+
+    pub fn is_on_target(&self) -> bool {
+        self.on_target
+    }
+
+    /// The current yaw and pitch from rest, in radians.
+    pub fn angles(&self) -> (f32, f32) {
+        (self.yaw, self.pitch)
+    }
+
+    /// The muzzle's global transform this frame.
+    pub fn muzzle_transform(&self) -> GlobalTransform {
+        self.muzzle_transform
+    }
+
+    /// A shot from the muzzle along the barrel. Set the shooter to the turret
+    /// root so the shot can't hit the turret.
+    pub fn shot(&self) -> Shot {
+        Shot {
+            muzzle: Some(self.muzzle),
+            ..Shot::new(
+                self.muzzle_transform.translation(),
+                self.muzzle_transform.rotation() * Vec3::Z,
+            )
+        }
+    }
+
+    /// Where to aim to hit a target at `position` moving at `velocity`, fired
+    /// from `from`.
+    fn lead(&self, from: Vec3, position: Vec3, velocity: Vec3) -> Vec3 {
+        let Some(speed) = self.projectile_speed.filter(|speed| *speed > 0.0) else {
+            return position;
+        };
+        let mut aim = position;
+        for _ in 0..LEAD_ITERATIONS {
+            let flight_secs = aim.distance(from) / speed;
+            aim = position + velocity * flight_secs;
+        }
+        aim
+    }
+}
+
+/// Moves `current` towards `target` by at most `max_step`.
+fn step_towards(current: f32, target: f32, max_step: f32) -> f32 {
+    current + (target - current).clamp(-max_step, max_step)
+}
+
+fn aim_turrets(
+    mut turrets: Query<(Entity, &mut Turret)>,
+    mut transforms: Query<&mut Transform>,
+    global_transforms: Query<&GlobalTransform>,
+    parents: Query<&ChildOf>,
+    mut events: EventWriter<OnTargetEvent>,
+    routing: Res<EventRouting>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for (root, mut turret) in turrets.iter_mut() {
+        let turret = turret.as_mut();
+        let (Ok(&base), Ok(&barrel)) =
+            (transforms.get(turret.base), transforms.get(turret.barrel))
+        else {
+            continue;
+        };
+        let (rest_base, rest_barrel) = *turret
+            .rest
+            .get_or_insert((base.rotation, barrel.rotation));
+
+        // The target and the barrel in the space the base turns in, as of
+        // last frame.
+        let parent = parents
+            .get(turret.base)
+            .ok()
+            .and_then(|parent| global_transforms.get(parent.parent()).ok())
+            .copied()
+            .unwrap_or_default();
+        let to_parent = parent.affine().inverse();
+        let target = match turret.target {
+            Some(TurretTarget::Entity(entity)) => global_transforms
+                .get(entity)
+                .ok()
+                .map(GlobalTransform::translation),
+            Some(TurretTarget::Point(point)) => Some(point),
+            None => None,
+        };
+        if let (Some(target), Some(last), true) = (target, turret.last_target_position, dt > 0.0)
+        {
+            turret.target_velocity = (target - last) / dt;
+        }
+        turret.last_target_position = target;
+        let aim = target.map(|target| {
+            let from = turret.muzzle_transform.translation();
+            turret.lead(from, target, turret.target_velocity)
+        });
+
+        let (yaw, pitch) = match (aim, global_transforms.get(turret.barrel)) {
+            (Some(aim), Ok(barrel_global)) => {
+                let aim = to_parent.transform_point3(aim);
+                let from_base = aim - base.translation;
+                let from_barrel = aim - to_parent.transform_point3(barrel_global.translation());
+                let yaw = from_base.x.atan2(from_base.z);
+                let pitch = from_barrel.y.atan2(from_barrel.xz().length());
+                (yaw, pitch)
+            }
+            _ => (0.0, 0.0),
+        };
+        let yaw = match turret.yaw_limits {
+            Some((min, max)) => yaw.clamp(min, max),
+            // Turn the short way round.
+            None => turret.yaw + ((yaw - turret.yaw + PI).rem_euclid(TAU) - PI),
+        };
+        let pitch = pitch.clamp(turret.pitch_limits.0, turret.pitch_limits.1);
+        turret.yaw = step_towards(turret.yaw, yaw, turret.yaw_speed * dt);
+        turret.pitch = step_towards(turret.pitch, pitch, turret.pitch_speed * dt);
+        if turret.yaw_limits.is_none() {
+            turret.yaw = (turret.yaw + PI).rem_euclid(TAU) - PI;
+        }
+
+        if let Ok(mut base) = transforms.get_mut(turret.base) {
+            base.rotation = Quat::from_rotation_y(turret.yaw) * rest_base;
+        }
+        if let Ok(mut barrel) = transforms.get_mut(turret.barrel) {
+            // Pitching up is a negative rotation about X.
+            barrel.rotation = rest_barrel * Quat::from_rotation_x(-turret.pitch);
+        }
+
+        // Compare the muzzle with the lead point, as of last frame.
+        let on_target = aim.is_some_and(|aim| {
+            let muzzle = turret.muzzle_transform;
+            let forward = muzzle.rotation() * Vec3::Z;
+            (aim - muzzle.translation()).angle_between(forward) <= turret.tolerance
+        });
+        if on_target != turret.on_target {
+            turret.on_target = on_target;
+            if routing.emits::<OnTargetEvent>() {
+                let position = turret.muzzle_transform.translation();
+                events.write(OnTargetEvent {
+                    meta: EventMeta::new(root, &time, position),
+                    target: match turret.target {
+                        Some(TurretTarget::Entity(entity)) => Some(entity),
+                        _ => None,
+                    },
+                    on_target,
+                });
+            }
+        }
+    }
+}
+
+fn record_muzzles(mut turrets: Query<&mut Turret>, global_transforms: Query<&GlobalTransform>) {
+    for mut turret in turrets.iter_mut() {
+        if let Ok(muzzle) = global_transforms.get(turret.muzzle) {
+            turret.muzzle_transform = *muzzle;
+        }
+    }
+}
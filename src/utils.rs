@@ -1,4 +1,5 @@
 use bevy::{
+    animation::graph::AnimationNodeType,
     prelude::*,
     window::{CursorGrabMode, PrimaryWindow},
 };
@@ -8,7 +9,9 @@ pub fn toggle_cursor_grab_with_esc(
     mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
 ) {
     if keys.just_pressed(KeyCode::Escape) {
-        let mut primary_window = q_windows.single_mut().unwrap();
+        let Ok(mut primary_window) = q_windows.single_mut() else {
+            return;
+        };
         primary_window.cursor_options.visible = !primary_window.cursor_options.visible;
         primary_window.cursor_options.grab_mode = if primary_window.cursor_options.visible {
             CursorGrabMode::None
@@ -33,9 +36,12 @@ pub mod freecam {
         }
     }
 
+    /// Tags a camera for the free camera controls to fly. Other cameras, e.g.
+    /// a `CameraRig` or split-screen views, are left alone.
     #[derive(Component)]
     pub struct FreeCamera {
         pub speed: f32,
+        /// Whether the keys and mouse fly this camera.
         pub movement_enabled: bool,
     }
 
@@ -52,11 +58,11 @@ pub mod freecam {
     fn free_camera_movement(
         time: Res<Time>,
         keys: Res<ButtonInput<KeyCode>>,
-        mut query: Query<(&mut Transform, &mut FreeCamera)>,
+        mut query: Query<(&mut Transform, &FreeCamera)>,
     ) {
         for (mut transform, camera) in query.iter_mut() {
             if !camera.movement_enabled {
-                return;
+                continue;
             }
             let forward = transform.rotation.mul_vec3(Vec3::new(0.0, 0.0, -1.0));
             let right = transform.rotation.mul_vec3(Vec3::new(1.0, 0.0, 0.0));
@@ -90,22 +96,25 @@ pub mod freecam {
         }
     }
 
-    /// Rotates the player based on mouse movement.
+    /// Rotates the enabled free cameras based on mouse movement.
     fn mouse_look(
         mut mouse_motion: EventReader<MouseMotion>,
-        mut camera: Query<&mut Transform, With<FreeCamera>>,
+        mut cameras: Query<(&mut Transform, &FreeCamera)>,
         q_windows: Query<&Window, With<PrimaryWindow>>,
     ) {
-        let primary_window = q_windows.single().unwrap();
+        let Ok(primary_window) = q_windows.single() else {
+            return;
+        };
         if primary_window.cursor_options.grab_mode != CursorGrabMode::Locked {
             return;
         }
-        let Ok(mut camera) = camera.single_mut() else {
-            return;
-        };
-        for motion in mouse_motion.read() {
-            let yaw = -motion.delta.x * 0.003;
-            let pitch = -motion.delta.y * 0.002;
+        let delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+        for (mut camera, free_camera) in cameras.iter_mut() {
+            if !free_camera.movement_enabled {
+                continue;
+            }
+            let yaw = -delta.x * 0.003;
+            let pitch = -delta.y * 0.002;
             camera.rotate_y(yaw);
             camera.rotate_local_x(pitch);
         }
@@ -210,6 +219,18 @@ pub fn find_child_with_name(
     return None;
 }
 
+/// Gets the duration of the clip played by a clip node.
+pub fn clip_duration(
+    graph: &AnimationGraph,
+    node: AnimationNodeIndex,
+    clips: &Assets<AnimationClip>,
+) -> Option<f32> {
+    match graph.get(node)?.node_type {
+        AnimationNodeType::Clip(ref clip) => clips.get(clip).map(AnimationClip::duration),
+        _ => None,
+    }
+}
+
 pub fn most_aligned(v: Vec2) -> IVec2 {
     if v.length() < 0.1 {
         IVec2::ZERO
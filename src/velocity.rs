@@ -0,0 +1,155 @@
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::anim::CharAnimSet;
+use crate::state::{PlayerAnimationInput, PlayerAnimationState};
+
+/// Drives the movement input of characters from how fast they're actually
+/// moving, so simple games don't need to write any input code.
+pub struct VelocityDriverPlugin {
+    /// The schedule the input is driven in, the same as the state machines'.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for VelocityDriverPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl VelocityDriverPlugin {
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for VelocityDriverPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<VelocityDriver>();
+        app.add_systems(
+            self.schedule,
+            drive_animation_from_velocity.in_set(CharAnimSet::ModifyInput),
+        );
+    }
+}
+
+/// Add to a character root. The velocity is read from a rapier `Velocity` if
+/// there is one, otherwise from how far the `Transform` moved since last frame.
+///
+/// Only the movement fields of the input are written, in
+/// `CharAnimSet::ModifyInput`, so systems that set the rest of it (e.g. for
+/// looking or jumping) go in `CharAnimSet::Input`.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct VelocityDriver {
+    /// The speed that maps to a full movement input in m/s.
+    pub walk_speed: f32,
+    /// The speed above which the character sprints, if moving forwards.
+    pub sprint_speed: f32,
+    /// Below this speed the character is idle.
+    pub moving_threshold: f32,
+    /// The time constant in seconds that the velocity is smoothed over.
+    pub smoothing: f32,
+
+    last_position: Option<Vec3>,
+    velocity: Vec3,
+    speed: f32,
+    local_direction: Vec2,
+    is_moving: bool,
+}
+
+impl Default for VelocityDriver {
+    fn default() -> Self {
+        Self {
+            walk_speed: 2.0,
+            sprint_speed: 4.0,
+            moving_threshold: 0.2,
+            smoothing: 0.1,
+            last_position: None,
+            velocity: Vec3::ZERO,
+            speed: 0.0,
+            local_direction: Vec2::ZERO,
+            is_moving: false,
+        }
+    }
+}
+
+impl VelocityDriver {
+    /// The smoothed horizontal speed in m/s.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// The smoothed movement direction relative to where the character is
+    /// looking, +Y is forward. The length is the speed relative to the walk
+    /// speed, up to 1.
+    pub fn local_direction(&self) -> Vec2 {
+        self.local_direction
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.is_moving
+    }
+}
+
+pub fn drive_animation_from_velocity(
+    mut drivers: Query<(Entity, &mut VelocityDriver, &GlobalTransform, Option<&Velocity>)>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (root, mut driver, transform, physics_velocity) in drivers.iter_mut() {
+        let position = transform.translation();
+        let raw_velocity = match (physics_velocity, driver.last_position) {
+            (Some(velocity), _) => velocity.linvel,
+            (None, Some(last_position)) => (position - last_position) / dt,
+            (None, None) => Vec3::ZERO,
+        };
+        driver.last_position = Some(position);
+        let alpha = 1.0 - (-dt / driver.smoothing.max(f32::EPSILON)).exp();
+        driver.velocity = driver.velocity.lerp(raw_velocity, alpha);
+
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        if state.input().is_none() {
+            state.set_input(PlayerAnimationInput {
+                is_grounded: true,
+                ..default()
+            });
+        }
+        let input = state.input_mut().unwrap();
+
+        let horizontal = driver.velocity.with_y(0.0);
+        let local = Quat::from_axis_angle(Vec3::Y, input.look_y).inverse() * horizontal;
+        driver.speed = horizontal.length();
+        driver.is_moving = driver.speed > driver.moving_threshold;
+        driver.local_direction = if driver.is_moving {
+            Vec2::new(-local.x, local.z).normalize() * (driver.speed / driver.walk_speed).min(1.0)
+        } else {
+            Vec2::ZERO
+        };
+
+        input.local_movement_direction = driver.local_direction;
+        input.is_sprinting = input.is_grounded
+            && driver.speed > driver.sprint_speed
+            && driver.local_direction.y > driver.local_direction.x.abs();
+        input.vertical_speed = driver.velocity.y;
+    }
+}
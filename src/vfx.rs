@@ -0,0 +1,505 @@
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    render::{
+        primitives::Frustum,
+        view::{RenderLayers, VisibilitySystems},
+    },
+};
+#[cfg(all(feature = "hanabi", not(feature = "webgl2")))]
+use bevy_hanabi::HanabiPlugin;
+
+use crate::effect_rng::EffectRng;
+
+/// One place to turn the crate's effects down for low end hardware. Change the
+/// [`VfxQuality`] resource and the effect plugins pick it up: particle effects
+/// are rebuilt with the new counts and new tracers and projectiles spawn with
+/// the new lights. Effects no camera can see are skipped, see
+/// [`EffectCulling`], and each active camera, e.g. each viewport in split
+/// screen, draws only the effect lights nearest to it, up to
+/// [`VfxSettings::lights_per_viewport`], on a render layer of its own from
+/// [`LIGHT_BUDGET_LAYER`] up.
+///
+/// Which cameras see effects, e.g. in split screen or on a security monitor,
+/// is set by render layers, either per profile or for everything a character
+/// spawns with [`EffectLayers`].
+///
+/// The randomness of effects is seeded from the `EffectRng`.
+pub struct VfxQualityPlugin;
+
+impl Plugin for VfxQualityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VfxQuality>();
+        app.init_resource::<EffectRng>();
+        app.init_resource::<EffectCulling>();
+        app.register_type::<VfxQuality>();
+        app.register_type::<EffectCulling>();
+        app.register_type::<EffectLayers>();
+        app.register_type::<ParticleKind>();
+        app.add_systems(
+            PostUpdate,
+            (propagate_effect_layers, budget_effect_lights)
+                .chain()
+                .before(VisibilitySystems::CheckVisibility),
+        );
+    }
+}
+
+/// The render layers of the effects a character spawns, e.g. its tracers,
+/// impacts and footstep dust, for the profiles that don't set their own. Add to
+/// the character root or the muzzle. The character itself keeps its own
+/// layers.
+#[derive(Component, Reflect, Clone, PartialEq, Debug, Default)]
+#[reflect(Component)]
+pub struct EffectLayers(pub RenderLayers);
+
+impl EffectLayers {
+    /// The layers of the effects spawned by `entity`, from it or the nearest
+    /// ancestor with [`EffectLayers`].
+    pub fn find(
+        entity: Entity,
+        layers: &Query<&EffectLayers>,
+        parents: &Query<&ChildOf>,
+    ) -> Option<RenderLayers> {
+        std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|e| layers.get(e).ok())
+            .map(|layers| layers.0.clone())
+    }
+}
+
+/// The render layers for a profile's `render_layers`, falling back to
+/// `fallback`, e.g. the layers of the character that spawned the effect.
+pub(crate) fn profile_layers(
+    render_layers: &Option<Vec<usize>>,
+    fallback: Option<RenderLayers>,
+) -> Option<RenderLayers> {
+    render_layers
+        .as_ref()
+        .map(|layers| RenderLayers::from_layers(layers))
+        .or(fallback)
+}
+
+/// An effect whose `RenderLayers` are copied to everything spawned under it,
+/// e.g. its particles, flash and light. Effects give their parts the layers
+/// when they spawn them, and the copy only catches up on parts added or layers
+/// changed later.
+#[derive(Component)]
+pub(crate) struct LayeredEffect;
+
+fn propagate_effect_layers(
+    mut commands: Commands,
+    effects: Query<
+        (Entity, &RenderLayers),
+        (
+            With<LayeredEffect>,
+            Or<(Changed<RenderLayers>, Changed<Children>)>,
+        ),
+    >,
+    children: Query<&Children>,
+    layers: Query<&RenderLayers>,
+) {
+    for (root, root_layers) in effects.iter() {
+        for entity in children.iter_descendants(root) {
+            if layers.get(entity).ok() != Some(root_layers) {
+                commands.entity(entity).insert(root_layers.clone());
+            }
+        }
+    }
+}
+
+/// A light spawned by an effect, e.g. a tracer's, that counts against the
+/// light budget.
+#[derive(Component, Default)]
+pub(crate) struct EffectLight {
+    /// The layers the light was spawned on, and the ones written to draw it
+    /// only in the viewports with budget for it, to tell whether the effect
+    /// has changed them since.
+    layers: Option<(RenderLayers, RenderLayers)>,
+}
+
+/// The first of the render layers the light budget gives each active camera,
+/// to draw a light in some viewports and not others. Keep the layers of the
+/// world and the effects below it.
+pub const LIGHT_BUDGET_LAYER: usize = 32;
+
+/// The render layer of a camera that only the effect lights within its budget
+/// are on.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct LightBudgetLayer(pub usize);
+
+/// Draws the effect lights nearest each active camera, up to the budget, in
+/// that camera's viewport only. Lights over one viewport's budget are still
+/// drawn in the others, so one player's firefight doesn't turn off the lights
+/// in another's view in split screen.
+fn budget_effect_lights(
+    mut commands: Commands,
+    quality: Res<VfxQuality>,
+    cameras: Query<(
+        Entity,
+        &Camera,
+        &GlobalTransform,
+        Option<&RenderLayers>,
+        Option<&LightBudgetLayer>,
+    )>,
+    mut lights: Query<(
+        Entity,
+        &GlobalTransform,
+        &mut EffectLight,
+        Option<&mut RenderLayers>,
+    )>,
+) {
+    let budget = quality.settings().lights_per_viewport;
+    let mut taken: Vec<usize> = cameras
+        .iter()
+        .filter_map(|(.., budget_layer)| budget_layer.map(|layer| layer.0))
+        .collect();
+    let mut viewports = Vec::new();
+    for (entity, camera, transform, layers, budget_layer) in cameras.iter() {
+        if !camera.is_active {
+            continue;
+        }
+        let layers = layers.cloned().unwrap_or_default();
+        let budget_layer = budget_layer.map_or_else(
+            || {
+                let layer = (LIGHT_BUDGET_LAYER..)
+                    .find(|layer| !taken.contains(layer))
+                    .unwrap();
+                taken.push(layer);
+                commands.entity(entity).insert(LightBudgetLayer(layer));
+                layer
+            },
+            |layer| layer.0,
+        );
+        if !layers.iter().any(|layer| layer == budget_layer) {
+            commands
+                .entity(entity)
+                .insert(layers.clone().with(budget_layer));
+        }
+        viewports.push((
+            transform.translation(),
+            layers.without(budget_layer),
+            budget_layer,
+        ));
+    }
+    if viewports.is_empty() {
+        return;
+    }
+
+    let lights: Vec<_> = lights
+        .iter_mut()
+        .map(|(entity, transform, light, layers)| {
+            let current = layers.as_deref().cloned().unwrap_or_default();
+            let authored = match &light.layers {
+                Some((authored, written)) if *written == current => authored.clone(),
+                _ => current,
+            };
+            (entity, transform.translation(), authored, light, layers)
+        })
+        .collect();
+    let mut written = vec![RenderLayers::none(); lights.len()];
+    for (position, layers, budget_layer) in viewports.iter() {
+        let mut seen: Vec<_> = lights
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, authored, ..))| authored.intersects(layers))
+            .map(|(index, (_, light_position, ..))| {
+                (index, light_position.distance_squared(*position))
+            })
+            .collect();
+        seen.sort_by(|a, b| a.1.total_cmp(&b.1));
+        for (index, _) in seen.into_iter().take(budget) {
+            written[index] = written[index].clone().with(*budget_layer);
+        }
+    }
+    for ((entity, _, authored, mut light, layers), written) in lights.into_iter().zip(written) {
+        light.layers = Some((authored, written.clone()));
+        match layers {
+            Some(mut layers) => {
+                layers.set_if_neq(written);
+            }
+            None => {
+                commands.entity(entity).insert(written);
+            }
+        }
+    }
+}
+
+/// Skips spawning the visuals of effects that are off screen or too far away
+/// for every active camera: tracers with their flashes and lights, and impact
+/// particles. Their events still fire, so gameplay doesn't depend on where the
+/// cameras look. With no active camera nothing is culled.
+#[derive(Resource, Reflect, Clone, Copy, Debug)]
+#[reflect(Resource, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct EffectCulling {
+    pub enabled: bool,
+    /// How far outside the frustum an effect is still spawned, in meters, so
+    /// effects that grow or move, e.g. smoke, don't pop in at the edges.
+    pub margin: f32,
+    /// Effects farther than this from every camera are culled.
+    pub max_distance: f32,
+}
+
+impl Default for EffectCulling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            margin: 5.0,
+            max_distance: 300.0,
+        }
+    }
+}
+
+/// Tells whether an effect is worth spawning, following [`EffectCulling`].
+#[derive(SystemParam)]
+pub struct EffectVisibility<'w, 's> {
+    culling: Res<'w, EffectCulling>,
+    cameras: Query<'w, 's, (&'static Camera, &'static Frustum, &'static GlobalTransform)>,
+}
+
+impl EffectVisibility<'_, '_> {
+    /// Whether a camera may see anything of a sphere.
+    pub fn sees(&self, center: Vec3, radius: f32) -> bool {
+        self.sees_segment(center, center, radius)
+    }
+
+    /// Whether a camera may see anything within `radius` of the segment, e.g.
+    /// a tracer. It's conservative: the test is against a sphere around the
+    /// whole segment.
+    pub fn sees_segment(&self, start: Vec3, end: Vec3, radius: f32) -> bool {
+        if !self.culling.enabled {
+            return true;
+        }
+        let bounds = bevy::render::primitives::Sphere {
+            center: start.midpoint(end).into(),
+            radius: start.distance(end) / 2.0 + radius + self.culling.margin,
+        };
+        let mut cameras = self
+            .cameras
+            .iter()
+            .filter(|(camera, ..)| camera.is_active)
+            .peekable();
+        if cameras.peek().is_none() {
+            return true;
+        }
+        cameras.any(|(_, frustum, transform)| {
+            let closest = closest_point_on_segment(start, end, transform.translation());
+            closest.distance(transform.translation()) <= self.culling.max_distance + radius
+                && frustum.intersects_sphere(&bounds, true)
+        })
+    }
+}
+
+fn closest_point_on_segment(start: Vec3, end: Vec3, point: Vec3) -> Vec3 {
+    let segment = end - start;
+    let t = (point - start).dot(segment) / segment.length_squared().max(f32::EPSILON);
+    start + segment * t.clamp(0.0, 1.0)
+}
+
+/// What the effects may cost.
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct VfxSettings {
+    /// Scales how many particles each effect spawns.
+    pub particle_scale: f32,
+    /// Whether tracers and projectiles light up their surroundings.
+    pub tracer_lights: bool,
+    /// Whether those lights cast shadows, if the tracer profile asks for it.
+    pub tracer_shadows: bool,
+    /// The most bullet holes kept alive at once, scorches get an eighth of it.
+    pub decal_budget: usize,
+    /// The most effect lights drawn for each active camera, the nearest ones
+    /// to it.
+    pub lights_per_viewport: usize,
+}
+
+#[derive(Resource, Reflect, Clone, Copy, PartialEq, Debug, Default)]
+#[reflect(Resource, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum VfxQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Custom(VfxSettings),
+}
+
+impl VfxQuality {
+    pub fn settings(&self) -> VfxSettings {
+        match self {
+            Self::Low => VfxSettings {
+                particle_scale: 0.25,
+                tracer_lights: false,
+                tracer_shadows: false,
+                decal_budget: 32,
+                lights_per_viewport: 0,
+            },
+            Self::Medium => VfxSettings {
+                particle_scale: 0.5,
+                tracer_lights: true,
+                tracer_shadows: false,
+                decal_budget: 128,
+                lights_per_viewport: 4,
+            },
+            Self::High => VfxSettings {
+                particle_scale: 1.0,
+                tracer_lights: true,
+                tracer_shadows: true,
+                decal_budget: 512,
+                lights_per_viewport: 8,
+            },
+            Self::Custom(settings) => *settings,
+        }
+    }
+
+    /// Scales a particle count, keeping at least one particle.
+    pub fn particles(&self, count: f32) -> f32 {
+        (count * self.settings().particle_scale).max(1.0)
+    }
+}
+
+/// Which of the crate's particle effects an entity is. Every particle effect
+/// the crate spawns has one, so the CPU particles know what to look like.
+#[derive(Component, Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Component)]
+pub enum ParticleKind {
+    MuzzleFlash,
+    /// The small flash of a suppressed muzzle.
+    MinimalMuzzleFlash,
+    MuzzleSmoke,
+    /// Off a surface that was hit.
+    Impact,
+    /// Dust kicked up by a foot.
+    Footstep,
+    BloodSpray,
+    Fireball,
+    /// The column of smoke rising from an explosion.
+    SmokeColumn,
+}
+
+/// Adds the particle backend once: Hanabi's GPU particles, or the CPU
+/// fallback without the `hanabi` feature or with the `webgl2` feature, as
+/// WebGL2 has no compute shaders.
+pub(crate) fn add_particle_plugin(app: &mut App) {
+    #[cfg(all(feature = "hanabi", not(feature = "webgl2")))]
+    if !app.is_plugin_added::<HanabiPlugin>() {
+        app.add_plugins(HanabiPlugin);
+    }
+    #[cfg(any(feature = "webgl2", not(feature = "hanabi")))]
+    if !app.is_plugin_added::<crate::webgl2::CpuParticlesPlugin>() {
+        app.add_plugins(crate::webgl2::CpuParticlesPlugin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::AnimationTestApp;
+
+    /// Budgets lights at [`VfxQuality::Medium`], four per viewport.
+    fn budget_test_app() -> AnimationTestApp {
+        let mut test = AnimationTestApp::new();
+        test.app.insert_resource(VfxQuality::Medium).add_systems(
+            PostUpdate,
+            budget_effect_lights.after(TransformSystem::TransformPropagate),
+        );
+        test
+    }
+
+    fn spawn_camera(test: &mut AnimationTestApp, x: f32) -> Entity {
+        let transform = Transform::from_xyz(x, 0.0, 0.0);
+        test.app
+            .world_mut()
+            .spawn((Camera::default(), transform))
+            .id()
+    }
+
+    fn spawn_light(test: &mut AnimationTestApp, x: f32) -> Entity {
+        let transform = Transform::from_xyz(x, 1.0, 0.0);
+        test.app
+            .world_mut()
+            .spawn((EffectLight::default(), transform))
+            .id()
+    }
+
+    fn drawn_in(test: &AnimationTestApp, camera: Entity, light: Entity) -> bool {
+        let world = test.app.world();
+        let layers = |entity| {
+            world
+                .get::<RenderLayers>(entity)
+                .cloned()
+                .unwrap_or_default()
+        };
+        layers(camera).intersects(&layers(light))
+    }
+
+    #[test]
+    fn each_viewport_draws_its_nearest_lights() {
+        for players in 2..=4 {
+            let mut test = budget_test_app();
+            let viewports: Vec<_> = (0..players)
+                .map(|player| {
+                    let x = player as f32 * 50.0;
+                    let camera = spawn_camera(&mut test, x);
+                    let lights: Vec<_> = (1..=6)
+                        .map(|offset| spawn_light(&mut test, x + offset as f32))
+                        .collect();
+                    (camera, lights)
+                })
+                .collect();
+            test.tick(2);
+
+            for (camera, own_lights) in viewports.iter() {
+                let drawn: Vec<_> = viewports
+                    .iter()
+                    .flat_map(|(_, lights)| lights.iter().copied())
+                    .filter(|light| drawn_in(&test, *camera, *light))
+                    .collect();
+                assert_eq!(drawn, own_lights[..4], "{players} players");
+            }
+        }
+    }
+
+    #[test]
+    fn lights_over_one_budget_stay_in_other_viewports() {
+        let mut test = budget_test_app();
+        let left = spawn_camera(&mut test, 0.0);
+        let right = spawn_camera(&mut test, 6.0);
+        let lights: Vec<_> = (1..=5).map(|x| spawn_light(&mut test, x as f32)).collect();
+        test.tick(2);
+
+        assert!(drawn_in(&test, left, lights[0]));
+        assert!(!drawn_in(&test, right, lights[0]));
+        assert!(!drawn_in(&test, left, lights[4]));
+        assert!(drawn_in(&test, right, lights[4]));
+        for light in &lights[1..4] {
+            assert!(drawn_in(&test, left, *light) && drawn_in(&test, right, *light));
+        }
+    }
+
+    #[test]
+    fn lights_only_count_in_viewports_that_see_their_layers() {
+        let mut test = budget_test_app();
+        let world_camera = spawn_camera(&mut test, 0.0);
+        let monitor = spawn_camera(&mut test, 100.0);
+        test.app
+            .world_mut()
+            .entity_mut(monitor)
+            .insert(RenderLayers::layer(1));
+        let monitor_light = spawn_light(&mut test, 0.5);
+        test.app
+            .world_mut()
+            .entity_mut(monitor_light)
+            .insert(RenderLayers::layer(1));
+        let world_lights: Vec<_> = (1..=4).map(|x| spawn_light(&mut test, x as f32)).collect();
+        test.tick(2);
+
+        assert!(!drawn_in(&test, world_camera, monitor_light));
+        assert!(drawn_in(&test, monitor, monitor_light));
+        for light in world_lights {
+            assert!(drawn_in(&test, world_camera, light));
+            assert!(!drawn_in(&test, monitor, light));
+        }
+    }
+}
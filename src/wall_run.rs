@@ -0,0 +1,336 @@
+use bevy::{app::Animation, prelude::*};
+
+use crate::anim::CharAnimSet;
+use crate::character::{RigBone, RigMap};
+use crate::hitscan::Hitscan;
+use crate::ik::solve_two_bone_ik;
+use crate::montage::Montage;
+use crate::netsync::FixedRootMotion;
+use crate::pose_authority::apply_pose_authority;
+use crate::state::{LocomotionClip, LocomotionOverride, PlayerAnimationState};
+use crate::velocity::drive_animation_from_velocity;
+
+/// Wall running. A character with a [`WallRun`] that's in the air and moving
+/// fast past a wall runs along it, playing the loop for the side the wall is
+/// on with the body tilted towards it. The run follows an arc that falls
+/// slower than a jump, and jumping off plays a wall-jump montage and hands the
+/// controller a launch velocity.
+pub struct WallRunPlugin;
+
+impl Plugin for WallRunPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WallRun>();
+        app.add_systems(
+            Update,
+            wall_run
+                .after(drive_animation_from_velocity)
+                .in_set(CharAnimSet::ModifyInput),
+        );
+        app.add_systems(
+            PostUpdate,
+            tilt_wall_runners
+                .after(Animation)
+                .before(solve_two_bone_ik)
+                .before(apply_pose_authority)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+const GRAVITY: f32 = 9.81;
+
+/// Which side of the character the wall is on.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WallSide {
+    Left,
+    Right,
+}
+
+#[derive(Reflect, Clone, Copy, Debug)]
+struct ActiveWallRun {
+    side: WallSide,
+    /// The horizontal direction out of the wall, in global world space.
+    normal: Vec3,
+    /// The horizontal direction of the run along the wall.
+    direction: Vec3,
+    /// The speed along the wall in m/s.
+    speed: f32,
+    /// The speed up in m/s, falling with the scaled gravity.
+    vertical_speed: f32,
+    secs: f32,
+}
+
+/// Lets a character run along walls. Add to the character root with a
+/// `RigMap`. While [`WallRun::is_running`], the controller should leave the
+/// character's movement, gravity included, to the wall run.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct WallRun {
+    /// The run loop with the wall on the left.
+    pub left: Handle<AnimationClip>,
+    /// The run loop with the wall on the right.
+    pub right: Handle<AnimationClip>,
+    /// Played when jumping off the wall, if set.
+    pub jump: Option<Montage>,
+    /// How far from the root a wall is found, in meters.
+    pub reach: f32,
+    /// The height above the root that walls are looked for at.
+    pub chest_height: f32,
+    /// The slowest the character can move in m/s and still start a run.
+    pub min_speed: f32,
+    /// The speed up in m/s when the run starts, the start of the arc.
+    pub entry_vertical_speed: f32,
+    /// How much of gravity pulls the character down while running.
+    pub gravity_scale: f32,
+    /// The longest a run lasts, in seconds.
+    pub max_secs: f32,
+    /// How far the body tilts towards the wall, in radians.
+    pub tilt: f32,
+    /// How fast the tilt moves in radians per second.
+    pub tilt_speed: f32,
+    /// The launch of a wall jump in m/s, x out of the wall and y up. The run
+    /// speed along the wall is kept.
+    pub jump_velocity: Vec2,
+    /// How much the spine aims while running, so the character can shoot.
+    pub aim_weight: f32,
+    input: bool,
+    jump_requested: bool,
+    /// Cleared when a run ends without a jump, until the character lands.
+    armed: bool,
+    last_position: Option<Vec3>,
+    active: Option<ActiveWallRun>,
+    /// The current tilt, positive to the right, and the axis it's about.
+    tilt_angle: f32,
+    tilt_axis: Vec3,
+    launch: Option<Vec3>,
+}
+
+impl WallRun {
+    pub fn new(left: Handle<AnimationClip>, right: Handle<AnimationClip>) -> Self {
+        Self {
+            left,
+            right,
+            jump: None,
+            reach: 0.8,
+            chest_height: 1.2,
+            min_speed: 3.0,
+            entry_vertical_speed: 2.0,
+            gravity_scale: 0.3,
+            max_secs: 1.5,
+            tilt: 15f32.to_radians(),
+            tilt_speed: 90f32.to_radians(),
+            jump_velocity: Vec2::new(4.0, 5.0),
+            aim_weight: 0.5,
+            input: false,
+            jump_requested: false,
+            armed: true,
+            last_position: None,
+            active: None,
+            tilt_angle: 0.0,
+            tilt_axis: Vec3::Z,
+            launch: None,
+        }
+    }
+
+    pub fn with_jump(mut self, jump: Montage) -> Self {
+        self.jump = Some(jump);
+        self
+    }
+
+    /// Sets whether the character wants to wall run, e.g. while jump or
+    /// sprint is held. Letting go drops off the wall.
+    pub fn set_input(&mut self, input: bool) {
+        self.input = input;
+    }
+
+    /// Jumps off the wall, if running on one.
+    pub fn jump(&mut self) {
+        self.jump_requested = true;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// The side the wall being run on is on.
+    pub fn side(&self) -> Option<WallSide> {
+        self.active.map(|active| active.side)
+    }
+
+    /// The horizontal direction out of the wall being run on.
+    pub fn wall_normal(&self) -> Option<Vec3> {
+        self.active.map(|active| active.normal)
+    }
+
+    /// The velocity of the last wall jump, for the controller to carry on
+    /// with. Returns it once.
+    pub fn take_launch(&mut self) -> Option<Vec3> {
+        self.launch.take()
+    }
+}
+
+/// The horizontal direction out of a wall on `side`, if there's one in reach.
+fn find_wall(
+    from: Vec3,
+    side: Vec3,
+    reach: f32,
+    root: Entity,
+    hitscan: &Hitscan,
+) -> Option<Vec3> {
+    let hit = hitscan.cast_ray(from, Dir3::new(side).ok()?, reach, Some(root))?;
+    // Steep enough to run on, not a slope or a ceiling.
+    if hit.normal.y.abs() > 0.3 {
+        return None;
+    }
+    hit.normal.with_y(0.0).try_normalize()
+}
+
+fn wall_run(
+    mut characters: Query<(
+        Entity,
+        &mut WallRun,
+        &mut Transform,
+        Option<&mut FixedRootMotion>,
+    )>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+    hitscan: Hitscan,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (root, mut wall_run, mut transform, fixed) in characters.iter_mut() {
+        let position = transform.translation;
+        let velocity = wall_run
+            .last_position
+            .replace(position)
+            .map_or(Vec3::ZERO, |last| (position - last).with_y(0.0) / dt);
+        let jump = std::mem::take(&mut wall_run.jump_requested);
+
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let grounded = state.input().is_some_and(|input| input.is_grounded);
+        let wall_run = wall_run.as_mut();
+        if grounded {
+            wall_run.armed = true;
+        }
+        let chest = position + Vec3::Y * wall_run.chest_height;
+
+        if wall_run.active.is_none() {
+            let speed = velocity.length();
+            if !(wall_run.input && wall_run.armed && !grounded && speed >= wall_run.min_speed) {
+                continue;
+            }
+            let direction = velocity / speed;
+            let right = direction.cross(Vec3::Y);
+            let found = [(WallSide::Right, right), (WallSide::Left, -right)]
+                .into_iter()
+                .find_map(|(side, towards)| {
+                    find_wall(chest, towards, wall_run.reach, root, &hitscan)
+                        .map(|normal| (side, normal))
+                });
+            let Some((side, normal)) = found else {
+                continue;
+            };
+            let Some(along) = direction.reject_from_normalized(normal).try_normalize() else {
+                continue;
+            };
+            wall_run.active = Some(ActiveWallRun {
+                side,
+                normal,
+                direction: along,
+                speed,
+                vertical_speed: wall_run.entry_vertical_speed,
+                secs: 0.0,
+            });
+        }
+
+        let active = wall_run.active.as_mut().unwrap();
+        active.secs += dt;
+        if jump {
+            wall_run.launch = Some(
+                active.direction * active.speed
+                    + active.normal * wall_run.jump_velocity.x
+                    + Vec3::Y * wall_run.jump_velocity.y,
+            );
+            wall_run.active = None;
+            if let Some(montage) = wall_run.jump.clone() {
+                state.play_montage(montage);
+            }
+            continue;
+        }
+
+        // Follow the wall round gentle curves, and drop off where it ends.
+        let wall = find_wall(chest, -active.normal, wall_run.reach, root, &hitscan);
+        if let Some(normal) = wall {
+            active.direction = active
+                .direction
+                .reject_from_normalized(normal)
+                .try_normalize()
+                .unwrap_or(active.direction);
+            active.normal = normal;
+        }
+        if wall.is_none() || grounded || !wall_run.input || active.secs > wall_run.max_secs {
+            wall_run.active = None;
+            wall_run.armed = false;
+            continue;
+        }
+
+        active.vertical_speed -= GRAVITY * wall_run.gravity_scale * dt;
+        let step = (active.direction * active.speed + Vec3::Y * active.vertical_speed) * dt;
+        match fixed {
+            Some(mut fixed) => fixed.pending += step,
+            None => transform.translation += step,
+        }
+        wall_run.tilt_axis = active.direction;
+        let clip = match active.side {
+            WallSide::Left => wall_run.left.clone(),
+            WallSide::Right => wall_run.right.clone(),
+        };
+        state.override_locomotion(LocomotionOverride {
+            clips: vec![LocomotionClip::new(clip, 1.0)],
+            pitch: 0.0,
+            yaw: Some(active.direction.x.atan2(active.direction.z)),
+            aim_weight: wall_run.aim_weight,
+        });
+    }
+}
+
+fn tilt_wall_runners(
+    mut characters: Query<(&mut WallRun, &RigMap)>,
+    mut transforms: Query<&mut Transform>,
+    global_transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
+) {
+    for (mut wall_run, rig) in characters.iter_mut() {
+        let target = match wall_run.side() {
+            Some(WallSide::Right) => wall_run.tilt,
+            Some(WallSide::Left) => -wall_run.tilt,
+            None => 0.0,
+        };
+        let step = wall_run.tilt_speed * time.delta_secs();
+        wall_run.tilt_angle += (target - wall_run.tilt_angle).clamp(-step, step);
+        if wall_run.tilt_angle == 0.0 {
+            continue;
+        }
+        let Some(hips) = rig.get(RigBone::Hips) else {
+            continue;
+        };
+        let (Ok(hips_global), Ok(mut transform)) =
+            (global_transforms.get(hips), transforms.get_mut(hips))
+        else {
+            continue;
+        };
+        // The bone's global rotation is last frame's, close enough for the axis.
+        let axis = (hips_global.rotation().inverse() * wall_run.tilt_axis).normalize();
+        transform.rotation *= Quat::from_axis_angle(axis, wall_run.tilt_angle);
+    }
+}
@@ -0,0 +1,179 @@
+use bevy::{app::Animation, prelude::*};
+
+use crate::damping::SpringDamper;
+use crate::ik::solve_two_bone_ik;
+
+/// Weapon lag. A [`WeaponLag`] on the node holding a weapon makes it trail
+/// behind the camera when the view turns and drag behind when it moves, then
+/// catch up on critically damped springs, so fast turns have weight. Heavier
+/// weapons lag further and settle slower.
+///
+/// It works the same in first person, with the weapon under the camera, and
+/// in third person, with it in a hand socket. The lag is its own offset,
+/// applied on top of whatever else poses the weapon, e.g. sway or recoil, and
+/// taken off again at the start of the next frame like camera kick.
+pub struct WeaponLagPlugin;
+
+impl Plugin for WeaponLagPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WeaponLag>();
+        app.add_systems(PreUpdate, remove_weapon_lag);
+        app.add_systems(
+            PostUpdate,
+            apply_weapon_lag
+                .after(Animation)
+                .before(solve_two_bone_ik)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Add to the weapon, or the node it hangs off, so hands posed to it by IK
+/// follow the lag.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct WeaponLag {
+    /// What the weapon lags behind, or its parent if none. Usually the
+    /// camera.
+    pub camera: Option<Entity>,
+    /// The weapon's mass in kg.
+    pub mass: f32,
+    /// Seconds per kg for the springs to get halfway back.
+    pub halflife_per_kg: f32,
+    /// The furthest the weapon trails a turn, in radians.
+    pub max_angle: f32,
+    /// How far the weapon drags behind per m/s the camera moves, per kg, in
+    /// meters.
+    pub drag_per_kg: f32,
+    /// The furthest the weapon drags, in meters.
+    pub max_offset: f32,
+    /// The rotation behind the camera, as a scaled axis in the camera's space.
+    angle: [SpringDamper; 3],
+    /// The offset behind the camera, in the camera's space.
+    offset: [SpringDamper; 3],
+    last: Option<(Quat, Vec3)>,
+    /// The offset applied this frame, in the weapon's parent's space.
+    #[reflect(ignore)]
+    applied: Option<Transform>,
+}
+
+impl Default for WeaponLag {
+    fn default() -> Self {
+        Self::new(3.0)
+    }
+}
+
+impl WeaponLag {
+    pub fn new(mass: f32) -> Self {
+        Self {
+            camera: None,
+            mass,
+            halflife_per_kg: 0.02,
+            max_angle: 8f32.to_radians(),
+            drag_per_kg: 0.002,
+            max_offset: 0.04,
+            angle: default(),
+            offset: default(),
+            last: None,
+            applied: None,
+        }
+    }
+
+    pub fn with_camera(mut self, camera: Entity) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    /// How far the weapon trails the camera's rotation, as a scaled axis in
+    /// the camera's space.
+    pub fn angle(&self) -> Vec3 {
+        Vec3::from_array(self.angle.map(|spring| spring.value))
+    }
+
+    /// How far the weapon drags behind the camera, in the camera's space.
+    pub fn offset(&self) -> Vec3 {
+        Vec3::from_array(self.offset.map(|spring| spring.value))
+    }
+
+    fn halflife(&self) -> f32 {
+        self.halflife_per_kg * self.mass
+    }
+
+    /// Adds the camera's turn since last frame to the lag, and springs the
+    /// lag back towards the camera and the drag towards `drag`.
+    fn update(&mut self, turn: Vec3, drag: Vec3, delta_secs: f32) {
+        let halflife = self.halflife();
+        let angle = (self.angle() + turn).clamp_length_max(self.max_angle);
+        for (spring, angle) in self.angle.iter_mut().zip(angle.to_array()) {
+            spring.value = angle;
+            spring.update(0.0, halflife, delta_secs);
+        }
+        for (spring, drag) in self.offset.iter_mut().zip(drag.to_array()) {
+            spring.update(drag, halflife, delta_secs);
+        }
+    }
+}
+
+fn apply_weapon_lag(
+    mut weapons: Query<(Entity, &mut WeaponLag, &mut Transform)>,
+    global_transforms: Query<&GlobalTransform>,
+    parents: Query<&ChildOf>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut lag, mut transform) in weapons.iter_mut() {
+        let lag = lag.as_mut();
+        // As of last frame, which is what the player saw.
+        let parent = parents
+            .get(entity)
+            .ok()
+            .and_then(|parent| global_transforms.get(parent.parent()).ok())
+            .copied()
+            .unwrap_or_default();
+        let camera = lag
+            .camera
+            .and_then(|camera| global_transforms.get(camera).ok())
+            .copied()
+            .unwrap_or(parent);
+        let rotation = camera.rotation();
+        let position = camera.translation();
+        let Some((last_rotation, last_position)) = lag.last.replace((rotation, position)) else {
+            continue;
+        };
+        if dt <= 0.0 {
+            continue;
+        }
+
+        // The turn back to where the camera was, and the drag against its
+        // movement, in the camera's space.
+        let turn = (rotation.inverse() * last_rotation).to_scaled_axis();
+        let velocity = rotation.inverse() * (position - last_position) / dt;
+        let drag = (-velocity * lag.drag_per_kg * lag.mass).clamp_length_max(lag.max_offset);
+        lag.update(turn, drag, dt);
+
+        // Turn about the weapon's own origin, so it pivots at the grip.
+        let to_parent = parent.rotation().inverse() * rotation;
+        let applied = Transform::from_translation(
+            parent
+                .affine()
+                .inverse()
+                .transform_vector3(rotation * lag.offset()),
+        )
+        .with_rotation(to_parent * Quat::from_scaled_axis(lag.angle()) * to_parent.inverse());
+        transform.translation += applied.translation;
+        transform.rotation = applied.rotation * transform.rotation;
+        lag.applied = Some(applied);
+    }
+}
+
+/// Takes off the lag applied last frame, so the game moves the weapon from
+/// where it put it.
+fn remove_weapon_lag(mut weapons: Query<(&mut WeaponLag, &mut Transform)>) {
+    for (mut lag, mut transform) in weapons.iter_mut() {
+        let Some(applied) = lag.applied.take() else {
+            continue;
+        };
+        transform.rotation = applied.rotation.inverse() * transform.rotation;
+        transform.translation -= applied.translation;
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::ads::AimDownSights;
+use crate::anim::CharAnimSet;
+use crate::spread::{RecoilPattern, WeaponSpread};
+use crate::state::PlayerAnimationState;
+
+/// Upper body pose sets per weapon class. Register a [`WeaponPoseSet`] for
+/// each class in the [`WeaponPoseSets`] of a character, and setting its
+/// [`WeaponClass`] swaps the upper body pose, aim offset and recoil to match
+/// the weapon.
+pub struct WeaponPosePlugin;
+
+impl Plugin for WeaponPosePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WeaponClass>();
+        app.register_type::<WeaponPoseSets>();
+        app.add_systems(Update, swap_weapon_poses.in_set(CharAnimSet::ModifyInput));
+    }
+}
+
+/// The kind of weapon a character has equipped. Add to the character root, and
+/// remove it when the character is unarmed.
+#[derive(Component, Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Component, Hash, PartialEq)]
+pub enum WeaponClass {
+    Rifle,
+    Pistol,
+    Bow,
+    TwoHandMelee,
+}
+
+/// How a character holds a class of weapon.
+#[derive(Reflect, Clone, Debug, Default)]
+pub struct WeaponPoseSet {
+    /// The looping upper body pose, or the upper body idle if none.
+    pub pose: Option<Handle<AnimationClip>>,
+    /// Added to the aim in radians, x is yaw to the right and y is pitch up.
+    pub aim_offset: Vec2,
+    /// Replaces the recoil of the character's `WeaponSpread`, if it has one.
+    /// Sets without one use the recoil the `WeaponSpread` started with.
+    pub recoil: Option<RecoilPattern>,
+}
+
+impl WeaponPoseSet {
+    pub fn new(pose: Handle<AnimationClip>) -> Self {
+        Self {
+            pose: Some(pose),
+            ..default()
+        }
+    }
+
+    pub fn with_aim_offset(mut self, aim_offset: Vec2) -> Self {
+        self.aim_offset = aim_offset;
+        self
+    }
+
+    pub fn with_recoil(mut self, recoil: RecoilPattern) -> Self {
+        self.recoil = Some(recoil);
+        self
+    }
+}
+
+/// The pose sets a character knows. Add to the character root.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct WeaponPoseSets {
+    pub sets: HashMap<WeaponClass, WeaponPoseSet>,
+    /// The class whose set was last applied, `Some(None)` for unarmed.
+    /// Cleared when the sets change, so they're applied again.
+    #[reflect(ignore)]
+    applied: Option<Option<WeaponClass>>,
+    /// The recoil of the `WeaponSpread` before any set replaced it.
+    #[reflect(ignore)]
+    base_recoil: Option<RecoilPattern>,
+}
+
+impl WeaponPoseSets {
+    pub fn with_set(mut self, class: WeaponClass, set: WeaponPoseSet) -> Self {
+        self.sets.insert(class, set);
+        self
+    }
+
+    pub fn get(&self, class: WeaponClass) -> Option<&WeaponPoseSet> {
+        self.sets.get(&class)
+    }
+}
+
+pub(crate) fn swap_weapon_poses(
+    mut characters: Query<(
+        Entity,
+        &mut WeaponPoseSets,
+        Option<&WeaponClass>,
+        Option<&mut WeaponSpread>,
+        Option<&mut AimDownSights>,
+    )>,
+    mut states: Query<&mut PlayerAnimationState>,
+    children: Query<&Children>,
+) {
+    for (root, mut sets, class, spread, ads) in characters.iter_mut() {
+        let class = class.copied();
+        if sets.is_changed() {
+            sets.bypass_change_detection().applied = None;
+        }
+        if sets.applied == Some(class) {
+            continue;
+        }
+        // The state is spawned with the scene, so wait for it.
+        let Some(state_entity) = children
+            .iter_descendants(root)
+            .find(|e| states.contains(*e))
+        else {
+            continue;
+        };
+        let mut state = states.get_mut(state_entity).unwrap();
+        let set = class.and_then(|class| sets.get(class));
+        let pose = set.and_then(|set| set.pose.clone());
+        // While aiming, the new pose waits until the aim is released.
+        if !ads.is_some_and(|mut ads| ads.swap_hip_pose(pose.clone())) {
+            state.set_upper_body_pose(pose);
+        }
+        state.config_mut().aim_offset = set.map_or(Vec2::ZERO, |set| set.aim_offset);
+        let recoil = set.and_then(|set| set.recoil.clone());
+        let sets = sets.bypass_change_detection();
+        if let Some(mut spread) = spread {
+            let base_recoil = sets.base_recoil.get_or_insert_with(|| spread.recoil.clone());
+            spread.recoil = recoil.unwrap_or_else(|| base_recoil.clone());
+            spread.reset();
+        }
+        sets.applied = Some(class);
+    }
+}
@@ -0,0 +1,338 @@
+use bevy::{pbr::NotShadowCaster, platform::collections::HashMap, prelude::*};
+use rand::Rng;
+
+use crate::effect_rng::{EffectRng, CPU_PARTICLE_STREAM};
+use crate::tracer::EffectsPaused;
+use crate::vfx::{ParticleKind, VfxQuality};
+
+/// A CPU stand in for Hanabi, for WebGL2, which has no compute shaders, and
+/// for builds without the `hanabi` feature. Every particle effect spawned by
+/// the crate puffs out small balls with a [`CpuPreset`] picked by its
+/// [`ParticleKind`], so flashes glow and fly forward, blood and debris fall,
+/// and smoke rises and spreads. Continuous effects, i.e. muzzle smoke, keep
+/// emitting for as long as they're spawned. Impacts fly out along the
+/// effect's forward axis, i.e. its -Z.
+pub struct CpuParticlesPlugin;
+
+impl Plugin for CpuParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        // The effect plugins still build their assets and spawn effects.
+        #[cfg(feature = "hanabi")]
+        app.init_asset::<bevy_hanabi::EffectAsset>();
+        app.init_resource::<EffectsPaused>();
+        app.init_resource::<CpuParticleAssets>();
+        app.init_resource::<EffectRng>();
+        app.add_systems(
+            Update,
+            (
+                start_cpu_particles,
+                emit_cpu_particles,
+                update_cpu_particles,
+            )
+                .chain()
+                .run_if(|paused: Res<EffectsPaused>| !paused.paused),
+        );
+    }
+}
+
+/// Which way the particles of a [`CpuPreset`] fly out.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Emission {
+    /// Along the effect's forward axis, spread by `spread`.
+    Forward,
+    /// In every direction.
+    Sphere,
+}
+
+/// How the CPU particles of a [`ParticleKind`] look and move.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CpuPreset {
+    pub color: LinearRgba,
+    /// How many times brighter than its colour a particle glows, 0 for unlit
+    /// smoke and debris.
+    pub glow: f32,
+    /// How many particles a burst puffs out at high quality.
+    pub count: f32,
+    /// How many particles are emitted per second while the effect lives, 0 for
+    /// a single burst.
+    pub rate: f32,
+    pub emission: Emission,
+    /// How far sideways particles fly, as a fraction of their speed.
+    pub spread: f32,
+    /// The range of speeds in m/s.
+    pub speed: (f32, f32),
+    /// The range of diameters in meters.
+    pub size: (f32, f32),
+    /// The range of lifetimes in seconds.
+    pub lifetime: (f32, f32),
+    /// The downwards acceleration in m/s², negative to rise.
+    pub gravity: f32,
+    /// The size at the end of a particle's life, relative to its start, e.g.
+    /// 0 to shrink away or more than 1 for smoke to spread.
+    pub end_scale: f32,
+}
+
+impl CpuPreset {
+    pub fn for_kind(kind: ParticleKind) -> Self {
+        let base = Self {
+            color: LinearRgba::WHITE,
+            glow: 0.0,
+            count: 8.0,
+            rate: 0.0,
+            emission: Emission::Forward,
+            spread: 0.3,
+            speed: (0.5, 2.0),
+            size: (0.03, 0.07),
+            lifetime: (0.2, 0.4),
+            gravity: 0.0,
+            end_scale: 0.0,
+        };
+        match kind {
+            ParticleKind::MuzzleFlash => Self {
+                color: LinearRgba::new(1.0, 0.8, 0.3, 1.0),
+                glow: 4.0,
+                ..base
+            },
+            ParticleKind::MinimalMuzzleFlash => Self {
+                color: LinearRgba::new(1.0, 0.7, 0.3, 1.0),
+                glow: 2.0,
+                count: 3.0,
+                speed: (0.3, 1.0),
+                size: (0.02, 0.03),
+                lifetime: (0.1, 0.2),
+                ..base
+            },
+            ParticleKind::MuzzleSmoke => Self {
+                color: LinearRgba::new(0.6, 0.6, 0.6, 0.35),
+                count: 0.0,
+                rate: 12.0,
+                spread: 0.5,
+                speed: (0.1, 0.3),
+                size: (0.04, 0.08),
+                lifetime: (0.8, 1.5),
+                gravity: -0.5,
+                end_scale: 3.0,
+                ..base
+            },
+            ParticleKind::Impact => Self {
+                color: LinearRgba::new(0.8, 0.7, 0.5, 1.0),
+                count: 10.0,
+                spread: 0.6,
+                speed: (1.0, 3.0),
+                size: (0.02, 0.04),
+                lifetime: (0.3, 0.6),
+                gravity: 9.81,
+                ..base
+            },
+            ParticleKind::Footstep => Self {
+                color: LinearRgba::new(0.6, 0.55, 0.45, 0.6),
+                count: 4.0,
+                spread: 1.0,
+                speed: (0.3, 0.8),
+                size: (0.03, 0.06),
+                lifetime: (0.3, 0.6),
+                gravity: 2.0,
+                end_scale: 2.0,
+                ..base
+            },
+            ParticleKind::BloodSpray => Self {
+                color: LinearRgba::new(0.35, 0.01, 0.01, 1.0),
+                count: 12.0,
+                spread: 0.4,
+                speed: (1.5, 4.0),
+                size: (0.02, 0.04),
+                lifetime: (0.4, 0.8),
+                gravity: 9.81,
+                ..base
+            },
+            ParticleKind::Fireball => Self {
+                color: LinearRgba::new(1.0, 0.5, 0.15, 1.0),
+                glow: 6.0,
+                count: 24.0,
+                emission: Emission::Sphere,
+                speed: (2.0, 6.0),
+                size: (0.15, 0.35),
+                lifetime: (0.3, 0.7),
+                gravity: -1.0,
+                ..base
+            },
+            ParticleKind::SmokeColumn => Self {
+                color: LinearRgba::new(0.25, 0.24, 0.22, 0.7),
+                count: 16.0,
+                emission: Emission::Sphere,
+                speed: (0.3, 1.0),
+                size: (0.3, 0.6),
+                lifetime: (1.5, 3.0),
+                gravity: -2.0,
+                end_scale: 2.5,
+                ..base
+            },
+        }
+    }
+
+    fn material(&self) -> StandardMaterial {
+        StandardMaterial {
+            base_color: self.color.into(),
+            emissive: self.color * self.glow,
+            unlit: true,
+            alpha_mode: match self.color.alpha < 1.0 {
+                true => AlphaMode::Blend,
+                false => AlphaMode::Opaque,
+            },
+            ..default()
+        }
+    }
+}
+
+#[derive(Resource)]
+struct CpuParticleAssets {
+    mesh: Handle<Mesh>,
+    materials: HashMap<ParticleKind, Handle<StandardMaterial>>,
+}
+
+impl FromWorld for CpuParticleAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Sphere::new(0.5).mesh().ico(1).unwrap());
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let materials = [
+            ParticleKind::MuzzleFlash,
+            ParticleKind::MinimalMuzzleFlash,
+            ParticleKind::MuzzleSmoke,
+            ParticleKind::Impact,
+            ParticleKind::Footstep,
+            ParticleKind::BloodSpray,
+            ParticleKind::Fireball,
+            ParticleKind::SmokeColumn,
+        ]
+        .into_iter()
+        .map(|kind| (kind, materials.add(CpuPreset::for_kind(kind).material())))
+        .collect();
+        Self { mesh, materials }
+    }
+}
+
+/// Keeps a continuous effect emitting.
+#[derive(Component)]
+struct CpuEmitter {
+    /// Particles owed from fractions of frames.
+    owed: f32,
+}
+
+#[derive(Component)]
+struct CpuParticle {
+    /// In the effect's space.
+    velocity: Vec3,
+    gravity: f32,
+    size: f32,
+    end_scale: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+fn spawn_particles(
+    commands: &mut Commands,
+    effect: Entity,
+    kind: ParticleKind,
+    count: usize,
+    assets: &CpuParticleAssets,
+    rng: &mut impl Rng,
+) {
+    let preset = CpuPreset::for_kind(kind);
+    let material = assets.materials[&kind].clone();
+    commands.entity(effect).with_children(|parent| {
+        for _ in 0..count {
+            let direction = match preset.emission {
+                Emission::Forward => {
+                    let spread = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+                    (Vec3::NEG_Z + (spread * preset.spread).extend(0.0)).normalize()
+                }
+                Emission::Sphere => Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .normalize_or(Vec3::Y),
+            };
+            let size = rng.gen_range(preset.size.0..=preset.size.1);
+            parent.spawn((
+                CpuParticle {
+                    velocity: direction * rng.gen_range(preset.speed.0..=preset.speed.1),
+                    gravity: preset.gravity,
+                    size,
+                    end_scale: preset.end_scale,
+                    age: 0.0,
+                    lifetime: rng.gen_range(preset.lifetime.0..=preset.lifetime.1),
+                },
+                Mesh3d(assets.mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                NotShadowCaster,
+                Transform::from_scale(Vec3::splat(size)),
+            ));
+        }
+    });
+}
+
+fn start_cpu_particles(
+    mut commands: Commands,
+    effects: Query<(Entity, &ParticleKind), Added<ParticleKind>>,
+    assets: Res<CpuParticleAssets>,
+    quality: Res<VfxQuality>,
+    mut effect_rng: ResMut<EffectRng>,
+) {
+    let rng = effect_rng.stream(CPU_PARTICLE_STREAM);
+    for (effect, kind) in effects.iter() {
+        let preset = CpuPreset::for_kind(*kind);
+        if preset.rate > 0.0 {
+            commands.entity(effect).insert(CpuEmitter { owed: 0.0 });
+        }
+        if preset.count > 0.0 {
+            let count = quality.particles(preset.count) as usize;
+            spawn_particles(&mut commands, effect, *kind, count, &assets, rng);
+        }
+    }
+}
+
+fn emit_cpu_particles(
+    mut commands: Commands,
+    mut emitters: Query<(Entity, &ParticleKind, &mut CpuEmitter)>,
+    assets: Res<CpuParticleAssets>,
+    quality: Res<VfxQuality>,
+    mut effect_rng: ResMut<EffectRng>,
+    time: Res<Time>,
+) {
+    let rng = effect_rng.stream(CPU_PARTICLE_STREAM);
+    for (effect, kind, mut emitter) in emitters.iter_mut() {
+        let rate = quality.particles(CpuPreset::for_kind(*kind).rate);
+        emitter.owed += rate * time.delta_secs();
+        let count = emitter.owed.floor();
+        emitter.owed -= count;
+        spawn_particles(&mut commands, effect, *kind, count as usize, &assets, rng);
+    }
+}
+
+fn update_cpu_particles(
+    mut commands: Commands,
+    mut particles: Query<(Entity, &mut CpuParticle, &mut Transform, &ChildOf)>,
+    global_transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
+) {
+    let delta_secs = time.delta_secs();
+    for (entity, mut particle, mut transform, child_of) in particles.iter_mut() {
+        particle.age += delta_secs;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // Gravity pulls down in the world whichever way the effect faces.
+        let to_effect = global_transforms
+            .get(child_of.parent())
+            .map_or(Quat::IDENTITY, |effect| effect.rotation().inverse());
+        let gravity = to_effect * Vec3::NEG_Y * particle.gravity;
+        particle.velocity += gravity * delta_secs;
+        transform.translation += particle.velocity * delta_secs;
+        let t = particle.age / particle.lifetime;
+        transform.scale = Vec3::splat(particle.size * (1.0 + (particle.end_scale - 1.0) * t));
+    }
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+use crate::character::{RigBone, RigMap};
+use crate::ik::{IkTarget, TwoBoneIk};
+use crate::utils;
+
+/// Support for characters driven by tracked XR controllers. The XR runtime
+/// integration (e.g. bevy_mod_openxr) is expected to keep the transforms of the
+/// [`XrController`] and [`XrMuzzle`] entities up to date.
+pub struct XrPlugin;
+
+impl Plugin for XrPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<XrMode>();
+        app.register_type::<XrMode>();
+        app.register_type::<XrController>();
+        app.register_type::<XrMuzzle>();
+        app.register_type::<XrCharacter>();
+        app.add_systems(Update, drive_xr_hand_ik);
+    }
+}
+
+/// Whether XR mode is enabled. While it's on the tracked controllers drive the
+/// hands, and camera shake and kick are off since they cause motion sickness.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct XrMode {
+    pub enabled: bool,
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum XrHand {
+    Left,
+    Right,
+}
+
+impl XrHand {
+    fn bone(&self) -> RigBone {
+        match self {
+            Self::Left => RigBone::LeftHand,
+            Self::Right => RigBone::RightHand,
+        }
+    }
+}
+
+/// A tracked controller that drives the hand IK of the character it's a child of.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct XrController {
+    pub hand: XrHand,
+}
+
+/// The muzzle of a tracked weapon. In XR mode tracers should originate here
+/// rather than at the animated bullet point.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct XrMuzzle;
+
+/// A character root whose hands follow the tracked controllers. The hands are
+/// found through its `RigMap`.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct XrCharacter;
+
+/// Gets the transform tracers should originate from, if in XR mode and there is
+/// a tracked muzzle.
+pub fn xr_tracer_origin(
+    xr: &XrMode,
+    muzzles: &Query<&GlobalTransform, With<XrMuzzle>>,
+) -> Option<GlobalTransform> {
+    if !xr.enabled {
+        return None;
+    }
+    muzzles.iter().next().copied()
+}
+
+fn drive_xr_hand_ik(
+    mut commands: Commands,
+    xr: Res<XrMode>,
+    controllers: Query<(Entity, &XrController)>,
+    characters: Query<&XrCharacter>,
+    rigs: Query<&RigMap>,
+    parents: Query<&ChildOf>,
+    iks: Query<&TwoBoneIk>,
+) {
+    let weight = if xr.enabled { 1.0 } else { 0.0 };
+    for (controller, tracked) in controllers.iter() {
+        let Some((root, _)) = utils::find_upwards(controller, &parents, &characters) else {
+            continue;
+        };
+        let Ok(rig) = rigs.get(root) else {
+            continue;
+        };
+        let Some(hand) = rig.get(tracked.hand.bone()) else {
+            continue;
+        };
+
+        if iks.get(hand).is_ok_and(|ik| ik.weight == weight) {
+            continue;
+        }
+
+        let mut ik = TwoBoneIk::new(IkTarget::Entity(controller));
+        ik.match_target_rotation = true;
+        ik.weight = weight;
+        commands.entity(hand).insert(ik);
+    }
+}